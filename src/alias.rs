@@ -0,0 +1,80 @@
+// alias: Loads the display names of buckets
+#![forbid(unsafe_code)]
+#![deny(missing_docs)]
+use anyhow::Result;
+use crate::common::S3duError;
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use tracing::debug;
+
+/// Returns the bucket aliases in the TOML file at `path`, keyed on the bucket
+/// name.
+///
+/// The file should map bucket names to the names that they're printed with,
+/// eg. `"logs-prod-a1b2c3d4" = "prod-application-logs"`.
+pub fn load_aliases(path: &Path) -> Result<HashMap<String, String>> {
+    debug!(path = ?path, "load_aliases: Loading bucket aliases");
+
+    let aliases_error = |e: &dyn std::fmt::Display| {
+        S3duError::BucketAliases(format!("{}: {}", path.display(), e))
+    };
+
+    let contents = fs::read_to_string(path)
+        .map_err(|e| aliases_error(&e))?;
+
+    let aliases: HashMap<String, String> = toml::from_str(&contents)
+        .map_err(|e| aliases_error(&e))?;
+
+    debug!(aliases = aliases.len(), "load_aliases: Loaded bucket aliases");
+
+    Ok(aliases)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_helpers::temp_path;
+    use pretty_assertions::assert_eq;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_load_aliases() {
+        let path = Path::new("test-data/bucket-aliases.toml");
+        let ret  = load_aliases(path).unwrap();
+
+        let expected: HashMap<String, String> = vec![
+            ("logs-prod-a1b2c3d4".to_string(),     "prod-application-logs".to_string()),
+            ("media-3f9e1c2b.example".to_string(), "Media uploads".to_string()),
+        ].into_iter().collect();
+
+        assert_eq!(ret, expected);
+    }
+
+    #[test]
+    fn test_load_aliases_errors() {
+        let path = temp_path("bucket-aliases.toml");
+
+        let tests = vec![
+            ("\"logs-prod-a1b2c3d4\" = ",  "unexpected eof"),
+            ("\"logs-prod-a1b2c3d4\" = 1", "invalid type"),
+        ];
+
+        for test in tests {
+            let (contents, expected) = test;
+
+            fs::write(&path, contents).unwrap();
+            let ret = load_aliases(&path).unwrap_err();
+
+            let ret = ret.downcast_ref::<S3duError>().unwrap();
+
+            assert!(matches!(ret, S3duError::BucketAliases(_)));
+            assert!(ret.to_string().contains(expected), "{}", ret);
+        }
+
+        let missing = PathBuf::from("test-data/no-such-aliases.toml");
+        let ret     = load_aliases(&missing).unwrap_err();
+
+        assert!(ret.to_string().contains("no-such-aliases.toml"), "{}", ret);
+    }
+}
@@ -0,0 +1,8 @@
+// Analysis of the objects listed in a bucket
+#![forbid(unsafe_code)]
+#![deny(missing_docs)]
+
+/// Histogram of the ages of objects in a bucket.
+mod histogram;
+
+pub use histogram::*;
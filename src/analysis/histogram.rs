@@ -0,0 +1,200 @@
+// histogram: Distribution of the ages of objects in a bucket
+#![forbid(unsafe_code)]
+#![deny(missing_docs)]
+use chrono::{
+    DateTime,
+    Duration,
+    Utc,
+};
+use crate::s3::object_size;
+use rusoto_s3::Object;
+
+/// Upper bounds of the age bins in days, and the labels that they're
+/// reported with. Objects exactly as old as a bound fall in the next bin.
+const AGE_BINS: &[(Option<i64>, &str)] = &[
+    (Some(7),   "0-7 days"),
+    (Some(30),  "7-30 days"),
+    (Some(90),  "30-90 days"),
+    (Some(365), "90-365 days"),
+    (None,      "over 1 year"),
+];
+
+/// `AgeBin` holds the objects of an age range.
+#[derive(Debug, Default, Eq, PartialEq)]
+pub struct AgeBin {
+    /// Label that the bin is reported with.
+    pub label: &'static str,
+
+    /// Number of objects in the bin.
+    pub count: u64,
+
+    /// Total size of the objects in the bin in bytes.
+    pub size: u64,
+}
+
+impl AgeBin {
+    /// Returns an empty `AgeBin` with the given `label`.
+    fn new(label: &'static str) -> Self {
+        Self {
+            label: label,
+            ..Default::default()
+        }
+    }
+
+    /// Adds an object of `size` bytes to the bin.
+    fn add(&mut self, size: u64) {
+        self.count += 1;
+        self.size  += size;
+    }
+}
+
+/// `AgeHistogram` is the distribution of the ages of objects.
+#[derive(Debug, Eq, PartialEq)]
+pub struct AgeHistogram {
+    /// Bins of objects, youngest first.
+    pub bins: Vec<AgeBin>,
+
+    /// Objects with a missing or unparseable `last_modified` time.
+    pub unknown: AgeBin,
+}
+
+impl AgeHistogram {
+    /// Returns the number of objects in the histogram.
+    pub fn count(&self) -> u64 {
+        self.bins.iter()
+            .chain(Some(&self.unknown))
+            .map(|bin| bin.count)
+            .sum()
+    }
+
+    /// Returns the total size of the objects in the histogram in bytes.
+    pub fn size(&self) -> u64 {
+        self.bins.iter()
+            .chain(Some(&self.unknown))
+            .map(|bin| bin.size)
+            .sum()
+    }
+}
+
+/// Returns the histogram of the ages of `objects` at `now`.
+///
+/// Objects modified after `now`, which can happen with clock skew, are
+/// placed in the youngest bin. Objects without a usable `last_modified` time
+/// are counted as `unknown`, so that every object is in the histogram.
+pub fn age_histogram(objects: &[Object], now: DateTime<Utc>) -> AgeHistogram {
+    let mut histogram = AgeHistogram {
+        bins:    AGE_BINS.iter().map(|(_, label)| AgeBin::new(label)).collect(),
+        unknown: AgeBin::new("unknown age"),
+    };
+
+    for object in objects {
+        let size = object_size(object.size.unwrap_or_default());
+
+        let last_modified = object.last_modified.as_deref()
+            .and_then(|lm| DateTime::parse_from_rfc3339(lm).ok());
+
+        let age = match last_modified {
+            Some(dt) => now.signed_duration_since(dt.with_timezone(&Utc)),
+            None     => {
+                histogram.unknown.add(size);
+                continue;
+            },
+        };
+
+        // The last bin has no upper bound, so a bin is always found.
+        let index = AGE_BINS.iter()
+            .position(|(days, _)| match days {
+                Some(days) => age < Duration::days(*days),
+                None       => true,
+            })
+            .unwrap_or(AGE_BINS.len() - 1);
+
+        histogram.bins[index].add(size);
+    }
+
+    histogram
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use pretty_assertions::assert_eq;
+
+    // Returns an object of the given size, last modified `age` before `now`.
+    fn object(now: DateTime<Utc>, age: Duration, size: i64) -> Object {
+        Object {
+            key:           Some("test-object".into()),
+            last_modified: Some((now - age).to_rfc3339()),
+            size:          Some(size),
+            ..Default::default()
+        }
+    }
+
+    // Returns the bin with the given counts and size.
+    fn bin(label: &'static str, count: u64, size: u64) -> AgeBin {
+        AgeBin {
+            label: label,
+            count: count,
+            size:  size,
+        }
+    }
+
+    #[test]
+    fn test_age_histogram() {
+        let now = Utc.ymd(2021, 6, 1).and_hms(0, 0, 0);
+
+        let objects = vec![
+            object(now, Duration::zero(), 1),
+            object(now, Duration::days(7) - Duration::seconds(1), 2),
+            // Exactly 7 days old is the start of the next bin.
+            object(now, Duration::days(7), 4),
+            object(now, Duration::days(30) - Duration::seconds(1), 8),
+            // As is exactly 30 days old.
+            object(now, Duration::days(30), 16),
+            object(now, Duration::days(89), 32),
+            object(now, Duration::days(90), 64),
+            object(now, Duration::days(365), 128),
+            object(now, Duration::days(3650), 256),
+            // Modified in the future, from clock skew.
+            object(now, Duration::days(-1), 512),
+            Object {
+                key:           Some("broken".into()),
+                last_modified: Some("not-a-timestamp".into()),
+                size:          Some(1024),
+                ..Default::default()
+            },
+            Object {
+                key:  Some("missing".into()),
+                size: Some(2048),
+                ..Default::default()
+            },
+        ];
+
+        let ret = age_histogram(&objects, now);
+
+        let expected = AgeHistogram {
+            bins: vec![
+                bin("0-7 days",    3, 1 + 2 + 512),
+                bin("7-30 days",   2, 4 + 8),
+                bin("30-90 days",  2, 16 + 32),
+                bin("90-365 days", 1, 64),
+                bin("over 1 year", 2, 128 + 256),
+            ],
+            unknown: bin("unknown age", 2, 1024 + 2048),
+        };
+
+        assert_eq!(ret, expected);
+        assert_eq!(ret.count(), objects.len() as u64);
+        assert_eq!(ret.size(), 4095);
+    }
+
+    #[test]
+    fn test_age_histogram_empty() {
+        let ret = age_histogram(&[], Utc::now());
+
+        assert_eq!(ret.count(), 0);
+        assert_eq!(ret.size(), 0);
+        assert_eq!(ret.bins.len(), AGE_BINS.len());
+    }
+}
@@ -6,17 +6,63 @@ use clap::{
     crate_description,
     crate_name,
     crate_version,
+    value_t,
     App,
     Arg,
     ArgMatches,
 };
-use lazy_static::lazy_static;
-use log::debug;
+use anyhow::Result;
+use crate::{
+    alias,
+    credentials,
+    MinSize,
+};
+use crate::common::{
+    BucketAliases,
+    ClientConfig,
+    ClientMode,
+    CostModel,
+    RequestCounter,
+    S3duError,
+    SdkConfig,
+    SizeUnit,
+    StorageClass,
+    TimestampFormat,
+    resolve_region,
+};
+use crate::output::{
+    ColorChoice,
+    OutputMode,
+};
+use num_format::Locale;
 use rusoto_core::Region;
+use std::path::{
+    Path,
+    PathBuf,
+};
 use std::str::FromStr;
+use std::time::Duration as StdDuration;
+use tracing::debug;
+use tracing::level_filters::LevelFilter;
+
+use chrono::{
+    DateTime,
+    Duration,
+    NaiveDate,
+    Utc,
+};
+
+#[cfg(feature = "s3")]
+use crate::common::{
+    ObjectVersions,
+    env_region_name,
+};
+
+#[cfg(feature = "s3")]
+use crate::endpoint;
 
 #[cfg(feature = "s3")]
-use url::Url;
+use std::ops::Range;
 
 // This catches cases where we've compiled with either:
 //   - Only "cloudwatch"
@@ -31,23 +77,66 @@ const DEFAULT_MODE: &str = "cloudwatch";
 #[cfg(all(feature = "s3", not(feature = "cloudwatch")))]
 const DEFAULT_MODE: &str = "s3";
 
+/// Default number of bucket metadata API calls to make at once in S3 mode.
+#[cfg(feature = "s3")]
+const DEFAULT_CHUNK_SIZE: &str = "10";
+
+/// Default maximum depth of prefixes to output when a delimiter is given.
+#[cfg(feature = "s3")]
+const DEFAULT_MAX_DEPTH: &str = "1";
+
+/// Default delimiter used to split keys into prefixes for `--tree`.
+#[cfg(feature = "s3")]
+pub const DEFAULT_TREE_DELIMITER: &str = "/";
+
+/// Default number of objects to sample the metadata of in each bucket.
+#[cfg(feature = "s3")]
+const DEFAULT_METADATA_SAMPLE_SIZE: &str = "100";
+
+/// Default number of top-level prefixes that a bucket may have before it's
+/// warned about as a namespace bucket.
+#[cfg(feature = "s3")]
+const DEFAULT_NAMESPACE_THRESHOLD: &str = "100";
+
 /// Default object versions to sum in S3 mode.
 #[cfg(feature = "s3")]
 const DEFAULT_OBJECT_VERSIONS: &str = "current";
 
-lazy_static! {
-    /// Default AWS region if one isn't provided on the command line.
-    ///
-    /// Obtains the default region in the following order:
-    ///   - `AWS_DEFAULT_REGION` environment variable
-    ///   - `AWS_REGION` environment variable
-    ///   - Falls back to `us-east-1` if regions in the environment variables
-    ///     are malformed or unknown to Rusoto.
-    static ref DEFAULT_REGION: String = {
-        let region = Region::default();
-        region.name().into()
-    };
-}
+/// Maximum number of keys that S3 returns in each page of an object listing.
+#[cfg(feature = "s3")]
+const MAX_PAGE_SIZE: i64 = 1000;
+
+/// `--region` alias for `--all-regions`.
+pub const ALL_REGIONS: &str = "all";
+
+/// Number of days that CloudWatch retains daily metric statistics for. This
+/// is roughly 15 months.
+#[cfg(feature = "cloudwatch")]
+const CLOUDWATCH_RETENTION_DAYS: i64 = 455;
+
+/// Characters that separate the language of a locale name from its region.
+const LOCALE_REGION_SEPARATORS: &[char] = &['_', '-'];
+
+/// Maximum length of CloudWatch namespaces and metric names.
+#[cfg(feature = "cloudwatch")]
+const MAX_CLOUDWATCH_NAME_LENGTH: usize = 255;
+
+/// Default choice of when to color the text output.
+const DEFAULT_COLOR: &str = "auto";
+
+/// Default for including overhead storage types in CloudWatch bucket sizes.
+/// This matches the bucket sizes shown in the AWS console.
+#[cfg(feature = "cloudwatch")]
+const DEFAULT_INCLUDE_OVERHEAD: &str = "true";
+
+/// Default format to write log messages in.
+const DEFAULT_LOG_FORMAT: &str = "text";
+
+/// Default format to output bucket sizes in.
+const DEFAULT_OUTPUT: &str = "text";
+
+/// Default format to display timestamps in.
+const DEFAULT_TIMESTAMP_FORMAT: &str = "rfc3339";
 
 /// Default unit to display sizes in.
 const DEFAULT_UNIT: &str = "binary";
@@ -58,10 +147,67 @@ const DEFAULT_UNIT: &str = "binary";
 const VALID_MODES: &[&str] = &[
     #[cfg(feature = "cloudwatch")]
     "cloudwatch",
+    #[cfg(all(feature = "cloudwatch", feature = "s3"))]
+    "combined",
     #[cfg(feature = "s3")]
     "s3",
 ];
 
+// This should match the string values in the CostModel FromStr impl in
+// common, with the addition of custom.
+/// Valid cost models for the `--cost-model` command line switch.
+const VALID_COST_MODELS: &[&str] = &[
+    "aws-infrequent-access",
+    "aws-standard",
+    "custom",
+];
+
+// This should match the string values in the ColorChoice FromStr impl in
+// output.
+/// Valid choices for the `--color` command line switch.
+const VALID_COLOR_CHOICES: &[&str] = &[
+    "always",
+    "auto",
+    "never",
+];
+
+/// Valid values for the `--include-overhead` command line switch.
+#[cfg(feature = "cloudwatch")]
+const VALID_INCLUDE_OVERHEAD: &[&str] = &[
+    "false",
+    "true",
+];
+
+// This should match the string values in the LogFormat FromStr impl in
+// logging.
+/// Valid log formats for the `--log-format` command line switch.
+const VALID_LOG_FORMATS: &[&str] = &[
+    "json",
+    "text",
+];
+
+// This should match the string values in the OutputMode FromStr impl in
+// output.
+/// Valid output formats for the `--output` command line switch.
+const VALID_OUTPUTS: &[&str] = &[
+    "csv",
+    "flux",
+    "json",
+    "ndjson",
+    "ndjson-schema",
+    "prometheus",
+    "text",
+];
+
+// This should match the string values in the TimestampFormat FromStr impl in
+// common.
+/// Valid timestamp formats for the `--timestamp-format` command line switch.
+const VALID_TIMESTAMP_FORMATS: &[&str] = &[
+    "human",
+    "rfc3339",
+    "unix",
+];
+
 // This should match the string values in the UnitSize FromStr impl in common.
 /// Valid unit sizes for the `--unit` command line switch.
 const VALID_SIZE_UNITS: &[&str] = &[
@@ -70,24 +216,462 @@ const VALID_SIZE_UNITS: &[&str] = &[
     "decimal",
 ];
 
-// This should match the ObjectVersions in the common.rs
-/// Valid S3 object versions for the `--object-versions` switch.
-#[cfg(feature = "s3")]
-const OBJECT_VERSIONS: &[&str] = &[
-    "all",
-    "current",
-    "multipart",
-    "non-current",
+// These are the S3 API names understood by StorageClass::from in common.
+/// Storage classes listed in the error for an unknown `--ignore-storage-class`.
+const STORAGE_CLASSES: &[&str] = &[
+    "DEEP_ARCHIVE",
+    "GLACIER",
+    "GLACIER_IR",
+    "INTELLIGENT_TIERING",
+    "ONEZONE_IA",
+    "OUTPOSTS",
+    "REDUCED_REDUNDANCY",
+    "STANDARD",
+    "STANDARD_IA",
+];
+
+// These should match the units that humansize outputs sizes in, so that sizes
+// can be copied from our output.
+/// Size suffixes understood by `parse_size` and their multipliers.
+///
+/// Longer suffixes must come before any shorter suffixes that they end with.
+const SIZE_SUFFIXES: &[(&str, u64)] = &[
+    ("kib", 1 << 10),
+    ("mib", 1 << 20),
+    ("gib", 1 << 30),
+    ("tib", 1 << 40),
+    ("pib", 1 << 50),
+    ("kb",  1_000),
+    ("mb",  1_000_000),
+    ("gb",  1_000_000_000),
+    ("tb",  1_000_000_000_000),
+    ("pb",  1_000_000_000_000_000),
+    ("b",   1),
 ];
 
-/// Ensures that the AWS region that we're passed is valid.
+/// Parses a size such as `1GiB`, `1.5MB` or `1024` into a number of bytes.
+///
+/// Sizes without a suffix are in bytes, and suffixes are case insensitive.
+pub fn parse_size(s: &str) -> Result<u64, String> {
+    let size = s.to_ascii_lowercase();
+
+    let (number, multiplier) = SIZE_SUFFIXES.iter()
+        .find(|(suffix, _)| size.ends_with(suffix))
+        .map(|(suffix, multiplier)| (&size[..size.len() - suffix.len()], *multiplier))
+        .unwrap_or((&size, 1));
+
+    let number = number.trim().parse::<f64>()
+        .map_err(|e| format!("Could not parse size '{}': {}", s, e))?;
+
+    let bytes = number * multiplier as f64;
+
+    if !bytes.is_finite() || bytes < 0.0 || bytes > u64::MAX as f64 {
+        return Err(format!("Size '{}' is out of range", s));
+    }
+
+    Ok(bytes as u64)
+}
+
+/// Parses a locale name, such as `en_US`, `de-CH` or `fr`, into a `Locale`.
+///
+/// Any encoding is ignored, so `LANG` style names such as `en_US.UTF-8` are
+/// accepted. Regions that format numbers the same as their language, such as
+/// `en_US`, are only known by their language, so the language is used if the
+/// region isn't known.
+pub fn parse_locale(s: &str) -> Result<Locale, String> {
+    let name     = s.split('.').next().unwrap_or_default();
+    let language = name.split(LOCALE_REGION_SEPARATORS).next().unwrap_or_default();
+
+    Locale::from_name(name)
+        .or_else(|_| Locale::from_name(language))
+        .map_err(|_| format!("Unknown locale '{}'", s))
+}
+
+/// Parses a `YYYY-MM-DD` date into a `DateTime<Utc>` at midnight.
+pub fn parse_date(s: &str) -> Result<DateTime<Utc>, String> {
+    let date = NaiveDate::parse_from_str(s, "%Y-%m-%d")
+        .map_err(|e| format!("Could not parse date '{}': {}", s, e))?;
+
+    Ok(DateTime::<Utc>::from_utc(date.and_hms(0, 0, 0), Utc))
+}
+
+/// Parses a `START..END` bucket creation date range, where both ends are
+/// RFC 3339 timestamps, into a half-open `Range`.
+///
+/// The start of the range must be before its end.
+#[cfg(feature = "s3")]
+pub fn parse_creation_date_range(
+    s: &str,
+) -> Result<Range<DateTime<Utc>>, String> {
+    let mut parts = s.splitn(2, "..");
+
+    let start = parts.next().unwrap_or_default();
+    let end   = match parts.next() {
+        Some(end) => end,
+        None      => {
+            return Err("Creation date range must be given as START..END".into());
+        },
+    };
+
+    let parse = |date: &str| {
+        DateTime::parse_from_rfc3339(date)
+            .map(|date| date.with_timezone(&Utc))
+            .map_err(|e| format!("Could not parse creation date '{}': {}", date, e))
+    };
+
+    let start = parse(start)?;
+    let end   = parse(end)?;
+
+    if start >= end {
+        return Err("Creation date range start must be before its end".into());
+    }
+
+    Ok(start..end)
+}
+
+/// Parses a `YYYY-MM-DD` report date into a `DateTime<Utc>` at midnight.
+///
+/// The date must not be in the future relative to `now`, and must not be
+/// older than CloudWatch's metric retention period.
+#[cfg(feature = "cloudwatch")]
+pub fn parse_report_date(
+    s: &str,
+    now: DateTime<Utc>,
+) -> Result<DateTime<Utc>, String> {
+    let date = parse_date(s)?;
+
+    if date > now {
+        return Err("Report date cannot be in the future".into());
+    }
+
+    if now - date > Duration::days(CLOUDWATCH_RETENTION_DAYS) {
+        return Err(
+            "Report date is older than the CloudWatch retention period of 15 months".into()
+        );
+    }
+
+    Ok(date)
+}
+
+/// Parses an object age such as `30d` or `1y`, returning the time that age
+/// was before `now`.
+///
+/// Ages are a whole number followed by one of the units `s` (seconds), `m`
+/// (minutes), `h` (hours), `d` (days), `w` (weeks) or `y` (365 day years).
+#[cfg(feature = "s3")]
+pub fn parse_age_cutoff(
+    s: &str,
+    now: DateTime<Utc>,
+) -> Result<DateTime<Utc>, String> {
+    // Units are ASCII, so this is always a character boundary when the last
+    // character is a valid unit.
+    let unit_seconds = match s.chars().last() {
+        Some('s') => 1,
+        Some('m') => 60,
+        Some('h') => 3_600,
+        Some('d') => 86_400,
+        Some('w') => 604_800,
+        Some('y') => 31_536_000,
+        _         => {
+            return Err(format!(
+                "Age '{}' must end with one of the units: s, m, h, d, w, y",
+                s,
+            ));
+        },
+    };
+
+    let count = s[..s.len() - 1].parse::<i64>()
+        .map_err(|e| format!("Could not parse age '{}': {}", s, e))?;
+
+    if count <= 0 {
+        return Err("Age must be greater than 0".into());
+    }
+
+    count.checked_mul(unit_seconds)
+        .filter(|seconds| *seconds <= Duration::max_value().num_seconds())
+        .and_then(|seconds| now.checked_sub_signed(Duration::seconds(seconds)))
+        .ok_or_else(|| format!("Age '{}' is too large", s))
+}
+
+/// Parses a number of days for `--since-days`, returning the midnight that
+/// many days before `now`.
+///
+/// `0` is midnight today, so that only objects modified today are included,
+/// `1` is midnight yesterday, and so on.
+#[cfg(feature = "s3")]
+pub fn parse_since_days(
+    s: &str,
+    now: DateTime<Utc>,
+) -> Result<DateTime<Utc>, String> {
+    let days = s.parse::<i64>()
+        .map_err(|e| format!("Could not parse days '{}': {}", s, e))?;
+
+    if days < 0 {
+        return Err("Since days must not be negative".into());
+    }
+
+    let midnight = now.date().and_hms(0, 0, 0);
+
+    days.checked_mul(86_400)
+        .filter(|seconds| *seconds <= Duration::max_value().num_seconds())
+        .and_then(|seconds| midnight.checked_sub_signed(Duration::seconds(seconds)))
+        .ok_or_else(|| format!("Days '{}' is too large", s))
+}
+
+/// Parses a `DIMENSION=VALUE` CloudWatch dimension filter.
+#[cfg(feature = "cloudwatch")]
+pub fn parse_dimension_filter(s: &str) -> Result<(String, String), String> {
+    let mut parts = s.splitn(2, '=');
+
+    let name  = parts.next().unwrap_or_default();
+    let value = parts.next();
+
+    match value {
+        Some(value) if !name.is_empty() && !value.is_empty() => {
+            Ok((name.into(), value.into()))
+        },
+        _ => Err("Dimension filter must be given as DIMENSION=VALUE".into()),
+    }
+}
+
+/// Parses a `KEY=VALUE` S3 bucket tag filter.
 ///
-/// There's a chance that this can be incorrect if AWS releases a region and
-/// Rusoto lags behind on updating the Region list in `rusoto_core`.
-fn is_valid_aws_region(s: String) -> Result<(), String> {
-    match Region::from_str(&s) {
+/// Tag values may be empty, but keys may not.
+#[cfg(feature = "s3")]
+pub fn parse_tag_filter(s: &str) -> Result<(String, String), String> {
+    let mut parts = s.splitn(2, '=');
+
+    let key   = parts.next().unwrap_or_default();
+    let value = parts.next();
+
+    match value {
+        Some(value) if !key.is_empty() => Ok((key.into(), value.into())),
+        _ => Err("Tag filter must be given as KEY=VALUE".into()),
+    }
+}
+
+/// Ensures that the locale we're passed is known.
+fn is_valid_locale(s: String) -> Result<(), String> {
+    parse_locale(&s).map(|_| ())
+}
+
+/// Ensures that the tag filter we're passed is valid.
+#[cfg(feature = "s3")]
+fn is_valid_tag_filter(s: String) -> Result<(), String> {
+    parse_tag_filter(&s).map(|_| ())
+}
+
+/// Ensures that the dimension filter we're passed is valid.
+#[cfg(feature = "cloudwatch")]
+fn is_valid_dimension_filter(s: String) -> Result<(), String> {
+    parse_dimension_filter(&s).map(|_| ())
+}
+
+/// Ensures that the CloudWatch namespace or metric name we're passed isn't
+/// empty or longer than CloudWatch allows.
+#[cfg(feature = "cloudwatch")]
+fn is_valid_cloudwatch_name(s: String) -> Result<(), String> {
+    if s.is_empty() {
+        return Err("CloudWatch names cannot be empty".into());
+    }
+
+    if s.chars().count() > MAX_CLOUDWATCH_NAME_LENGTH {
+        return Err(format!(
+            "CloudWatch names cannot be longer than {} characters",
+            MAX_CLOUDWATCH_NAME_LENGTH,
+        ));
+    }
+
+    Ok(())
+}
+
+/// Ensures that the report date we're passed is valid.
+#[cfg(feature = "cloudwatch")]
+fn is_valid_report_date(s: String) -> Result<(), String> {
+    parse_report_date(&s, Utc::now()).map(|_| ())
+}
+
+/// Ensures that the API call timeout we're passed is a positive integer
+/// number of milliseconds.
+fn is_valid_api_timeout(s: String) -> Result<(), String> {
+    match s.parse::<u64>() {
+        Ok(0)  => Err("Timeout must be greater than 0".into()),
+        Ok(_)  => Ok(()),
+        Err(e) => Err(format!("Could not parse timeout: {}", e)),
+    }
+}
+
+/// Ensures that the chunk size we're passed is a positive integer.
+#[cfg(feature = "s3")]
+fn is_valid_chunk_size(s: String) -> Result<(), String> {
+    match s.parse::<usize>() {
+        Ok(0)  => Err("Chunk size must be greater than 0".into()),
+        Ok(_)  => Ok(()),
+        Err(e) => Err(format!("Could not parse chunk size: {}", e)),
+    }
+}
+
+/// Ensures that the maximum number of objects we're passed is a positive
+/// integer.
+#[cfg(feature = "s3")]
+fn is_valid_max_objects(s: String) -> Result<(), String> {
+    match s.parse::<usize>() {
+        Ok(0)  => Err("Max objects must be greater than 0".into()),
+        Ok(_)  => Ok(()),
+        Err(e) => Err(format!("Could not parse max objects: {}", e)),
+    }
+}
+
+/// Ensures that the metadata sample size we're passed is a positive integer.
+#[cfg(feature = "s3")]
+fn is_valid_metadata_sample_size(s: String) -> Result<(), String> {
+    match s.parse::<usize>() {
+        Ok(0)  => Err("Metadata sample size must be greater than 0".into()),
+        Ok(_)  => Ok(()),
+        Err(e) => Err(format!("Could not parse metadata sample size: {}", e)),
+    }
+}
+
+/// Ensures that the namespace threshold we're passed is valid.
+#[cfg(feature = "s3")]
+fn is_valid_namespace_threshold(s: String) -> Result<(), String> {
+    match s.parse::<usize>() {
+        Ok(0)  => Err("Namespace threshold must be greater than 0".into()),
+        Ok(_)  => Ok(()),
+        Err(e) => Err(format!("Could not parse namespace threshold: {}", e)),
+    }
+}
+
+/// Ensures that the page size we're passed is a positive integer no larger
+/// than S3 allows.
+#[cfg(feature = "s3")]
+fn is_valid_page_size(s: String) -> Result<(), String> {
+    match s.parse::<i64>() {
+        Ok(size) if size < 1 => Err("Page size must be greater than 0".into()),
+        Ok(size) if size > MAX_PAGE_SIZE => {
+            Err(format!("Page size must be no more than {}", MAX_PAGE_SIZE))
+        },
+        Ok(_)  => Ok(()),
+        Err(e) => Err(format!("Could not parse page size: {}", e)),
+    }
+}
+
+/// Ensures that the object age we're passed can be parsed.
+#[cfg(feature = "s3")]
+fn is_valid_age(s: String) -> Result<(), String> {
+    parse_age_cutoff(&s, Utc::now()).map(|_| ())
+}
+
+/// Ensures that the number of days we're passed can be parsed.
+#[cfg(feature = "s3")]
+fn is_valid_since_days(s: String) -> Result<(), String> {
+    parse_since_days(&s, Utc::now()).map(|_| ())
+}
+
+/// Ensures that the creation date range we're passed can be parsed.
+#[cfg(feature = "s3")]
+fn is_valid_creation_date_range(s: String) -> Result<(), String> {
+    parse_creation_date_range(&s).map(|_| ())
+}
+
+/// Ensures that the date we're passed can be parsed.
+#[cfg(feature = "s3")]
+fn is_valid_date(s: String) -> Result<(), String> {
+    parse_date(&s).map(|_| ())
+}
+
+/// Ensures that the delimiter we're passed isn't empty.
+#[cfg(feature = "s3")]
+fn is_valid_delimiter(s: String) -> Result<(), String> {
+    if s.is_empty() {
+        return Err("Delimiter cannot be empty".into());
+    }
+
+    Ok(())
+}
+
+/// Ensures that the maximum prefix depth we're passed is a positive integer.
+#[cfg(feature = "s3")]
+fn is_valid_max_depth(s: String) -> Result<(), String> {
+    match s.parse::<usize>() {
+        Ok(0)  => Err("Max depth must be greater than 0".into()),
         Ok(_)  => Ok(()),
-        Err(e) => Err(e.to_string()),
+        Err(e) => Err(format!("Could not parse max depth: {}", e)),
+    }
+}
+
+/// Ensures that the size we're passed can be parsed.
+fn is_valid_size(s: String) -> Result<(), String> {
+    parse_size(&s).map(|_| ())
+}
+
+/// Ensures that the storage class we're passed is one that `StorageClass`
+/// knows about.
+fn is_valid_storage_class(s: String) -> Result<(), String> {
+    match StorageClass::from(&s) {
+        StorageClass::Unknown(_) => {
+            Err(format!(
+                "Unknown storage class '{}', expected one of: {}",
+                s,
+                STORAGE_CLASSES.join(", "),
+            ))
+        },
+        _ => Ok(()),
+    }
+}
+
+/// Ensures that the role ARN we're passed looks like an IAM role ARN.
+fn is_valid_role_arn(s: String) -> Result<(), String> {
+    let parts: Vec<&str> = s.splitn(6, ':').collect();
+
+    match parts[..] {
+        ["arn", _, "iam", "", account, resource]
+            if !account.is_empty() && resource.starts_with("role/") => Ok(()),
+        _ => Err("Role ARN must be of the form arn:aws:iam::ACCOUNT:role/NAME".into()),
+    }
+}
+
+/// Ensures that the role session name we're passed is valid.
+///
+/// Session names must be between 2 and 64 characters, consisting of
+/// alphanumeric characters and any of `+=,.@-_`.
+fn is_valid_role_session_name(s: String) -> Result<(), String> {
+    if s.len() < 2 || s.len() > 64 {
+        return Err("Role session name must be between 2 and 64 characters".into());
+    }
+
+    let valid = s.chars()
+        .all(|c| c.is_ascii_alphanumeric() || "+=,.@-_".contains(c));
+
+    if !valid {
+        return Err("Role session name contains invalid characters".into());
+    }
+
+    Ok(())
+}
+
+/// Parses an AWS region name into a `Region`.
+///
+/// The `all` alias for `--all-regions` is returned as `None`, as it doesn't
+/// name a single region.
+pub fn parse_region(s: &str) -> Result<Option<Region>, String> {
+    if s == ALL_REGIONS {
+        return Ok(None);
+    }
+
+    match Region::from_str(s) {
+        Ok(region) => Ok(Some(region)),
+        Err(_)     => Err(format!("Unknown region '{}'", s)),
+    }
+}
+
+/// Ensures that the AWS region that we're passed to exclude is valid.
+///
+/// Excluding every region with the `all` alias would leave nothing to size.
+fn is_valid_excluded_region(s: String) -> Result<(), String> {
+    match parse_region(&s)? {
+        Some(_) => Ok(()),
+        None    => Err("Cannot exclude all regions".into()),
     }
 }
 
@@ -118,36 +702,10 @@ fn is_valid_aws_s3_bucket_name(s: String) -> Result<(), String> {
 /// Ensures that a given endpoint is valid, where valid means:
 ///   - Is not an empty string
 ///   - Is not an AWS endpoint
-///   - Parses as a valid URL
+///   - Parses as a valid HTTP or HTTPS URL without a query string
 #[cfg(feature = "s3")]
 fn is_valid_endpoint(s: String) -> Result<(), String> {
-    // Endpoint cannot be an empty string
-    if s.is_empty() {
-        return Err("Endpoint cannot be empty".into());
-    }
-
-    // Endpoint must parse as a valid URL
-    let url = match Url::parse(&s) {
-        Ok(u)  => Ok(u),
-        Err(e) => Err(format!("Could not parse endpoint: {}", e)),
-    }?;
-
-    // We can only use HTTP or HTTPS URLs.
-    match url.scheme() {
-        "http" | "https" => Ok(()),
-        scheme           => {
-            Err(format!("URL scheme must be http or https, found {}", scheme))
-        },
-    }?;
-
-    // Endpoint cannot be an AWS endpoint
-    if let Some(hostname) = url.host_str() {
-        if hostname.contains("amazonaws.com") {
-            return Err("Endpoint cannot be used to specify AWS endpoints".into());
-        }
-    }
-
-    Ok(())
+    endpoint::parse_endpoint(&s).map(|_| ())
 }
 
 /// Create the command line parser
@@ -158,6 +716,20 @@ fn create_app<'a, 'b>() -> App<'a, 'b> {
         .version(crate_version!())
         .author(crate_authors!())
         .about(crate_description!())
+        .arg(
+            Arg::with_name("ALL_REGIONS")
+                .long("all-regions")
+                .help("Size buckets in every standard AWS region")
+        )
+        .arg(
+            Arg::with_name("AWS_SDK_CONFIG")
+                .env("S3DU_AWS_SDK_CONFIG")
+                .hide_env_values(true)
+                .long("aws-sdk-config")
+                .value_name("FILE")
+                .help("Load overrides of the AWS SDK configuration from the JSON FILE")
+                .takes_value(true)
+        )
         .arg(
             Arg::with_name("BUCKET")
                 .env("S3DU_BUCKET")
@@ -169,102 +741,2396 @@ fn create_app<'a, 'b>() -> App<'a, 'b> {
                 .validator(is_valid_aws_s3_bucket_name)
         )
         .arg(
-            Arg::with_name("MODE")
-                .env("S3DU_MODE")
-                .hide_env_values(true)
-                .long("mode")
-                .short("m")
-                .value_name("MODE")
-                .help("Use either CloudWatch or S3 to obtain bucket sizes")
+            Arg::with_name("BUCKET_NAME")
+                .long("bucket")
+                .value_name("NAME")
+                .help("Bucket to retrieve size of, the same as the BUCKET argument")
                 .takes_value(true)
-                .default_value(DEFAULT_MODE)
-                .possible_values(VALID_MODES)
+                .conflicts_with("BUCKET")
+                .validator(is_valid_aws_s3_bucket_name)
         )
         .arg(
-            Arg::with_name("REGION")
-                .env("AWS_REGION")
+            Arg::with_name("BUCKET_ALIASES")
+                .env("S3DU_BUCKET_ALIASES")
                 .hide_env_values(true)
-                .long("region")
-                .short("r")
-                .value_name("REGION")
-                .help("Set the AWS region to create the client in.")
+                .long("bucket-aliases")
+                .value_name("FILE")
+                .help("Print buckets with the display names mapped to their names in the TOML FILE")
                 .takes_value(true)
-                .default_value(&DEFAULT_REGION)
-                .validator(is_valid_aws_region)
         )
         .arg(
-            Arg::with_name("UNIT")
-                .env("S3DU_UNIT")
-                .hide_env_values(true)
-                .long("unit")
+            Arg::with_name("CHECK_EBS_SNAPSHOTS")
+                .long("check-ebs-snapshots")
+                .help("List the sizes of the account's EBS snapshots alongside the buckets")
+        )
+        .arg(
+            Arg::with_name("CHECK_ECR")
+                .long("check-ecr")
+                .help("List the image sizes of ECR repositories alongside the buckets")
+        )
+        .arg(
+            Arg::with_name("CHECK_EFS")
+                .long("check-efs")
+                .help("List the sizes of EFS file systems alongside the buckets")
+        )
+        .arg(
+            Arg::with_name("CHECK_FSX")
+                .long("check-fsx")
+                .help("List the capacities of FSx file systems alongside the buckets")
+        )
+        .arg(
+            Arg::with_name("COLOR")
+                .env("S3DU_COLOR")
+                .hide_env_values(true)
+                .long("color")
+                .value_name("WHEN")
+                .help("Sets when to color the text output, 'auto' colors it on a terminal unless NO_COLOR is set")
+                .takes_value(true)
+                .default_value(DEFAULT_COLOR)
+                .possible_values(VALID_COLOR_CHOICES)
+        )
+        .arg(
+            Arg::with_name("COST_MODEL")
+                .env("S3DU_COST_MODEL")
+                .hide_env_values(true)
+                .long("cost-model")
+                .value_name("MODEL")
+                .help("Show the estimated monthly cost of each bucket using MODEL prices")
+                .takes_value(true)
+                .possible_values(VALID_COST_MODELS)
+        )
+        .arg(
+            Arg::with_name("COST_MODEL_FILE")
+                .env("S3DU_COST_MODEL_FILE")
+                .hide_env_values(true)
+                .long("cost-model-file")
+                .value_name("FILE")
+                .help("Load the prices for the 'custom' cost model from FILE")
+                .takes_value(true)
+        )
+        .arg(
+            Arg::with_name("DIFF")
+                .env("S3DU_DIFF")
+                .hide_env_values(true)
+                .long("diff")
+                .value_name("FILE")
+                .help("Output the changes in bucket sizes since the snapshot in FILE")
+                .takes_value(true)
+        )
+        .arg(
+            Arg::with_name("DRY_RUN")
+                .long("dry-run")
+                .help("List the buckets that would be sized and estimate the requests to size them")
+                .conflicts_with("LIST_BUCKETS_ONLY")
+        )
+        .arg(
+            Arg::with_name("EXCLUDE_GLACIER")
+                .long("exclude-glacier")
+                .help("Exclude Glacier and Deep Archive storage from bucket sizes")
+        )
+        .arg(
+            Arg::with_name("EXCLUDE_REGION")
+                .env("S3DU_EXCLUDE_REGION")
+                .hide_env_values(true)
+                .long("exclude-region")
+                .value_name("REGION")
+                .help("Skip buckets in REGION when sizing more than one region, may be repeated")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .validator(is_valid_excluded_region)
+        )
+        .arg(
+            Arg::with_name("FAIL_FAST")
+                .long("fail-fast")
+                .visible_alias("fail-on-error")
+                .help("Abort on the first bucket that fails to be sized")
+        )
+        .arg(
+            Arg::with_name("FORMAT_NUMBER")
+                .env("S3DU_FORMAT_NUMBER")
+                .hide_env_values(true)
+                .long("format-number")
+                .value_name("LOCALE")
+                .help("Format sizes in bytes with the thousands separator of LOCALE, eg. en_US")
+                .takes_value(true)
+                .validator(is_valid_locale)
+        )
+        .arg(
+            Arg::with_name("GROUP_BY_REGION")
+                .long("group-by-region")
+                .help("Show buckets grouped by region, with a subtotal for each region")
+        )
+        .arg(
+            Arg::with_name("IGNORE_STORAGE_CLASS")
+                .env("S3DU_IGNORE_STORAGE_CLASS")
+                .hide_env_values(true)
+                .long("ignore-storage-class")
+                .value_name("CLASS")
+                .help("Exclude the given storage class from bucket sizes, may be repeated")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .use_delimiter(true)
+                .validator(is_valid_storage_class)
+        )
+        .arg(
+            Arg::with_name("LIST_BUCKETS_ONLY")
+                .long("list-buckets-only")
+                .help("Output only the names of the buckets that would be sized, without sizing them")
+        )
+        .arg(
+            Arg::with_name("LOG_FORMAT")
+                .env("S3DU_LOG_FORMAT")
+                .hide_env_values(true)
+                .long("log-format")
+                .value_name("FORMAT")
+                .help("Format of the log messages written to stderr")
+                .takes_value(true)
+                .default_value(DEFAULT_LOG_FORMAT)
+                .possible_values(VALID_LOG_FORMATS)
+        )
+        .arg(
+            Arg::with_name("MIN_SIZE")
+                .env("S3DU_MIN_SIZE")
+                .hide_env_values(true)
+                .long("min-size")
+                .value_name("SIZE")
+                .help("Hide buckets smaller than SIZE, eg. 1GiB, from the output")
+                .takes_value(true)
+                .validator(is_valid_size)
+        )
+        .arg(
+            Arg::with_name("MIN_SIZE_AFFECTS_TOTAL")
+                .long("min-size-affects-total")
+                .help("Exclude buckets hidden by --min-size from the total")
+                .requires("MIN_SIZE")
+        )
+        .arg(
+            Arg::with_name("MODE")
+                .env("S3DU_MODE")
+                .hide_env_values(true)
+                .long("mode")
+                .short("m")
+                .value_name("MODE")
+                .help("Use CloudWatch, S3, or both combined to obtain bucket sizes")
+                .takes_value(true)
+                .default_value(DEFAULT_MODE)
+                .possible_values(VALID_MODES)
+        )
+        .arg(
+            Arg::with_name("NO_TOTAL")
+                .long("no-total")
+                .help("Don't show the total size of all buckets in text output")
+        )
+        .arg(
+            Arg::with_name("OUTPUT")
+                .env("S3DU_OUTPUT")
+                .hide_env_values(true)
+                .long("output")
+                .value_name("FORMAT")
+                .help("Sets the format to output bucket sizes in")
+                .takes_value(true)
+                .default_value(DEFAULT_OUTPUT)
+                .possible_values(VALID_OUTPUTS)
+        )
+        .arg(
+            Arg::with_name("OUTPUT_FILE")
+                .env("S3DU_OUTPUT_FILE")
+                .hide_env_values(true)
+                .long("output-file")
+                .value_name("PATH")
+                .help("Write the output to PATH instead of stdout")
+                .takes_value(true)
+        )
+        .arg(
+            Arg::with_name("PROFILE")
+                .env("S3DU_PROFILE")
+                .hide_env_values(true)
+                .long("profile")
+                .short("p")
+                .value_name("PROFILE")
+                .help("Use credentials from the named AWS profile")
+                .takes_value(true)
+        )
+        .arg(
+            Arg::with_name("QUIET")
+                .long("quiet")
+                .short("q")
+                .help("Only output results, suppressing warnings and progress")
+                .conflicts_with("VERBOSE")
+        )
+        .arg(
+            Arg::with_name("REGION")
+                .long("region")
+                .short("r")
+                .value_name("REGION")
+                .help("Set the AWS region to create the client in, or 'all' for --all-regions")
+                .takes_value(true)
+        )
+        .arg(
+            Arg::with_name("ROLE_ARN")
+                .env("S3DU_ROLE_ARN")
+                .hide_env_values(true)
+                .long("role-arn")
+                .visible_alias("assume-role-arn")
+                .value_name("ARN")
+                .help("Assume the given IAM role to obtain credentials")
+                .takes_value(true)
+                .validator(is_valid_role_arn)
+        )
+        .arg(
+            Arg::with_name("ROLE_SESSION_NAME")
+                .env("S3DU_ROLE_SESSION_NAME")
+                .hide_env_values(true)
+                .long("role-session-name")
+                .value_name("NAME")
+                .help("Set the session name used when assuming a role")
+                .takes_value(true)
+                .requires("ROLE_ARN")
+                .validator(is_valid_role_session_name)
+        )
+        .arg(
+            Arg::with_name("SAVE_SNAPSHOT")
+                .env("S3DU_SAVE_SNAPSHOT")
+                .hide_env_values(true)
+                .long("save-snapshot")
+                .value_name("FILE")
+                .help("Save a snapshot of the bucket sizes to FILE")
+                .takes_value(true)
+        )
+        .arg(
+            Arg::with_name("SHOW_REQUEST_COUNT")
+                .long("show-request-count")
+                .help("Print the number of AWS API requests made to each service on stderr")
+        )
+        .arg(
+            Arg::with_name("SKIP_INACCESSIBLE_BUCKETS")
+                .long("skip-inaccessible-buckets")
+                .help("Omit buckets that access is denied to, counting them in a footer")
+        )
+        .arg(
+            Arg::with_name("TIME")
+                .long("time")
+                .help("Show the time that each size was measured at")
+        )
+        .arg(
+            Arg::with_name("TIMEOUT_PER_API_CALL")
+                .env("S3DU_TIMEOUT_PER_API_CALL")
+                .hide_env_values(true)
+                .long("timeout-per-api-call")
+                .value_name("MS")
+                .help("Fail any single AWS API call that takes longer than MS milliseconds")
+                .takes_value(true)
+                .validator(is_valid_api_timeout)
+        )
+        .arg(
+            Arg::with_name("TIMESTAMP_FORMAT")
+                .env("S3DU_TIMESTAMP_FORMAT")
+                .hide_env_values(true)
+                .long("timestamp-format")
+                .value_name("FORMAT")
+                .help("Sets the format to use for timestamp display")
+                .takes_value(true)
+                .default_value(DEFAULT_TIMESTAMP_FORMAT)
+                .possible_values(VALID_TIMESTAMP_FORMATS)
+        )
+        .arg(
+            Arg::with_name("UNIT")
+                .env("S3DU_UNIT")
+                .hide_env_values(true)
+                .long("unit")
                 .short("u")
                 .value_name("UNIT")
                 .help("Sets the unit to use for size display")
                 .takes_value(true)
                 .default_value(DEFAULT_UNIT)
                 .possible_values(VALID_SIZE_UNITS)
+        )
+        .arg(
+            Arg::with_name("VERBOSE")
+                .long("verbose")
+                .short("v")
+                .multiple(true)
+                .help("Increases the log level, may be given multiple times")
+        );
+
+    #[cfg(feature = "cloudwatch")]
+    let app = app
+        .arg(
+            Arg::with_name("CLOUDWATCH_LIST_METRICS_FILTER")
+                .env("S3DU_CLOUDWATCH_LIST_METRICS_FILTER")
+                .hide_env_values(true)
+                .long("cloudwatch-list-metrics-filter")
+                .visible_alias("cloudwatch-dimension-filter")
+                .value_name("DIMENSION=VALUE")
+                .help("Filter CloudWatch metrics on the given dimension, may be repeated")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .use_delimiter(true)
+                .validator(is_valid_dimension_filter)
+        )
+        .arg(
+            Arg::with_name("CLOUDWATCH_METRIC_NAME")
+                .env("S3DU_CLOUDWATCH_METRIC_NAME")
+                .hide_env_values(true)
+                .long("cloudwatch-metric-name")
+                .value_name("NAME")
+                .help("Read bucket sizes from the CloudWatch metric NAME instead of BucketSizeBytes")
+                .takes_value(true)
+                .validator(is_valid_cloudwatch_name)
+        )
+        .arg(
+            Arg::with_name("CLOUDWATCH_NAMESPACE")
+                .env("S3DU_CLOUDWATCH_NAMESPACE")
+                .hide_env_values(true)
+                .long("cloudwatch-namespace")
+                .value_name("NS")
+                .help("Read bucket sizes from the CloudWatch namespace NS instead of AWS/S3")
+                .takes_value(true)
+                .validator(is_valid_cloudwatch_name)
+        )
+        .arg(
+            Arg::with_name("CLOUDWATCH_NEXT_TOKEN_PERSIST")
+                .env("S3DU_CLOUDWATCH_NEXT_TOKEN_PERSIST")
+                .hide_env_values(true)
+                .long("cloudwatch-next-token-persist")
+                .value_name("FILE")
+                .help("Save the CloudWatch metric listing position to FILE, resuming from it on the next run")
+                .takes_value(true)
+        )
+        .arg(
+            Arg::with_name("INCLUDE_CLOUDWATCH_ERRORS")
+                .long("include-cloudwatch-errors")
+                .help("Show why CloudWatch couldn't size buckets in the output")
+        )
+        .arg(
+            Arg::with_name("INCLUDE_OVERHEAD")
+                .env("S3DU_INCLUDE_OVERHEAD")
+                .hide_env_values(true)
+                .long("include-overhead")
+                .value_name("BOOL")
+                .help("Include overhead storage types in CloudWatch bucket sizes")
+                .takes_value(true)
+                .default_value(DEFAULT_INCLUDE_OVERHEAD)
+                .possible_values(VALID_INCLUDE_OVERHEAD)
+        )
+        .arg(
+            Arg::with_name("REPORT_DATE")
+                .env("S3DU_REPORT_DATE")
+                .hide_env_values(true)
+                .long("report-date")
+                .value_name("DATE")
+                .help("Report CloudWatch bucket sizes for the given YYYY-MM-DD date")
+                .takes_value(true)
+                .validator(is_valid_report_date)
+        )
+        .arg(
+            Arg::with_name("REPORT_S3_CLASS_TRANSITIONS")
+                .long("report-s3-class-transitions")
+                .help("Show how the storage class distribution changed over 30 days in CloudWatch mode")
+        )
+        .arg(
+            Arg::with_name("REPORT_TO_CLOUDWATCH")
+                .long("report-to-cloudwatch")
+                .help("Publish the bucket sizes as custom CloudWatch metrics in the s3du namespace")
         );
 
     #[cfg(feature = "s3")]
-    let app = app
-        .arg(
-            Arg::with_name("ENDPOINT")
-                .env("S3DU_ENDPOINT")
-                .hide_env_values(true)
-                .long("endpoint")
-                .short("e")
-                .value_name("URL")
-                .help("Sets a custom endpoint to connect to")
-                .takes_value(true)
-                .validator(is_valid_endpoint)
-        )
-        .arg(
-            Arg::with_name("OBJECT_VERSIONS")
-                .env("S3DU_OBJECT_VERSIONS")
-                .hide_env_values(true)
-                .long("object-versions")
-                .short("o")
-                .value_name("VERSIONS")
-                .help("Set which object versions to sum in S3 mode")
-                .takes_value(true)
-                .default_value(DEFAULT_OBJECT_VERSIONS)
-                .possible_values(OBJECT_VERSIONS)
-        );
+    let app = app
+        .arg(
+            Arg::with_name("AUTO_REGION")
+                .long("auto-region")
+                .help("Size every bucket with a client in the bucket's own region in S3 mode")
+        )
+        .arg(
+            Arg::with_name("CHECK_GLACIER_VAULT")
+                .long("check-glacier-vault")
+                .help("List the sizes of Glacier vaults alongside the buckets in S3 mode")
+        )
+        .arg(
+            Arg::with_name("CHECK_LOGGING")
+                .long("check-logging")
+                .help("Show the server access logging status of each bucket in S3 mode")
+        )
+        .arg(
+            Arg::with_name("CHECK_SELECT_FROM_GLACIER")
+                .long("check-select-from-glacier")
+                .help("Report the active Glacier Select and retrieval jobs in each Glacier vault in S3 mode")
+        )
+        .arg(
+            Arg::with_name("CHECK_VERSIONING_LIFECYCLE")
+                .long("check-versioning-lifecycle")
+                .help("Warn about versioned buckets without a rule expiring old versions in S3 mode")
+        )
+        .arg(
+            Arg::with_name("CHUNK_SIZE")
+                .env("S3DU_CHUNK_SIZE")
+                .hide_env_values(true)
+                .long("chunk-size")
+                .value_name("SIZE")
+                .help("Set how many bucket metadata API calls to make at once in S3 mode")
+                .takes_value(true)
+                .default_value(DEFAULT_CHUNK_SIZE)
+                .validator(is_valid_chunk_size)
+        )
+        .arg(
+            Arg::with_name("COUNT")
+                .long("count")
+                .help("Show counts of object versions and delete markers in S3 mode")
+        )
+        .arg(
+            Arg::with_name("DELIMITER")
+                .env("S3DU_DELIMITER")
+                .hide_env_values(true)
+                .long("delimiter")
+                .value_name("DELIMITER")
+                .help("Group objects into prefixes split on DELIMITER in S3 mode")
+                .takes_value(true)
+                .validator(is_valid_delimiter)
+        )
+        .arg(
+            Arg::with_name("DETECT_EMPTY_PREFIX_NAMESPACES")
+                .long("detect-empty-prefix-namespaces")
+                .help("Warn about buckets with many top-level prefixes, such as one per tenant, in S3 mode")
+        )
+        .arg(
+            Arg::with_name("ENDPOINT")
+                .env("S3DU_ENDPOINT")
+                .hide_env_values(true)
+                .long("endpoint")
+                .visible_alias("endpoint-url")
+                .short("e")
+                .value_name("URL")
+                .help("Sets a custom endpoint to connect to")
+                .takes_value(true)
+                .validator(is_valid_endpoint)
+        )
+        .arg(
+            Arg::with_name("FILTER_BY_CREATION_DATE_RANGE")
+                .env("S3DU_FILTER_BY_CREATION_DATE_RANGE")
+                .hide_env_values(true)
+                .long("filter-by-creation-date-range")
+                .value_name("START..END")
+                .help("Only size buckets created from START up to, but not including, END in S3 mode")
+                .takes_value(true)
+                .validator(is_valid_creation_date_range)
+        )
+        .arg(
+            Arg::with_name("FIND_DUPLICATES")
+                .long("find-duplicates")
+                .help("Report objects that share an ETag and the space they waste in S3 mode")
+        )
+        .arg(
+            Arg::with_name("INCLUDE_MULTIPART_UPLOADS")
+                .long("include-incomplete-multipart-uploads")
+                .visible_alias("include-multipart")
+                .help("Include incomplete multipart uploads in bucket sizes in S3 mode")
+        )
+        .arg(
+            Arg::with_name("INCLUDE_OBJECT_METADATA")
+                .long("include-object-metadata")
+                .help("Estimate the user-defined metadata size per object from a sample in S3 mode")
+        )
+        .arg(
+            Arg::with_name("INCLUDE_SIZE_ON_DISK")
+                .long("include-size-on-disk")
+                .help("Size objects at the minimum billable size of their storage class in S3 mode")
+        )
+        .arg(
+            Arg::with_name("LAST_MODIFIED_AFTER")
+                .env("S3DU_LAST_MODIFIED_AFTER")
+                .hide_env_values(true)
+                .long("last-modified-after")
+                .value_name("DATE")
+                .help("Only size objects last modified on or after DATE in S3 mode")
+                .takes_value(true)
+                .conflicts_with_all(&["NEWER_THAN", "SINCE_DAYS"])
+                .validator(is_valid_date)
+        )
+        .arg(
+            Arg::with_name("LAST_MODIFIED_BEFORE")
+                .env("S3DU_LAST_MODIFIED_BEFORE")
+                .hide_env_values(true)
+                .long("last-modified-before")
+                .value_name("DATE")
+                .help("Only size objects last modified on or before DATE in S3 mode")
+                .takes_value(true)
+                .conflicts_with("OLDER_THAN")
+                .validator(is_valid_date)
+        )
+        .arg(
+            Arg::with_name("MAX_DEPTH")
+                .env("S3DU_MAX_DEPTH")
+                .hide_env_values(true)
+                .long("max-depth")
+                .value_name("DEPTH")
+                .help("Set how many levels of prefixes to output with --delimiter")
+                .takes_value(true)
+                .default_value(DEFAULT_MAX_DEPTH)
+                .validator(is_valid_max_depth)
+        )
+        .arg(
+            Arg::with_name("MAX_OBJECTS")
+                .env("S3DU_MAX_OBJECTS")
+                .hide_env_values(true)
+                .long("max-objects")
+                .value_name("N")
+                .help("Abandon sizing a bucket once more than N objects have been listed in S3 mode")
+                .takes_value(true)
+                .validator(is_valid_max_objects)
+        )
+        .arg(
+            Arg::with_name("METADATA_SAMPLE_SIZE")
+                .env("S3DU_METADATA_SAMPLE_SIZE")
+                .hide_env_values(true)
+                .long("metadata-sample-size")
+                .value_name("SIZE")
+                .help("Set how many objects to sample with --include-object-metadata")
+                .takes_value(true)
+                .default_value(DEFAULT_METADATA_SAMPLE_SIZE)
+                .validator(is_valid_metadata_sample_size)
+        )
+        .arg(
+            Arg::with_name("NAMESPACE_THRESHOLD")
+                .env("S3DU_NAMESPACE_THRESHOLD")
+                .hide_env_values(true)
+                .long("namespace-threshold")
+                .value_name("N")
+                .help("Set how many top-level prefixes make a namespace bucket with --detect-empty-prefix-namespaces")
+                .takes_value(true)
+                .default_value(DEFAULT_NAMESPACE_THRESHOLD)
+                .validator(is_valid_namespace_threshold)
+        )
+        .arg(
+            Arg::with_name("NO_SIGN_REQUEST")
+                .long("no-sign-request")
+                .help("Send unsigned requests, for sizing a public bucket without credentials")
+                .conflicts_with_all(&["PROFILE", "ROLE_ARN"])
+        )
+        .arg(
+            Arg::with_name("OBJECT_AGE_HISTOGRAM")
+                .long("object-age-histogram")
+                .help("Report the ages of the objects in the given bucket in S3 mode")
+        )
+        .arg(
+            Arg::with_name("OBJECT_TAGS_FILTER")
+                .env("S3DU_OBJECT_TAGS_FILTER")
+                .hide_env_values(true)
+                .long("object-tags-filter")
+                .value_name("KEY=VALUE")
+                .help("Only size objects with the given tag in S3 mode, may be repeated")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .validator(is_valid_tag_filter)
+        )
+        .arg(
+            Arg::with_name("OBJECT_VERSIONS")
+                .env("S3DU_OBJECT_VERSIONS")
+                .hide_env_values(true)
+                .long("object-versions")
+                .short("o")
+                .value_name("VERSIONS")
+                .help("Set which object versions to sum in S3 mode")
+                .takes_value(true)
+                .default_value(DEFAULT_OBJECT_VERSIONS)
+                .possible_values(ObjectVersions::VARIANTS)
+        )
+        .arg(
+            Arg::with_name("NEWER_THAN")
+                .env("S3DU_NEWER_THAN")
+                .hide_env_values(true)
+                .long("newer-than")
+                .value_name("AGE")
+                .help("Only size objects modified more recently than AGE ago in S3 mode")
+                .takes_value(true)
+                .conflicts_with("SINCE_DAYS")
+                .validator(is_valid_age)
+        )
+        .arg(
+            Arg::with_name("OLDER_THAN")
+                .env("S3DU_OLDER_THAN")
+                .hide_env_values(true)
+                .long("older-than")
+                .value_name("AGE")
+                .help("Only size objects last modified more than AGE ago in S3 mode")
+                .takes_value(true)
+                .validator(is_valid_age)
+        )
+        .arg(
+            Arg::with_name("PAGE_SIZE")
+                .env("S3DU_PAGE_SIZE")
+                .hide_env_values(true)
+                .long("page-size")
+                .value_name("KEYS")
+                .help("Request at most KEYS objects in each page of object listings in S3 mode, up to 1000")
+                .takes_value(true)
+                .validator(is_valid_page_size)
+        )
+        .arg(
+            Arg::with_name("SHARD_PREFIXES")
+                .env("S3DU_SHARD_PREFIXES")
+                .hide_env_values(true)
+                .long("shard-prefixes")
+                .value_name("PREFIXES")
+                .help("Split bucket listings at the given prefixes to list them concurrently")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .use_delimiter(true)
+        )
+        .arg(
+            Arg::with_name("SINCE_DAYS")
+                .env("S3DU_SINCE_DAYS")
+                .hide_env_values(true)
+                .long("since-days")
+                .value_name("N")
+                .help("Only size objects last modified today or in the N days before in S3 mode")
+                .takes_value(true)
+                .validator(is_valid_since_days)
+        )
+        .arg(
+            Arg::with_name("TAG_FILTER")
+                .env("S3DU_TAG_FILTER")
+                .hide_env_values(true)
+                .long("tag-filter")
+                .value_name("KEY=VALUE")
+                .help("Only size buckets with the given tag in S3 mode, may be repeated")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .validator(is_valid_tag_filter)
+        )
+        .arg(
+            Arg::with_name("TREE")
+                .long("tree")
+                .help("Show the prefixes in each bucket as a tree, down to --max-depth")
+        )
+        .arg(
+            Arg::with_name("VALIDATE_REGION")
+                .env("S3DU_VALIDATE_REGION")
+                .hide_env_values(true)
+                .long("validate-region")
+                .value_name("BUCKET")
+                .help("Check that BUCKET is in the configured region and exit")
+                .takes_value(true)
+                .validator(is_valid_aws_s3_bucket_name)
+        );
+
+    app
+}
+
+/// Returns a `bool` indicating if buckets should be sized in every region,
+/// either with `--all-regions` or the `all` alias for `--region`.
+pub fn is_all_regions(matches: &ArgMatches) -> bool {
+    matches.is_present("ALL_REGIONS")
+        || matches.value_of("REGION") == Some(ALL_REGIONS)
+}
+
+/// Returns the region given with `--region`, unless it's the alias for all
+/// regions.
+///
+/// The name is checked against the AWS regions, unless a custom endpoint is
+/// given, as S3 compatible storage may sign requests for any region name.
+/// There's a chance that this check can be incorrect if AWS releases a
+/// region and Rusoto lags behind on updating the Region list in
+/// `rusoto_core`.
+pub fn region_name<'a>(matches: &'a ArgMatches) -> Result<Option<&'a str>, String> {
+    let name = match matches.value_of("REGION") {
+        Some(name) if name != ALL_REGIONS => name,
+        _                                 => return Ok(None),
+    };
+
+    if !matches.is_present("ENDPOINT") {
+        parse_region(name)?;
+    }
+
+    Ok(Some(name))
+}
+
+/// Returns the name of the bucket to size, given either with `--bucket` or
+/// as the `BUCKET` argument, if any.
+pub fn bucket_name<'a>(matches: &'a ArgMatches) -> Option<&'a str> {
+    matches.value_of("BUCKET_NAME")
+        .or_else(|| matches.value_of("BUCKET"))
+}
+
+/// Returns the name of the first option given that only changes how objects
+/// are listed by S3, if any.
+///
+/// These options have no effect on CloudWatch metrics, so giving them outside
+/// of a mode that lists objects is a mistake that shouldn't go unnoticed.
+#[cfg(feature = "s3")]
+pub fn s3_listing_option(matches: &ArgMatches) -> Option<&'static str> {
+    if matches.is_present("DELIMITER") {
+        return Some("Delimiter");
+    }
+
+    // Object versions always have a value, so only a value other than the
+    // default shows that they were given.
+    if matches.value_of("OBJECT_VERSIONS") != Some(DEFAULT_OBJECT_VERSIONS) {
+        return Some("Object versions");
+    }
+
+    if matches.is_present("PAGE_SIZE") {
+        return Some("Page size");
+    }
+
+    None
+}
+
+/// Returns the `LevelFilter` for the given number of `--verbose` flags, or
+/// `Off` if `quiet` was given.
+///
+/// Warnings are shown by default, each `--verbose` raises this by one level,
+/// up to `Trace`.
+pub fn log_level(verbose: u64, quiet: bool) -> LevelFilter {
+    if quiet {
+        return LevelFilter::OFF;
+    }
+
+    match verbose {
+        0 => LevelFilter::WARN,
+        1 => LevelFilter::INFO,
+        2 => LevelFilter::DEBUG,
+        _ => LevelFilter::TRACE,
+    }
+}
+
+/// Returns an `S3duError::InvalidArguments` error with the given `message`,
+/// for arguments that can't be used together.
+fn invalid<T>(message: &str) -> Result<T> {
+    Err(S3duError::InvalidArguments(message.into()).into())
+}
+
+/// Returns the `OutputMode` that sizes are output in.
+///
+/// Diffs, grouping by region and trees replace the regular output, so they
+/// can't be combined with an output format.
+pub fn output_mode(matches: &ArgMatches) -> Result<OutputMode> {
+    let output = if matches.is_present("DIFF") {
+        if matches.occurrences_of("OUTPUT") > 0 {
+            return invalid("Diff cannot be used with an output format");
+        }
+
+        if matches.is_present("GROUP_BY_REGION") {
+            return invalid("Diff cannot be used when grouping by region");
+        }
+
+        OutputMode::Diff
+    }
+    else if matches.is_present("GROUP_BY_REGION") {
+        if matches.occurrences_of("OUTPUT") > 0 {
+            return invalid("Group by region cannot be used with an output format");
+        }
+
+        // Buckets are output after sizing, without the times they were
+        // sized at.
+        if matches.is_present("TIME") {
+            return invalid("Time cannot be used when grouping by region");
+        }
+
+        OutputMode::GroupByRegion
+    }
+    else {
+        value_t!(matches, "OUTPUT", OutputMode)?
+    };
+
+    // Trees replace the indented prefixes of the text output, drawn from the
+    // prefixes that S3 lists.
+    #[cfg(feature = "s3")]
+    let output = if matches.is_present("TREE") {
+        if output != OutputMode::Text || matches.occurrences_of("OUTPUT") > 0 {
+            return invalid("Tree can only be used with text output");
+        }
+
+        if !value_t!(matches, "MODE", ClientMode)?.uses_s3() {
+            return invalid("Tree supplied but client mode doesn't use S3");
+        }
+
+        OutputMode::Tree
+    }
+    else {
+        output
+    };
+
+    Ok(output)
+}
+
+/// Returns the `ClientConfig` that buckets are sized with, for output in the
+/// given `output` mode.
+///
+/// The region is resolved from the environment or the named profile when
+/// one isn't given. Arguments that can't be used together, such as options
+/// for a client mode other than the one given, are returned as an error.
+///
+/// Only the S3 options depend on the `output`.
+#[cfg_attr(not(feature = "s3"), allow(unused_variables))]
+pub fn client_config(matches: &ArgMatches, output: OutputMode) -> Result<ClientConfig> {
+    // Get the bucket name, if any.
+    let bucket_name = bucket_name(matches).map(|name| name.to_string());
+
+    // Get the client mode
+    let mode = value_t!(matches, "MODE", ClientMode)?;
+
+    // Buckets may be sized in every region, rather than just the one region.
+    let all_regions = is_all_regions(matches);
+
+    // The region given on the command line, if any, which isn't the alias for
+    // all regions.
+    let region_name = region_name(matches)
+        .map_err(S3duError::InvalidArguments)?;
+
+    if all_regions && region_name.is_some() {
+        return invalid("Region supplied but all regions were requested");
+    }
+
+    // Unsigned requests can't list buckets or look up their region, so a
+    // public bucket must be named and is assumed to be in the given region.
+    // CloudWatch always requires credentials.
+    #[cfg(feature = "s3")]
+    let no_sign_request = matches.is_present("NO_SIGN_REQUEST");
+
+    #[cfg(not(feature = "s3"))]
+    let no_sign_request = false;
+
+    if no_sign_request {
+        if mode.uses_cloudwatch() {
+            return invalid("No sign request supplied but client mode uses CloudWatch, which requires credentials");
+        }
+
+        if bucket_name.is_none() {
+            return invalid("No sign request requires a bucket, buckets can't be listed without credentials");
+        }
+
+        if all_regions {
+            return invalid("All regions cannot be used with no sign request");
+        }
+    }
+
+    // If a custom endpoint is set, that is used as the region. Requests to
+    // custom endpoints are signed for the region if one was explicitly given,
+    // as some S3 compatible storage checks it.
+    #[cfg(feature = "s3")]
+    let endpoint_region = if matches.is_present("ENDPOINT") {
+        if all_regions {
+            return invalid("All regions cannot be used with a custom endpoint");
+        }
+
+        #[cfg(feature = "cloudwatch")]
+        {
+            if matches.is_present("REPORT_TO_CLOUDWATCH") {
+                return invalid("Report to CloudWatch cannot be used with a custom endpoint");
+            }
+        }
+
+        if mode == ClientMode::S3 {
+            let endpoint = matches.value_of("ENDPOINT").unwrap();
+
+            let name = region_name
+                .map(|name| name.to_string())
+                .or_else(env_region_name)
+                .unwrap_or_else(|| endpoint::DEFAULT_CUSTOM_REGION_NAME.into());
+
+            let region = endpoint::custom_region(endpoint, &name)
+                .map_err(S3duError::InvalidRegion)?;
+
+            Some(region)
+        }
+        else {
+            return invalid("Endpoint supplied but client mode is not S3, CloudWatch isn't available at custom endpoints");
+        }
+    }
+    else {
+        None
+    };
+
+    // Endpoint selection isn't supported for CloudWatch, so we can drop it if
+    // we're compiled without the S3 feature.
+    #[cfg(not(feature = "s3"))]
+    let endpoint_region: Option<Region> = None;
+
+    // Get the named AWS profile to load credentials from, if any.
+    let profile = matches.value_of("PROFILE").map(|name| name.to_string());
+
+    // Load any overrides of the AWS SDK configuration.
+    let sdk_config = match matches.value_of("AWS_SDK_CONFIG") {
+        Some(path) => SdkConfig::from_file(Path::new(path))?,
+        None       => SdkConfig::default(),
+    };
+
+    // Load the names that buckets are printed with, if any.
+    let bucket_aliases = match matches.value_of("BUCKET_ALIASES") {
+        Some(path) => alias::load_aliases(Path::new(path))?,
+        None       => BucketAliases::new(),
+    };
+
+    // With all regions, a client is created for each region when sizing, so
+    // the default region is only used until then.
+    let region = match endpoint_region {
+        Some(region)        => region,
+        None if all_regions => Region::UsEast1,
+        None                => {
+            resolve_region(region_name, || match profile.as_deref() {
+                Some(profile) => credentials::profile_region(profile),
+                None          => Ok(None),
+            })?
+        },
+    };
+
+    let mut config = ClientConfig {
+        bucket_aliases:    bucket_aliases,
+        bucket_name:       bucket_name,
+        mode:              mode,
+        no_sign_request:   no_sign_request,
+        profile:           profile,
+        region:            region,
+        role_arn:          matches.value_of("ROLE_ARN").map(|arn| arn.into()),
+        role_session_name: matches.value_of("ROLE_SESSION_NAME").map(|name| name.into()),
+        sdk_config:        sdk_config,
+        ..Default::default()
+    };
+
+    // Requests are counted by every client created from the config, so that
+    // clients in every region are counted together.
+    if matches.is_present("SHOW_REQUEST_COUNT") {
+        config.request_counter = Some(RequestCounter::default());
+    }
+
+    // Each individual API call may be limited to a number of milliseconds.
+    // This was validated in the CLI parser.
+    if matches.is_present("TIMEOUT_PER_API_CALL") {
+        let timeout = value_t!(matches, "TIMEOUT_PER_API_CALL", u64)?;
+
+        config.api_timeout = Some(StdDuration::from_millis(timeout));
+    }
+
+    // Validating a bucket's region replaces sizing buckets, the exit code
+    // tells scripts whether the configured region is correct.
+    #[cfg(feature = "s3")]
+    {
+        if matches.is_present("VALIDATE_REGION") && all_regions {
+            return invalid("All regions cannot be used when validating a bucket's region");
+        }
+    }
+
+    // Archived storage classes are excluded if requested, leaving only "hot"
+    // storage in the bucket sizes.
+    if matches.is_present("EXCLUDE_GLACIER") {
+        config.excluded_storage_classes.extend(vec![
+            StorageClass::DeepArchive,
+            StorageClass::GlacierFlexible,
+        ]);
+    }
+
+    // Any other storage classes to exclude. These were validated in the CLI
+    // parser.
+    if let Some(classes) = matches.values_of("IGNORE_STORAGE_CLASS") {
+        for class in classes.map(StorageClass::from) {
+            if !config.excluded_storage_classes.contains(&class) {
+                config.excluded_storage_classes.push(class);
+            }
+        }
+    }
+
+    // If have s3 mode available we also need to pull in the ObjectVersions
+    // and chunk size from the command line.
+    #[cfg(feature = "s3")]
+    {
+        if config.mode.uses_s3() {
+            config.chunk_size = value_t!(matches, "CHUNK_SIZE", usize)?;
+
+            config.object_versions =
+                value_t!(matches, "OBJECT_VERSIONS", ObjectVersions)?;
+
+            config.include_multipart_uploads =
+                matches.is_present("INCLUDE_MULTIPART_UPLOADS");
+
+            config.include_size_on_disk =
+                matches.is_present("INCLUDE_SIZE_ON_DISK");
+
+            // Every object listing requests pages of this size. This was
+            // validated in the CLI parser.
+            if matches.is_present("PAGE_SIZE") {
+                config.page_size = Some(value_t!(matches, "PAGE_SIZE", i64)?);
+            }
+
+            // Versions and delete markers are only seen when listing object
+            // versions.
+            if matches.is_present("COUNT") {
+                let lists_versions = matches!(
+                    config.object_versions,
+                    ObjectVersions::All | ObjectVersions::NonCurrent
+                );
+
+                if !lists_versions {
+                    return invalid("Count can only be used with all or non-current object versions");
+                }
+
+                config.count_versions = true;
+            }
+
+            // Trees split keys on the default delimiter unless another was
+            // given.
+            let delimiter = match matches.value_of("DELIMITER") {
+                Some(delimiter)                    => Some(delimiter),
+                None if output == OutputMode::Tree => Some(DEFAULT_TREE_DELIMITER),
+                None                               => None,
+            };
+
+            // Prefix trees are built from listings of current objects, so
+            // they can't be combined with other object versions.
+            if let Some(delimiter) = delimiter {
+                if config.object_versions != ObjectVersions::Current {
+                    return invalid("Delimiter can only be used with current object versions");
+                }
+
+                config.delimiter = Some(delimiter.into());
+                config.max_depth = value_t!(matches, "MAX_DEPTH", usize)?;
+            }
+
+            // Sharded listings only size current objects, and prefix trees
+            // already list each prefix separately.
+            if let Some(prefixes) = matches.values_of("SHARD_PREFIXES") {
+                if config.object_versions != ObjectVersions::Current {
+                    return invalid("Shard prefixes can only be used with current object versions");
+                }
+
+                if config.delimiter.is_some() {
+                    return invalid("Shard prefixes can't be used with a delimiter");
+                }
+
+                config.shard_prefixes = prefixes
+                    .map(|prefix| prefix.into())
+                    .collect();
+            }
+
+            // Objects are counted while listing a bucket's current objects in
+            // a single pass, which prefix trees and shards don't do.
+            if matches.is_present("MAX_OBJECTS") {
+                if config.object_versions != ObjectVersions::Current {
+                    return invalid("Max objects can only be used with current object versions");
+                }
+
+                if config.delimiter.is_some() {
+                    return invalid("Max objects can't be used with a delimiter");
+                }
+
+                if !config.shard_prefixes.is_empty() {
+                    return invalid("Max objects can't be used with shard prefixes");
+                }
+
+                config.max_objects = Some(value_t!(matches, "MAX_OBJECTS", usize)?);
+            }
+        }
+        else if let Some(option) = s3_listing_option(matches) {
+            return invalid(&format!("{} supplied but client mode is not S3 or combined", option));
+        }
+
+        // Each bucket is sized by a client in its own region, rather than
+        // only the buckets in the given region. Combined mode sizes most
+        // buckets via CloudWatch, which is only queried in one region.
+        if matches.is_present("AUTO_REGION") {
+            if config.mode != ClientMode::S3 {
+                return invalid("Auto region requested but client mode is not S3");
+            }
+
+            if all_regions {
+                return invalid("Auto region cannot be used with all regions");
+            }
+
+            if config.no_sign_request {
+                return invalid("Auto region cannot be used with no sign request, bucket locations require credentials");
+            }
+
+            if matches.is_present("ENDPOINT") {
+                return invalid("Auto region cannot be used with a custom endpoint");
+            }
+
+            config.auto_region = true;
+        }
+
+        // A named bucket is sized in the region that it's in, rather than
+        // being silently filtered out when it isn't in the given region.
+        // Unsigned requests can't look up its region and custom endpoints
+        // have no other regions.
+        let finds_named_bucket = config.mode == ClientMode::S3
+            && config.bucket_name.is_some()
+            && !config.no_sign_request
+            && !matches.is_present("ENDPOINT");
+
+        if finds_named_bucket {
+            debug!("Sizing named bucket in its own region");
+
+            config.auto_region = true;
+        }
+
+        // Buckets that fail to be discovered in their own region are handled
+        // in the same way as buckets that fail to be sized.
+        config.fail_fast         = matches.is_present("FAIL_FAST");
+        config.skip_inaccessible = matches.is_present("SKIP_INACCESSIBLE_BUCKETS");
+
+        // Creation dates are only returned by ListBuckets, which combined
+        // mode doesn't use and unsigned requests can't call.
+        if let Some(range) = matches.value_of("FILTER_BY_CREATION_DATE_RANGE") {
+            if config.mode != ClientMode::S3 {
+                return invalid("Creation date range supplied but client mode is not S3");
+            }
+
+            if config.no_sign_request {
+                return invalid("Creation date range cannot be used with no sign request, buckets can't be listed without credentials");
+            }
+
+            // This should be safe, we validated this in the CLI parser.
+            config.creation_date_range = Some(parse_creation_date_range(range).unwrap());
+        }
+
+        // Combined mode discovers buckets via CloudWatch, which doesn't know
+        // about bucket tags.
+        if let Some(filters) = matches.values_of("TAG_FILTER") {
+            if config.mode != ClientMode::S3 {
+                return invalid("Tag filter supplied but client mode is not S3");
+            }
+
+            if config.no_sign_request {
+                return invalid("Tag filter cannot be used with no sign request, bucket tags require credentials");
+            }
+
+            // This should be safe, we validated these in the CLI parser.
+            config.tag_filters = filters
+                .map(|filter| parse_tag_filter(filter).unwrap())
+                .collect();
+        }
+
+        // Object tags are looked up for every current object listed, so they
+        // can't be applied to buckets sized by CloudWatch, object versions or
+        // prefix trees.
+        if let Some(filters) = matches.values_of("OBJECT_TAGS_FILTER") {
+            if config.mode != ClientMode::S3 {
+                return invalid("Object tags filter supplied but client mode is not S3");
+            }
+
+            if config.object_versions != ObjectVersions::Current {
+                return invalid("Object tags filter can only be used with current object versions");
+            }
+
+            if config.delimiter.is_some() {
+                return invalid("Object tags filter can't be used with a delimiter");
+            }
+
+            warning!("Object tags filter makes a GetObjectTagging call for every object, which may be slow and costly");
+
+            // This should be safe, we validated these in the CLI parser.
+            config.object_tag_filters = filters
+                .map(|filter| parse_tag_filter(filter).unwrap())
+                .collect();
+        }
+
+        // Glacier vaults are sized from their inventory, so are only listed
+        // alongside buckets that S3 sized, in the text output.
+        if matches.is_present("CHECK_GLACIER_VAULT") {
+            if config.mode != ClientMode::S3 {
+                return invalid("Glacier vault check requested but client mode is not S3");
+            }
+
+            if !output.is_text() {
+                return invalid("Glacier vault check can only be used with text output");
+            }
+        }
+
+        // Glacier jobs are reported on stderr, so any output format can be
+        // used.
+        if matches.is_present("CHECK_SELECT_FROM_GLACIER") && config.mode != ClientMode::S3 {
+            return invalid("Glacier Select check requested but client mode is not S3");
+        }
+
+        // Checks are made while sizing buckets by S3, so combined mode would
+        // skip buckets sized by CloudWatch.
+        if matches.is_present("CHECK_LOGGING") {
+            if config.mode != ClientMode::S3 {
+                return invalid("Logging check requested but client mode is not S3");
+            }
+
+            config.check_logging = true;
+        }
+
+        if matches.is_present("CHECK_VERSIONING_LIFECYCLE") {
+            if config.mode != ClientMode::S3 {
+                return invalid("Versioning lifecycle check requested but client mode is not S3");
+            }
+
+            config.check_versioning = true;
+        }
+
+        // Duplicates are found among the current objects kept while S3 sizes
+        // each bucket.
+        if matches.is_present("FIND_DUPLICATES") {
+            if config.mode != ClientMode::S3 {
+                return invalid("Duplicate detection requested but client mode is not S3");
+            }
+
+            if config.object_versions != ObjectVersions::Current {
+                return invalid("Duplicate detection can only be used with current object versions");
+            }
+
+            // The report is output beneath each bucket, which only the text
+            // output has room for.
+            if !output.is_text() {
+                return invalid("Duplicate detection can only be used with text output");
+            }
+
+            config.find_duplicates = true;
+        }
+
+        // The histogram is built from the current objects kept while sizing,
+        // only for a named bucket, as every sized object is held in memory.
+        if matches.is_present("OBJECT_AGE_HISTOGRAM") {
+            if config.mode != ClientMode::S3 {
+                return invalid("Object age histogram requested but client mode is not S3");
+            }
+
+            if config.object_versions != ObjectVersions::Current {
+                return invalid("Object age histogram can only be used with current object versions");
+            }
+
+            if config.bucket_name.is_none() {
+                return invalid("Object age histogram requires a bucket");
+            }
+
+            if !output.is_text() {
+                return invalid("Object age histogram can only be used with text output");
+            }
+
+            config.object_age_histogram = true;
+        }
+
+        // Namespace buckets are found while sizing buckets by S3, like the
+        // other checks.
+        if matches.is_present("DETECT_EMPTY_PREFIX_NAMESPACES") {
+            if config.mode != ClientMode::S3 {
+                return invalid("Namespace detection requested but client mode is not S3");
+            }
+
+            config.namespace_threshold = Some(
+                value_t!(matches, "NAMESPACE_THRESHOLD", usize)?,
+            );
+        }
+
+        // Metadata is only sampled for buckets sized by S3, so combined mode
+        // would leave gaps for buckets sized by CloudWatch.
+        if matches.is_present("INCLUDE_OBJECT_METADATA") {
+            if config.mode != ClientMode::S3 {
+                return invalid("Object metadata requested but client mode is not S3");
+            }
+
+            config.metadata_sample_size = Some(
+                value_t!(matches, "METADATA_SAMPLE_SIZE", usize)?,
+            );
+        }
+
+        // Last modified dates are ignored outside of S3 mode, rather than
+        // failing scheduled reports that are shared between modes.
+        // Since days is a shorthand for a last modified after date.
+        let mut modified_after  = matches.value_of("LAST_MODIFIED_AFTER");
+        let mut modified_before = matches.value_of("LAST_MODIFIED_BEFORE");
+        let mut since_days      = matches.value_of("SINCE_DAYS");
+
+        if config.mode != ClientMode::S3 {
+            if modified_after.is_some() || modified_before.is_some() || since_days.is_some() {
+                warning!("Ignoring last modified dates, client mode is not S3");
+            }
+
+            modified_after  = None;
+            modified_before = None;
+            since_days      = None;
+        }
+
+        let has_dates = modified_after.is_some()
+            || modified_before.is_some()
+            || since_days.is_some();
+
+        // Object ages are only known when listing objects, so combined mode
+        // can't apply them to buckets sized by CloudWatch.
+        let older_than = matches.value_of("OLDER_THAN");
+        let newer_than = matches.value_of("NEWER_THAN");
+
+        if older_than.is_some() || newer_than.is_some() || has_dates {
+            if config.mode != ClientMode::S3 {
+                return invalid("Object age supplied but client mode is not S3");
+            }
+
+            // Both cutoffs are taken from the same time, so that the range
+            // between them is exactly as given.
+            let now = Utc::now();
+
+            // These should be safe, we validated them in the CLI parser.
+            // Dates include the whole day, so objects are sized up to the
+            // midnight following the last modified before date.
+            config.modified_before = older_than
+                .map(|age| parse_age_cutoff(age, now).unwrap())
+                .or_else(|| {
+                    modified_before.map(|date| {
+                        parse_date(date).unwrap() + Duration::days(1)
+                    })
+                });
+
+            config.modified_after = newer_than
+                .map(|age| parse_age_cutoff(age, now).unwrap())
+                .or_else(|| since_days.map(|days| parse_since_days(days, now).unwrap()))
+                .or_else(|| modified_after.map(|date| parse_date(date).unwrap()));
+
+            if let (Some(after), Some(before)) = (config.modified_after, config.modified_before) {
+                if after >= before {
+                    if has_dates {
+                        return invalid("Last modified after must not be later than last modified before");
+                    }
+
+                    return invalid("Newer than age must be longer than older than age");
+                }
+            }
+        }
+    }
+
+    // A named bucket is found from any region, so with all regions it would
+    // be sized once in every region.
+    #[cfg(feature = "s3")]
+    let all_regions = all_regions && !config.auto_region;
+
+    // Regions can only be excluded when more than one region is scanned,
+    // either with all regions or by finding the region of each bucket.
+    if let Some(regions) = matches.values_of("EXCLUDE_REGION") {
+        #[cfg(feature = "s3")]
+        let multiple_regions = all_regions || config.auto_region;
+
+        #[cfg(not(feature = "s3"))]
+        let multiple_regions = all_regions;
+
+        if !multiple_regions {
+            return invalid("Region exclusion requires all regions or auto region");
+        }
+
+        // This should be safe, we validated these in the CLI parser.
+        config.exclude_regions = regions
+            .filter_map(|region| parse_region(region).unwrap())
+            .collect();
+    }
+
+    // If we have cloudwatch mode available we also need to pull in the report
+    // date and list metrics filters from the command line.
+    #[cfg(feature = "cloudwatch")]
+    {
+        if let Some(filters) = matches.values_of("CLOUDWATCH_LIST_METRICS_FILTER") {
+            if !config.mode.uses_cloudwatch() {
+                return invalid("List metrics filter supplied but client mode is not CloudWatch");
+            }
+
+            // This should be safe, we validated these in the CLI parser.
+            config.list_metrics_filters = filters
+                .map(|filter| parse_dimension_filter(filter).unwrap())
+                .collect();
+        }
+
+        // Republished metrics are read in place of the S3 metrics, wherever
+        // CloudWatch is used.
+        if let Some(namespace) = matches.value_of("CLOUDWATCH_NAMESPACE") {
+            if !config.mode.uses_cloudwatch() {
+                return invalid("CloudWatch namespace supplied but client mode is not CloudWatch");
+            }
+
+            config.cloudwatch_namespace = Some(namespace.into());
+        }
+
+        if let Some(metric_name) = matches.value_of("CLOUDWATCH_METRIC_NAME") {
+            if !config.mode.uses_cloudwatch() {
+                return invalid("CloudWatch metric name supplied but client mode is not CloudWatch");
+            }
+
+            config.cloudwatch_metric_name = Some(metric_name.into());
+        }
+
+        // Combined mode would size the buckets on pages that weren't listed
+        // this run via S3, so resuming is only allowed in CloudWatch mode.
+        if let Some(path) = matches.value_of("CLOUDWATCH_NEXT_TOKEN_PERSIST") {
+            if config.mode != ClientMode::CloudWatch {
+                return invalid("Next token file supplied but client mode is not CloudWatch");
+            }
+
+            config.next_token_file = Some(path.into());
+        }
+
+        // Combined mode already falls back to S3 for buckets that CloudWatch
+        // can't size, so errors are only included in CloudWatch mode.
+        if matches.is_present("INCLUDE_CLOUDWATCH_ERRORS") {
+            if config.mode != ClientMode::CloudWatch {
+                return invalid("Include CloudWatch errors can only be used in CloudWatch mode");
+            }
+
+            config.include_cloudwatch_errors = true;
+        }
+
+        // Overhead is included by default, so only an explicitly given value
+        // needs CloudWatch.
+        if matches.occurrences_of("INCLUDE_OVERHEAD") > 0
+            && !config.mode.uses_cloudwatch()
+        {
+            return invalid("Include overhead supplied but client mode is not CloudWatch");
+        }
+
+        config.include_overhead = value_t!(matches, "INCLUDE_OVERHEAD", bool)?;
+
+        if let Some(date) = matches.value_of("REPORT_DATE") {
+            if !config.mode.uses_cloudwatch() {
+                return invalid("Report date supplied but client mode is not CloudWatch");
+            }
+
+            // This should be safe, we validated this in the CLI parser.
+            let date = parse_report_date(date, Utc::now())
+                .unwrap();
+
+            config.report_date = Some(date);
+        }
+
+        if matches.is_present("REPORT_S3_CLASS_TRANSITIONS") {
+            if !config.mode.uses_cloudwatch() {
+                return invalid("Class transitions requested but client mode is not CloudWatch");
+            }
+
+            config.report_class_transitions = true;
+        }
+    }
+
+    Ok(config)
+}
+
+/// Options for sizing buckets and outputting their sizes, which aren't part
+/// of the `ClientConfig`.
+pub struct RunOptions {
+    /// Whether buckets are sized in every region, with a client for each.
+    pub all_regions: bool,
+
+    /// Whether EBS snapshots are listed alongside the buckets.
+    pub check_ebs_snapshots: bool,
+
+    /// Whether ECR repositories are listed alongside the buckets.
+    pub check_ecr: bool,
+
+    /// Whether EFS file systems are listed alongside the buckets.
+    pub check_efs: bool,
+
+    /// Whether FSx file systems are listed alongside the buckets.
+    pub check_fsx: bool,
+
+    /// Whether the jobs still running in Glacier vaults are reported.
+    #[cfg(feature = "s3")]
+    pub check_glacier_jobs: bool,
+
+    /// Whether Glacier vaults are listed alongside the buckets.
+    #[cfg(feature = "s3")]
+    pub check_glacier_vault: bool,
+
+    /// Whether the text output is colored.
+    pub color: bool,
+
+    /// Model that the monthly cost of each bucket is estimated with, if any.
+    pub cost_model: Option<CostModel>,
+
+    /// Path of the snapshot that sizes are diffed against, if any.
+    pub diff_path: Option<PathBuf>,
+
+    /// Whether the buckets that would be sized are listed, along with an
+    /// estimate of the requests to size them, instead of sizing them.
+    pub dry_run: bool,
+
+    /// Whether the first bucket sizing failure aborts the run.
+    pub fail_fast: bool,
+
+    /// Whether CloudWatch errors are shown alongside the sized buckets.
+    pub include_errors: bool,
+
+    /// Whether the buckets that would be sized are listed, without the
+    /// estimate of a dry run.
+    pub list_buckets_only: bool,
+
+    /// Minimum size of the buckets to display, if any.
+    pub min_size: Option<MinSize>,
+
+    /// Mode that sizes are output in.
+    pub output: OutputMode,
+
+    /// Whether the bucket sizes are published to CloudWatch once sized.
+    #[cfg(feature = "cloudwatch")]
+    pub report_to_cloudwatch: bool,
+
+    /// Whether the total size of all buckets is displayed.
+    pub show_total: bool,
+
+    /// Whether buckets that access is denied to are omitted from the output.
+    pub skip_inaccessible: bool,
+
+    /// Path to save a snapshot of the bucket sizes to, if any.
+    pub snapshot_path: Option<PathBuf>,
+
+    /// Format of the timestamps to display, if they're displayed.
+    pub time: Option<TimestampFormat>,
+
+    /// Unit that sizes are displayed in.
+    pub unit: SizeUnit,
+}
+
+/// Returns the `RunOptions` for sizing buckets with the given `config` and
+/// outputting them in the given `output` mode.
+///
+/// Options that can't be used with the output mode or client mode are
+/// returned as an error.
+pub fn run_options(
+    matches: &ArgMatches,
+    output: OutputMode,
+    config: &ClientConfig,
+) -> Result<RunOptions> {
+    // Get the unit size to display, sizes in bytes may be formatted with the
+    // thousands separator of a locale.
+    let unit = value_t!(matches, "UNIT", SizeUnit)?;
+
+    // This should be safe, we validated it in the CLI parser.
+    let unit = match matches.value_of("FORMAT_NUMBER") {
+        Some(name) => unit.with_locale(parse_locale(name).unwrap()),
+        None       => unit,
+    };
+
+    // Decide once whether the text output is colored, from --color, whether
+    // NO_COLOR is set, and whether the output is going to a terminal.
+    let color = value_t!(matches, "COLOR", ColorChoice)?
+        .enabled(matches.is_present("OUTPUT_FILE"));
+
+    // Get the timestamp format, if timestamps are to be displayed.
+    let time = if matches.is_present("TIME") {
+        Some(value_t!(matches, "TIMESTAMP_FORMAT", TimestampFormat)?)
+    }
+    else {
+        None
+    };
+
+    // Get the minimum size of buckets to display, if any.
+    // This should be safe, we validated it in the CLI parser.
+    let min_size = matches.value_of("MIN_SIZE")
+        .map(|size| MinSize {
+            size:          parse_size(size).unwrap(),
+            affects_total: matches.is_present("MIN_SIZE_AFFECTS_TOTAL"),
+        });
+
+    // Storage other than buckets is sized by its own service, so this works
+    // in any client mode, but is only listed in the text output.
+    let storage_checks = [
+        ("CHECK_EBS_SNAPSHOTS", "EBS snapshot"),
+        ("CHECK_ECR",           "ECR"),
+        ("CHECK_EFS",           "EFS"),
+        ("CHECK_FSX",           "FSx"),
+    ];
+
+    for (name, check) in &storage_checks {
+        if matches.is_present(name) && !output.is_text() {
+            return invalid(&format!("{} check can only be used with text output", check));
+        }
+    }
+
+    // A named bucket is found from any region, so with all regions it would
+    // be sized once in every region.
+    #[cfg(feature = "s3")]
+    let all_regions = is_all_regions(matches) && !config.auto_region;
+
+    #[cfg(not(feature = "s3"))]
+    let all_regions = is_all_regions(matches);
+
+    // Costs are estimated from the size of each storage class, which only
+    // CloudWatch reports.
+    let cost_model = match matches.value_of("COST_MODEL") {
+        Some(model) => {
+            if !config.mode.uses_cloudwatch() {
+                return invalid("Cost model supplied but client mode is not CloudWatch");
+            }
+
+            if output != OutputMode::Text {
+                return invalid("Cost model can only be used with text output");
+            }
+
+            let path = matches.value_of("COST_MODEL_FILE").map(Path::new);
+
+            // This should be safe, we validated this in the CLI parser.
+            match (model, path) {
+                ("custom", Some(path)) => Some(CostModel::from_file(path)?),
+                ("custom", None)       => {
+                    return invalid("Custom cost model requires a cost model file");
+                },
+                (_, Some(_))           => {
+                    return invalid("Cost model file can only be used with the custom cost model");
+                },
+                (model, None)          => Some(CostModel::from_str(model).unwrap()),
+            }
+        },
+        None if matches.is_present("COST_MODEL_FILE") => {
+            return invalid("Cost model file supplied without a cost model");
+        },
+        None => None,
+    };
+
+    // A dry run lists the buckets that would be sized, listing buckets only
+    // does the same without the count, for scripts. Neither sizes anything
+    // to diff, save or publish.
+    let dry_run           = matches.is_present("DRY_RUN");
+    let list_buckets_only = matches.is_present("LIST_BUCKETS_ONLY");
+
+    if dry_run || list_buckets_only {
+        let listing = if dry_run { "Dry run" } else { "List buckets only" };
+
+        if matches!(output, OutputMode::Diff | OutputMode::Prometheus) {
+            return invalid(&format!("{} cannot be used with diff or Prometheus output", listing));
+        }
+
+        if matches.is_present("SAVE_SNAPSHOT") {
+            return invalid(&format!("{} cannot be used when saving a snapshot", listing));
+        }
+
+        #[cfg(feature = "cloudwatch")]
+        {
+            if matches.is_present("REPORT_TO_CLOUDWATCH") {
+                return invalid(&format!("{} cannot be used when reporting to CloudWatch", listing));
+            }
+        }
+    }
+
+    let options = RunOptions {
+        all_regions:          all_regions,
+        check_ebs_snapshots:  matches.is_present("CHECK_EBS_SNAPSHOTS"),
+        check_ecr:            matches.is_present("CHECK_ECR"),
+        check_efs:            matches.is_present("CHECK_EFS"),
+        check_fsx:            matches.is_present("CHECK_FSX"),
+        #[cfg(feature = "s3")]
+        check_glacier_jobs:   matches.is_present("CHECK_SELECT_FROM_GLACIER"),
+        #[cfg(feature = "s3")]
+        check_glacier_vault:  matches.is_present("CHECK_GLACIER_VAULT"),
+        color:                color,
+        cost_model:           cost_model,
+        diff_path:            matches.value_of("DIFF").map(PathBuf::from),
+        dry_run:              dry_run,
+        fail_fast:            matches.is_present("FAIL_FAST"),
+        #[cfg(feature = "cloudwatch")]
+        include_errors:       config.include_cloudwatch_errors,
+        #[cfg(not(feature = "cloudwatch"))]
+        include_errors:       false,
+        list_buckets_only:    list_buckets_only,
+        min_size:             min_size,
+        output:               output,
+        #[cfg(feature = "cloudwatch")]
+        report_to_cloudwatch: matches.is_present("REPORT_TO_CLOUDWATCH"),
+        show_total:           !matches.is_present("NO_TOTAL"),
+        skip_inaccessible:    matches.is_present("SKIP_INACCESSIBLE_BUCKETS"),
+        snapshot_path:        matches.value_of("SAVE_SNAPSHOT").map(PathBuf::from),
+        time:                 time,
+        unit:                 unit,
+    };
+
+    Ok(options)
+}
+
+/// Parse the command line arguments
+pub fn parse_args<'a>() -> ArgMatches<'a> {
+    debug!("Parsing command line arguments");
+
+    create_app().get_matches()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rusoto_core::Region;
+
+    use chrono::TimeZone;
+
+    #[cfg(feature = "s3")]
+    use clap::{
+        value_t,
+        ErrorKind,
+    };
+
+    #[test]
+    fn test_bucket_name() {
+        let tests = vec![
+            (vec![],                         None),
+            (vec!["my-bucket"],              Some("my-bucket")),
+            (vec!["--bucket", "my-bucket"],  Some("my-bucket")),
+            (vec!["--bucket=other-bucket"],  Some("other-bucket")),
+        ];
+
+        for test in tests {
+            let args     = test.0;
+            let expected = test.1;
+
+            let matches = create_app()
+                .get_matches_from_safe(vec!["s3du"].into_iter().chain(args))
+                .unwrap();
+
+            assert_eq!(bucket_name(&matches), expected);
+        }
+
+        let ret = create_app()
+            .get_matches_from_safe(vec!["s3du", "--bucket=my-bucket", "other-bucket"]);
+
+        assert!(ret.is_err());
+    }
+
+    #[cfg(feature = "s3")]
+    #[test]
+    fn test_s3_listing_option() {
+        let tests = vec![
+            (vec![],                                     None),
+            (vec!["--object-versions=current"],          None),
+            (vec!["--chunk-size=5"],                     None),
+            (vec!["--delimiter=/"],                      Some("Delimiter")),
+            (vec!["--object-versions=all"],              Some("Object versions")),
+            (vec!["-o", "non-current", "--delimiter=/"], Some("Delimiter")),
+            (vec!["--page-size=100"],                    Some("Page size")),
+        ];
+
+        for test in tests {
+            let args     = test.0;
+            let expected = test.1;
+
+            let matches = create_app()
+                .get_matches_from_safe(vec!["s3du"].into_iter().chain(args))
+                .unwrap();
+
+            let ret = s3_listing_option(&matches);
+
+            assert_eq!(ret, expected);
+        }
+    }
+
+    #[cfg(feature = "s3")]
+    #[test]
+    fn test_object_versions() {
+        let tests = vec![
+            (vec![],                                ObjectVersions::Current),
+            (vec!["--object-versions=all"],         ObjectVersions::All),
+            (vec!["-o", "multipart"],               ObjectVersions::Multipart),
+            (vec!["--object-versions=non-current"], ObjectVersions::NonCurrent),
+        ];
+
+        for test in tests {
+            let args     = test.0;
+            let expected = test.1;
+
+            let matches = create_app()
+                .get_matches_from_safe(vec!["s3du"].into_iter().chain(args))
+                .unwrap();
+
+            let ret = value_t!(matches, "OBJECT_VERSIONS", ObjectVersions)
+                .unwrap();
+
+            assert_eq!(ret, expected);
+        }
+
+        // Invalid values are rejected by clap, before they're parsed.
+        let ret = create_app()
+            .get_matches_from_safe(vec!["s3du", "--object-versions=delete-markers-only"])
+            .unwrap_err();
+
+        assert_eq!(ret.kind, ErrorKind::InvalidValue);
+    }
+
+    #[test]
+    fn test_log_level() {
+        let tests = vec![
+            (0, false, LevelFilter::WARN),
+            (1, false, LevelFilter::INFO),
+            (2, false, LevelFilter::DEBUG),
+            (3, false, LevelFilter::TRACE),
+            (5, false, LevelFilter::TRACE),
+            (0, true,  LevelFilter::OFF),
+        ];
+
+        for test in tests {
+            let (verbose, quiet, expected) = test;
+
+            assert_eq!(log_level(verbose, quiet), expected);
+        }
+    }
+
+    #[test]
+    fn test_parse_locale() {
+        let tests = vec![
+            ("en_US",       Some(Locale::en)),
+            ("en-US",       Some(Locale::en)),
+            ("en_US.UTF-8", Some(Locale::en)),
+            ("en",          Some(Locale::en)),
+            ("de",          Some(Locale::de)),
+            ("de_CH",       Some(Locale::de_CH)),
+            ("de-CH",       Some(Locale::de_CH)),
+            ("xx_XX",       None),
+            ("",            None),
+        ];
+
+        for test in tests {
+            let (name, expected) = test;
+
+            let ret = parse_locale(name).ok();
+
+            assert_eq!(ret, expected);
+        }
+    }
+
+    #[test]
+    fn test_parse_date() {
+        let tests = vec![
+            ("2024-01-01", Some(Utc.ymd(2024, 1, 1).and_hms(0, 0, 0))),
+            ("2024-06-30", Some(Utc.ymd(2024, 6, 30).and_hms(0, 0, 0))),
+            ("2024-02-30", None),
+            ("2024-1-1x",  None),
+            ("",           None),
+        ];
+
+        for test in tests {
+            let date     = test.0;
+            let expected = test.1;
+
+            let ret = parse_date(date).ok();
+
+            assert_eq!(ret, expected);
+        }
+    }
+
+    #[cfg(feature = "s3")]
+    #[test]
+    fn test_parse_creation_date_range() {
+        let start = Utc.ymd(2024, 1, 1).and_hms(0, 0, 0);
+        let end   = Utc.ymd(2024, 2, 1).and_hms(12, 30, 0);
+
+        let tests = vec![
+            ("2024-01-01T00:00:00Z..2024-02-01T12:30:00Z",           Some(start..end)),
+            ("2024-01-01T01:00:00+01:00..2024-02-01T12:30:00.000Z", Some(start..end)),
+            ("2024-02-01T12:30:00Z..2024-01-01T00:00:00Z",           None),
+            ("2024-01-01T00:00:00Z..2024-01-01T00:00:00Z",           None),
+            ("2024-01-01..2024-02-01",                               None),
+            ("2024-01-01T00:00:00Z",                                 None),
+            ("2024-01-01T00:00:00Z..",                               None),
+            ("",                                                     None),
+        ];
+
+        for test in tests {
+            let range    = test.0;
+            let expected = test.1;
+
+            let ret = parse_creation_date_range(range).ok();
+
+            assert_eq!(ret, expected, "{}", range);
+        }
+    }
+
+    #[cfg(feature = "cloudwatch")]
+    #[test]
+    fn test_parse_report_date() {
+        let now = Utc.ymd(2021, 6, 1).and_hms(12, 0, 0);
+
+        let tests = vec![
+            ("2021-06-01", Some(Utc.ymd(2021, 6, 1).and_hms(0, 0, 0))),
+            ("2021-01-15", Some(Utc.ymd(2021, 1, 15).and_hms(0, 0, 0))),
+            ("2020-03-04", Some(Utc.ymd(2020, 3, 4).and_hms(0, 0, 0))),
+            ("2020-03-01", None),
+            ("2021-06-02", None),
+            ("2021-13-01", None),
+            ("yesterday",  None),
+            ("",           None),
+        ];
+
+        for test in tests {
+            let date     = test.0;
+            let expected = test.1;
+
+            let ret = parse_report_date(date, now).ok();
+
+            assert_eq!(ret, expected);
+        }
+    }
+
+    #[cfg(feature = "s3")]
+    #[test]
+    fn test_parse_age_cutoff() {
+        let now = Utc.ymd(2021, 6, 1).and_hms(12, 0, 0);
+
+        let tests = vec![
+            ("30s",             Some(Utc.ymd(2021, 6, 1).and_hms(11, 59, 30))),
+            ("15m",             Some(Utc.ymd(2021, 6, 1).and_hms(11, 45, 0))),
+            ("12h",             Some(Utc.ymd(2021, 6, 1).and_hms(0, 0, 0))),
+            ("7d",              Some(Utc.ymd(2021, 5, 25).and_hms(12, 0, 0))),
+            ("2w",              Some(Utc.ymd(2021, 5, 18).and_hms(12, 0, 0))),
+            ("1y",              Some(Utc.ymd(2020, 6, 1).and_hms(12, 0, 0))),
+            ("0d",              None),
+            ("-1d",             None),
+            ("1.5d",            None),
+            ("d",               None),
+            ("30",              None),
+            ("1mo",             None),
+            ("1€",              None),
+            ("99999999999999y", None),
+            ("",                None),
+        ];
+
+        for test in tests {
+            let age      = test.0;
+            let expected = test.1;
+
+            let ret = parse_age_cutoff(age, now).ok();
+
+            assert_eq!(ret, expected);
+        }
+    }
+
+    #[cfg(feature = "s3")]
+    #[test]
+    fn test_parse_since_days() {
+        let now = Utc.ymd(2021, 6, 1).and_hms(12, 0, 0);
+
+        let tests = vec![
+            ("0",                   Some(Utc.ymd(2021, 6, 1).and_hms(0, 0, 0))),
+            ("1",                   Some(Utc.ymd(2021, 5, 31).and_hms(0, 0, 0))),
+            ("30",                  Some(Utc.ymd(2021, 5, 2).and_hms(0, 0, 0))),
+            ("-1",                  None),
+            ("1.5",                 None),
+            ("1d",                  None),
+            ("99999999999999999",   None),
+            ("",                    None),
+        ];
+
+        for test in tests {
+            let days     = test.0;
+            let expected = test.1;
+
+            let ret = parse_since_days(days, now).ok();
+
+            assert_eq!(ret, expected);
+        }
+
+        // Objects are included if they were last modified at or after the
+        // cutoff.
+        let yesterday = Utc.ymd(2021, 5, 31).and_hms(23, 59, 59);
+        let today     = Utc.ymd(2021, 6, 1).and_hms(0, 0, 1);
+
+        let cutoff = parse_since_days("0", now).unwrap();
+        assert!(today >= cutoff);
+        assert!(yesterday < cutoff);
+
+        let cutoff = parse_since_days("1", now).unwrap();
+        assert!(today >= cutoff);
+        assert!(yesterday >= cutoff);
+        assert!(Utc.ymd(2021, 5, 30).and_hms(23, 59, 59) < cutoff);
+    }
+
+    #[cfg(feature = "cloudwatch")]
+    #[test]
+    fn test_is_valid_cloudwatch_name() {
+        let tests = vec![
+            ("AWS/S3".to_string(),          true),
+            ("BucketSizeBytes".to_string(), true),
+            ("a".repeat(255),               true),
+            ("a".repeat(256),               false),
+            ("".to_string(),                false),
+        ];
+
+        for test in tests {
+            let name  = test.0;
+            let valid = test.1;
+
+            let ret = is_valid_cloudwatch_name(name);
+
+            assert_eq!(ret.is_ok(), valid);
+        }
+    }
+
+    #[cfg(feature = "cloudwatch")]
+    #[test]
+    fn test_parse_dimension_filter() {
+        let tests = vec![
+            ("BucketName=my-bucket",       Some(("BucketName", "my-bucket"))),
+            ("StorageType=GlacierStorage", Some(("StorageType", "GlacierStorage"))),
+            ("Name=a=b",                   Some(("Name", "a=b"))),
+            ("BucketName=",                None),
+            ("=my-bucket",                 None),
+            ("BucketName",                 None),
+            ("",                           None),
+        ];
+
+        for test in tests {
+            let filter   = test.0;
+            let expected = test.1
+                .map(|(name, value)| (name.to_string(), value.to_string()));
+
+            let ret = parse_dimension_filter(filter).ok();
+
+            assert_eq!(ret, expected);
+        }
+    }
+
+    #[cfg(feature = "s3")]
+    #[test]
+    fn test_parse_tag_filter() {
+        let tests = vec![
+            ("team=platform",     Some(("team", "platform"))),
+            ("cost-centre=",      Some(("cost-centre", ""))),
+            ("url=https://a?b=c", Some(("url", "https://a?b=c"))),
+            ("a,b=c,d",           Some(("a,b", "c,d"))),
+            ("=platform",         None),
+            ("team",              None),
+            ("",                  None),
+        ];
 
-    app
-}
+        for test in tests {
+            let filter   = test.0;
+            let expected = test.1
+                .map(|(key, value)| (key.to_string(), value.to_string()));
 
-/// Parse the command line arguments
-pub fn parse_args<'a>() -> ArgMatches<'a> {
-    debug!("Parsing command line arguments");
+            let ret = parse_tag_filter(filter).ok();
 
-    create_app().get_matches()
-}
+            assert_eq!(ret, expected);
+        }
+    }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use rusoto_core::Region;
-    use std::str::FromStr;
+    #[test]
+    fn test_is_valid_api_timeout() {
+        let tests = vec![
+            ("1",     true),
+            ("30000", true),
+            ("0",     false),
+            ("-1",    false),
+            ("1.5",   false),
+            ("ten",   false),
+            ("",      false),
+        ];
+
+        for test in tests {
+            let timeout = test.0;
+            let valid   = test.1;
+
+            let ret = is_valid_api_timeout(timeout.into());
+
+            assert_eq!(ret.is_ok(), valid);
+        }
+    }
+
+    #[cfg(feature = "s3")]
+    #[test]
+    fn test_is_valid_chunk_size() {
+        let tests = vec![
+            ("1",    true),
+            ("10",   true),
+            ("0",    false),
+            ("-1",   false),
+            ("ten",  false),
+            ("",     false),
+        ];
+
+        for test in tests {
+            let size  = test.0;
+            let valid = test.1;
+
+            let ret = is_valid_chunk_size(size.into());
+
+            assert_eq!(ret.is_ok(), valid);
+        }
+    }
+
+    #[cfg(feature = "s3")]
+    #[test]
+    fn test_is_valid_max_objects() {
+        let tests = vec![
+            ("1",       true),
+            ("1000000", true),
+            ("0",       false),
+            ("-1",      false),
+            ("lots",    false),
+            ("",        false),
+        ];
+
+        for test in tests {
+            let max   = test.0;
+            let valid = test.1;
+
+            let ret = is_valid_max_objects(max.into());
+
+            assert_eq!(ret.is_ok(), valid);
+        }
+    }
+
+    #[cfg(feature = "s3")]
+    #[test]
+    fn test_is_valid_page_size() {
+        let tests = vec![
+            ("1",    true),
+            ("1000", true),
+            ("0",    false),
+            ("-1",   false),
+            ("1001", false),
+            ("many", false),
+            ("",     false),
+        ];
+
+        for test in tests {
+            let size  = test.0;
+            let valid = test.1;
+
+            let ret = is_valid_page_size(size.into());
+
+            assert_eq!(ret.is_ok(), valid);
+        }
+    }
+
+    #[cfg(feature = "s3")]
+    #[test]
+    fn test_is_valid_metadata_sample_size() {
+        let tests = vec![
+            ("1",    true),
+            ("100",  true),
+            ("0",    false),
+            ("-1",   false),
+            ("many", false),
+            ("",     false),
+        ];
+
+        for test in tests {
+            let size  = test.0;
+            let valid = test.1;
+
+            let ret = is_valid_metadata_sample_size(size.into());
+
+            assert_eq!(ret.is_ok(), valid);
+        }
+    }
+
+    #[cfg(feature = "s3")]
+    #[test]
+    fn test_is_valid_namespace_threshold() {
+        let tests = vec![
+            ("1",    true),
+            ("100",  true),
+            ("0",    false),
+            ("-1",   false),
+            ("many", false),
+            ("",     false),
+        ];
+
+        for test in tests {
+            let threshold = test.0;
+            let valid     = test.1;
+
+            let ret = is_valid_namespace_threshold(threshold.into());
+
+            assert_eq!(ret.is_ok(), valid);
+        }
+    }
+
+    #[cfg(feature = "s3")]
+    #[test]
+    fn test_is_valid_max_depth() {
+        let tests = vec![
+            ("1",   true),
+            ("5",   true),
+            ("0",   false),
+            ("-1",  false),
+            ("one", false),
+            ("",    false),
+        ];
+
+        for test in tests {
+            let depth = test.0;
+            let valid = test.1;
+
+            let ret = is_valid_max_depth(depth.into());
+
+            assert_eq!(ret.is_ok(), valid);
+        }
+    }
+
+    #[test]
+    fn test_parse_size() {
+        let tests = vec![
+            ("1024",   Some(1_024)),
+            ("0",      Some(0)),
+            ("512B",   Some(512)),
+            ("1KiB",   Some(1_024)),
+            ("1MiB",   Some(1_048_576)),
+            ("1.5GiB", Some(1_610_612_736)),
+            ("1gib",   Some(1_073_741_824)),
+            ("1 TiB",  Some(1_099_511_627_776)),
+            ("1.02KB", Some(1_020)),
+            ("1MB",    Some(1_000_000)),
+            ("2GB",    Some(2_000_000_000)),
+            ("-1KiB",  None),
+            ("1XB",    None),
+            ("GiB",    None),
+            ("NaN",    None),
+            ("",       None),
+        ];
+
+        for test in tests {
+            let size     = test.0;
+            let expected = test.1;
+
+            let ret = parse_size(size).ok();
+
+            assert_eq!(ret, expected);
+        }
+    }
+
+    #[test]
+    fn test_is_valid_storage_class() {
+        let tests = vec![
+            ("GLACIER",         true),
+            ("GLACIER_IR",      true),
+            ("DEEP_ARCHIVE",    true),
+            ("StandardStorage", true),
+            ("glacier",         false),
+            ("COLD",            false),
+            ("",                false),
+        ];
+
+        for test in tests {
+            let class = test.0;
+            let valid = test.1;
+
+            let ret = is_valid_storage_class(class.into());
+
+            assert_eq!(ret.is_ok(), valid);
+        }
+    }
+
+    #[test]
+    fn test_is_valid_role_arn() {
+        let tests = vec![
+            ("arn:aws:iam::123456789012:role/s3du",        true),
+            ("arn:aws:iam::123456789012:role/path/s3du",   true),
+            ("arn:aws-cn:iam::123456789012:role/s3du",     true),
+            ("arn:aws:iam::123456789012:user/s3du",        false),
+            ("arn:aws:s3:::my-bucket",                     false),
+            ("arn:aws:iam:::role/s3du",                    false),
+            ("role/s3du",                                  false),
+            ("",                                           false),
+        ];
+
+        for test in tests {
+            let arn   = test.0;
+            let valid = test.1;
+
+            let ret = is_valid_role_arn(arn.into());
+
+            assert_eq!(ret.is_ok(), valid);
+        }
+    }
+
+    #[test]
+    fn test_is_valid_role_session_name() {
+        let long_invalid = "a".repeat(65);
+
+        let tests = vec![
+            ("s3du",             true),
+            ("s3du@example.org", true),
+            ("s3du_session-1",   true),
+            ("a",                false),
+            ("s3du session",     false),
+            ("s3du/session",     false),
+            (&long_invalid,      false),
+        ];
+
+        for test in tests {
+            let name  = test.0;
+            let valid = test.1;
+
+            let ret = is_valid_role_session_name(name.into());
+
+            assert_eq!(ret.is_ok(), valid);
+        }
+    }
+
+    #[test]
+    fn test_region_name() {
+        let tests = vec![
+            ("eu-central-1",        Ok(Some("eu-central-1"))),
+            ("eu-west-1",           Ok(Some("eu-west-1"))),
+            ("int-space-station-1", Err("Unknown region 'int-space-station-1'".to_string())),
+            ("nope-nope-42",        Err("Unknown region 'nope-nope-42'".to_string())),
+            ("us-east-1",           Ok(Some("us-east-1"))),
+            ("all",                 Ok(None)),
+        ];
+
+        for test in tests {
+            let region   = test.0;
+            let expected = test.1;
+
+            let matches = create_app()
+                .get_matches_from_safe(vec!["s3du", "--region", region])
+                .unwrap();
+
+            assert_eq!(region_name(&matches), expected);
+        }
+
+        let matches = create_app()
+            .get_matches_from_safe(vec!["s3du"])
+            .unwrap();
+
+        assert_eq!(region_name(&matches), Ok(None));
+    }
+
+    #[cfg(feature = "s3")]
+    #[test]
+    fn test_region_name_endpoint() {
+        // S3 compatible storage may use region names that AWS doesn't.
+        let matches = create_app()
+            .get_matches_from_safe(vec![
+                "s3du",
+                "--endpoint-url=https://s3.us-west-002.backblazeb2.com",
+                "--region=us-west-002",
+            ])
+            .unwrap();
+
+        assert_eq!(region_name(&matches), Ok(Some("us-west-002")));
+    }
+
+    #[test]
+    fn test_is_valid_excluded_region() {
+        let tests = vec![
+            ("ap-southeast-1", Ok(())),
+            ("cn-north-1",     Ok(())),
+            ("all",            Err("Cannot exclude all regions".into())),
+            ("nope-nope-42",   Err("Unknown region 'nope-nope-42'".into())),
+        ];
+
+        for test in tests {
+            let region   = test.0;
+            let expected = test.1;
+
+            let ret = is_valid_excluded_region(region.into());
+
+            assert_eq!(ret, expected);
+        }
+    }
 
     #[test]
-    fn test_is_valid_aws_region() {
+    fn test_parse_region() {
         let tests = vec![
-            ("eu-central-1",        true),
-            ("eu-west-1",           true),
-            ("eu-west-2",           true),
-            ("int-space-station-1", false),
-            ("nope-nope-42",        false),
-            ("us-east-1",           true),
+            ("eu-west-1", Ok(Some(Region::EuWest1))),
+            ("us-east-1", Ok(Some(Region::UsEast1))),
+            ("all",       Ok(None)),
+            ("us-esat-1", Err("Unknown region 'us-esat-1'".to_string())),
+            ("ALL",       Err("Unknown region 'ALL'".to_string())),
+            ("",          Err("Unknown region ''".to_string())),
         ];
 
         for test in tests {
-            let region = test.0;
-            let valid  = test.1;
+            let region   = test.0;
+            let expected = test.1;
 
-            let region = Region::from_str(region);
+            let ret = parse_region(region);
 
-            assert_eq!(region.is_ok(), valid);
+            assert_eq!(ret, expected);
         }
     }
 
@@ -322,4 +3188,135 @@ mod tests {
             assert_eq!(ret.is_ok(), valid);
         }
     }
+
+    // Returns the matches for the given arguments.
+    fn matches_from(args: Vec<&str>) -> ArgMatches<'_> {
+        create_app()
+            .get_matches_from_safe(vec!["s3du"].into_iter().chain(args))
+            .unwrap()
+    }
+
+    #[test]
+    fn test_output_mode() {
+        let tests = vec![
+            (vec![],                                       Ok(OutputMode::Text)),
+            (vec!["--output=csv"],                         Ok(OutputMode::Csv)),
+            (vec!["--diff=snapshot.json"],                 Ok(OutputMode::Diff)),
+            (vec!["--group-by-region"],                    Ok(OutputMode::GroupByRegion)),
+            (vec!["--diff=snapshot.json", "--output=csv"], Err("Diff cannot be used with an output format")),
+            (vec!["--group-by-region", "--time"],          Err("Time cannot be used when grouping by region")),
+        ];
+
+        for test in tests {
+            let matches  = matches_from(test.0);
+            let expected = test.1;
+
+            let ret = output_mode(&matches).map_err(|e| e.to_string());
+
+            assert_eq!(ret, expected.map_err(|e| e.to_string()));
+        }
+    }
+
+    #[test]
+    fn test_client_config() {
+        let matches = matches_from(vec![
+            "--region=eu-west-1",
+            "--timeout-per-api-call=500",
+            "my-bucket",
+        ]);
+
+        let config = client_config(&matches, OutputMode::Text).unwrap();
+
+        assert_eq!(config.bucket_name, Some("my-bucket".into()));
+        assert_eq!(config.region, Region::EuWest1);
+        assert_eq!(config.api_timeout, Some(StdDuration::from_millis(500)));
+    }
+
+    #[cfg(feature = "s3")]
+    #[test]
+    fn test_client_config_invalid_arguments() {
+        let tests = vec![
+            (
+                vec!["--mode=s3", "--region=eu-west-1", "--count"],
+                "Count can only be used with all or non-current object versions",
+            ),
+            (
+                vec!["--mode=s3", "--region=eu-west-1", "--object-versions=all", "--delimiter=/"],
+                "Delimiter can only be used with current object versions",
+            ),
+            (
+                vec!["--mode=s3", "--no-sign-request"],
+                "No sign request requires a bucket, buckets can't be listed without credentials",
+            ),
+        ];
+
+        for test in tests {
+            let matches  = matches_from(test.0);
+            let expected = test.1;
+
+            let ret = client_config(&matches, OutputMode::Text).unwrap_err();
+
+            assert!(matches!(
+                ret.downcast_ref::<S3duError>(),
+                Some(S3duError::InvalidArguments(_)),
+            ));
+            assert_eq!(ret.to_string(), expected);
+        }
+    }
+
+    #[test]
+    fn test_run_options() {
+        let matches = matches_from(vec![
+            "--region=eu-west-1",
+            "--min-size=1KiB",
+            "--no-total",
+            "--save-snapshot=snapshot.json",
+        ]);
+
+        let config  = client_config(&matches, OutputMode::Text).unwrap();
+        let options = run_options(&matches, OutputMode::Text, &config).unwrap();
+
+        let min_size = MinSize {
+            size:          1024,
+            affects_total: false,
+        };
+
+        assert_eq!(options.min_size, Some(min_size));
+        assert!(!options.show_total);
+        assert_eq!(options.snapshot_path, Some(PathBuf::from("snapshot.json")));
+        assert!(!options.all_regions);
+        assert!(!options.dry_run);
+    }
+
+    #[test]
+    fn test_run_options_invalid_arguments() {
+        let tests = vec![
+            (
+                vec!["--check-ebs-snapshots", "--output=csv"],
+                OutputMode::Csv,
+                "EBS snapshot check can only be used with text output",
+            ),
+            (
+                vec!["--dry-run", "--save-snapshot=snapshot.json"],
+                OutputMode::Text,
+                "Dry run cannot be used when saving a snapshot",
+            ),
+            (
+                vec!["--list-buckets-only", "--output=prometheus"],
+                OutputMode::Prometheus,
+                "List buckets only cannot be used with diff or Prometheus output",
+            ),
+        ];
+
+        for test in tests {
+            let matches  = matches_from(["--region=eu-west-1"].iter().copied().chain(test.0).collect());
+            let output   = test.1;
+            let expected = test.2;
+
+            let config = client_config(&matches, output).unwrap();
+            let ret    = run_options(&matches, output, &config);
+
+            assert_eq!(ret.err().map(|e| e.to_string()), Some(expected.into()));
+        }
+    }
 }
@@ -12,4 +12,12 @@ mod bucket_sizer;
 /// CloudWatch `Client`.
 mod client;
 
+/// Persisting the `ListMetrics` next token between runs.
+mod next_token;
+
+/// Publishing bucket sizes as custom CloudWatch metrics.
+mod publisher;
+
+pub use bucket_metrics::*;
 pub use client::*;
+pub use publisher::*;
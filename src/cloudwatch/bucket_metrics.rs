@@ -5,37 +5,79 @@ use crate::common::{
     BucketNames,
     StorageTypes,
 };
-use log::debug;
 use rusoto_cloudwatch::Metric;
-use std::collections::HashMap;
+use std::collections::hash_map::{
+    self,
+    HashMap,
+};
+use std::fmt;
+use tracing::debug;
 
 // This Hash is keyed by bucket name and contains a list of storage types that
 // are used within the bucket.
 /// Holds a HashMap of bucket names and their storage types.
-#[derive(Debug, PartialEq)]
+#[derive(PartialEq)]
 pub struct BucketMetrics(pub HashMap<String, StorageTypes>);
 
 impl BucketMetrics {
     /// Return the bucket names from the `BucketMetrics`.
+    ///
+    /// Empty names are never returned, as they can't name a bucket.
     pub fn bucket_names(&self) -> BucketNames {
         debug!(
-            "BucketMetrics::bucket_names: Returning names from: {:#?}",
-            self.0,
+            metrics = %self.debug_full(),
+            "BucketMetrics::bucket_names: Returning names",
         );
 
         self.0
-            .iter()
-            .map(|(k, _v)| k.to_string())
+            .keys()
+            .filter(|k| !k.is_empty())
+            .map(|k| k.to_string())
             .collect()
     }
 
-    /// Return storage types of a given bucket.
-    pub fn storage_types(&self, bucket: &str) -> &StorageTypes {
-        // Unwrap should be safe here, elsewhere we already check that the
-        // bucket is valid.
-        self.0
-            .get(bucket)
-            .unwrap()
+    /// Returns the full debug representation of the `BucketMetrics`,
+    /// including every bucket and its storage types.
+    pub fn debug_full(&self) -> String {
+        format!("{:#?}", self.0)
+    }
+
+    /// Return storage types of a given bucket, or `None` if there are no
+    /// metrics for the bucket.
+    pub fn storage_types(&self, bucket: &str) -> Option<&StorageTypes> {
+        self.0.get(bucket)
+    }
+}
+
+/// Iterates over the bucket names and their storage types, in no particular
+/// order.
+impl IntoIterator for BucketMetrics {
+    type Item     = (String, StorageTypes);
+    type IntoIter = hash_map::IntoIter<String, StorageTypes>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.into_iter()
+    }
+}
+
+/// Iterates over references to the bucket names and their storage types, in
+/// no particular order.
+impl<'a> IntoIterator for &'a BucketMetrics {
+    type Item     = (&'a String, &'a StorageTypes);
+    type IntoIter = hash_map::Iter<'a, String, StorageTypes>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.iter()
+    }
+}
+
+/// Only the number of buckets is shown, since there may be hundreds of them.
+/// Use `debug_full` to show every bucket and its storage types.
+impl fmt::Debug for BucketMetrics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BucketMetrics")
+            .field("count", &self.0.len())
+            .finish()
     }
 }
 
@@ -66,6 +108,14 @@ impl From<Vec<Metric>> for BucketMetrics {
                 }
             }
 
+            // A malformed metric without a bucket name would otherwise add a
+            // bucket with an empty name.
+            if name.is_empty() {
+                debug!(storage_type = %storage_type, "From: Skipping metric without a bucket name");
+
+                continue;
+            }
+
             // Get the existing StorageTypes entry for the bucket, or create a
             // new one if it doesn't exist yet.
             let storage_types = bucket_metrics
@@ -158,6 +208,36 @@ mod tests {
         assert_eq!(metrics, expected);
     }
 
+    #[test]
+    fn test_bucket_metrics_from_skips_missing_bucket_name() {
+        let mut metrics = get_metrics();
+
+        metrics.push(Metric {
+            metric_name: Some("BucketSizeBytes".into()),
+            namespace:   Some("AWS/S3".into()),
+            dimensions:  Some(vec![
+                Dimension {
+                    name:  "StorageType".into(),
+                    value: "StandardStorage".into(),
+                },
+            ]),
+        });
+
+        let metrics: BucketMetrics = metrics.into();
+
+        assert_eq!(metrics.storage_types(""), None);
+        assert_eq!(metrics, get_metrics().into());
+
+        // Empty names are also dropped from metrics built by hand.
+        let mut buckets = HashMap::new();
+        buckets.insert("".into(), vec!["StandardStorage".into()]);
+        buckets.insert("some-bucket-name".into(), vec!["StandardStorage".into()]);
+
+        let ret = BucketMetrics(buckets).bucket_names();
+
+        assert_eq!(ret, vec!["some-bucket-name"]);
+    }
+
     #[test]
     fn test_bucket_metrics_bucket_names() {
         let metrics = get_metrics();
@@ -174,4 +254,119 @@ mod tests {
 
         assert_eq!(ret, expected);
     }
+
+    #[test]
+    fn test_bucket_metrics_debug() {
+        let metrics: BucketMetrics = get_metrics().into();
+
+        let ret = format!("{:?}", metrics);
+
+        assert_eq!(ret, "BucketMetrics { count: 2 }");
+    }
+
+    #[test]
+    fn test_bucket_metrics_debug_full() {
+        let metrics: BucketMetrics = get_metrics().into();
+
+        let ret = metrics.debug_full();
+
+        assert!(ret.contains("\"some-bucket-name\""));
+        assert!(ret.contains("\"some-other-bucket-name\""));
+        assert!(ret.contains("\"StandardIAStorage\""));
+    }
+
+    #[test]
+    fn test_bucket_metrics_from_accumulates_storage_types() {
+        // Two separate metrics for the same bucket, each with a different
+        // storage type.
+        let metrics = vec![
+            Metric {
+                metric_name: Some("BucketSizeBytes".into()),
+                namespace:   Some("AWS/S3".into()),
+                dimensions:  Some(vec![
+                    Dimension {
+                        name:  "BucketName".into(),
+                        value: "some-bucket-name".into(),
+                    },
+                    Dimension {
+                        name:  "StorageType".into(),
+                        value: "StandardStorage".into(),
+                    },
+                ]),
+            },
+            Metric {
+                metric_name: Some("BucketSizeBytes".into()),
+                namespace:   Some("AWS/S3".into()),
+                dimensions:  Some(vec![
+                    Dimension {
+                        name:  "BucketName".into(),
+                        value: "some-bucket-name".into(),
+                    },
+                    Dimension {
+                        name:  "StorageType".into(),
+                        value: "GlacierStorage".into(),
+                    },
+                ]),
+            },
+        ];
+
+        let metrics: BucketMetrics = metrics.into();
+
+        let ret = metrics.storage_types("some-bucket-name").unwrap();
+
+        let expected = vec![
+            "StandardStorage",
+            "GlacierStorage",
+        ];
+
+        assert_eq!(ret, &expected);
+    }
+
+    #[test]
+    fn test_bucket_metrics_storage_types() {
+        let metrics: BucketMetrics = get_metrics().into();
+
+        let tests = vec![
+            ("some-bucket-name",       Some(vec!["StandardIAStorage", "StandardStorage"])),
+            ("some-other-bucket-name", Some(vec!["StandardStorage"])),
+            ("missing-bucket-name",    None),
+        ];
+
+        for test in tests {
+            let bucket   = test.0;
+            let expected = test.1
+                .map(|types| types.iter().map(|t| t.to_string()).collect());
+
+            let ret = metrics.storage_types(bucket).cloned();
+
+            assert_eq!(ret, expected);
+        }
+    }
+
+    #[test]
+    fn test_bucket_metrics_into_iter() {
+        let metrics: BucketMetrics = get_metrics().into();
+
+        let mut ret: Vec<(String, usize)> = (&metrics).into_iter()
+            .map(|(bucket, types)| (bucket.to_owned(), types.len()))
+            .collect();
+
+        ret.sort();
+
+        let expected = vec![
+            ("some-bucket-name".to_string(),       2),
+            ("some-other-bucket-name".to_string(), 1),
+        ];
+
+        assert_eq!(ret, expected);
+
+        // The owned iterator yields the same buckets.
+        let mut ret: Vec<String> = metrics.into_iter()
+            .map(|(bucket, _)| bucket)
+            .collect();
+
+        ret.sort();
+
+        assert_eq!(ret, vec!["some-bucket-name", "some-other-bucket-name"]);
+    }
 }
@@ -1,19 +1,20 @@
 // Implements the BucketSizer trait for CloudWatch Client
 #![forbid(unsafe_code)]
 #![deny(missing_docs)]
-use anyhow::{
-    anyhow,
-    Result,
-};
+use anyhow::Result;
 use async_trait::async_trait;
 use chrono::prelude::*;
 use crate::common::{
     Bucket,
     Buckets,
     BucketSizer,
+    ClassSizes,
+    ClassTrend,
+    S3duError,
+    StorageClass,
 };
-use log::debug;
-use super::bucket_metrics::BucketMetrics;
+use tracing::debug;
+use super::BucketMetrics;
 use super::client::Client;
 
 #[async_trait]
@@ -22,6 +23,12 @@ impl BucketSizer for Client {
     /// We also cache the returned metrics here, since we need to reference this
     /// elsewhere, and we don't want to have to query for it again.
     async fn buckets(&self) -> Result<Buckets> {
+        if let Some(buckets) = self.bucket_cache.lock().unwrap().as_ref() {
+            debug!("buckets: Returning cached buckets");
+
+            return Ok(buckets.to_owned());
+        }
+
         debug!("buckets: Listing...");
 
         let metrics: BucketMetrics = self.list_metrics().await?.into();
@@ -29,30 +36,54 @@ impl BucketSizer for Client {
         let mut buckets = Buckets::new();
 
         for bucket in metrics.bucket_names() {
-            let storage_types = metrics.storage_types(&bucket).to_owned();
-
+            // Drop any storage types that we're excluding from the size.
+            let storage_types = metrics.storage_types(&bucket)
+                .into_iter()
+                .flatten()
+                .filter(|st| !self.is_excluded_storage_type(st))
+                .cloned()
+                .collect();
+
+            // Metrics are only listed for buckets in our region.
             let bucket = Bucket {
+                alias:         None,
                 name:          bucket,
-                region:        None,
+                region:        Some(self.region.to_owned()),
                 storage_types: Some(storage_types),
             };
 
             buckets.push(bucket);
         }
 
+        *self.bucket_cache.lock().unwrap() = Some(buckets.clone());
+
         Ok(buckets)
     }
 
     /// Get the size of a given bucket
-    async fn bucket_size(&self, bucket: &Bucket) -> Result<usize> {
+    async fn bucket_size(&self, bucket: &Bucket) -> Result<u64> {
         let bucket_name = &bucket.name;
 
-        debug!("bucket_size: Calculating size for '{}'", bucket_name);
+        debug!(bucket = %bucket, "bucket_size: Calculating size");
+
+        let mut size: u64 = 0;
+        let mut latest: Option<DateTime<Utc>> = None;
+
+        // Overhead storage types are collapsed into their storage class.
+        let mut class_sizes = ClassSizes::new();
+
+        // Track whether any storage type had datapoints, to tell buckets
+        // without metrics apart from empty buckets.
+        let mut found_datapoints = false;
 
-        let mut size: usize = 0;
+        // Statistics are returned in the same order as the bucket's storage
+        // types, so we can pair them up.
+        let storage_types = bucket.storage_types
+            .as_deref()
+            .unwrap_or_default();
 
         let metric_statistics = self.get_metric_statistics(bucket).await?;
-        for stats in metric_statistics {
+        for (storage_type, stats) in storage_types.iter().zip(metric_statistics) {
             // If we don't get any datapoints, proceed to the next input.
             let mut datapoints = match stats.datapoints {
                 Some(d) => d,
@@ -62,9 +93,7 @@ impl BucketSizer for Client {
             // It's possible that CloudWatch could return nothing. Return an
             // error in this case.
             if datapoints.is_empty() {
-                return Err(
-                    anyhow!("Failed to fetch any CloudWatch datapoints!")
-                )
+                return Err(S3duError::NoDatapoints(bucket_name.into()).into())
             };
 
             // We don't know which order datapoints will be in if we get more
@@ -88,23 +117,79 @@ impl BucketSizer for Client {
 
             let datapoint = &datapoints[0];
 
+            found_datapoints = true;
+
+            // Track the most recent datapoint across all storage types, this
+            // is the time that the bucket size was reported for.
+            if let Some(timestamp) = datapoint.timestamp.as_ref() {
+                let timestamp: DateTime<Utc> = timestamp
+                    .parse()
+                    .expect("Couldn't parse timestamp");
+
+                latest = latest.max(Some(timestamp));
+            }
+
             // BucketSizeBytes only supports Average, so this should be safe
             // to unwrap.
             let bytes = datapoint.average
                 .expect("Could't unwrap average");
 
+            let storage_class = StorageClass::from(storage_type);
+
+            debug!(
+                bucket        = %bucket_name,
+                bytes         = bytes,
+                storage_class = ?storage_class,
+                "bucket_size: Storage class size",
+            );
+
             // Add up the size of each storage type
-            size += bytes as usize;
+            size += bytes as u64;
+
+            *class_sizes.entry(storage_class).or_default() += bytes as u64;
+        }
+
+        if self.include_errors && !storage_types.is_empty() && !found_datapoints {
+            return Err(S3duError::NoDatapoints(bucket_name.into()).into())
         }
 
         debug!(
-            "bucket_size: Calculated bucket size for '{}' is '{}'",
-            bucket_name,
-            size,
+            bucket = %bucket_name,
+            size   = size,
+            "bucket_size: Calculated bucket size",
         );
 
+        if let Some(latest) = latest {
+            self.timestamp_cache.lock().unwrap().insert(bucket_name.into(), latest);
+        }
+
+        self.class_size_cache.lock().unwrap().insert(bucket_name.into(), class_sizes);
+
+        if self.report_class_transitions {
+            let trends = self.get_class_transitions(bucket).await?;
+
+            self.transition_cache.lock().unwrap().insert(bucket_name.into(), trends);
+        }
+
         Ok(size)
     }
+
+    /// Return the timestamp of the most recent datapoint used to size
+    /// `bucket`.
+    fn bucket_timestamp(&self, bucket: &Bucket) -> Option<DateTime<Utc>> {
+        self.timestamp_cache.lock().unwrap().get(&bucket.name).copied()
+    }
+
+    /// Return the size of each storage class found while sizing `bucket`.
+    fn bucket_class_sizes(&self, bucket: &Bucket) -> Option<ClassSizes> {
+        self.class_size_cache.lock().unwrap().get(&bucket.name).cloned()
+    }
+
+    /// Return the change in size of each storage type in `bucket` over the
+    /// class transition window, if it was fetched while sizing `bucket`.
+    fn bucket_transitions(&self, bucket: &Bucket) -> Option<Vec<ClassTrend>> {
+        self.transition_cache.lock().unwrap().get(&bucket.name).cloned()
+    }
 }
 
 #[cfg(test)]
@@ -112,12 +197,17 @@ mod tests {
     use super::*;
     use pretty_assertions::assert_eq;
     use rusoto_cloudwatch::CloudWatchClient;
+    use rusoto_core::Region;
+    use rusoto_core::signature::SignedRequestPayload;
     use rusoto_mock::{
         MockCredentialsProvider,
         MockRequestDispatcher,
         MockResponseReader,
+        MultipleMockRequestDispatcher,
         ReadMockResponse,
     };
+    use std::collections::HashMap;
+    use std::sync::Mutex;
 
     // Create a mock CloudWatch client, returning the data from the specified
     // data_file.
@@ -126,7 +216,7 @@ mod tests {
     ) -> Client {
         let data = match data_file {
             None    => "".to_string(),
-            Some(d) => MockResponseReader::read_response("test-data", d.into()),
+            Some(d) => MockResponseReader::read_response("test-data", d),
         };
 
         let client = CloudWatchClient::new_with(
@@ -136,8 +226,23 @@ mod tests {
         );
 
         Client {
-            client:      client,
-            bucket_name: None,
+            client:                   client,
+            api_timeout:              None,
+            bucket_cache:             Mutex::new(None),
+            bucket_name:              None,
+            class_size_cache:         Mutex::new(HashMap::new()),
+            report_date:              None,
+            report_class_transitions: false,
+            dimension_filters:        Vec::new(),
+            excluded_storage_classes: Vec::new(),
+            include_errors:           false,
+            include_overhead:         true,
+            metric_name:              "BucketSizeBytes".into(),
+            namespace:                "AWS/S3".into(),
+            next_token_file:          None,
+            region:                   Region::UsEast1,
+            timestamp_cache:          Mutex::new(HashMap::new()),
+            transition_cache:         Mutex::new(HashMap::new()),
         }
     }
 
@@ -148,11 +253,11 @@ mod tests {
             "another-bucket-name",
         ];
 
-        let mut client = mock_client(
+        let client = mock_client(
             Some("cloudwatch-list-metrics.xml"),
         );
 
-        let buckets = Client::buckets(&mut client).await.unwrap();
+        let buckets = Client::buckets(&client).await.unwrap();
 
         let mut buckets: Vec<String> = buckets.iter()
             .map(|b| b.name.to_owned())
@@ -163,6 +268,112 @@ mod tests {
         assert_eq!(buckets, expected);
     }
 
+    #[tokio::test]
+    async fn test_buckets_paginated() {
+        let page1 = MockResponseReader::read_response(
+            "test-data",
+            "cloudwatch-list-metrics-page1.xml",
+        );
+
+        let page2 = MockResponseReader::read_response(
+            "test-data",
+            "cloudwatch-list-metrics-page2.xml",
+        );
+
+        // The second page must be requested with the token from the first,
+        // and listing must stop after it as it has no token of its own.
+        let mock = MultipleMockRequestDispatcher::new(vec![
+            MockRequestDispatcher::default().with_body(&page1),
+            MockRequestDispatcher::default()
+                .with_body(&page2)
+                .with_request_checker(|request| {
+                    let payload = match &request.payload {
+                        Some(SignedRequestPayload::Buffer(b)) => {
+                            String::from_utf8(b.to_vec()).unwrap()
+                        },
+                        _ => panic!("ListMetrics request had no payload"),
+                    };
+
+                    assert!(payload.contains("NextToken=page-2-token"));
+                }),
+        ]);
+
+        let mut client = mock_client(None);
+        client.client = CloudWatchClient::new_with(
+            mock,
+            MockCredentialsProvider,
+            Default::default(),
+        );
+
+        let buckets = Client::buckets(&client).await.unwrap();
+
+        let mut ret: Vec<(String, Option<Vec<String>>)> = buckets.into_iter()
+            .map(|b| (b.name, b.storage_types))
+            .collect();
+
+        ret.sort();
+
+        let expected = vec![
+            (
+                "a-bucket-name".to_string(),
+                Some(vec![
+                    "StandardStorage".to_string(),
+                    "StandardIAStorage".to_string(),
+                ]),
+            ),
+            (
+                "another-bucket-name".to_string(),
+                Some(vec!["StandardStorage".to_string()]),
+            ),
+        ];
+
+        assert_eq!(ret, expected);
+    }
+
+    #[tokio::test]
+    async fn test_bucket_count() {
+        // Only a single ListMetrics response is mocked, the count must come
+        // from the bucket cache.
+        let data = MockResponseReader::read_response(
+            "test-data",
+            "cloudwatch-list-metrics.xml",
+        );
+
+        let mock = MultipleMockRequestDispatcher::new(vec![
+            MockRequestDispatcher::default().with_body(&data),
+        ]);
+
+        let mut client = mock_client(None);
+        client.client = CloudWatchClient::new_with(
+            mock,
+            MockCredentialsProvider,
+            Default::default(),
+        );
+
+        let buckets = Client::buckets(&client).await.unwrap();
+        let count   = Client::bucket_count(&client).await.unwrap();
+
+        assert_eq!(count, buckets.len());
+        assert_eq!(count, 2);
+    }
+
+    #[tokio::test]
+    async fn test_bucket_names() {
+        let client = mock_client(
+            Some("cloudwatch-list-metrics.xml"),
+        );
+
+        let buckets = Client::buckets(&client).await.unwrap();
+        let names   = Client::bucket_names(&client).await.unwrap();
+
+        let expected: Vec<String> = buckets.into_iter()
+            .map(|bucket| bucket.name)
+            .collect();
+
+        assert_eq!(names, expected);
+        assert_eq!(names.len(), 2);
+    }
+
     #[tokio::test]
     async fn test_bucket_size() {
         let client = mock_client(
@@ -174,6 +385,7 @@ mod tests {
         ];
 
         let bucket = Bucket {
+            alias:         None,
             name:          "some-other-bucket-name".into(),
             region:        None,
             storage_types: Some(storage_types),
@@ -185,4 +397,163 @@ mod tests {
 
         assert_eq!(ret, expected);
     }
+
+    #[tokio::test]
+    async fn test_bucket_size_no_datapoints() {
+        let bucket = Bucket {
+            alias:         None,
+            name:          "new-bucket-name".into(),
+            region:        None,
+            storage_types: Some(vec!["StandardStorage".into()]),
+        };
+
+        // Without include_errors, a bucket without datapoints is empty.
+        let mut client = mock_client(
+            Some("cloudwatch-get-metric-statistics-empty.xml"),
+        );
+
+        let ret = Client::bucket_size(&client, &bucket).await.unwrap();
+
+        assert_eq!(ret, 0);
+
+        // With include_errors, the missing datapoints are an error.
+        client.include_errors = true;
+
+        let ret = Client::bucket_size(&client, &bucket).await.unwrap_err();
+        let ret = ret.downcast_ref::<S3duError>().unwrap();
+
+        assert!(matches!(ret, S3duError::NoDatapoints(_)));
+    }
+
+    #[tokio::test]
+    async fn test_bucket_size_overhead() {
+        let list_metrics = MockResponseReader::read_response(
+            "test-data",
+            "cloudwatch-list-metrics-overhead.xml",
+        );
+
+        let metric_statistics = MockResponseReader::read_response(
+            "test-data",
+            "cloudwatch-get-metric-statistics.xml",
+        );
+
+        // (include_overhead, storage types, size)
+        let tests = vec![
+            (true,  vec!["StandardIAStorage", "StandardIASizeOverhead"], 246913578),
+            (false, vec!["StandardIAStorage"],                           123456789),
+        ];
+
+        for test in tests {
+            let (include_overhead, storage_types, expected) = test;
+
+            // One GetMetricStatistics response for each storage type.
+            let mut dispatchers = vec![
+                MockRequestDispatcher::default().with_body(&list_metrics),
+            ];
+
+            for _ in &storage_types {
+                dispatchers.push(
+                    MockRequestDispatcher::default().with_body(&metric_statistics),
+                );
+            }
+
+            let mut client = mock_client(None);
+            client.include_overhead = include_overhead;
+            client.client           = CloudWatchClient::new_with(
+                MultipleMockRequestDispatcher::new(dispatchers),
+                MockCredentialsProvider,
+                Default::default(),
+            );
+
+            let buckets = Client::buckets(&client).await.unwrap();
+
+            assert_eq!(buckets[0].storage_types, Some(
+                storage_types.iter().map(|st| st.to_string()).collect()
+            ));
+
+            let ret = Client::bucket_size(&client, &buckets[0]).await.unwrap();
+
+            assert_eq!(ret, expected);
+
+            // Overhead is collapsed into its storage class.
+            let class_sizes = Client::bucket_class_sizes(&client, &buckets[0])
+                .unwrap();
+
+            assert_eq!(class_sizes.get(&StorageClass::StandardIA), Some(&expected));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_bucket_timestamp() {
+        let client = mock_client(
+            Some("cloudwatch-get-metric-statistics.xml"),
+        );
+
+        let bucket = Bucket {
+            alias:         None,
+            name:          "some-other-bucket-name".into(),
+            region:        None,
+            storage_types: Some(vec!["StandardStorage".into()]),
+        };
+
+        // There is no timestamp until the bucket has been sized.
+        assert_eq!(Client::bucket_timestamp(&client, &bucket), None);
+
+        Client::bucket_size(&client, &bucket).await.unwrap();
+
+        let ret = Client::bucket_timestamp(&client, &bucket);
+
+        let expected = Some(Utc.ymd(2020, 3, 1).and_hms(20, 59, 0));
+
+        assert_eq!(ret, expected);
+    }
+
+    #[tokio::test]
+    async fn test_bucket_sizer_trait_object() {
+        let list_metrics = MockResponseReader::read_response(
+            "test-data",
+            "cloudwatch-list-metrics.xml",
+        );
+
+        let metric_statistics = MockResponseReader::read_response(
+            "test-data",
+            "cloudwatch-get-metric-statistics.xml",
+        );
+
+        let mock = MultipleMockRequestDispatcher::new(vec![
+            MockRequestDispatcher::default().with_body(&list_metrics),
+            MockRequestDispatcher::default().with_body(&metric_statistics),
+        ]);
+
+        let mut client = mock_client(None);
+        client.region  = Region::EuWest1;
+        client.client  = CloudWatchClient::new_with(
+            mock,
+            MockCredentialsProvider,
+            Default::default(),
+        );
+
+        // The binary only sees clients as a BucketSizer.
+        let sizer: Box<dyn BucketSizer> = Box::new(client);
+
+        let buckets = sizer.buckets().await.unwrap();
+
+        // Metrics are only listed in the client's region, so every bucket is
+        // in that region.
+        let regions: Vec<Option<Region>> = buckets.iter()
+            .map(|b| b.region.to_owned())
+            .collect();
+
+        assert_eq!(regions, vec![Some(Region::EuWest1); 2]);
+
+        // another-bucket-name only has StandardStorage, so it is sized with a
+        // single GetMetricStatistics call.
+        let bucket = buckets.iter()
+            .find(|b| b.name == "another-bucket-name")
+            .unwrap();
+
+        let ret = sizer.bucket_size(bucket).await.unwrap();
+
+        assert_eq!(ret, 123456789);
+    }
 }
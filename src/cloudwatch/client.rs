@@ -6,9 +6,14 @@ use chrono::prelude::*;
 use chrono::Duration;
 use crate::common::{
     Bucket,
+    Buckets,
+    ClassSizes,
+    ClassTrend,
     ClientConfig,
+    StorageClass,
+    with_api_timeout,
 };
-use log::debug;
+use crate::credentials;
 use rusoto_cloudwatch::{
     CloudWatch,
     CloudWatchClient,
@@ -19,29 +24,140 @@ use rusoto_cloudwatch::{
     ListMetricsInput,
     Metric,
 };
+use rusoto_core::Region;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use tracing::debug;
+use super::next_token::{
+    load_next_token,
+    save_next_token,
+};
+
+/// Namespace of the bucket metrics published by S3.
+const S3_NAMESPACE: &str = "AWS/S3";
+
+/// Name of the bucket size metric published by S3.
+const BUCKET_SIZE_METRIC: &str = "BucketSizeBytes";
+
+/// The number of days of datapoints used to report storage class
+/// transitions.
+const TRANSITION_WINDOW_DAYS: i64 = 30;
+
+/// Returns a `bool` indicating if the given CloudWatch `storage_type` is the
+/// overhead that S3 charges for objects in some storage classes, such as the
+/// minimum object size of `StandardIASizeOverhead` or the index of
+/// `GlacierS3ObjectOverhead`.
+fn is_overhead_storage_type(storage_type: &str) -> bool {
+    storage_type.ends_with("Overhead")
+}
 
 /// A CloudWatch `Client`
 pub struct Client {
     /// The Rusoto `CloudWatchClient`.
     pub client: CloudWatchClient,
 
+    /// Maximum time that each API call may take, if any.
+    pub api_timeout: Option<std::time::Duration>,
+
+    /// Cache of the `Buckets` discovered by this client.
+    pub bucket_cache: Mutex<Option<Buckets>>,
+
     /// Bucket name that was selected, if any.
     pub bucket_name: Option<String>,
+
+    /// Sizes of each storage class used to size each bucket, keyed on bucket
+    /// name.
+    pub class_size_cache: Mutex<HashMap<String, ClassSizes>>,
+
+    /// Date that the metric statistics query window is centered on, if any.
+    pub report_date: Option<DateTime<Utc>>,
+
+    /// Whether the size of each storage type over the last 30 days is
+    /// fetched while sizing buckets.
+    pub report_class_transitions: bool,
+
+    /// Additional dimension filters applied when listing metrics.
+    pub dimension_filters: Vec<DimensionFilter>,
+
+    /// Storage classes that are excluded from the bucket size.
+    pub excluded_storage_classes: Vec<StorageClass>,
+
+    /// Whether buckets without any datapoints are reported as errors, rather
+    /// than having a size of `0`.
+    pub include_errors: bool,
+
+    /// Whether overhead storage types are included in the bucket size.
+    pub include_overhead: bool,
+
+    /// Name of the metric that bucket sizes are read from.
+    pub metric_name: String,
+
+    /// Namespace that bucket size metrics are read from.
+    pub namespace: String,
+
+    /// File that the `ListMetrics` next token is saved in while listing, so
+    /// that an interrupted listing can be resumed, if any.
+    pub next_token_file: Option<PathBuf>,
+
+    /// `Region` that we're listing metrics in.
+    pub region: Region,
+
+    /// Timestamps of the datapoints used to size each bucket, keyed on
+    /// bucket name.
+    pub timestamp_cache: Mutex<HashMap<String, DateTime<Utc>>>,
+
+    /// Changes in the size of each storage type over the class transition
+    /// window, keyed on bucket name.
+    pub transition_cache: Mutex<HashMap<String, Vec<ClassTrend>>>,
 }
 
 impl Client {
     /// Return a new `Client` with the given `ClientConfig`.
     pub fn new(config: ClientConfig) -> Self {
+        let aws_client  = credentials::aws_client(&config);
         let bucket_name = config.bucket_name;
         let region      = config.region;
 
-        debug!("new: Creating CloudWatchClient in region '{}'", region.name());
+        let metric_name = config.cloudwatch_metric_name
+            .unwrap_or_else(|| BUCKET_SIZE_METRIC.into());
 
-        let client = CloudWatchClient::new(region);
+        let namespace = config.cloudwatch_namespace
+            .unwrap_or_else(|| S3_NAMESPACE.into());
+
+        let dimension_filters = config.list_metrics_filters
+            .into_iter()
+            .map(|(name, value)| DimensionFilter {
+                name:  name,
+                value: Some(value),
+            })
+            .collect();
+
+        debug!(region = region.name(), "new: Creating CloudWatchClient");
+
+        let client = CloudWatchClient::new_with_client(
+            aws_client,
+            region.to_owned(),
+        );
 
         Self {
-            client:      client,
-            bucket_name: bucket_name,
+            client:                   client,
+            api_timeout:              config.api_timeout,
+            bucket_cache:             Mutex::new(None),
+            bucket_name:              bucket_name,
+            class_size_cache:         Mutex::new(HashMap::new()),
+            report_date:              config.report_date,
+            report_class_transitions: config.report_class_transitions,
+            dimension_filters:        dimension_filters,
+            excluded_storage_classes: config.excluded_storage_classes,
+            include_errors:           config.include_cloudwatch_errors,
+            include_overhead:         config.include_overhead,
+            metric_name:              metric_name,
+            namespace:                namespace,
+            next_token_file:          config.next_token_file,
+            region:                   region,
+            timestamp_cache:          Mutex::new(HashMap::new()),
+            transition_cache:         Mutex::new(HashMap::new()),
         }
     }
 
@@ -53,10 +169,9 @@ impl Client {
         &self,
         bucket: &Bucket,
     ) -> Result<Vec<GetMetricStatisticsOutput>> {
-        debug!("get_metric_statistics: Processing {:?}", bucket);
+        debug!(bucket = ?bucket, "get_metric_statistics: Processing");
 
-        let now: DateTime<Utc> = Utc::now();
-        let one_day            = Duration::days(1);
+        let (start_time, end_time) = self.metric_window();
 
         let storage_types = match &bucket.storage_types {
             Some(st) => st.to_owned(),
@@ -66,48 +181,167 @@ impl Client {
         let inputs: Vec<GetMetricStatisticsInput> = storage_types
             .iter()
             .map(|storage_type| {
-                let dimensions = vec![
-                    Dimension {
-                        name:  "BucketName".into(),
-                        value: bucket.name.to_owned(),
-                    },
-                    Dimension {
-                        name:  "StorageType".into(),
-                        value: storage_type.to_owned(),
-                    },
-                ];
-
-                GetMetricStatisticsInput {
-                    dimensions:  Some(dimensions),
-                    end_time:    self.iso8601(now),
-                    metric_name: "BucketSizeBytes".into(),
-                    namespace:   "AWS/S3".into(),
-                    period:      one_day.num_seconds(),
-                    start_time:  self.iso8601(now - (one_day * 2)),
-                    statistics:  Some(vec!["Average".into()]),
-                    unit:        Some("Bytes".into()),
-                    ..Default::default()
-                }
+                self.metric_statistics_input(
+                    &bucket.name,
+                    storage_type,
+                    start_time,
+                    end_time,
+                )
             })
             .collect();
 
         let mut outputs = Vec::new();
 
         for input in inputs {
-            let output = self.client.get_metric_statistics(input).await?;
+            let output = with_api_timeout(
+                self.api_timeout,
+                self.client.get_metric_statistics(input),
+            ).await?;
+
             outputs.push(output);
         }
 
         Ok(outputs)
     }
 
+    /// Returns the change in size of each of the storage types of `bucket`
+    /// over the `TRANSITION_WINDOW_DAYS` leading up to the report date.
+    ///
+    /// Storage types without any datapoints in the window are skipped.
+    /// CloudWatch only reports `NumberOfObjects` for all storage types
+    /// together, so the trends are based on `BucketSizeBytes`.
+    pub async fn get_class_transitions(
+        &self,
+        bucket: &Bucket,
+    ) -> Result<Vec<ClassTrend>> {
+        debug!(bucket = ?bucket, "get_class_transitions: Processing");
+
+        let end_time   = self.report_date.unwrap_or_else(Utc::now);
+        let start_time = end_time - Duration::days(TRANSITION_WINDOW_DAYS);
+
+        let storage_types = match &bucket.storage_types {
+            Some(st) => st.to_owned(),
+            None     => Vec::new(),
+        };
+
+        let mut trends = Vec::new();
+
+        for storage_type in storage_types {
+            let input = self.metric_statistics_input(
+                &bucket.name,
+                &storage_type,
+                start_time,
+                end_time,
+            );
+
+            let output = with_api_timeout(
+                self.api_timeout,
+                self.client.get_metric_statistics(input),
+            ).await?;
+
+            // Pair each average with its timestamp, so that the earliest and
+            // latest datapoints can be found.
+            let mut datapoints: Vec<(DateTime<Utc>, f64)> = output.datapoints
+                .unwrap_or_default()
+                .into_iter()
+                .filter_map(|datapoint| {
+                    let timestamp = datapoint.timestamp?.parse().ok()?;
+
+                    Some((timestamp, datapoint.average?))
+                })
+                .collect();
+
+            datapoints.sort_by_key(|datapoint| datapoint.0);
+
+            let (first, last) = match (datapoints.first(), datapoints.last()) {
+                (Some(first), Some(last)) => (first.1, last.1),
+                _                         => continue,
+            };
+
+            trends.push(ClassTrend {
+                storage_type: storage_type,
+                start:        first as u64,
+                end:          last as u64,
+            });
+        }
+
+        Ok(trends)
+    }
+
+    /// Returns the `GetMetricStatisticsInput` for the daily average of our
+    /// bucket size metric for `storage_type` in `bucket_name` between
+    /// `start_time` and `end_time`.
+    fn metric_statistics_input(
+        &self,
+        bucket_name: &str,
+        storage_type: &str,
+        start_time: DateTime<Utc>,
+        end_time: DateTime<Utc>,
+    ) -> GetMetricStatisticsInput {
+        let dimensions = vec![
+            Dimension {
+                name:  "BucketName".into(),
+                value: bucket_name.into(),
+            },
+            Dimension {
+                name:  "StorageType".into(),
+                value: storage_type.into(),
+            },
+        ];
+
+        GetMetricStatisticsInput {
+            dimensions:  Some(dimensions),
+            end_time:    self.iso8601(end_time),
+            metric_name: self.metric_name.to_owned(),
+            namespace:   self.namespace.to_owned(),
+            period:      Duration::days(1).num_seconds(),
+            start_time:  self.iso8601(start_time),
+            statistics:  Some(vec!["Average".into()]),
+            unit:        Some("Bytes".into()),
+            ..Default::default()
+        }
+    }
+
+    /// Returns the start and end times of the metric statistics query window.
+    ///
+    /// If a `report_date` was given, the window is centered on it, otherwise
+    /// the window covers the two days leading up to the current time.
+    pub fn metric_window(&self) -> (DateTime<Utc>, DateTime<Utc>) {
+        let one_day = Duration::days(1);
+
+        match self.report_date {
+            Some(date) => (date - one_day, date + one_day),
+            None       => {
+                let now: DateTime<Utc> = Utc::now();
+
+                (now - (one_day * 2), now)
+            },
+        }
+    }
+
+    /// Returns a `bool` indicating if the given CloudWatch `storage_type` is
+    /// excluded from the bucket size.
+    ///
+    /// Overhead storage types are excluded when overhead isn't included, as
+    /// well as when their storage class is excluded.
+    pub fn is_excluded_storage_type(&self, storage_type: &str) -> bool {
+        if !self.include_overhead && is_overhead_storage_type(storage_type) {
+            return true;
+        }
+
+        let storage_class = StorageClass::from(storage_type);
+
+        self.excluded_storage_classes.contains(&storage_class)
+    }
+
     /// Return an ISO8601 formatted timestamp suitable for
     /// `GetMetricsStatisticsInput`.
     pub fn iso8601(&self, dt: DateTime<Utc>) -> String {
         dt.to_rfc3339_opts(SecondsFormat::Secs, true)
     }
 
-    /// Get list of buckets with `BucketSizeBytes` metrics.
+    /// Get list of buckets with bucket size metrics, `BucketSizeBytes` in
+    /// `AWS/S3` unless another metric name or namespace was configured.
     ///
     /// An individual metric resembles the following:
     /// ```rust
@@ -126,23 +360,42 @@ impl Client {
     ///   ]),
     /// }
     /// ```
+    ///
+    /// If we have a `next_token_file`, listing resumes from the token saved
+    /// in it, and the token is saved after each page until listing completes.
     pub async fn list_metrics(&self) -> Result<Vec<Metric>> {
         debug!("list_metrics: Listing...");
 
         let mut metrics    = Vec::new();
-        let mut next_token = None;
-
-        // If we selected a bucket to list, filter for it here.
-        let dimensions = match self.bucket_name.as_ref() {
-            Some(bucket_name) => {
-                let filter = DimensionFilter {
-                    name: "BucketName".into(),
-                    value: Some(bucket_name.to_owned()),
-                };
+        let mut next_token = match self.next_token_file.as_ref() {
+            Some(path) => load_next_token(path)?,
+            None       => None,
+        };
 
-                Some(vec![filter])
-            },
-            None => None,
+        if next_token.is_some() {
+            debug!(next_token = ?next_token, "list_metrics: Resuming listing");
+        }
+
+        // If we selected a bucket to list, filter for it here, along with any
+        // other dimension filters we were given.
+        let mut dimensions = Vec::new();
+
+        if let Some(bucket_name) = self.bucket_name.as_ref() {
+            let filter = DimensionFilter {
+                name: "BucketName".into(),
+                value: Some(bucket_name.to_owned()),
+            };
+
+            dimensions.push(filter);
+        }
+
+        dimensions.extend(self.dimension_filters.iter().cloned());
+
+        let dimensions = if dimensions.is_empty() {
+            None
+        }
+        else {
+            Some(dimensions)
         };
 
         // We loop until we've processed everything.
@@ -150,22 +403,29 @@ impl Client {
             // Input for CloudWatch API
             let list_metrics_input = ListMetricsInput {
                 dimensions:  dimensions.clone(),
-                metric_name: Some("BucketSizeBytes".into()),
-                namespace:   Some("AWS/S3".into()),
+                metric_name: Some(self.metric_name.to_owned()),
+                namespace:   Some(self.namespace.to_owned()),
                 next_token:  next_token,
                 ..Default::default()
             };
 
             // Call the API
-            let output = self.client.list_metrics(list_metrics_input).await?;
+            let output = with_api_timeout(
+                self.api_timeout,
+                self.client.list_metrics(list_metrics_input),
+            ).await?;
 
-            debug!("list_metrics: API returned: {:#?}", output);
+            debug!(output = ?output, "list_metrics: API returned");
 
             // If we get any metrics, append them to our vec
             if let Some(m) = output.metrics {
                 metrics.append(&mut m.clone());
             }
 
+            if let Some(path) = self.next_token_file.as_ref() {
+                save_next_token(path, output.next_token.as_deref())?;
+            }
+
             // If there was a next token, use it, otherwise the loop is done.
             match output.next_token {
                 Some(t) => next_token = Some(t),
@@ -173,7 +433,7 @@ impl Client {
             };
         }
 
-        debug!("list_metrics: Metrics collection: {:#?}", metrics);
+        debug!(metrics = ?metrics, "list_metrics: Metrics collection");
 
         Ok(metrics)
     }
@@ -182,18 +442,22 @@ impl Client {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::test_helpers::temp_path;
     use pretty_assertions::assert_eq;
     use rusoto_cloudwatch::{
         Datapoint,
         Dimension,
         Metric,
     };
+    use rusoto_core::signature::SignedRequestPayload;
     use rusoto_mock::{
         MockCredentialsProvider,
         MockRequestDispatcher,
         MockResponseReader,
+        MultipleMockRequestDispatcher,
         ReadMockResponse,
     };
+    use std::fs;
 
     // Create a mock CloudWatch client, returning the data from the specified
     // data_file.
@@ -202,7 +466,7 @@ mod tests {
     ) -> Client {
         let data = match data_file {
             None    => "".to_string(),
-            Some(d) => MockResponseReader::read_response("test-data", d.into()),
+            Some(d) => MockResponseReader::read_response("test-data", d),
         };
 
         let client = CloudWatchClient::new_with(
@@ -212,8 +476,23 @@ mod tests {
         );
 
         Client {
-            client:      client,
-            bucket_name: None,
+            client:                   client,
+            api_timeout:              None,
+            bucket_cache:             Mutex::new(None),
+            bucket_name:              None,
+            class_size_cache:         Mutex::new(HashMap::new()),
+            report_date:              None,
+            report_class_transitions: false,
+            dimension_filters:        Vec::new(),
+            excluded_storage_classes: Vec::new(),
+            include_errors:           false,
+            include_overhead:         true,
+            metric_name:              "BucketSizeBytes".into(),
+            namespace:                "AWS/S3".into(),
+            next_token_file:          None,
+            region:                   Region::UsEast1,
+            timestamp_cache:          Mutex::new(HashMap::new()),
+            transition_cache:         Mutex::new(HashMap::new()),
         }
     }
 
@@ -228,6 +507,7 @@ mod tests {
         ];
 
         let bucket = Bucket {
+            alias:         None,
             name:          "test-bucket".into(),
             region:        None,
             storage_types: Some(storage_types),
@@ -256,6 +536,111 @@ mod tests {
         assert_eq!(ret, expected);
     }
 
+    #[tokio::test]
+    async fn test_get_class_transitions() {
+        let client = mock_client(
+            Some("cloudwatch-get-metric-statistics-history.xml"),
+        );
+
+        let storage_types = vec![
+            "StandardStorage".into(),
+        ];
+
+        let bucket = Bucket {
+            alias:         None,
+            name:          "test-bucket".into(),
+            region:        None,
+            storage_types: Some(storage_types),
+        };
+
+        let ret = client.get_class_transitions(&bucket)
+            .await
+            .unwrap();
+
+        // Datapoints are unordered, the earliest and latest are used.
+        let expected = vec![
+            ClassTrend {
+                storage_type: "StandardStorage".into(),
+                start:        4000,
+                end:          1000,
+            },
+        ];
+
+        assert_eq!(ret, expected);
+    }
+
+    #[tokio::test]
+    async fn test_get_class_transitions_no_datapoints() {
+        let client = mock_client(
+            Some("cloudwatch-get-metric-statistics-empty.xml"),
+        );
+
+        let bucket = Bucket {
+            alias:         None,
+            name:          "test-bucket".into(),
+            region:        None,
+            storage_types: Some(vec!["StandardStorage".into()]),
+        };
+
+        let ret = client.get_class_transitions(&bucket)
+            .await
+            .unwrap();
+
+        assert_eq!(ret, Vec::new());
+    }
+
+    #[test]
+    fn test_is_excluded_storage_type() {
+        let mut client = mock_client(None);
+        client.excluded_storage_classes = vec![
+            StorageClass::DeepArchive,
+            StorageClass::GlacierFlexible,
+        ];
+
+        let tests = vec![
+            ("DeepArchiveStorage",      true),
+            ("GlacierS3ObjectOverhead", true),
+            ("GlacierStorage",          true),
+            ("GlacierIRStorage",        false),
+            ("StandardIAStorage",       false),
+            ("StandardStorage",         false),
+        ];
+
+        for test in tests {
+            let storage_type = test.0;
+            let expected     = test.1;
+
+            let ret = client.is_excluded_storage_type(storage_type);
+
+            assert_eq!(ret, expected);
+        }
+    }
+
+    #[test]
+    fn test_is_excluded_storage_type_overhead() {
+        let mut client = mock_client(None);
+
+        // (include_overhead, storage_type, expected)
+        let tests = vec![
+            (true,  "StandardIASizeOverhead",  false),
+            (true,  "GlacierS3ObjectOverhead", false),
+            (true,  "StandardIAStorage",       false),
+            (false, "StandardIASizeOverhead",  true),
+            (false, "GlacierS3ObjectOverhead", true),
+            (false, "StandardIAStorage",       false),
+        ];
+
+        for test in tests {
+            let (include_overhead, storage_type, expected) = test;
+
+            client.include_overhead = include_overhead;
+
+            let ret = client.is_excluded_storage_type(storage_type);
+
+            assert_eq!(ret, expected, "{} {}", include_overhead, storage_type);
+        }
+    }
+
     #[test]
     fn test_iso8601() {
         let dt       = Utc.ymd(2020, 3, 1).and_hms(0, 16, 27);
@@ -267,13 +652,207 @@ mod tests {
         assert_eq!(ret, expected);
     }
 
+    #[test]
+    fn test_metric_window_report_date() {
+        let mut client = mock_client(None);
+        client.report_date = Some(Utc.ymd(2020, 3, 1).and_hms(0, 0, 0));
+
+        let ret = Client::metric_window(&client);
+
+        let expected = (
+            Utc.ymd(2020, 2, 29).and_hms(0, 0, 0),
+            Utc.ymd(2020, 3, 2).and_hms(0, 0, 0),
+        );
+
+        assert_eq!(ret, expected);
+    }
+
+    #[test]
+    fn test_metric_window_now() {
+        let client = mock_client(None);
+
+        let (start, end) = Client::metric_window(&client);
+
+        assert_eq!(end - start, Duration::days(2));
+        assert!(end <= Utc::now());
+    }
+
+    #[tokio::test]
+    async fn test_list_metrics_dimension_filters() {
+        let data = MockResponseReader::read_response(
+            "test-data",
+            "cloudwatch-list-metrics.xml",
+        );
+
+        // The bucket name filter comes first, followed by the dimension
+        // filters in the order they were given.
+        let dispatcher = MockRequestDispatcher::default()
+            .with_body(&data)
+            .with_request_checker(|request| {
+                let payload = match &request.payload {
+                    Some(SignedRequestPayload::Buffer(b)) => {
+                        String::from_utf8(b.to_vec()).unwrap()
+                    },
+                    _ => panic!("ListMetrics request had no payload"),
+                };
+
+                let expected = vec![
+                    "Dimensions.member.1.Name=BucketName",
+                    "Dimensions.member.1.Value=a-bucket-name",
+                    "Dimensions.member.2.Name=StorageType",
+                    "Dimensions.member.2.Value=StandardStorage",
+                ];
+
+                for param in expected {
+                    assert!(payload.contains(param), "{} not in {}", param, payload);
+                }
+            });
+
+        let mut client = mock_client(None);
+        client.bucket_name       = Some("a-bucket-name".into());
+        client.dimension_filters = vec![
+            DimensionFilter {
+                name:  "StorageType".into(),
+                value: Some("StandardStorage".into()),
+            },
+        ];
+        client.client = CloudWatchClient::new_with(
+            dispatcher,
+            MockCredentialsProvider,
+            Default::default(),
+        );
+
+        let ret = Client::list_metrics(&client).await;
+
+        assert!(ret.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_list_metrics_custom_metric() {
+        let data = MockResponseReader::read_response(
+            "test-data",
+            "cloudwatch-list-metrics.xml",
+        );
+
+        let dispatcher = MockRequestDispatcher::default()
+            .with_body(&data)
+            .with_request_checker(|request| {
+                let payload = match &request.payload {
+                    Some(SignedRequestPayload::Buffer(b)) => {
+                        String::from_utf8(b.to_vec()).unwrap()
+                    },
+                    _ => panic!("ListMetrics request had no payload"),
+                };
+
+                let expected = vec![
+                    "MetricName=StreamedBucketSizeBytes",
+                    "Namespace=Custom%2FS3",
+                ];
+
+                for param in expected {
+                    assert!(payload.contains(param), "{} not in {}", param, payload);
+                }
+            });
+
+        let mut client = mock_client(None);
+        client.metric_name = "StreamedBucketSizeBytes".into();
+        client.namespace   = "Custom/S3".into();
+        client.client      = CloudWatchClient::new_with(
+            dispatcher,
+            MockCredentialsProvider,
+            Default::default(),
+        );
+
+        let ret = Client::list_metrics(&client).await;
+
+        assert!(ret.is_ok());
+
+        // Metric statistics are fetched from the same metric.
+        let now   = Utc::now();
+        let input = client.metric_statistics_input(
+            "a-bucket-name",
+            "StandardStorage",
+            now,
+            now,
+        );
+
+        assert_eq!(input.metric_name, "StreamedBucketSizeBytes");
+        assert_eq!(input.namespace, "Custom/S3");
+    }
+
+    #[test]
+    fn test_new_metric_defaults() {
+        let client = Client::new(ClientConfig::default());
+
+        assert_eq!(client.metric_name, "BucketSizeBytes");
+        assert_eq!(client.namespace, "AWS/S3");
+
+        let config = ClientConfig {
+            cloudwatch_metric_name: Some("StreamedBucketSizeBytes".into()),
+            cloudwatch_namespace:   Some("Custom/S3".into()),
+            ..Default::default()
+        };
+
+        let client = Client::new(config);
+
+        assert_eq!(client.metric_name, "StreamedBucketSizeBytes");
+        assert_eq!(client.namespace, "Custom/S3");
+    }
+
+    #[tokio::test]
+    async fn test_list_metrics_next_token_file() {
+        let path = temp_path("list-metrics.token");
+
+        let page = |data_file| MockRequestDispatcher::default().with_body(
+            &MockResponseReader::read_response("test-data", data_file),
+        );
+
+        // The first run fails after listing the first page.
+        let mut client = mock_client(None);
+        client.next_token_file = Some(path.to_owned());
+        client.client = CloudWatchClient::new_with(
+            MultipleMockRequestDispatcher::new(vec![
+                page("cloudwatch-list-metrics-page1.xml"),
+                MockRequestDispatcher::with_status(500),
+            ]),
+            MockCredentialsProvider,
+            Default::default(),
+        );
+
+        assert!(client.list_metrics().await.is_err());
+        assert_eq!(fs::read_to_string(&path).unwrap(), "page-2-token\n");
+
+        // The next run resumes from the second page, and removes the token
+        // once listing completes.
+        client.client = CloudWatchClient::new_with(
+            page("cloudwatch-list-metrics-page2.xml")
+                .with_request_checker(|request| {
+                    let payload = match &request.payload {
+                        Some(SignedRequestPayload::Buffer(b)) => {
+                            String::from_utf8(b.to_vec()).unwrap()
+                        },
+                        _ => panic!("ListMetrics request had no payload"),
+                    };
+
+                    assert!(payload.contains("NextToken=page-2-token"));
+                }),
+            MockCredentialsProvider,
+            Default::default(),
+        );
+
+        let ret = client.list_metrics().await.unwrap();
+
+        assert_eq!(ret.len(), 1);
+        assert!(!path.exists());
+    }
+
     #[tokio::test]
     async fn test_list_metrics() {
-        let mut client = mock_client(
+        let client = mock_client(
             Some("cloudwatch-list-metrics.xml"),
         );
 
-        let ret = Client::list_metrics(&mut client).await.unwrap();
+        let ret = Client::list_metrics(&client).await.unwrap();
 
         let expected = vec![
             Metric {
@@ -0,0 +1,110 @@
+// Persists the ListMetrics next token between runs
+#![forbid(unsafe_code)]
+#![deny(missing_docs)]
+use anyhow::Result;
+use crate::common::S3duError;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::Path;
+use tracing::debug;
+
+/// Returns the next token saved at `path` by a previous run, if any.
+///
+/// A missing or empty file means that there's no listing to resume.
+pub fn load_next_token(path: &Path) -> Result<Option<String>> {
+    debug!(path = ?path, "load_next_token: Loading next token");
+
+    let token = match fs::read_to_string(path) {
+        Ok(token)                                   => token,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+        Err(e)                                      => {
+            let message = format!("{}: {}", path.display(), e);
+
+            return Err(S3duError::NextToken(message).into());
+        },
+    };
+
+    let token = token.trim();
+
+    if token.is_empty() {
+        Ok(None)
+    }
+    else {
+        Ok(Some(token.into()))
+    }
+}
+
+/// Saves `token` at `path`, so that a later run can resume listing from it.
+///
+/// Once there's no next token the listing is complete, so any saved token is
+/// removed instead.
+pub fn save_next_token(path: &Path, token: Option<&str>) -> Result<()> {
+    debug!(path = ?path, token = ?token, "save_next_token: Saving next token");
+
+    let ret = match token {
+        Some(token) => fs::write(path, format!("{}\n", token)),
+        None        => {
+            match fs::remove_file(path) {
+                Err(e) if e.kind() == ErrorKind::NotFound => Ok(()),
+                ret                                        => ret,
+            }
+        },
+    };
+
+    ret.map_err(|e| {
+        S3duError::NextToken(format!("{}: {}", path.display(), e))
+    })?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_helpers::temp_path;
+    use pretty_assertions::assert_eq;
+    use std::env;
+
+    #[test]
+    fn test_save_load_next_token() {
+        let path = temp_path("next-token.token");
+
+        // Nothing has been saved yet.
+        assert_eq!(load_next_token(&path).unwrap(), None);
+
+        save_next_token(&path, Some("page-2-token")).unwrap();
+
+        assert_eq!(load_next_token(&path).unwrap(), Some("page-2-token".into()));
+
+        // The end of the listing removes the token.
+        save_next_token(&path, None).unwrap();
+
+        assert!(!path.exists());
+        assert_eq!(load_next_token(&path).unwrap(), None);
+
+        // Removing a token that isn't there is fine.
+        assert!(save_next_token(&path, None).is_ok());
+    }
+
+    #[test]
+    fn test_load_next_token_empty() {
+        let path = temp_path("next-token-empty.token");
+
+        fs::write(&path, "\n").unwrap();
+        let ret = load_next_token(&path).unwrap();
+
+        assert_eq!(ret, None);
+    }
+
+    #[test]
+    fn test_next_token_errors() {
+        // A directory can't be read or written as a file.
+        let path = env::temp_dir();
+
+        let ret = load_next_token(&path).unwrap_err();
+        assert!(matches!(ret.downcast_ref(), Some(S3duError::NextToken(_))));
+
+        let ret = save_next_token(&path, Some("token")).unwrap_err();
+        assert!(matches!(ret.downcast_ref(), Some(S3duError::NextToken(_))));
+    }
+}
@@ -0,0 +1,231 @@
+// Publishes bucket sizes as custom CloudWatch metrics
+#![forbid(unsafe_code)]
+#![deny(missing_docs)]
+use anyhow::Result;
+use crate::common::{
+    ClassSizes,
+    ClientConfig,
+    with_api_timeout,
+};
+use crate::credentials;
+use rusoto_cloudwatch::{
+    CloudWatch,
+    CloudWatchClient,
+    Dimension,
+    MetricDatum,
+    PutMetricDataInput,
+};
+use tracing::debug;
+
+/// Namespace that bucket sizes are published under.
+const METRIC_NAMESPACE: &str = "s3du";
+
+/// Name of the published bucket size metric, matching the native S3 metric.
+const METRIC_NAME: &str = "BucketSizeBytes";
+
+/// `StorageClass` dimension value used for buckets without storage class
+/// sizes, matching the native S3 `StorageType` for whole buckets.
+const ALL_STORAGE_TYPES: &str = "AllStorageTypes";
+
+/// Maximum number of metric datums that `PutMetricData` accepts in a single
+/// request.
+const MAX_DATUMS_PER_REQUEST: usize = 20;
+
+/// `BucketMetric` is the measured size of a bucket to publish.
+#[derive(Debug, PartialEq)]
+pub struct BucketMetric {
+    /// Name of the bucket.
+    pub bucket: String,
+
+    /// Size of the bucket in bytes.
+    pub size: u64,
+
+    /// Sizes of each storage class in the bucket, if the client that sized
+    /// it saw them.
+    pub class_sizes: Option<ClassSizes>,
+}
+
+/// `Publisher` publishes bucket sizes to CloudWatch as custom metrics.
+pub struct Publisher {
+    /// The Rusoto `CloudWatchClient`.
+    pub client: CloudWatchClient,
+
+    /// Maximum time that each API call may take, if any.
+    pub api_timeout: Option<std::time::Duration>,
+}
+
+impl Publisher {
+    /// Return a new `Publisher` for the region in the given `ClientConfig`.
+    pub fn new(config: &ClientConfig) -> Self {
+        let aws_client = credentials::aws_client(config);
+
+        debug!(
+            region = config.region.name(),
+            "new: Creating CloudWatchClient for publishing",
+        );
+
+        let client = CloudWatchClient::new_with_client(
+            aws_client,
+            config.region.to_owned(),
+        );
+
+        Self {
+            client:      client,
+            api_timeout: config.api_timeout,
+        }
+    }
+
+    /// Publishes the given `metrics` under the `s3du` namespace, batching
+    /// them into as few `PutMetricData` requests as possible.
+    pub async fn publish(&self, metrics: &[BucketMetric]) -> Result<()> {
+        let datums: Vec<MetricDatum> = metrics.iter()
+            .flat_map(metric_datums)
+            .collect();
+
+        debug!(datums = datums.len(), "publish: Publishing metric datums");
+
+        for chunk in datums.chunks(MAX_DATUMS_PER_REQUEST) {
+            let input = PutMetricDataInput {
+                metric_data: chunk.to_vec(),
+                namespace:   METRIC_NAMESPACE.into(),
+            };
+
+            with_api_timeout(
+                self.api_timeout,
+                self.client.put_metric_data(input),
+            ).await?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Returns the metric datums for `metric`, with `BucketName` and
+/// `StorageClass` dimensions.
+///
+/// Buckets with storage class sizes have a datum for each storage class,
+/// ordered by name. Other buckets have a single datum for the whole bucket.
+fn metric_datums(metric: &BucketMetric) -> Vec<MetricDatum> {
+    let mut sizes: Vec<(&str, u64)> = match &metric.class_sizes {
+        Some(class_sizes) => {
+            class_sizes.iter()
+                .map(|(class, size)| (class.name(), *size))
+                .collect()
+        },
+        None => vec![(ALL_STORAGE_TYPES, metric.size)],
+    };
+
+    sizes.sort();
+
+    sizes.into_iter()
+        .map(|(class, size)| MetricDatum {
+            dimensions: Some(vec![
+                Dimension {
+                    name:  "BucketName".into(),
+                    value: metric.bucket.to_owned(),
+                },
+                Dimension {
+                    name:  "StorageClass".into(),
+                    value: class.into(),
+                },
+            ]),
+            metric_name: METRIC_NAME.into(),
+            unit:        Some("Bytes".into()),
+            value:       Some(size as f64),
+            ..Default::default()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::StorageClass;
+    use pretty_assertions::assert_eq;
+    use rusoto_mock::{
+        MockCredentialsProvider,
+        MockRequestDispatcher,
+    };
+
+    // Returns the bucket name, storage class and value of each datum.
+    fn datum_values(datums: &[MetricDatum]) -> Vec<(String, String, f64)> {
+        datums.iter()
+            .map(|datum| {
+                let dimensions = datum.dimensions.as_ref().unwrap();
+
+                (
+                    dimensions[0].value.to_owned(),
+                    dimensions[1].value.to_owned(),
+                    datum.value.unwrap(),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_metric_datums() {
+        let class_sizes: ClassSizes = vec![
+            (StorageClass::StandardIA, 2048),
+            (StorageClass::Standard,   1024),
+        ].into_iter().collect();
+
+        let metric = BucketMetric {
+            bucket:      "bucket-a".into(),
+            size:        3072,
+            class_sizes: Some(class_sizes),
+        };
+
+        let ret = metric_datums(&metric);
+
+        let expected = vec![
+            ("bucket-a".to_string(), "STANDARD".to_string(),    1024.0),
+            ("bucket-a".to_string(), "STANDARD_IA".to_string(), 2048.0),
+        ];
+
+        assert_eq!(datum_values(&ret), expected);
+        assert_eq!(ret[0].metric_name, "BucketSizeBytes");
+        assert_eq!(ret[0].unit, Some("Bytes".into()));
+    }
+
+    #[test]
+    fn test_metric_datums_no_class_sizes() {
+        let metric = BucketMetric {
+            bucket:      "bucket-b".into(),
+            size:        4096,
+            class_sizes: None,
+        };
+
+        let expected = vec![
+            ("bucket-b".to_string(), "AllStorageTypes".to_string(), 4096.0),
+        ];
+
+        assert_eq!(datum_values(&metric_datums(&metric)), expected);
+    }
+
+    #[tokio::test]
+    async fn test_publish() {
+        let client = CloudWatchClient::new_with(
+            MockRequestDispatcher::default(),
+            MockCredentialsProvider,
+            Default::default()
+        );
+
+        let publisher = Publisher {
+            client:      client,
+            api_timeout: None,
+        };
+
+        // More buckets than fit in a single request.
+        let metrics: Vec<BucketMetric> = (0..25)
+            .map(|i| BucketMetric {
+                bucket:      format!("bucket-{}", i),
+                size:        i,
+                class_sizes: None,
+            })
+            .collect();
+
+        let ret = publisher.publish(&metrics).await;
+
+        assert!(ret.is_ok());
+    }
+}
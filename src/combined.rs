@@ -0,0 +1,288 @@
+// Implements the combined CloudWatch and S3 Client
+#![forbid(unsafe_code)]
+#![deny(missing_docs)]
+use anyhow::Result;
+use async_trait::async_trait;
+use chrono::{
+    DateTime,
+    Utc,
+};
+use crate::cloudwatch;
+use crate::common::{
+    Bucket,
+    Buckets,
+    BucketSizer,
+    BucketStats,
+    ClassSizes,
+    ClassTrend,
+    ClientConfig,
+    MetadataStats,
+    PrefixNode,
+};
+use crate::s3;
+use tracing::debug;
+
+/// The combined `Client`.
+///
+/// This sizes buckets via CloudWatch, falling back to S3 for any bucket that
+/// CloudWatch has no metrics for.
+pub struct Client {
+    /// The CloudWatch `Client`.
+    pub cloudwatch: cloudwatch::Client,
+
+    /// The S3 `Client`.
+    pub s3: s3::Client,
+}
+
+impl Client {
+    /// Return a new combined `Client` with the given `ClientConfig`.
+    pub fn new(config: ClientConfig) -> Self {
+        debug!("new: Creating combined CloudWatch and S3 clients");
+
+        Self {
+            cloudwatch: cloudwatch::Client::new(config.clone()),
+            s3:         s3::Client::new(config),
+        }
+    }
+}
+
+#[async_trait]
+impl BucketSizer for Client {
+    /// Return the union of the `Buckets` discovered by CloudWatch and S3.
+    ///
+    /// Buckets discovered by both clients are merged, taking the storage
+    /// types from CloudWatch and the region from S3.
+    async fn buckets(&self) -> Result<Buckets> {
+        debug!("buckets: Listing...");
+
+        let mut buckets = self.cloudwatch.buckets().await?;
+
+        for s3_bucket in self.s3.buckets().await? {
+            match buckets.iter_mut().find(|b| b.name == s3_bucket.name) {
+                Some(bucket) => bucket.region = s3_bucket.region,
+                None         => buckets.push(s3_bucket),
+            }
+        }
+
+        buckets.sort_by(|a, b| a.name.cmp(&b.name));
+
+        Ok(buckets)
+    }
+
+    /// Return the size of `bucket` from CloudWatch, falling back to S3 if
+    /// CloudWatch returns a size of `0` or an error.
+    async fn bucket_size(&self, bucket: &Bucket) -> Result<u64> {
+        match self.cloudwatch.bucket_size(bucket).await {
+            Ok(0) => {
+                debug!(
+                    bucket = %bucket,
+                    "bucket_size: CloudWatch returned 0, falling back to S3",
+                );
+
+                // The bucket is being sized live by S3, so the CloudWatch
+                // timestamp and storage class sizes no longer apply.
+                self.cloudwatch.timestamp_cache.lock().unwrap().remove(&bucket.name);
+                self.cloudwatch.class_size_cache.lock().unwrap().remove(&bucket.name);
+            },
+            Ok(size) => return Ok(size),
+            Err(e)   => {
+                debug!(
+                    bucket = %bucket,
+                    error  = %e,
+                    "bucket_size: CloudWatch failed, falling back to S3",
+                );
+            },
+        }
+
+        self.s3.bucket_size(bucket).await
+    }
+
+    /// Return the timestamp from CloudWatch, if CloudWatch was used to size
+    /// `bucket`.
+    fn bucket_timestamp(&self, bucket: &Bucket) -> Option<DateTime<Utc>> {
+        self.cloudwatch.bucket_timestamp(bucket)
+    }
+
+    /// Return the storage class sizes from CloudWatch, if CloudWatch was used
+    /// to size `bucket`.
+    fn bucket_class_sizes(&self, bucket: &Bucket) -> Option<ClassSizes> {
+        self.cloudwatch.bucket_class_sizes(bucket)
+    }
+
+    /// Return the storage type trends from CloudWatch, if CloudWatch was used
+    /// to size `bucket`.
+    fn bucket_transitions(&self, bucket: &Bucket) -> Option<Vec<ClassTrend>> {
+        self.cloudwatch.bucket_transitions(bucket)
+    }
+
+    /// Return the prefix tree from S3, if S3 was used to size `bucket`.
+    fn bucket_prefixes(&self, bucket: &Bucket) -> Option<PrefixNode> {
+        self.s3.bucket_prefixes(bucket)
+    }
+
+    /// Return the incomplete multipart upload size from S3, if S3 was used
+    /// to size `bucket`.
+    fn bucket_multipart_size(&self, bucket: &Bucket) -> Option<u64> {
+        self.s3.bucket_multipart_size(bucket)
+    }
+
+    /// Return the counts from S3, if S3 was used to size `bucket`.
+    fn bucket_stats(&self, bucket: &Bucket) -> Option<BucketStats> {
+        self.s3.bucket_stats(bucket)
+    }
+
+    /// Return the metadata estimates from S3, if S3 was used to size
+    /// `bucket`.
+    fn bucket_metadata(&self, bucket: &Bucket) -> Option<MetadataStats> {
+        self.s3.bucket_metadata(bucket)
+    }
+
+    /// Return the duplicate object report from S3, if S3 was used to size
+    /// `bucket`.
+    fn bucket_duplicates(&self, bucket: &Bucket) -> Option<Vec<String>> {
+        self.s3.bucket_duplicates(bucket)
+    }
+
+    /// Return the object age histogram report from S3, if S3 was used to
+    /// size `bucket`.
+    fn bucket_age_histogram(&self, bucket: &Bucket) -> Option<Vec<String>> {
+        self.s3.bucket_age_histogram(bucket)
+    }
+
+    /// Close both the CloudWatch and S3 clients.
+    ///
+    /// The S3 client is closed even if closing the CloudWatch client fails.
+    async fn close(&mut self) -> Result<()> {
+        let cloudwatch = self.cloudwatch.close().await;
+
+        self.s3.close().await?;
+
+        cloudwatch
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_helpers::{
+        dispatcher_with_body,
+        s3_client,
+    };
+    use pretty_assertions::assert_eq;
+    use rusoto_cloudwatch::CloudWatchClient;
+    use rusoto_core::Region;
+    use rusoto_mock::{
+        MockCredentialsProvider,
+        MockRequestDispatcher,
+    };
+    use rusoto_s3::S3Client;
+    use std::collections::HashMap;
+    use std::sync::Mutex;
+
+    // Create a combined client from the given CloudWatch and S3 dispatchers.
+    fn mock_client(
+        cloudwatch: MockRequestDispatcher,
+        s3:         MockRequestDispatcher,
+    ) -> Client {
+        let cloudwatch = CloudWatchClient::new_with(
+            cloudwatch,
+            MockCredentialsProvider,
+            Default::default(),
+        );
+
+        let s3 = S3Client::new_with(
+            s3,
+            MockCredentialsProvider,
+            Default::default(),
+        );
+
+        Client {
+            cloudwatch: cloudwatch::Client {
+                client:                   cloudwatch,
+                api_timeout:              None,
+                bucket_cache:             Mutex::new(None),
+                bucket_name:              None,
+                class_size_cache:         Mutex::new(HashMap::new()),
+                report_date:              None,
+                report_class_transitions: false,
+                dimension_filters:        Vec::new(),
+                excluded_storage_classes: Vec::new(),
+                include_errors:           false,
+                include_overhead:         true,
+                metric_name:              "BucketSizeBytes".into(),
+                namespace:                "AWS/S3".into(),
+                next_token_file:          None,
+                region:                   Region::UsEast1,
+                timestamp_cache:          Mutex::new(HashMap::new()),
+                transition_cache:         Mutex::new(HashMap::new()),
+            },
+            s3: s3_client(s3),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_bucket_size_fallback() {
+        // A bucket without storage types will always be sized as 0 by
+        // CloudWatch.
+        let client = mock_client(
+            MockRequestDispatcher::with_status(500),
+            dispatcher_with_body("s3-list-objects.xml"),
+        );
+
+        let bucket = Bucket {
+            alias:         None,
+            name:          "test-bucket".into(),
+            region:        None,
+            storage_types: None,
+        };
+
+        let ret = Client::bucket_size(&client, &bucket).await.unwrap();
+
+        let expected = 33792;
+
+        assert_eq!(ret, expected);
+    }
+
+    #[tokio::test]
+    async fn test_bucket_size_fallback_on_error() {
+        let client = mock_client(
+            MockRequestDispatcher::with_status(500),
+            dispatcher_with_body("s3-list-objects.xml"),
+        );
+
+        let bucket = Bucket {
+            alias:         None,
+            name:          "test-bucket".into(),
+            region:        None,
+            storage_types: Some(vec!["StandardStorage".into()]),
+        };
+
+        let ret = Client::bucket_size(&client, &bucket).await.unwrap();
+
+        let expected = 33792;
+
+        assert_eq!(ret, expected);
+    }
+
+    #[tokio::test]
+    async fn test_bucket_size_cloudwatch() {
+        // S3 would error here, so this ensures that we didn't fall back.
+        let client = mock_client(
+            dispatcher_with_body("cloudwatch-get-metric-statistics.xml"),
+            MockRequestDispatcher::with_status(500),
+        );
+
+        let bucket = Bucket {
+            alias:         None,
+            name:          "test-bucket".into(),
+            region:        None,
+            storage_types: Some(vec!["StandardStorage".into()]),
+        };
+
+        let ret = Client::bucket_size(&client, &bucket).await.unwrap();
+
+        let expected = 123456789;
+
+        assert_eq!(ret, expected);
+    }
+}
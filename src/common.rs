@@ -2,6 +2,9 @@
 #![forbid(unsafe_code)]
 #![deny(missing_docs)]
 
+/// `with_api_timeout` limits how long individual AWS API calls may take.
+mod api_timeout;
+
 /// The `Bucket` struct
 mod bucket;
 
@@ -14,27 +17,95 @@ mod client_config;
 /// `ClientMode` enum is used to select which `Client` will be used.
 mod client_mode;
 
-/// `HumanSize` trait for `usize` used to output friendly bucket sizes.
+/// `S3duError` enum represents the common failure modes of the `Client`s.
+mod error;
+
+/// `TimestampFormat` enum and timestamp formatting used for output.
+mod fmt;
+
+/// `HumanSize` trait for `u64` used to output friendly bucket sizes.
 mod human_size;
 
+/// `STANDARD_REGIONS` lists the regions buckets are sized in with
+/// `--all-regions`.
+mod regions;
+
+/// `RequestCounter` struct counts the AWS API requests made by the
+/// `Client`s.
+mod request_counter;
+
+/// `SdkConfig` struct holds overrides of the AWS SDK configuration.
+mod sdk_config;
+
 /// `SizeUnit` enum is used to select how the bucket sizes will be output.
 mod size_unit;
 
+/// `StorageClass` enum represents the S3 storage class of objects.
+mod storage_class;
+
+/// `StorageSizer` trait and `StorageResource` struct, for sizing storage
+/// other than S3 buckets.
+mod storage_sizer;
+
+/// `CostModel` enum holds the prices used to estimate the cost of buckets
+/// sized in CloudWatch.
+mod cost_model;
+
+/// `ClassTrend` struct holds the change in size of a storage type in buckets
+/// in CloudWatch.
+#[cfg(feature = "cloudwatch")]
+mod class_trend;
+
+/// `BucketStats` struct holds counts found while sizing buckets in S3.
+#[cfg(feature = "s3")]
+mod bucket_stats;
+
+/// `MetadataStats` struct holds estimates of the object metadata in buckets
+/// in S3.
+#[cfg(feature = "s3")]
+mod metadata_stats;
+
 /// `ObjectVersions` selects which S3 objects will be used when summing the
 /// size of the buckets.
 #[cfg(feature = "s3")]
 mod object_versions;
 
+/// `PrefixNode` struct holds the tree of prefixes within a bucket sized in
+/// S3.
+#[cfg(feature = "s3")]
+mod prefix_node;
+
+pub use api_timeout::*;
 pub use bucket::*;
 pub use bucket_sizer::*;
 pub use client_config::*;
 pub use client_mode::*;
+pub use cost_model::*;
+pub use error::*;
+pub use fmt::*;
 pub use human_size::*;
+pub use regions::*;
+pub use request_counter::*;
+pub use sdk_config::*;
 pub use size_unit::*;
+pub use storage_class::*;
+pub use storage_sizer::*;
+
+#[cfg(feature = "cloudwatch")]
+pub use class_trend::*;
+
+#[cfg(feature = "s3")]
+pub use bucket_stats::*;
+
+#[cfg(feature = "s3")]
+pub use metadata_stats::*;
 
 #[cfg(feature = "s3")]
 pub use object_versions::*;
 
+#[cfg(feature = "s3")]
+pub use prefix_node::*;
+
 /// `BucketNames` is a convenience type used by both the CloudWatch and S3
 /// clients.
 pub type BucketNames = Vec<String>;
@@ -0,0 +1,96 @@
+// Timeouts for individual AWS API calls
+#![forbid(unsafe_code)]
+#![deny(missing_docs)]
+use rusoto_core::RusotoError;
+use std::error::Error;
+use std::future::Future;
+use std::time::Duration;
+use super::S3duError;
+
+/// Await the given API call `future`, returning its output untouched.
+///
+/// If a `timeout` is given and the call doesn't complete within it, the call
+/// is abandoned and `S3duError::Timeout` is returned instead.
+pub async fn timeout_api_call<F: Future>(
+    timeout: Option<Duration>,
+    future: F,
+) -> Result<F::Output, S3duError> {
+    let timeout = match timeout {
+        Some(timeout) => timeout,
+        None          => return Ok(future.await),
+    };
+
+    match tokio::time::timeout(timeout, future).await {
+        Ok(output) => Ok(output),
+        Err(_)     => {
+            let message = format!(
+                "API call did not complete within {}ms",
+                timeout.as_millis(),
+            );
+
+            Err(S3duError::Timeout(message))
+        },
+    }
+}
+
+/// Await the given Rusoto API call `future` with `timeout_api_call`,
+/// converting any error it returns into an `S3duError`.
+pub async fn with_api_timeout<F, T, E>(
+    timeout: Option<Duration>,
+    future: F,
+) -> Result<T, S3duError>
+where
+    F: Future<Output = Result<T, RusotoError<E>>>,
+    E: Error + 'static,
+{
+    timeout_api_call(timeout, future)
+        .await?
+        .map_err(S3duError::from)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use rusoto_core::request::HttpDispatchError;
+
+    // A Rusoto style API call that completes after the given delay.
+    async fn api_call(
+        delay: Duration,
+        output: Result<usize, RusotoError<HttpDispatchError>>,
+    ) -> Result<usize, RusotoError<HttpDispatchError>> {
+        tokio::time::sleep(delay).await;
+
+        output
+    }
+
+    #[tokio::test]
+    async fn test_with_api_timeout() {
+        let delay   = Duration::from_millis(1);
+        let timeout = Some(Duration::from_secs(60));
+
+        let ret = with_api_timeout(None, api_call(delay, Ok(1))).await;
+        assert_eq!(ret, Ok(1));
+
+        let ret = with_api_timeout(timeout, api_call(delay, Ok(2))).await;
+        assert_eq!(ret, Ok(2));
+
+        let error = RusotoError::Validation("invalid".into());
+        let ret   = with_api_timeout(timeout, api_call(delay, Err(error))).await;
+        assert_eq!(ret, Err(S3duError::Other("invalid".into())));
+    }
+
+    #[tokio::test]
+    async fn test_with_api_timeout_expired() {
+        let delay   = Duration::from_secs(60);
+        let timeout = Some(Duration::from_millis(10));
+
+        let ret = with_api_timeout(timeout, api_call(delay, Ok(1))).await;
+
+        let expected = S3duError::Timeout(
+            "API call did not complete within 10ms".into(),
+        );
+
+        assert_eq!(ret, Err(expected));
+    }
+}
@@ -2,15 +2,27 @@
 #![forbid(unsafe_code)]
 #![deny(missing_docs)]
 use rusoto_core::Region;
+use std::collections::HashMap;
+use std::fmt;
 
 /// Convenience type for a list of storage types
 pub type StorageTypes = Vec<String>;
 
+/// Convenience type for the display names of buckets, keyed on the bucket
+/// name.
+pub type BucketAliases = HashMap<String, String>;
+
 /// Represents an S3 bucket.
 ///
 /// This will always have a `name`.
-#[derive(Debug)]
+#[derive(Clone, Debug, Eq, PartialEq)]
 pub struct Bucket {
+    /// The name that the bucket is printed with, if it has been given one.
+    ///
+    /// This is only used in the output, API calls are always made with the
+    /// `name`.
+    pub alias: Option<String>,
+
     /// The name of the S3 bucket.
     pub name: String,
 
@@ -25,5 +37,73 @@ pub struct Bucket {
     pub storage_types: Option<StorageTypes>,
 }
 
+impl Bucket {
+    /// Returns the name that the bucket should be printed with, its alias if
+    /// it has one, otherwise its name.
+    pub fn display_name(&self) -> &str {
+        self.alias.as_deref().unwrap_or(&self.name)
+    }
+}
+
+/// Displays the bucket name, followed by its region if it's known, eg.
+/// `bucket-name (region: us-east-1)`.
+impl fmt::Display for Bucket {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.region {
+            Some(region) => write!(f, "{} (region: {})", self.name, region.name()),
+            None         => write!(f, "{}", self.name),
+        }
+    }
+}
+
 /// Convenience type for a list of `Bucket`.
 pub type Buckets = Vec<Bucket>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_display() {
+        let tests = vec![
+            (Some(Region::UsEast1), "bucket-name (region: us-east-1)"),
+            (Some(Region::EuWest1), "bucket-name (region: eu-west-1)"),
+            (None,                  "bucket-name"),
+        ];
+
+        for test in tests {
+            let (region, expected) = test;
+
+            let bucket = Bucket {
+                alias:         None,
+                name:          "bucket-name".into(),
+                region:        region,
+                storage_types: None,
+            };
+
+            assert_eq!(bucket.to_string(), expected);
+        }
+    }
+
+    #[test]
+    fn test_display_name() {
+        let tests = vec![
+            (Some("prod-application-logs"), "prod-application-logs"),
+            (None,                          "logs-prod-a1b2c3d4"),
+        ];
+
+        for test in tests {
+            let (alias, expected) = test;
+
+            let bucket = Bucket {
+                alias:         alias.map(|alias| alias.into()),
+                name:          "logs-prod-a1b2c3d4".into(),
+                region:        Some(Region::UsEast1),
+                storage_types: None,
+            };
+
+            assert_eq!(bucket.display_name(), expected);
+        }
+    }
+}
@@ -3,9 +3,24 @@
 #![deny(missing_docs)]
 use anyhow::Result;
 use async_trait::async_trait;
+use chrono::{
+    DateTime,
+    Utc,
+};
 use super::{
     Bucket,
     Buckets,
+    ClassSizes,
+};
+
+#[cfg(feature = "cloudwatch")]
+use super::ClassTrend;
+
+#[cfg(feature = "s3")]
+use super::{
+    BucketStats,
+    MetadataStats,
+    PrefixNode,
 };
 
 /// `BucketSizer` represents the required methods to list S3 buckets and find
@@ -13,10 +28,132 @@ use super::{
 ///
 /// This trait should be implemented by all `Client`s performing these tasks.
 #[async_trait]
-pub trait BucketSizer {
+pub trait BucketSizer: Send + Sync {
     /// Returns a list of bucket names.
     async fn buckets(&self) -> Result<Buckets>;
 
     /// Returns the size of the given `bucket` in bytes.
-    async fn bucket_size(&self, bucket: &Bucket) -> Result<usize>;
+    async fn bucket_size(&self, bucket: &Bucket) -> Result<u64>;
+
+    /// Returns the names of the discovered buckets, for callers that don't
+    /// need the rest of each `Bucket`.
+    ///
+    /// By default this maps the `Buckets` returned by `buckets` to their
+    /// names. `s3du` itself sizes the full `Bucket`s, so doesn't call this.
+    #[allow(dead_code)]
+    async fn bucket_names(&self) -> Result<Vec<String>> {
+        let buckets = self.buckets().await?;
+
+        let names = buckets.into_iter()
+            .map(|bucket| bucket.name)
+            .collect();
+
+        Ok(names)
+    }
+
+    /// Returns the number of discovered buckets.
+    ///
+    /// By default this counts the `Buckets` returned by `buckets`. `s3du`
+    /// itself counts the `Buckets` that it already has, so doesn't call this.
+    #[allow(dead_code)]
+    async fn bucket_count(&self) -> Result<usize> {
+        let buckets = self.buckets().await?;
+
+        Ok(buckets.len())
+    }
+
+    /// Returns the time that the size of `bucket` was measured at, if it
+    /// differs from the time that `bucket_size` was called.
+    ///
+    /// By default this returns `None`, meaning that the bucket was sized live.
+    fn bucket_timestamp(&self, _bucket: &Bucket) -> Option<DateTime<Utc>> {
+        None
+    }
+
+    /// Returns the size of each storage class in `bucket` found while sizing
+    /// it, if any.
+    ///
+    /// Only clients sizing buckets via CloudWatch see the size of each
+    /// storage class, so by default this returns `None`.
+    fn bucket_class_sizes(&self, _bucket: &Bucket) -> Option<ClassSizes> {
+        None
+    }
+
+    /// Returns the change in size of each storage type in `bucket` over the
+    /// class transition reporting window, if any.
+    ///
+    /// Trends are only fetched by clients sizing buckets via CloudWatch when
+    /// asked to, so by default this returns `None`.
+    #[cfg(feature = "cloudwatch")]
+    fn bucket_transitions(&self, _bucket: &Bucket) -> Option<Vec<ClassTrend>> {
+        None
+    }
+
+    /// Returns the tree of prefixes built while sizing `bucket`, if any.
+    ///
+    /// Trees are only built by clients sizing buckets via S3 with a
+    /// delimiter, so by default this returns `None`.
+    #[cfg(feature = "s3")]
+    fn bucket_prefixes(&self, _bucket: &Bucket) -> Option<PrefixNode> {
+        None
+    }
+
+    /// Returns the size of the incomplete multipart uploads that were added
+    /// to the size of `bucket`, if any.
+    ///
+    /// Incomplete multipart uploads are only added by clients sizing buckets
+    /// via S3 when asked to, so by default this returns `None`.
+    #[cfg(feature = "s3")]
+    fn bucket_multipart_size(&self, _bucket: &Bucket) -> Option<u64> {
+        None
+    }
+
+    /// Returns the counts of object versions and delete markers found while
+    /// sizing `bucket`, if any.
+    ///
+    /// Counts are only kept by clients listing object versions via S3 when
+    /// asked to, so by default this returns `None`.
+    #[cfg(feature = "s3")]
+    fn bucket_stats(&self, _bucket: &Bucket) -> Option<BucketStats> {
+        None
+    }
+
+    /// Returns the estimates of the object metadata in `bucket` made while
+    /// sizing it, if any.
+    ///
+    /// Metadata is only sampled by clients sizing buckets via S3 when asked
+    /// to, so by default this returns `None`.
+    #[cfg(feature = "s3")]
+    fn bucket_metadata(&self, _bucket: &Bucket) -> Option<MetadataStats> {
+        None
+    }
+
+    /// Returns the lines of the report of duplicate objects found while
+    /// sizing `bucket`, if any.
+    ///
+    /// Duplicates are only looked for by clients sizing buckets via S3 when
+    /// asked to, so by default this returns `None`.
+    #[cfg(feature = "s3")]
+    fn bucket_duplicates(&self, _bucket: &Bucket) -> Option<Vec<String>> {
+        None
+    }
+
+    /// Returns the lines of the report of the ages of the objects sized in
+    /// `bucket`, if any.
+    ///
+    /// Ages are only reported by clients sizing buckets via S3 when asked
+    /// to, so by default this returns `None`.
+    #[cfg(feature = "s3")]
+    fn bucket_age_histogram(&self, _bucket: &Bucket) -> Option<Vec<String>> {
+        None
+    }
+
+    /// Releases any resources held by the client once sizing has finished,
+    /// such as flushing files that it has written to.
+    ///
+    /// This is called even if sizing failed. By default there is nothing to
+    /// release.
+    async fn close(&mut self) -> Result<()> {
+        Ok(())
+    }
 }
@@ -0,0 +1,45 @@
+// BucketStats
+#![forbid(unsafe_code)]
+#![deny(missing_docs)]
+use std::fmt;
+
+/// Counts of the things found in a bucket while it was being sized.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct BucketStats {
+    /// The number of object versions that were included in the bucket size.
+    pub version_count: usize,
+
+    /// The number of delete markers in the bucket.
+    ///
+    /// Delete markers have no size, but each one is a candidate for cleanup.
+    pub delete_marker_count: usize,
+}
+
+/// Displays the counts, eg. `14 versions, 1 delete markers`.
+impl fmt::Display for BucketStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} versions, {} delete markers",
+            self.version_count,
+            self.delete_marker_count,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_display() {
+        let stats = BucketStats {
+            version_count:       14,
+            delete_marker_count: 1,
+        };
+
+        assert_eq!(stats.to_string(), "14 versions, 1 delete markers");
+        assert_eq!(BucketStats::default().to_string(), "0 versions, 0 delete markers");
+    }
+}
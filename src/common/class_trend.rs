@@ -0,0 +1,54 @@
+// ClassTrend
+#![forbid(unsafe_code)]
+#![deny(missing_docs)]
+
+/// The change in the size of a CloudWatch storage type in a bucket over the
+/// class transition reporting window.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct ClassTrend {
+    /// The CloudWatch `StorageType` dimension value, eg. `StandardStorage`.
+    pub storage_type: String,
+
+    /// The size of the storage type at the start of the window, in bytes.
+    pub start: u64,
+
+    /// The size of the storage type at the end of the window, in bytes.
+    pub end: u64,
+}
+
+impl ClassTrend {
+    /// Returns the percentage of the `total` bucket size that `size` makes
+    /// up.
+    ///
+    /// An empty bucket has no distribution, so every storage type makes up
+    /// `0` percent of it.
+    pub fn share(size: u64, total: u64) -> f64 {
+        if total == 0 {
+            return 0.0;
+        }
+
+        size as f64 / total as f64 * 100.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_share() {
+        let tests = vec![
+            (0,   0,   0.0),
+            (0,   100, 0.0),
+            (25,  100, 25.0),
+            (100, 100, 100.0),
+        ];
+
+        for test in tests {
+            let (size, total, expected) = test;
+
+            assert_eq!(ClassTrend::share(size, total), expected);
+        }
+    }
+}
@@ -2,30 +2,349 @@
 #![forbid(unsafe_code)]
 #![deny(missing_docs)]
 use rusoto_core::Region;
-use super::ClientMode;
+use std::time::Duration;
+use super::{
+    BucketAliases,
+    ClientMode,
+    RequestCounter,
+    SdkConfig,
+    StorageClass,
+};
+
+use chrono::{
+    DateTime,
+    Utc,
+};
 
 #[cfg(feature = "s3")]
 use super::ObjectVersions;
 
+#[cfg(feature = "s3")]
+use std::ops::Range;
+
+#[cfg(feature = "cloudwatch")]
+use std::path::PathBuf;
+
 /// Client configuration.
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct ClientConfig {
+    /// The maximum time that each individual AWS API call may take.
+    ///
+    /// If this isn't given, API calls may take as long as they need.
+    pub api_timeout: Option<Duration>,
+
+    /// Whether each bucket is sized by a client in its own region, rather
+    /// than only sizing the buckets in `region`.
+    ///
+    /// This only has an effect when running in S3 mode and the field will only
+    /// be present when compiled with the `s3` feature.
+    #[cfg(feature = "s3")]
+    pub auto_region: bool,
+
+    /// Names that buckets are printed with instead of their own names, keyed
+    /// on the bucket name.
+    ///
+    /// These are applied once buckets have been discovered, API calls are
+    /// always made with the bucket name.
+    pub bucket_aliases: BucketAliases,
+
     /// The bucket name that the client should report the size of.
     ///
     /// If this isn't given, all discovered S3 buckets will have their sizes
     /// reported.
     pub bucket_name: Option<String>,
 
+    /// Whether the server access logging status of each bucket should be
+    /// shown, warning about buckets whose logs add to their own size.
+    ///
+    /// This only has an effect when running in S3 mode and the field will only
+    /// be present when compiled with the `s3` feature.
+    #[cfg(feature = "s3")]
+    pub check_logging: bool,
+
+    /// Whether buckets with versioning enabled but no lifecycle rule expiring
+    /// old versions should be warned about.
+    ///
+    /// This only has an effect when running in S3 mode and the field will only
+    /// be present when compiled with the `s3` feature.
+    #[cfg(feature = "s3")]
+    pub check_versioning: bool,
+
+    /// The number of bucket metadata API calls (`GetBucketLocation`,
+    /// `HeadBucket`) that will be in flight at once during bucket discovery.
+    ///
+    /// This only has an effect when running in S3 mode and the field will only
+    /// be present when compiled with the `s3` feature.
+    #[cfg(feature = "s3")]
+    pub chunk_size: usize,
+
+    /// The name of the CloudWatch metric that bucket sizes are read from, if
+    /// not `BucketSizeBytes`.
+    ///
+    /// This only has an effect when running in CloudWatch mode and the field
+    /// will only be present when compiled with the `cloudwatch` feature.
+    #[cfg(feature = "cloudwatch")]
+    pub cloudwatch_metric_name: Option<String>,
+
+    /// The CloudWatch namespace that bucket sizes are read from, if not
+    /// `AWS/S3`.
+    ///
+    /// This only has an effect when running in CloudWatch mode and the field
+    /// will only be present when compiled with the `cloudwatch` feature.
+    #[cfg(feature = "cloudwatch")]
+    pub cloudwatch_namespace: Option<String>,
+
+    /// Whether object versions and delete markers should be counted while
+    /// listing object versions.
+    ///
+    /// This only has an effect when running in S3 mode and the field will only
+    /// be present when compiled with the `s3` feature.
+    #[cfg(feature = "s3")]
+    pub count_versions: bool,
+
+    /// Only buckets created within this half-open range are sized, if
+    /// given.
+    ///
+    /// This only has an effect when running in S3 mode and the field will only
+    /// be present when compiled with the `s3` feature.
+    #[cfg(feature = "s3")]
+    pub creation_date_range: Option<Range<DateTime<Utc>>>,
+
+    /// The delimiter used to group objects into prefixes (virtual
+    /// directories) in the output.
+    ///
+    /// If this isn't given, only the bucket sizes are output. This only has
+    /// an effect when running in S3 mode and the field will only be present
+    /// when compiled with the `s3` feature.
+    #[cfg(feature = "s3")]
+    pub delimiter: Option<String>,
+
+    /// Regions that are skipped when scanning more than one region, with
+    /// `--all-regions` or by finding the region of each bucket.
+    pub exclude_regions: Vec<Region>,
+
+    /// Storage classes that should be excluded when calculating the bucket
+    /// size.
+    pub excluded_storage_classes: Vec<StorageClass>,
+
+    /// Whether the first bucket that fails to be discovered aborts the run,
+    /// rather than being warned about and skipped.
+    ///
+    /// This only has an effect when running in S3 mode and finding the region
+    /// of each bucket, and the field will only be present when compiled with
+    /// the `s3` feature.
+    #[cfg(feature = "s3")]
+    pub fail_fast: bool,
+
+    /// Whether objects that share an ETag should be reported as duplicates
+    /// after each bucket is sized, along with the space that they waste.
+    ///
+    /// This only has an effect when running in S3 mode and the field will only
+    /// be present when compiled with the `s3` feature.
+    #[cfg(feature = "s3")]
+    pub find_duplicates: bool,
+
+    /// Whether buckets without CloudWatch datapoints should be reported as
+    /// errors, rather than as having a size of `0`.
+    ///
+    /// This only has an effect when running in CloudWatch mode and the field
+    /// will only be present when compiled with the `cloudwatch` feature.
+    #[cfg(feature = "cloudwatch")]
+    pub include_cloudwatch_errors: bool,
+
+    /// Whether CloudWatch overhead storage types, such as
+    /// `StandardIASizeOverhead`, are included in the bucket size.
+    ///
+    /// This only has an effect when running in CloudWatch mode and the field
+    /// will only be present when compiled with the `cloudwatch` feature.
+    #[cfg(feature = "cloudwatch")]
+    pub include_overhead: bool,
+
+    /// Whether the size of incomplete multipart uploads should be added to the
+    /// bucket size.
+    ///
+    /// This only has an effect when running in S3 mode and the field will only
+    /// be present when compiled with the `s3` feature.
+    #[cfg(feature = "s3")]
+    pub include_multipart_uploads: bool,
+
+    /// Whether object sizes are raised to the minimum billable object size
+    /// of their storage class.
+    ///
+    /// This only has an effect when running in S3 mode and the field will only
+    /// be present when compiled with the `s3` feature.
+    #[cfg(feature = "s3")]
+    pub include_size_on_disk: bool,
+
+    /// Additional `(dimension, value)` filters used when listing CloudWatch
+    /// metrics.
+    ///
+    /// This only has an effect when running in CloudWatch mode and the field
+    /// will only be present when compiled with the `cloudwatch` feature.
+    #[cfg(feature = "cloudwatch")]
+    pub list_metrics_filters: Vec<(String, String)>,
+
+    /// The maximum depth of prefixes to output when a `delimiter` is given.
+    ///
+    /// The field will only be present when compiled with the `s3` feature.
+    #[cfg(feature = "s3")]
+    pub max_depth: usize,
+
+    /// The maximum number of objects that may be listed in a bucket before
+    /// sizing it is abandoned, if any.
+    ///
+    /// The field will only be present when compiled with the `s3` feature.
+    #[cfg(feature = "s3")]
+    pub max_objects: Option<usize>,
+
+    /// The number of objects in each bucket to sample the user-defined
+    /// metadata of.
+    ///
+    /// If this isn't given, object metadata isn't sampled. This only has an
+    /// effect when running in S3 mode and the field will only be present when
+    /// compiled with the `s3` feature.
+    #[cfg(feature = "s3")]
+    pub metadata_sample_size: Option<usize>,
+
+    /// Only objects last modified after this time are included in the bucket
+    /// size.
+    ///
+    /// This only has an effect when running in S3 mode and the field will only
+    /// be present when compiled with the `s3` feature.
+    #[cfg(feature = "s3")]
+    pub modified_after: Option<DateTime<Utc>>,
+
+    /// Only objects last modified before this time are included in the bucket
+    /// size.
+    ///
+    /// This only has an effect when running in S3 mode and the field will only
+    /// be present when compiled with the `s3` feature.
+    #[cfg(feature = "s3")]
+    pub modified_before: Option<DateTime<Utc>>,
+
     /// The mode that `s3du` will run in.
     ///
     /// This selects which AWS client will be used.
     pub mode: ClientMode,
 
+    /// Buckets with more than this many top-level prefixes are warned about
+    /// as namespace buckets.
+    ///
+    /// If this isn't given, buckets aren't checked. This only has an effect
+    /// when running in S3 mode and the field will only be present when
+    /// compiled with the `s3` feature.
+    #[cfg(feature = "s3")]
+    pub namespace_threshold: Option<usize>,
+
+    /// File that the `ListMetrics` next token is saved in, so that an
+    /// interrupted metric listing can be resumed by the next run.
+    ///
+    /// This only has an effect when running in CloudWatch mode and the field
+    /// will only be present when compiled with the `cloudwatch` feature.
+    #[cfg(feature = "cloudwatch")]
+    pub next_token_file: Option<PathBuf>,
+
+    /// Whether requests are sent without signing them, for accessing public
+    /// buckets without credentials.
+    ///
+    /// This can't be used with `profile` or `role_arn`.
+    pub no_sign_request: bool,
+
+    /// Whether a histogram of the ages of the objects in the named bucket
+    /// should be reported after it is sized.
+    ///
+    /// This only has an effect when running in S3 mode and the field will only
+    /// be present when compiled with the `s3` feature.
+    #[cfg(feature = "s3")]
+    pub object_age_histogram: bool,
+
+    /// `(key, value)` tags that objects must have to be included in the
+    /// bucket size.
+    ///
+    /// Every listed object has its tags looked up when this isn't empty. This
+    /// only has an effect when running in S3 mode and the field will only be
+    /// present when compiled with the `s3` feature.
+    #[cfg(feature = "s3")]
+    pub object_tag_filters: Vec<(String, String)>,
+
+    /// The maximum number of keys requested in each page of an object
+    /// listing, if not the S3 default of 1000.
+    ///
+    /// This only has an effect when running in S3 mode and the field will only
+    /// be present when compiled with the `s3` feature.
+    #[cfg(feature = "s3")]
+    pub page_size: Option<i64>,
+
+    /// The named AWS profile to load credentials from.
+    ///
+    /// If this isn't given, the default AWS credentials chain is used.
+    pub profile: Option<String>,
+
     /// The region that our AWS client should be created in.
     ///
     /// This will affect bucket discovery.
     pub region: Region,
 
+    /// The date that CloudWatch metric statistics should be reported for.
+    ///
+    /// If this isn't given, the most recent metric statistics will be used.
+    /// This only has an effect when running in CloudWatch mode and the field
+    /// will only be present when compiled with the `cloudwatch` feature.
+    #[cfg(feature = "cloudwatch")]
+    pub report_date: Option<DateTime<Utc>>,
+
+    /// Whether the trend in the size of each storage type over the last 30
+    /// days should be reported, to show lifecycle transitions.
+    ///
+    /// This only has an effect when running in CloudWatch mode and the field
+    /// will only be present when compiled with the `cloudwatch` feature.
+    #[cfg(feature = "cloudwatch")]
+    pub report_class_transitions: bool,
+
+    /// Counter of the AWS API requests made by every client, shared between
+    /// clients created from this configuration.
+    ///
+    /// If this isn't given, requests aren't counted.
+    pub request_counter: Option<RequestCounter>,
+
+    /// The ARN of an IAM role to assume, using the credentials from `profile`
+    /// or the default credentials chain.
+    ///
+    /// If this isn't given, those credentials are used directly.
+    pub role_arn: Option<String>,
+
+    /// The session name to use when assuming `role_arn`.
+    pub role_session_name: Option<String>,
+
+    /// Overrides of the AWS SDK configuration, applied to every client.
+    pub sdk_config: SdkConfig,
+
+    /// Prefixes that bucket listings are split at, so that each part of the
+    /// bucket can be listed concurrently.
+    ///
+    /// If this is empty, buckets are listed in a single pass. This only has
+    /// an effect when running in S3 mode and the field will only be present
+    /// when compiled with the `s3` feature.
+    #[cfg(feature = "s3")]
+    pub shard_prefixes: Vec<String>,
+
+    /// Whether buckets that access is denied to while discovering them are
+    /// skipped without a warning, even with `fail_fast`.
+    ///
+    /// This only has an effect when running in S3 mode and finding the region
+    /// of each bucket, and the field will only be present when compiled with
+    /// the `s3` feature.
+    #[cfg(feature = "s3")]
+    pub skip_inaccessible: bool,
+
+    /// `(key, value)` bucket tags that buckets must all have to be sized.
+    ///
+    /// If this is empty, buckets aren't filtered on their tags. This only has
+    /// an effect when running in S3 mode and the field will only be present
+    /// when compiled with the `s3` feature.
+    #[cfg(feature = "s3")]
+    pub tag_filters: Vec<(String, String)>,
+
     /// The S3 object versions that should be used when calculating the bucket
     /// size.
     ///
@@ -41,15 +360,67 @@ impl Default for ClientConfig {
     /// If compiled with the `cloudwatch` feature, `CloudWatch` will be the
     /// default `ClientMode`, otherwise `S3` will be the default.
     ///
-    /// If compiled without the `s3` feature, the `object_versions` field
-    /// will be absent.
+    /// If compiled without the `s3` feature, the `auto_region`,
+    /// `check_logging`, `check_versioning`, `chunk_size`, `count_versions`,
+    /// `creation_date_range`, `delimiter`, `fail_fast`, `find_duplicates`,
+    /// `include_multipart_uploads`, `include_size_on_disk`, `max_depth`,
+    /// `max_objects`, `metadata_sample_size`, `modified_after`,
+    /// `modified_before`, `namespace_threshold`, `object_age_histogram`,
+    /// `object_tag_filters`, `page_size`, `shard_prefixes`,
+    /// `skip_inaccessible`, `tag_filters` and `object_versions` fields will
+    /// be absent. If compiled without the
+    /// `cloudwatch` feature, the `cloudwatch_metric_name`,
+    /// `cloudwatch_namespace`, `include_cloudwatch_errors`,
+    /// `include_overhead`, `list_metrics_filters`, `next_token_file`,
+    /// `report_date` and `report_class_transitions` fields will be absent.
     ///
     /// ```rust
     /// ClientConfig {
-    ///     bucket_name:     None,
-    ///     mode:            ClientMode::CloudWatch,
-    ///     region:          Region::UsEast1,
-    ///     object_versions: ObjectVersions::Current,
+    ///     api_timeout:               None,
+    ///     auto_region:               false,
+    ///     bucket_aliases:            BucketAliases::new(),
+    ///     bucket_name:               None,
+    ///     check_logging:             false,
+    ///     check_versioning:          false,
+    ///     chunk_size:                10,
+    ///     cloudwatch_metric_name:    None,
+    ///     cloudwatch_namespace:      None,
+    ///     count_versions:            false,
+    ///     creation_date_range:       None,
+    ///     delimiter:                 None,
+    ///     exclude_regions:           Vec::new(),
+    ///     excluded_storage_classes:  Vec::new(),
+    ///     fail_fast:                 false,
+    ///     find_duplicates:           false,
+    ///     include_cloudwatch_errors: false,
+    ///     include_overhead:          true,
+    ///     include_multipart_uploads: false,
+    ///     include_size_on_disk:      false,
+    ///     list_metrics_filters:      Vec::new(),
+    ///     max_depth:                 1,
+    ///     max_objects:               None,
+    ///     metadata_sample_size:      None,
+    ///     modified_after:            None,
+    ///     modified_before:           None,
+    ///     mode:                      ClientMode::CloudWatch,
+    ///     namespace_threshold:       None,
+    ///     next_token_file:           None,
+    ///     no_sign_request:           false,
+    ///     object_age_histogram:      false,
+    ///     object_tag_filters:        Vec::new(),
+    ///     page_size:                 None,
+    ///     profile:                   None,
+    ///     region:                    Region::UsEast1,
+    ///     report_date:               None,
+    ///     report_class_transitions:  false,
+    ///     request_counter:           None,
+    ///     role_arn:                  None,
+    ///     role_session_name:         None,
+    ///     sdk_config:                SdkConfig::default(),
+    ///     shard_prefixes:            Vec::new(),
+    ///     skip_inaccessible:         false,
+    ///     tag_filters:               Vec::new(),
+    ///     object_versions:           ObjectVersions::Current,
     /// }
     /// ```
     fn default() -> Self {
@@ -60,11 +431,83 @@ impl Default for ClientConfig {
         let mode = ClientMode::S3;
 
         Self {
-            bucket_name: None,
-            mode:        mode,
-            region:      Region::UsEast1,
+            api_timeout:               None,
+            #[cfg(feature = "s3")]
+            auto_region:               false,
+            bucket_aliases:            BucketAliases::new(),
+            bucket_name:               None,
+            #[cfg(feature = "s3")]
+            check_logging:             false,
+            #[cfg(feature = "s3")]
+            check_versioning:          false,
+            #[cfg(feature = "s3")]
+            chunk_size:                10,
+            #[cfg(feature = "cloudwatch")]
+            cloudwatch_metric_name:    None,
+            #[cfg(feature = "cloudwatch")]
+            cloudwatch_namespace:      None,
+            #[cfg(feature = "s3")]
+            count_versions:            false,
+            #[cfg(feature = "s3")]
+            creation_date_range:       None,
+            #[cfg(feature = "s3")]
+            delimiter:                 None,
+            exclude_regions:           Vec::new(),
+            excluded_storage_classes:  Vec::new(),
+            #[cfg(feature = "s3")]
+            fail_fast:                 false,
+            #[cfg(feature = "s3")]
+            find_duplicates:           false,
+            #[cfg(feature = "cloudwatch")]
+            include_cloudwatch_errors: false,
+            #[cfg(feature = "cloudwatch")]
+            include_overhead:          true,
+            #[cfg(feature = "s3")]
+            include_multipart_uploads: false,
+            #[cfg(feature = "s3")]
+            include_size_on_disk:      false,
+            #[cfg(feature = "cloudwatch")]
+            list_metrics_filters:      Vec::new(),
+            #[cfg(feature = "s3")]
+            max_depth:                 1,
+            #[cfg(feature = "s3")]
+            max_objects:               None,
+            #[cfg(feature = "s3")]
+            metadata_sample_size:      None,
+            #[cfg(feature = "s3")]
+            modified_after:            None,
+            #[cfg(feature = "s3")]
+            modified_before:           None,
+            mode:                      mode,
+            #[cfg(feature = "s3")]
+            namespace_threshold:       None,
+            #[cfg(feature = "cloudwatch")]
+            next_token_file:           None,
+            no_sign_request:           false,
+            #[cfg(feature = "s3")]
+            object_age_histogram:      false,
+            #[cfg(feature = "s3")]
+            object_tag_filters:        Vec::new(),
+            #[cfg(feature = "s3")]
+            page_size:                 None,
+            profile:                   None,
+            region:                    Region::UsEast1,
+            #[cfg(feature = "cloudwatch")]
+            report_date:               None,
+            #[cfg(feature = "cloudwatch")]
+            report_class_transitions:  false,
+            request_counter:           None,
+            role_arn:                  None,
+            role_session_name:         None,
+            sdk_config:                SdkConfig::default(),
+            #[cfg(feature = "s3")]
+            shard_prefixes:            Vec::new(),
+            #[cfg(feature = "s3")]
+            skip_inaccessible:         false,
+            #[cfg(feature = "s3")]
+            tag_filters:               Vec::new(),
             #[cfg(feature = "s3")]
-            object_versions: ObjectVersions::Current,
+            object_versions:           ObjectVersions::Current,
         }
     }
 }
@@ -5,18 +5,49 @@ use anyhow::Result;
 use std::str::FromStr;
 
 /// Valid modes that `s3du` can operate in.
-#[derive(Debug, Eq, PartialEq)]
+#[derive(Clone, Debug, Eq, PartialEq)]
 pub enum ClientMode {
     /// CloudWatch mode is available when compiled with the `cloudwatch`
     /// feature.
     #[cfg(feature = "cloudwatch")]
     CloudWatch,
 
+    /// Combined mode tries CloudWatch first and falls back to S3. It is
+    /// available when compiled with both the `cloudwatch` and `s3` features.
+    #[cfg(all(feature = "cloudwatch", feature = "s3"))]
+    Combined,
+
     /// S3 mode is available when compiled with the `s3` feature.
     #[cfg(feature = "s3")]
     S3,
 }
 
+impl ClientMode {
+    /// Returns `true` if this mode makes use of the CloudWatch `Client`.
+    pub fn uses_cloudwatch(&self) -> bool {
+        match self {
+            #[cfg(feature = "cloudwatch")]
+            Self::CloudWatch => true,
+            #[cfg(all(feature = "cloudwatch", feature = "s3"))]
+            Self::Combined   => true,
+            #[cfg(feature = "s3")]
+            Self::S3         => false,
+        }
+    }
+
+    /// Returns `true` if this mode makes use of the S3 `Client`.
+    #[cfg(feature = "s3")]
+    pub fn uses_s3(&self) -> bool {
+        match self {
+            #[cfg(feature = "cloudwatch")]
+            Self::CloudWatch => false,
+            #[cfg(feature = "cloudwatch")]
+            Self::Combined   => true,
+            Self::S3         => true,
+        }
+    }
+}
+
 /// This is used to work out which mode we're in after parsing the CLI.
 /// We shouldn't ever hit the error condition here.
 impl FromStr for ClientMode {
@@ -26,9 +57,55 @@ impl FromStr for ClientMode {
         match s {
             #[cfg(feature = "cloudwatch")]
             "cloudwatch" => Ok(Self::CloudWatch),
+            #[cfg(all(feature = "cloudwatch", feature = "s3"))]
+            "combined"   => Ok(Self::Combined),
             #[cfg(feature = "s3")]
             "s3"         => Ok(Self::S3),
             _            => Err("no match"),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[cfg(feature = "cloudwatch")]
+    #[test]
+    fn test_uses_cloudwatch() {
+        let tests = vec![
+            ("cloudwatch", true),
+            #[cfg(feature = "s3")]
+            ("combined",   true),
+            #[cfg(feature = "s3")]
+            ("s3",         false),
+        ];
+
+        for test in tests {
+            let mode     = ClientMode::from_str(test.0).unwrap();
+            let expected = test.1;
+
+            assert_eq!(mode.uses_cloudwatch(), expected);
+        }
+    }
+
+    #[cfg(feature = "s3")]
+    #[test]
+    fn test_uses_s3() {
+        let tests = vec![
+            #[cfg(feature = "cloudwatch")]
+            ("cloudwatch", false),
+            #[cfg(feature = "cloudwatch")]
+            ("combined",   true),
+            ("s3",         true),
+        ];
+
+        for test in tests {
+            let mode     = ClientMode::from_str(test.0).unwrap();
+            let expected = test.1;
+
+            assert_eq!(mode.uses_s3(), expected);
+        }
+    }
+}
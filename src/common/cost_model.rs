@@ -0,0 +1,249 @@
+// CostModel
+#![forbid(unsafe_code)]
+#![deny(missing_docs)]
+use anyhow::Result;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+use std::str::FromStr;
+use tracing::debug;
+use super::{
+    S3duError,
+    StorageClass,
+};
+
+/// Number of bytes in a GB, as AWS uses when pricing storage.
+const BYTES_PER_GB: f64 = 1_073_741_824.0;
+
+/// The size of each storage class in a bucket, in bytes.
+pub type ClassSizes = HashMap<StorageClass, u64>;
+
+/// `CostModel` represents the prices used to estimate the monthly cost of
+/// storing buckets.
+#[derive(Clone, Debug, PartialEq)]
+pub enum CostModel {
+    /// Public US East (N. Virginia) prices for the first 50TB of each storage
+    /// class.
+    AwsStandard,
+
+    /// Public US East (N. Virginia) prices, with Standard storage priced at
+    /// the Standard-IA rate, estimating the cost once objects have been
+    /// transitioned to infrequent access.
+    AwsInfrequentAccess,
+
+    /// Prices per GB-month for each storage class, loaded from a file.
+    ///
+    /// Storage classes that aren't given are priced as `AwsStandard`.
+    Custom(HashMap<StorageClass, f64>),
+}
+
+impl CostModel {
+    /// Returns the `Custom` cost model with the prices in the JSON file at
+    /// `path`.
+    ///
+    /// The file should hold an object of prices per GB-month, keyed on
+    /// storage class, eg. `{"STANDARD": 0.021, "GLACIER": 0.0036}`.
+    pub fn from_file(path: &Path) -> Result<Self> {
+        debug!(path = ?path, "from_file: Loading prices");
+
+        let cost_model_error = |e: &dyn std::fmt::Display| {
+            S3duError::CostModel(format!("{}: {}", path.display(), e))
+        };
+
+        let file = File::open(path)
+            .map_err(|e| cost_model_error(&e))?;
+
+        let prices: HashMap<String, f64> = serde_json::from_reader(BufReader::new(file))
+            .map_err(|e| cost_model_error(&e))?;
+
+        let mut custom = HashMap::new();
+
+        for (class, price) in prices {
+            let storage_class = StorageClass::from(class.as_str());
+
+            if let StorageClass::Unknown(_) = storage_class {
+                let e = format!("Unknown storage class '{}'", class);
+                return Err(cost_model_error(&e).into());
+            }
+
+            if !price.is_finite() || price < 0.0 {
+                let e = format!("Invalid price '{}' for '{}'", price, class);
+                return Err(cost_model_error(&e).into());
+            }
+
+            custom.insert(storage_class, price);
+        }
+
+        Ok(Self::Custom(custom))
+    }
+
+    /// Returns the price of storing a GB of `class` for a month, in USD.
+    pub fn cost_per_gb(&self, class: StorageClass) -> f64 {
+        match self {
+            Self::AwsStandard => standard_price(&class),
+            Self::AwsInfrequentAccess => {
+                match class {
+                    StorageClass::Standard => standard_price(&StorageClass::StandardIA),
+                    class                  => standard_price(&class),
+                }
+            },
+            Self::Custom(prices) => {
+                match prices.get(&class) {
+                    Some(price) => *price,
+                    None        => standard_price(&class),
+                }
+            },
+        }
+    }
+
+    /// Returns the estimated monthly cost of storing the given `sizes`, in
+    /// USD.
+    pub fn monthly_cost(&self, sizes: &ClassSizes) -> f64 {
+        sizes.iter()
+            .map(|(class, size)| {
+                *size as f64 / BYTES_PER_GB * self.cost_per_gb(class.to_owned())
+            })
+            .sum()
+    }
+}
+
+/// Returns the public US East (N. Virginia) price of storing a GB of `class`
+/// for a month, in USD.
+///
+/// Outposts storage is paid for as capacity rather than per GB, so it's free
+/// here. Unknown storage classes are priced as Standard storage.
+fn standard_price(class: &StorageClass) -> f64 {
+    match class {
+        StorageClass::DeepArchive             => 0.00099,
+        StorageClass::GlacierFlexible         => 0.0036,
+        StorageClass::GlacierInstantRetrieval => 0.004,
+        StorageClass::IntelligentTiering      => 0.023,
+        StorageClass::OneZoneIA               => 0.01,
+        StorageClass::Outposts                => 0.0,
+        StorageClass::ReducedRedundancy       => 0.024,
+        StorageClass::Standard                => 0.023,
+        StorageClass::StandardIA              => 0.0125,
+        StorageClass::Unknown(_)              => 0.023,
+    }
+}
+
+/// This converts from the string argument we receive from the command line to
+/// our enum type.
+///
+/// `custom` isn't handled here, since it needs prices loaded from a file.
+impl FromStr for CostModel {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "aws-infrequent-access" => Ok(Self::AwsInfrequentAccess),
+            "aws-standard"          => Ok(Self::AwsStandard),
+            _                       => Err("no match"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_helpers::temp_path;
+    use pretty_assertions::assert_eq;
+    use std::fs;
+
+    #[test]
+    fn test_cost_model_from_str() {
+        let tests = vec![
+            ("aws-standard",          Some(CostModel::AwsStandard)),
+            ("aws-infrequent-access", Some(CostModel::AwsInfrequentAccess)),
+            ("custom",                None),
+        ];
+
+        for test in tests {
+            let model    = test.0;
+            let expected = test.1;
+
+            let ret = CostModel::from_str(model).ok();
+
+            assert_eq!(ret, expected);
+        }
+    }
+
+    #[test]
+    fn test_cost_per_gb() {
+        let mut prices = HashMap::new();
+        prices.insert(StorageClass::Standard, 0.02);
+
+        let custom = CostModel::Custom(prices);
+
+        let tests = vec![
+            (CostModel::AwsStandard,         StorageClass::Standard,                0.023),
+            (CostModel::AwsStandard,         StorageClass::GlacierFlexible,         0.0036),
+            (CostModel::AwsStandard,         StorageClass::GlacierInstantRetrieval, 0.004),
+            (CostModel::AwsInfrequentAccess, StorageClass::Standard,                0.0125),
+            (CostModel::AwsInfrequentAccess, StorageClass::DeepArchive,             0.00099),
+            (custom.clone(),                 StorageClass::Standard,                0.02),
+            (custom,                         StorageClass::StandardIA,              0.0125),
+        ];
+
+        for test in tests {
+            let (model, class, expected) = test;
+
+            assert_eq!(model.cost_per_gb(class), expected);
+        }
+    }
+
+    #[test]
+    fn test_monthly_cost() {
+        let mut sizes = ClassSizes::new();
+        sizes.insert(StorageClass::Standard, 100 * 1_073_741_824);
+        sizes.insert(StorageClass::GlacierFlexible, 1000 * 1_073_741_824);
+
+        let ret = CostModel::AwsStandard.monthly_cost(&sizes);
+
+        // 100GB at 0.023 and 1000GB at 0.0036.
+        assert!((ret - 5.9).abs() < 1e-9);
+
+        assert_eq!(CostModel::AwsStandard.monthly_cost(&ClassSizes::new()), 0.0);
+    }
+
+    #[test]
+    fn test_from_file() {
+        let path = temp_path("cost-model.json");
+
+        fs::write(&path, r#"{"STANDARD": 0.021, "GlacierStorage": 0.0036}"#).unwrap();
+        let ret = CostModel::from_file(&path).unwrap();
+
+        let mut expected = HashMap::new();
+        expected.insert(StorageClass::Standard, 0.021);
+        expected.insert(StorageClass::GlacierFlexible, 0.0036);
+
+        assert_eq!(ret, CostModel::Custom(expected));
+    }
+
+    #[test]
+    fn test_from_file_errors() {
+        let path = temp_path("cost-model-errors.json");
+
+        let tests = vec![
+            r#"{"STANDARD": "#,
+            r#"{"PLATINUM": 0.1}"#,
+            r#"{"STANDARD": -0.1}"#,
+        ];
+
+        for contents in tests {
+            fs::write(&path, contents).unwrap();
+            let ret = CostModel::from_file(&path).unwrap_err();
+
+            let ret = ret.downcast_ref::<S3duError>().unwrap();
+
+            assert!(matches!(ret, S3duError::CostModel(_)));
+        }
+
+        // Missing file.
+        let ret = CostModel::from_file(&path).unwrap_err();
+        let ret = ret.downcast_ref::<S3duError>().unwrap();
+
+        assert!(matches!(ret, S3duError::CostModel(_)));
+    }
+}
@@ -0,0 +1,269 @@
+// S3duError
+#![forbid(unsafe_code)]
+#![deny(missing_docs)]
+use rusoto_core::RusotoError;
+use rusoto_core::request::BufferedHttpResponse;
+use std::error::Error;
+use thiserror::Error;
+
+#[cfg(feature = "s3")]
+use std::any::Any;
+
+#[cfg(feature = "s3")]
+use rusoto_s3::{
+    HeadBucketError,
+    ListObjectsV2Error,
+};
+
+/// Error codes returned by AWS APIs when a request was throttled.
+const THROTTLING_CODES: &[&str] = &[
+    "RequestLimitExceeded",
+    "SlowDown",
+    "Throttling",
+    "ThrottlingException",
+    "TooManyRequestsException",
+];
+
+/// `S3duError` represents the common failure modes of the CloudWatch and S3
+/// `Client`s.
+///
+/// Errors returned by Rusoto are mapped into this type so that callers can
+/// react to specific errors without needing to know which AWS API failed.
+#[derive(Debug, Error, Eq, PartialEq)]
+pub enum S3duError {
+    /// Access to a resource was denied.
+    #[error("Access denied: {0}")]
+    AccessDenied(String),
+
+    /// The bucket aliases could not be loaded.
+    #[error("Bucket aliases error: {0}")]
+    BucketAliases(String),
+
+    /// A cost model could not be loaded.
+    #[error("Cost model error: {0}")]
+    CostModel(String),
+
+    /// There was a problem obtaining AWS credentials.
+    #[error("Credentials error: {0}")]
+    Credentials(String),
+
+    /// There was a problem dispatching the HTTP request.
+    #[error("HTTP dispatch error: {0}")]
+    HttpDispatch(String),
+
+    /// The command line arguments can't be used together.
+    #[error("{0}")]
+    InvalidArguments(String),
+
+    /// An AWS region could not be parsed.
+    #[error("Invalid region: {0}")]
+    InvalidRegion(String),
+
+    /// More objects were listed in a bucket than `--max-objects` allows.
+    #[cfg(feature = "s3")]
+    #[error("Object limit exceeded: {0}")]
+    MaxObjects(String),
+
+    /// The `ListMetrics` next token could not be saved or loaded.
+    #[cfg(feature = "cloudwatch")]
+    #[error("Next token error: {0}")]
+    NextToken(String),
+
+    /// CloudWatch returned no datapoints for a bucket.
+    #[cfg(feature = "cloudwatch")]
+    #[error("No CloudWatch datapoints for bucket: {0}")]
+    NoDatapoints(String),
+
+    /// The requested bucket does not exist.
+    #[error("No such bucket: {0}")]
+    NoSuchBucket(String),
+
+    /// The output file could not be created.
+    #[error("Output file error: {0}")]
+    OutputFile(String),
+
+    /// The AWS SDK configuration could not be loaded.
+    #[error("SDK configuration error: {0}")]
+    SdkConfig(String),
+
+    /// A snapshot of bucket sizes could not be saved or loaded.
+    #[error("Snapshot error: {0}")]
+    Snapshot(String),
+
+    /// The request was throttled by AWS.
+    #[error("Request throttled: {0}")]
+    Throttled(String),
+
+    /// An API call did not complete within the allowed time.
+    #[error("Request timed out: {0}")]
+    Timeout(String),
+
+    /// Any other error.
+    #[error("{0}")]
+    Other(String),
+}
+
+impl S3duError {
+    /// Returns `true` if `e` is an access denied error.
+    pub fn is_access_denied(e: &anyhow::Error) -> bool {
+        matches!(e.downcast_ref::<Self>(), Some(Self::AccessDenied(_)))
+    }
+
+    /// Classify an unknown HTTP response by its status code and the error code
+    /// contained in its body.
+    fn from_response(response: &BufferedHttpResponse) -> Self {
+        let body    = response.body_as_str();
+        let message = format!("{}: {}", response.status, body);
+
+        let has_code = |code: &str| {
+            body.contains(&format!("<Code>{}</Code>", code))
+        };
+
+        if has_code("NoSuchBucket") {
+            Self::NoSuchBucket(message)
+        }
+        else if THROTTLING_CODES.iter().any(|code| has_code(code)) {
+            Self::Throttled(message)
+        }
+        else {
+            match response.status.as_u16() {
+                403 => Self::AccessDenied(message),
+                429 => Self::Throttled(message),
+                503 => Self::Throttled(message),
+                _   => Self::Other(message),
+            }
+        }
+    }
+
+    /// Classify a service specific error.
+    ///
+    /// Only the service errors that map onto one of our own variants are
+    /// handled here, everything else is returned as `Other`.
+    fn from_service<E: Error + 'static>(err: E) -> Self {
+        #[cfg(feature = "s3")]
+        {
+            let any = &err as &dyn Any;
+
+            if let Some(HeadBucketError::NoSuchBucket(msg)) = any.downcast_ref() {
+                return Self::NoSuchBucket(msg.to_owned());
+            }
+
+            if let Some(ListObjectsV2Error::NoSuchBucket(msg)) = any.downcast_ref() {
+                return Self::NoSuchBucket(msg.to_owned());
+            }
+        }
+
+        Self::Other(err.to_string())
+    }
+}
+
+/// Conversion from any `RusotoError` to our `S3duError`.
+impl<E: Error + 'static> From<RusotoError<E>> for S3duError {
+    fn from(err: RusotoError<E>) -> Self {
+        match err {
+            RusotoError::Credentials(e)  => Self::Credentials(e.to_string()),
+            RusotoError::HttpDispatch(e) => Self::HttpDispatch(e.to_string()),
+            RusotoError::Service(e)      => Self::from_service(e),
+            RusotoError::Unknown(ref r)  => Self::from_response(r),
+            e                            => Self::Other(e.to_string()),
+        }
+    }
+}
+
+#[cfg(all(test, feature = "s3"))]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use rusoto_mock::{
+        MockCredentialsProvider,
+        MockRequestDispatcher,
+    };
+    use rusoto_s3::{
+        ListObjectsV2Request,
+        S3,
+        S3Client,
+    };
+
+    // Return the S3duError produced by a ListObjectsV2 call against a mock
+    // dispatcher.
+    async fn list_objects_error(dispatcher: MockRequestDispatcher) -> S3duError {
+        let client = S3Client::new_with(
+            dispatcher,
+            MockCredentialsProvider,
+            Default::default(),
+        );
+
+        let input = ListObjectsV2Request {
+            bucket: "test-bucket".into(),
+            ..Default::default()
+        };
+
+        client.list_objects_v2(input)
+            .await
+            .unwrap_err()
+            .into()
+    }
+
+    // Return just the variant name of an S3duError.
+    fn variant(err: &S3duError) -> &'static str {
+        match err {
+            S3duError::AccessDenied(_)     => "AccessDenied",
+            S3duError::BucketAliases(_)    => "BucketAliases",
+            S3duError::CostModel(_)        => "CostModel",
+            S3duError::Credentials(_)      => "Credentials",
+            S3duError::HttpDispatch(_)     => "HttpDispatch",
+            S3duError::InvalidArguments(_) => "InvalidArguments",
+            S3duError::InvalidRegion(_)    => "InvalidRegion",
+            #[cfg(feature = "s3")]
+            S3duError::MaxObjects(_)       => "MaxObjects",
+            #[cfg(feature = "cloudwatch")]
+            S3duError::NextToken(_)        => "NextToken",
+            #[cfg(feature = "cloudwatch")]
+            S3duError::NoDatapoints(_)     => "NoDatapoints",
+            S3duError::NoSuchBucket(_)     => "NoSuchBucket",
+            S3duError::OutputFile(_)       => "OutputFile",
+            S3duError::SdkConfig(_)        => "SdkConfig",
+            S3duError::Snapshot(_)         => "Snapshot",
+            S3duError::Throttled(_)        => "Throttled",
+            S3duError::Timeout(_)          => "Timeout",
+            S3duError::Other(_)            => "Other",
+        }
+    }
+
+    #[tokio::test]
+    async fn test_from_rusoto_error() {
+        let tests = vec![
+            (403, "",                                         "AccessDenied"),
+            (404, "<Error><Code>NoSuchBucket</Code></Error>", "NoSuchBucket"),
+            (400, "<Error><Code>Throttling</Code></Error>",   "Throttled"),
+            (503, "<Error><Code>SlowDown</Code></Error>",     "Throttled"),
+            (503, "",                                         "Throttled"),
+            (500, "",                                         "Other"),
+        ];
+
+        for test in tests {
+            let status   = test.0;
+            let body     = test.1;
+            let expected = test.2;
+
+            let dispatcher = MockRequestDispatcher::with_status(status)
+                .with_body(body);
+
+            let err = list_objects_error(dispatcher).await;
+
+            assert_eq!(variant(&err), expected);
+        }
+    }
+
+    #[test]
+    fn test_from_service_error() {
+        let err = RusotoError::Service(
+            ListObjectsV2Error::NoSuchBucket("test-bucket".into()),
+        );
+
+        let ret: S3duError = err.into();
+        let expected = S3duError::NoSuchBucket("test-bucket".into());
+
+        assert_eq!(ret, expected);
+    }
+}
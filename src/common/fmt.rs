@@ -0,0 +1,149 @@
+// Timestamp formatting
+#![forbid(unsafe_code)]
+#![deny(missing_docs)]
+use chrono::{
+    DateTime,
+    SecondsFormat,
+    Utc,
+};
+use std::str::FromStr;
+
+/// `TimestampFormat` represents how we want timestamps to be displayed.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum TimestampFormat {
+    /// Represent timestamps relative to now, eg. `3 days ago`.
+    Human,
+
+    /// Represent timestamps as RFC 3339 strings, eg.
+    /// `2020-03-01T00:00:00Z`.
+    Rfc3339,
+
+    /// Represent timestamps as seconds since the Unix epoch.
+    Unix,
+}
+
+/// This converts from the string arguments we receive on the command line to
+/// our enum type.
+impl FromStr for TimestampFormat {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "human"   => Ok(Self::Human),
+            "rfc3339" => Ok(Self::Rfc3339),
+            "unix"    => Ok(Self::Unix),
+            _         => Err("no match"),
+        }
+    }
+}
+
+/// Returns `dt` formatted according to `fmt`.
+pub fn format_timestamp(dt: DateTime<Utc>, fmt: TimestampFormat) -> String {
+    match fmt {
+        TimestampFormat::Human   => format_relative(dt, Utc::now()),
+        TimestampFormat::Rfc3339 => dt.to_rfc3339_opts(SecondsFormat::Secs, true),
+        TimestampFormat::Unix    => dt.timestamp().to_string(),
+    }
+}
+
+/// Returns `dt` formatted relative to `now` in the largest whole unit, eg.
+/// `3 days ago`.
+fn format_relative(dt: DateTime<Utc>, now: DateTime<Utc>) -> String {
+    let seconds = (now - dt).num_seconds();
+
+    // Timestamps a little in the future can be caused by clock skew, treat
+    // them the same as the present.
+    if seconds < 60 {
+        return "just now".into();
+    }
+
+    let units = [
+        ("day",    86_400),
+        ("hour",   3_600),
+        ("minute", 60),
+    ];
+
+    // One of the units will always match, since we know that seconds is at
+    // least 60.
+    let (unit, count) = units.iter()
+        .map(|(unit, size)| (unit, seconds / size))
+        .find(|(_, count)| *count > 0)
+        .unwrap();
+
+    let plural = if count == 1 { "" } else { "s" };
+
+    format!("{} {}{} ago", count, unit, plural)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{
+        Duration,
+        TimeZone,
+    };
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_timestamp_format_from_str() {
+        let tests = vec![
+            ("human",   Some(TimestampFormat::Human)),
+            ("rfc3339", Some(TimestampFormat::Rfc3339)),
+            ("unix",    Some(TimestampFormat::Unix)),
+            ("iso8601", None),
+        ];
+
+        for test in tests {
+            let fmt      = test.0;
+            let expected = test.1;
+
+            let ret = TimestampFormat::from_str(fmt).ok();
+
+            assert_eq!(ret, expected);
+        }
+    }
+
+    #[test]
+    fn test_format_timestamp() {
+        let dt = Utc.ymd(2020, 3, 1).and_hms(12, 30, 0);
+
+        let tests = vec![
+            (TimestampFormat::Rfc3339, "2020-03-01T12:30:00Z"),
+            (TimestampFormat::Unix,    "1583065800"),
+        ];
+
+        for test in tests {
+            let fmt      = test.0;
+            let expected = test.1;
+
+            let ret = format_timestamp(dt, fmt);
+
+            assert_eq!(ret, expected);
+        }
+    }
+
+    #[test]
+    fn test_format_relative() {
+        let now = Utc.ymd(2020, 3, 10).and_hms(12, 0, 0);
+
+        let tests = vec![
+            (Duration::seconds(-30), "just now"),
+            (Duration::seconds(59),  "just now"),
+            (Duration::minutes(1),   "1 minute ago"),
+            (Duration::minutes(45),  "45 minutes ago"),
+            (Duration::hours(1),     "1 hour ago"),
+            (Duration::hours(23),    "23 hours ago"),
+            (Duration::days(1),      "1 day ago"),
+            (Duration::days(3),      "3 days ago"),
+        ];
+
+        for test in tests {
+            let age      = test.0;
+            let expected = test.1;
+
+            let ret = format_relative(now - age, now);
+
+            assert_eq!(ret, expected);
+        }
+    }
+}
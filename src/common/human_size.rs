@@ -2,7 +2,8 @@
 #![forbid(unsafe_code)]
 #![deny(missing_docs)]
 use humansize::FileSize;
-use log::debug;
+use num_format::ToFormattedString;
+use tracing::debug;
 use super::SizeUnit;
 
 /// `HumanSize` trait.
@@ -10,18 +11,19 @@ pub trait HumanSize {
     fn humansize(&self, unit: &SizeUnit) -> String;
 }
 
-/// `HumanSize` trait implementation for `usize`.
-impl HumanSize for usize {
+/// `HumanSize` trait implementation for `u64`.
+impl HumanSize for u64 {
     /// Return `self` as a human friendly size if requested by `unit`.
     fn humansize(&self, unit: &SizeUnit) -> String {
-        debug!("humansize: size {}, unit {:?}", self, unit);
+        debug!(size = self, unit = ?unit, "humansize");
 
-        // Unwrap should be fine here, usize cannot be negative, so file_size
+        // Unwrap should be fine here, u64 cannot be negative, so file_size
         // shouldn't error.
         match unit {
-            SizeUnit::Binary(unit)  => self.file_size(unit).unwrap(),
-            SizeUnit::Bytes         => self.to_string(),
-            SizeUnit::Decimal(unit) => self.file_size(unit).unwrap(),
+            SizeUnit::Binary(unit)        => self.file_size(unit).unwrap(),
+            SizeUnit::Bytes(None)         => self.to_string(),
+            SizeUnit::Bytes(Some(locale)) => self.to_formatted_string(locale),
+            SizeUnit::Decimal(unit)       => self.file_size(unit).unwrap(),
         }
     }
 }
@@ -29,6 +31,7 @@ impl HumanSize for usize {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use num_format::Locale;
     use pretty_assertions::assert_eq;
     use std::str::FromStr;
 
@@ -42,7 +45,7 @@ mod tests {
         ];
 
         for test in tests {
-            let size: usize = test.0;
+            let size: u64 = test.0;
             let unit        = SizeUnit::from_str(test.1).unwrap();
             let expected    = test.2;
 
@@ -51,4 +54,25 @@ mod tests {
             assert_eq!(ret, expected);
         }
     }
+
+    #[test]
+    fn test_humansize_locale() {
+        let tests = vec![
+            (1_234_567_890, "binary",  Locale::en, "1.15GiB"),
+            (1_234_567_890, "bytes",   Locale::en, "1,234,567,890"),
+            (1_234_567_890, "bytes",   Locale::de, "1.234.567.890"),
+            (999,           "bytes",   Locale::en, "999"),
+            (1_234_567_890, "decimal", Locale::en, "1.23GB"),
+        ];
+
+        for test in tests {
+            let (size, unit, locale, expected) = test;
+
+            let unit = SizeUnit::from_str(unit)
+                .unwrap()
+                .with_locale(locale);
+
+            assert_eq!(size.humansize(&unit), expected);
+        }
+    }
 }
@@ -0,0 +1,138 @@
+// MetadataStats
+#![forbid(unsafe_code)]
+#![deny(missing_docs)]
+
+/// z-score for a two sided 95% confidence interval.
+const Z_95: f64 = 1.96;
+
+/// Estimates of the user-defined metadata stored on the objects in a bucket,
+/// made from a sample of its objects.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct MetadataStats {
+    /// The number of objects in the bucket that the sample was taken from.
+    pub population: usize,
+
+    /// The number of objects that were sampled.
+    pub sampled: usize,
+
+    /// The mean number of metadata entries per sampled object.
+    pub average_count: f64,
+
+    /// The mean size of the metadata keys and values per sampled object, in
+    /// bytes.
+    pub average_size: f64,
+
+    /// The margin of error of `average_size` at 95% confidence, in bytes.
+    ///
+    /// This is `None` when there were too few samples to estimate it.
+    pub size_margin: Option<f64>,
+}
+
+impl MetadataStats {
+    /// Returns the `MetadataStats` for the `(count, size)` metadata `samples`
+    /// taken from a bucket holding `population` objects.
+    ///
+    /// The margin of error uses a finite population correction, so a sample
+    /// covering every object in the bucket has no margin of error.
+    pub fn from_samples(population: usize, samples: &[(usize, usize)]) -> Self {
+        let sampled = samples.len();
+
+        if sampled == 0 {
+            return Self {
+                population: population,
+                ..Default::default()
+            };
+        }
+
+        let n = sampled as f64;
+
+        let average_count = samples.iter()
+            .map(|(count, _)| *count as f64)
+            .sum::<f64>() / n;
+
+        let average_size = samples.iter()
+            .map(|(_, size)| *size as f64)
+            .sum::<f64>() / n;
+
+        let size_margin = if sampled >= population {
+            Some(0.0)
+        }
+        else if sampled < 2 {
+            None
+        }
+        else {
+            let variance = samples.iter()
+                .map(|(_, size)| (*size as f64 - average_size).powi(2))
+                .sum::<f64>() / (n - 1.0);
+
+            let population = population as f64;
+            let correction = ((population - n) / (population - 1.0)).sqrt();
+
+            Some(Z_95 * (variance / n).sqrt() * correction)
+        };
+
+        Self {
+            population:    population,
+            sampled:       sampled,
+            average_count: average_count,
+            average_size:  average_size,
+            size_margin:   size_margin,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_from_samples() {
+        let samples = vec![
+            (1, 10),
+            (2, 20),
+            (3, 30),
+            (2, 20),
+        ];
+
+        let ret = MetadataStats::from_samples(1000, &samples);
+
+        assert_eq!(ret.population, 1000);
+        assert_eq!(ret.sampled, 4);
+        assert_eq!(ret.average_count, 2.0);
+        assert_eq!(ret.average_size, 20.0);
+
+        // Sample standard deviation is sqrt(200 / 3), with a finite
+        // population correction of sqrt(996 / 999).
+        let margin   = ret.size_margin.unwrap();
+        let expected = 1.96 * (200.0_f64 / 3.0 / 4.0).sqrt()
+            * (996.0_f64 / 999.0).sqrt();
+
+        assert!((margin - expected).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_from_samples_whole_population() {
+        let samples = vec![
+            (1, 10),
+            (3, 50),
+        ];
+
+        let ret = MetadataStats::from_samples(2, &samples);
+
+        assert_eq!(ret.average_size, 30.0);
+        assert_eq!(ret.size_margin, Some(0.0));
+    }
+
+    #[test]
+    fn test_from_samples_too_few() {
+        let ret = MetadataStats::from_samples(10, &[(1, 10)]);
+
+        assert_eq!(ret.sampled, 1);
+        assert_eq!(ret.size_margin, None);
+
+        let ret = MetadataStats::from_samples(0, &[]);
+
+        assert_eq!(ret, MetadataStats::default());
+    }
+}
@@ -6,7 +6,7 @@ use std::str::FromStr;
 
 /// `ObjectVersions` represents which objects we're going to sum when
 /// operating in S3 mode.
-#[derive(Debug)]
+#[derive(Clone, Debug, Eq, PartialEq)]
 pub enum ObjectVersions {
     /// Sum size of all object versions (both `Current` and `NonCurrent`)
     All,
@@ -21,6 +21,17 @@ pub enum ObjectVersions {
     NonCurrent,
 }
 
+impl ObjectVersions {
+    /// The values accepted by `from_str`, for the possible values of the
+    /// `--object-versions` command line switch.
+    pub const VARIANTS: &'static [&'static str] = &[
+        "all",
+        "current",
+        "multipart",
+        "non-current",
+    ];
+}
+
 /// This converts from the string argument we receive from the command line to
 /// our enum type.
 impl FromStr for ObjectVersions {
@@ -36,3 +47,37 @@ impl FromStr for ObjectVersions {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_object_versions_from_str() {
+        let tests = vec![
+            ("all",         Some(ObjectVersions::All)),
+            ("current",     Some(ObjectVersions::Current)),
+            ("multipart",   Some(ObjectVersions::Multipart)),
+            ("non-current", Some(ObjectVersions::NonCurrent)),
+            ("noncurrent",  None),
+            ("Current",     None),
+        ];
+
+        for test in tests {
+            let (versions, expected) = test;
+
+            let ret = ObjectVersions::from_str(versions).ok();
+
+            assert_eq!(ret, expected);
+        }
+    }
+
+    #[test]
+    fn test_object_versions_variants() {
+        // Every possible value given to clap must parse.
+        for variant in ObjectVersions::VARIANTS {
+            assert!(ObjectVersions::from_str(variant).is_ok(), "{}", variant);
+        }
+    }
+}
@@ -0,0 +1,92 @@
+// PrefixNode
+#![forbid(unsafe_code)]
+#![deny(missing_docs)]
+
+/// A node in the tree of prefixes (virtual directories) within a bucket.
+///
+/// The root node of a tree represents the bucket itself and has an empty
+/// prefix. The `size` of a node includes the sizes of all of its children.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct PrefixNode {
+    /// The full key prefix of this node, including the trailing delimiter.
+    pub prefix: String,
+
+    /// The size of all objects under this prefix in bytes.
+    pub size: u64,
+
+    /// Prefixes found directly under this prefix.
+    pub children: Vec<PrefixNode>,
+}
+
+impl PrefixNode {
+    /// Returns a new, empty, `PrefixNode` for the given `prefix`.
+    pub fn new(prefix: String) -> Self {
+        Self {
+            prefix: prefix,
+            ..Default::default()
+        }
+    }
+
+    /// Returns every node below this one in depth-first order, paired with
+    /// its depth relative to this node.
+    ///
+    /// Direct children have a depth of `1`.
+    pub fn descendants(&self) -> Vec<(usize, &PrefixNode)> {
+        let mut nodes = Vec::new();
+
+        for child in &self.children {
+            nodes.push((1, child));
+
+            for (depth, node) in child.descendants() {
+                nodes.push((depth + 1, node));
+            }
+        }
+
+        nodes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_descendants() {
+        let tree = PrefixNode {
+            prefix:   "".into(),
+            size:     7,
+            children: vec![
+                PrefixNode {
+                    prefix:   "a/".into(),
+                    size:     3,
+                    children: vec![
+                        PrefixNode {
+                            prefix:   "a/b/".into(),
+                            size:     2,
+                            children: Vec::new(),
+                        },
+                    ],
+                },
+                PrefixNode {
+                    prefix:   "c/".into(),
+                    size:     4,
+                    children: Vec::new(),
+                },
+            ],
+        };
+
+        let expected = vec![
+            (1, "a/",   3),
+            (2, "a/b/", 2),
+            (1, "c/",   4),
+        ];
+
+        let ret: Vec<(usize, &str, u64)> = tree.descendants()
+            .iter()
+            .map(|(depth, node)| (*depth, node.prefix.as_str(), node.size))
+            .collect();
+
+        assert_eq!(ret, expected);
+    }
+}
@@ -0,0 +1,192 @@
+// Regions
+#![forbid(unsafe_code)]
+#![deny(missing_docs)]
+use anyhow::Result;
+use rusoto_core::Region;
+use std::env;
+use std::str::FromStr;
+use super::S3duError;
+
+/// Environment variables that the region is read from when it isn't given on
+/// the command line, in order of precedence.
+const REGION_ENV_VARS: &[&str] = &[
+    "AWS_REGION",
+    "AWS_DEFAULT_REGION",
+];
+
+/// Every region in the standard AWS partition, which buckets are sized in
+/// when running with `--all-regions`.
+///
+/// The GovCloud and China regions are in their own partitions, which need
+/// separate credentials, so they're left out. Opt-in regions are included,
+/// and will fail to be sized if they haven't been enabled.
+pub const STANDARD_REGIONS: &[Region] = &[
+    Region::AfSouth1,
+    Region::ApEast1,
+    Region::ApNortheast1,
+    Region::ApNortheast2,
+    Region::ApNortheast3,
+    Region::ApSouth1,
+    Region::ApSoutheast1,
+    Region::ApSoutheast2,
+    Region::CaCentral1,
+    Region::EuCentral1,
+    Region::EuNorth1,
+    Region::EuSouth1,
+    Region::EuWest1,
+    Region::EuWest2,
+    Region::EuWest3,
+    Region::MeSouth1,
+    Region::SaEast1,
+    Region::UsEast1,
+    Region::UsEast2,
+    Region::UsWest1,
+    Region::UsWest2,
+];
+
+/// Returns the `STANDARD_REGIONS` other than those in `exclude_regions`,
+/// which are the regions scanned with `--all-regions`.
+pub fn scanned_regions(exclude_regions: &[Region]) -> Vec<Region> {
+    STANDARD_REGIONS
+        .iter()
+        .filter(|region| !exclude_regions.contains(region))
+        .cloned()
+        .collect()
+}
+
+/// Returns the name of the region set in the environment, if any.
+///
+/// `AWS_REGION` takes precedence over `AWS_DEFAULT_REGION`, matching the AWS
+/// CLI. Empty variables are treated as unset.
+pub fn env_region_name() -> Option<String> {
+    REGION_ENV_VARS
+        .iter()
+        .filter_map(|name| env::var(name).ok())
+        .find(|value| !value.is_empty())
+}
+
+/// Returns the region that clients should be created in.
+///
+/// The region is resolved in the following order, matching the AWS CLI:
+///   - The `region` given on the command line
+///   - The `AWS_REGION` environment variable
+///   - The `AWS_DEFAULT_REGION` environment variable
+///   - The region returned by `profile_region`, which is only called if
+///     needed
+///   - `us-east-1`
+pub fn resolve_region<F>(region: Option<&str>, profile_region: F) -> Result<Region>
+where
+    F: FnOnce() -> Result<Option<Region>>,
+{
+    let name = region
+        .map(|name| name.to_string())
+        .or_else(env_region_name);
+
+    if let Some(name) = name {
+        let region = Region::from_str(&name)
+            .map_err(|_| S3duError::InvalidRegion(name))?;
+
+        return Ok(region);
+    }
+
+    let region = profile_region()?.unwrap_or(Region::UsEast1);
+
+    Ok(region)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_standard_regions() {
+        for region in STANDARD_REGIONS {
+            let name = region.name();
+
+            // Only the standard aws partition, not aws-us-gov or aws-cn.
+            assert!(!name.starts_with("us-gov-"));
+            assert!(!name.starts_with("cn-"));
+
+            assert_eq!(&Region::from_str(name).unwrap(), region);
+        }
+    }
+
+    #[test]
+    fn test_scanned_regions() {
+        assert_eq!(scanned_regions(&[]), STANDARD_REGIONS.to_vec());
+
+        let exclude = vec![
+            Region::ApSoutheast1,
+            Region::CnNorth1,
+            Region::EuWest1,
+        ];
+
+        let ret = scanned_regions(&exclude);
+
+        assert_eq!(ret.len(), STANDARD_REGIONS.len() - 2);
+        assert!(!ret.contains(&Region::ApSoutheast1));
+        assert!(!ret.contains(&Region::EuWest1));
+        assert!(ret.contains(&Region::UsEast1));
+    }
+
+    // The environment is shared between tests, so every case that sets the
+    // region variables is run from this one test.
+    #[test]
+    fn test_resolve_region() {
+        let saved: Vec<(&str, Option<String>)> = REGION_ENV_VARS
+            .iter()
+            .map(|name| (*name, env::var(name).ok()))
+            .collect();
+
+        let profile = || Ok(Some(Region::ApSouth1));
+        let none    = || Ok(None);
+
+        let tests = vec![
+            // (--region, AWS_REGION, AWS_DEFAULT_REGION, has profile, expected)
+            (Some("eu-west-1"), Some("us-west-1"), Some("us-west-2"), true,  Region::EuWest1),
+            (None,              Some("us-west-1"), Some("us-west-2"), true,  Region::UsWest1),
+            (None,              None,              Some("us-west-2"), true,  Region::UsWest2),
+            (None,              Some(""),          Some("us-west-2"), true,  Region::UsWest2),
+            (None,              None,              None,              true,  Region::ApSouth1),
+            (None,              None,              None,              false, Region::UsEast1),
+        ];
+
+        for test in tests {
+            let (region, aws_region, aws_default_region, has_profile, expected) = test;
+
+            for (name, value) in REGION_ENV_VARS.iter().zip(&[aws_region, aws_default_region]) {
+                match value {
+                    Some(value) => env::set_var(name, value),
+                    None        => env::remove_var(name),
+                }
+            }
+
+            let ret = if has_profile {
+                resolve_region(region, profile)
+            }
+            else {
+                resolve_region(region, none)
+            };
+
+            assert_eq!(ret.unwrap(), expected);
+        }
+
+        // Unknown regions in the environment are errors, rather than being
+        // silently replaced with the default.
+        env::set_var("AWS_REGION", "mars-north-1");
+        env::remove_var("AWS_DEFAULT_REGION");
+
+        let ret = resolve_region(None, none).unwrap_err();
+        let ret = ret.downcast_ref::<S3duError>().unwrap();
+
+        assert_eq!(ret, &S3duError::InvalidRegion("mars-north-1".into()));
+
+        for (name, value) in saved {
+            match value {
+                Some(value) => env::set_var(name, value),
+                None        => env::remove_var(name),
+            }
+        }
+    }
+}
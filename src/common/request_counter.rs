@@ -0,0 +1,65 @@
+// RequestCounter
+#![forbid(unsafe_code)]
+#![deny(missing_docs)]
+use std::collections::BTreeMap;
+use std::sync::{
+    Arc,
+    Mutex,
+};
+
+/// `RequestCounter` counts the AWS API requests made by the clients that
+/// share it, keyed on the name of the AWS service that they were made to.
+///
+/// Clones of a `RequestCounter` share the same counts, so that requests made
+/// by clients in different regions are counted together.
+#[derive(Clone, Debug, Default)]
+pub struct RequestCounter(Arc<Mutex<BTreeMap<String, usize>>>);
+
+impl RequestCounter {
+    /// Records a request made to the AWS `service`, eg. `s3`.
+    pub fn record(&self, service: &str) {
+        let mut counts = self.0.lock().unwrap();
+
+        *counts.entry(service.into()).or_insert(0) += 1;
+    }
+
+    /// Returns the number of requests made to each service, ordered by
+    /// service name.
+    pub fn counts(&self) -> BTreeMap<String, usize> {
+        self.0.lock().unwrap().clone()
+    }
+
+    /// Returns the total number of requests made to every service.
+    pub fn total(&self) -> usize {
+        self.0.lock().unwrap().values().sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_request_counter() {
+        let counter = RequestCounter::default();
+
+        assert_eq!(counter.total(), 0);
+        assert!(counter.counts().is_empty());
+
+        // Clones share their counts.
+        let clone = counter.clone();
+
+        counter.record("s3");
+        clone.record("monitoring");
+        clone.record("s3");
+
+        let expected: BTreeMap<String, usize> = vec![
+            ("monitoring".to_string(), 1),
+            ("s3".to_string(),         2),
+        ].into_iter().collect();
+
+        assert_eq!(counter.counts(), expected);
+        assert_eq!(counter.total(), 3);
+    }
+}
@@ -0,0 +1,130 @@
+// SdkConfig
+#![forbid(unsafe_code)]
+#![deny(missing_docs)]
+use anyhow::Result;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+use tracing::debug;
+use super::S3duError;
+
+/// Settings from the AWS shared configuration format that apply to each
+/// request, but that Rusoto has no way to honour.
+///
+/// These are rejected rather than ignored, so that a file written for
+/// another SDK doesn't silently behave differently.
+const UNSUPPORTED_SETTINGS: &[&str] = &[
+    "max_attempts",
+    "retry_mode",
+];
+
+/// `SdkConfig` holds overrides of the AWS SDK configuration, for settings
+/// that aren't exposed by their own command line arguments.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct SdkConfig {
+    /// Application ID added to the `User-Agent` of each request, as
+    /// `app/<id>`, so that requests can be attributed in CloudTrail.
+    pub sdk_ua_app_id: Option<String>,
+}
+
+impl SdkConfig {
+    /// Returns the `SdkConfig` in the JSON file at `path`.
+    ///
+    /// The file should hold an object of settings, named as in the AWS
+    /// shared configuration format, eg. `{"sdk_ua_app_id": "nightly-report"}`.
+    pub fn from_file(path: &Path) -> Result<Self> {
+        debug!(path = ?path, "from_file: Loading SDK configuration");
+
+        let sdk_config_error = |e: &dyn std::fmt::Display| {
+            S3duError::SdkConfig(format!("{}: {}", path.display(), e))
+        };
+
+        let file = File::open(path)
+            .map_err(|e| sdk_config_error(&e))?;
+
+        let settings: HashMap<String, Value> = serde_json::from_reader(BufReader::new(file))
+            .map_err(|e| sdk_config_error(&e))?;
+
+        let mut config = Self::default();
+
+        for (name, value) in settings {
+            match (name.as_str(), value) {
+                ("sdk_ua_app_id", Value::String(id)) => {
+                    config.sdk_ua_app_id = Some(id);
+                },
+                ("sdk_ua_app_id", value) => {
+                    let e = format!("Invalid value '{}' for '{}'", value, name);
+                    return Err(sdk_config_error(&e).into());
+                },
+                (name, _) if UNSUPPORTED_SETTINGS.contains(&name) => {
+                    let e = format!("Unsupported setting '{}'", name);
+                    return Err(sdk_config_error(&e).into());
+                },
+                (name, _) => {
+                    let e = format!("Unknown setting '{}'", name);
+                    return Err(sdk_config_error(&e).into());
+                },
+            }
+        }
+
+        Ok(config)
+    }
+
+    /// Returns the text appended to the `User-Agent` of each request, if
+    /// any.
+    pub fn user_agent(&self) -> Option<String> {
+        self.sdk_ua_app_id
+            .as_ref()
+            .map(|id| format!("app/{}", id))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_helpers::temp_path;
+    use pretty_assertions::assert_eq;
+    use std::fs;
+
+    #[test]
+    fn test_from_file() {
+        let path = temp_path("sdk-config.json");
+
+        fs::write(&path, r#"{"sdk_ua_app_id": "nightly-report"}"#).unwrap();
+        let ret = SdkConfig::from_file(&path).unwrap();
+
+        let expected = SdkConfig {
+            sdk_ua_app_id: Some("nightly-report".into()),
+        };
+
+        assert_eq!(ret, expected);
+        assert_eq!(ret.user_agent(), Some("app/nightly-report".into()));
+        assert_eq!(SdkConfig::default().user_agent(), None);
+    }
+
+    #[test]
+    fn test_from_file_errors() {
+        let path = temp_path("sdk-config-errors.json");
+
+        let tests = vec![
+            (r#"{"sdk_ua_app_id": "#,              "EOF while parsing"),
+            (r#"{"sdk_ua_app_id": 1}"#,            "Invalid value '1' for 'sdk_ua_app_id'"),
+            (r#"{"retry_mode": "adaptive"}"#,      "Unsupported setting 'retry_mode'"),
+            (r#"{"use_fips_endpoint": true}"#,     "Unknown setting 'use_fips_endpoint'"),
+        ];
+
+        for test in tests {
+            let (contents, expected) = test;
+
+            fs::write(&path, contents).unwrap();
+            let ret = SdkConfig::from_file(&path).unwrap_err();
+
+            let ret = ret.downcast_ref::<S3duError>().unwrap();
+
+            assert!(matches!(ret, S3duError::SdkConfig(_)));
+            assert!(ret.to_string().contains(expected), "{}", ret);
+        }
+    }
+}
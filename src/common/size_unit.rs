@@ -6,6 +6,7 @@ use humansize::file_size_opts::{
     self,
     FileSizeOpts,
 };
+use num_format::Locale;
 use std::str::FromStr;
 
 // We remove the space from the humansize output so that our own output is
@@ -31,14 +32,29 @@ pub enum SizeUnit {
     /// 1024).
     Binary(FileSizeOpts),
 
-    /// Represent bucket sizes as the number of bytes.
-    Bytes,
+    /// Represent bucket sizes as the number of bytes, with the thousands
+    /// separator of the `Locale`, if any.
+    Bytes(Option<Locale>),
 
     /// Represent bucket sizes as human readable using non-SI units (multiples
     /// of 1000).
     Decimal(FileSizeOpts),
 }
 
+impl SizeUnit {
+    /// Returns this unit with byte counts formatted using the thousands
+    /// separator of `locale`.
+    ///
+    /// Human readable units are returned unchanged, as their sizes are
+    /// already short enough to read.
+    pub fn with_locale(self, locale: Locale) -> Self {
+        match self {
+            Self::Bytes(_) => Self::Bytes(Some(locale)),
+            unit           => unit,
+        }
+    }
+}
+
 /// This converts from the string arguments we receive on the command line to
 /// our enum type.
 impl FromStr for SizeUnit {
@@ -47,7 +63,7 @@ impl FromStr for SizeUnit {
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         match s {
             "binary"  => Ok(Self::Binary(SIZE_UNIT_BINARY)),
-            "bytes"   => Ok(Self::Bytes),
+            "bytes"   => Ok(Self::Bytes(None)),
             "decimal" => Ok(Self::Decimal(SIZE_UNIT_DECIMAL)),
             _         => Err("no match"),
         }
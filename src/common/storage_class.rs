@@ -0,0 +1,380 @@
+// StorageClass
+#![forbid(unsafe_code)]
+#![deny(missing_docs)]
+use lazy_static::lazy_static;
+use std::collections::HashSet;
+use std::sync::Mutex;
+use tracing::{
+    debug,
+    warn,
+};
+
+/// Minimum billable object size of the infrequent access storage classes.
+#[cfg(feature = "s3")]
+const MIN_INFREQUENT_ACCESS_SIZE: u64 = 128 * 1024;
+
+/// Minimum billable object size of the Glacier archive storage classes,
+/// covering the metadata that S3 stores alongside each archived object.
+#[cfg(feature = "s3")]
+const MIN_ARCHIVE_SIZE: u64 = 40 * 1024;
+
+lazy_static! {
+    /// Unknown storage classes that have already been warned about, so that
+    /// each one is only warned about once per run.
+    static ref UNKNOWN_CLASSES: Mutex<HashSet<String>> = Mutex::new(HashSet::new());
+}
+
+/// `StorageClass` represents the S3 storage class of objects in a bucket.
+///
+/// This can be created from either the storage class strings returned by the
+/// S3 API (eg. `STANDARD_IA`) or the `StorageType` dimension values used by
+/// CloudWatch (eg. `StandardIAStorage`).
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub enum StorageClass {
+    /// Deep Archive storage.
+    DeepArchive,
+
+    /// Glacier Flexible Retrieval storage, formerly known as Glacier.
+    GlacierFlexible,
+
+    /// Glacier Instant Retrieval storage.
+    GlacierInstantRetrieval,
+
+    /// Intelligent Tiering storage.
+    IntelligentTiering,
+
+    /// One Zone Infrequent Access storage.
+    OneZoneIA,
+
+    /// S3 on Outposts storage.
+    Outposts,
+
+    /// Reduced Redundancy storage.
+    ReducedRedundancy,
+
+    /// Standard storage.
+    Standard,
+
+    /// Standard Infrequent Access storage.
+    StandardIA,
+
+    /// A storage class that we don't know about.
+    Unknown(String),
+}
+
+impl StorageClass {
+    /// Returns the storage class string used by the S3 API for this class,
+    /// eg. `STANDARD_IA`.
+    pub fn name(&self) -> &str {
+        match self {
+            Self::DeepArchive             => "DEEP_ARCHIVE",
+            Self::GlacierFlexible         => "GLACIER",
+            Self::GlacierInstantRetrieval => "GLACIER_IR",
+            Self::IntelligentTiering      => "INTELLIGENT_TIERING",
+            Self::OneZoneIA               => "ONEZONE_IA",
+            Self::Outposts                => "OUTPOSTS",
+            Self::ReducedRedundancy       => "REDUCED_REDUNDANCY",
+            Self::Standard                => "STANDARD",
+            Self::StandardIA              => "STANDARD_IA",
+            Self::Unknown(name)           => name,
+        }
+    }
+}
+
+/// Returns the size that an object of `actual` bytes in `class` is billed
+/// for.
+///
+/// Objects smaller than the minimum billable size of their storage class are
+/// billed as if they were that size, other objects are billed for their
+/// actual size.
+#[cfg(feature = "s3")]
+pub fn billable_size(actual: u64, class: &StorageClass) -> u64 {
+    let minimum = match class {
+        StorageClass::GlacierInstantRetrieval
+        | StorageClass::OneZoneIA
+        | StorageClass::StandardIA => MIN_INFREQUENT_ACCESS_SIZE,
+        StorageClass::DeepArchive
+        | StorageClass::GlacierFlexible => MIN_ARCHIVE_SIZE,
+        _ => 0,
+    };
+
+    actual.max(minimum)
+}
+
+/// This converts from the storage class strings returned by the S3 API and
+/// the storage types used by CloudWatch to our enum type.
+///
+/// CloudWatch overhead storage types are collapsed into their parent class.
+impl From<&str> for StorageClass {
+    fn from(s: &str) -> Self {
+        match s {
+            "DEEP_ARCHIVE"
+            | "DeepArchiveObjectOverhead"
+            | "DeepArchiveS3ObjectOverhead"
+            | "DeepArchiveStagingStorage"
+            | "DeepArchiveStorage" => Self::DeepArchive,
+            "GLACIER"
+            | "GlacierFlexibleRetrievalSizeOverhead"
+            | "GlacierFlexibleRetrievalStorage"
+            | "GlacierObjectOverhead"
+            | "GlacierS3ObjectOverhead"
+            | "GlacierStagingStorage"
+            | "GlacierStorage" => Self::GlacierFlexible,
+            "GLACIER_IR"
+            | "GlacierInstantRetrievalSizeOverhead"
+            | "GlacierInstantRetrievalStorage"
+            | "GlacierIRSizeOverhead"
+            | "GlacierIRStorage" => Self::GlacierInstantRetrieval,
+            "INTELLIGENT_TIERING"
+            | "IntAAObjectOverhead"
+            | "IntAAS3ObjectOverhead"
+            | "IntDAAObjectOverhead"
+            | "IntDAAS3ObjectOverhead"
+            | "IntelligentTieringAAStorage"
+            | "IntelligentTieringDAAStorage"
+            | "IntelligentTieringFAStorage"
+            | "IntelligentTieringIAStorage" => Self::IntelligentTiering,
+            "ONEZONE_IA"
+            | "OneZoneIASizeOverhead"
+            | "OneZoneIAStorage" => Self::OneZoneIA,
+            "OUTPOSTS" => Self::Outposts,
+            "REDUCED_REDUNDANCY"
+            | "ReducedRedundancyStorage" => Self::ReducedRedundancy,
+            "STANDARD"
+            | "StandardStorage" => Self::Standard,
+            "STANDARD_IA"
+            | "StandardIASizeOverhead"
+            | "StandardIAStorage" => Self::StandardIA,
+            unknown => {
+                warn_unknown(unknown);
+
+                Self::Unknown(unknown.into())
+            },
+        }
+    }
+}
+
+/// Warns that `class` is an unknown storage class, if it hasn't been warned
+/// about before.
+///
+/// New storage classes introduced by AWS end up here until we learn about
+/// them, so their sizes may be missing from filters and cost estimates.
+fn warn_unknown(class: &str) {
+    let first = UNKNOWN_CLASSES.lock().unwrap().insert(class.into());
+
+    if first {
+        warn!(
+            storage_class = class,
+            "Unknown storage class, sizes in this class may not be reported correctly",
+        );
+    }
+}
+
+/// Convenience conversion from a `String`.
+impl From<String> for StorageClass {
+    fn from(s: String) -> Self {
+        Self::from(s.as_str())
+    }
+}
+
+/// Convenience conversion from a `&String`.
+impl From<&String> for StorageClass {
+    fn from(s: &String) -> Self {
+        Self::from(s.as_str())
+    }
+}
+
+/// Conversion from the `Option<String>` storage class found on S3 objects.
+///
+/// S3 may omit the storage class, in which case the object is in the
+/// `Standard` storage class.
+impl From<Option<String>> for StorageClass {
+    fn from(s: Option<String>) -> Self {
+        match s {
+            Some(s) => Self::from(s.as_str()),
+            None    => {
+                debug!("StorageClass: No storage class given, using Standard");
+
+                Self::Standard
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use std::io;
+    use std::sync::Arc;
+
+    // Writer appending to a buffer shared with the test.
+    #[derive(Clone, Default)]
+    struct Buffer(Arc<Mutex<Vec<u8>>>);
+
+    impl io::Write for Buffer {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_from_str() {
+        let tests = vec![
+            ("DEEP_ARCHIVE",                        StorageClass::DeepArchive),
+            ("DeepArchiveStorage",                  StorageClass::DeepArchive),
+            ("GLACIER",                             StorageClass::GlacierFlexible),
+            ("GlacierS3ObjectOverhead",             StorageClass::GlacierFlexible),
+            ("GlacierStorage",                      StorageClass::GlacierFlexible),
+            ("GlacierFlexibleRetrievalStorage",     StorageClass::GlacierFlexible),
+            ("GLACIER_IR",                          StorageClass::GlacierInstantRetrieval),
+            ("GlacierIRStorage",                    StorageClass::GlacierInstantRetrieval),
+            ("GlacierInstantRetrievalSizeOverhead", StorageClass::GlacierInstantRetrieval),
+            ("INTELLIGENT_TIERING",                 StorageClass::IntelligentTiering),
+            ("IntelligentTieringFAStorage",         StorageClass::IntelligentTiering),
+            ("ONEZONE_IA",                          StorageClass::OneZoneIA),
+            ("OneZoneIAStorage",                    StorageClass::OneZoneIA),
+            ("OUTPOSTS",                            StorageClass::Outposts),
+            ("REDUCED_REDUNDANCY",                  StorageClass::ReducedRedundancy),
+            ("STANDARD",                            StorageClass::Standard),
+            ("StandardStorage",                     StorageClass::Standard),
+            ("STANDARD_IA",                         StorageClass::StandardIA),
+            ("StandardIASizeOverhead",              StorageClass::StandardIA),
+            ("NEW_CLASS",                           StorageClass::Unknown("NEW_CLASS".into())),
+        ];
+
+        for test in tests {
+            let class    = test.0;
+            let expected = test.1;
+
+            let ret = StorageClass::from(class);
+
+            assert_eq!(ret, expected);
+        }
+    }
+
+    #[cfg(feature = "cloudwatch")]
+    #[test]
+    fn test_name() {
+        let classes = vec![
+            StorageClass::DeepArchive,
+            StorageClass::GlacierFlexible,
+            StorageClass::GlacierInstantRetrieval,
+            StorageClass::IntelligentTiering,
+            StorageClass::OneZoneIA,
+            StorageClass::Outposts,
+            StorageClass::ReducedRedundancy,
+            StorageClass::Standard,
+            StorageClass::StandardIA,
+            StorageClass::Unknown("NEW_CLASS".into()),
+        ];
+
+        // Names convert back into the same storage class.
+        for class in classes {
+            assert_eq!(StorageClass::from(class.name()), class);
+        }
+    }
+
+    #[test]
+    fn test_from_string() {
+        let tests = vec![
+            ("GLACIER",         StorageClass::GlacierFlexible),
+            ("GLACIER_IR",      StorageClass::GlacierInstantRetrieval),
+            ("StandardStorage", StorageClass::Standard),
+            ("STANDARD_IA",     StorageClass::StandardIA),
+            ("NEW_CLASS",       StorageClass::Unknown("NEW_CLASS".into())),
+        ];
+
+        for test in tests {
+            let class    = test.0.to_string();
+            let expected = test.1;
+
+            let ret = StorageClass::from(&class);
+
+            assert_eq!(ret, expected);
+
+            let ret = StorageClass::from(class);
+
+            assert_eq!(ret, expected);
+        }
+    }
+
+    #[test]
+    fn test_from_option_string() {
+        let tests = vec![
+            (Some("GLACIER".to_string()),     StorageClass::GlacierFlexible),
+            (Some("GLACIER_IR".to_string()),  StorageClass::GlacierInstantRetrieval),
+            (Some("STANDARD_IA".to_string()), StorageClass::StandardIA),
+            (None,                            StorageClass::Standard),
+        ];
+
+        for test in tests {
+            let class    = test.0;
+            let expected = test.1;
+
+            let ret = StorageClass::from(class);
+
+            assert_eq!(ret, expected);
+        }
+    }
+
+    #[cfg(feature = "s3")]
+    #[test]
+    fn test_billable_size() {
+        let tests = vec![
+            (1,          StorageClass::StandardIA,              128 * 1024),
+            (200 * 1024, StorageClass::StandardIA,              200 * 1024),
+            (1,          StorageClass::OneZoneIA,               128 * 1024),
+            (1,          StorageClass::GlacierInstantRetrieval, 128 * 1024),
+            (1,          StorageClass::GlacierFlexible,         40 * 1024),
+            (1,          StorageClass::DeepArchive,             40 * 1024),
+            (64 * 1024,  StorageClass::GlacierFlexible,         64 * 1024),
+            (1,          StorageClass::Standard,                1),
+            (0,          StorageClass::IntelligentTiering,      0),
+            (1,          StorageClass::Unknown("NEW".into()),   1),
+        ];
+
+        for test in tests {
+            let (actual, class, expected) = test;
+
+            let ret = billable_size(actual, &class);
+
+            assert_eq!(ret, expected);
+        }
+    }
+
+    #[test]
+    fn test_from_str_warns_once() {
+        let buffer = Buffer::default();
+        let writer = buffer.clone();
+
+        let subscriber = tracing_subscriber::fmt()
+            .with_writer(move || writer.clone())
+            .with_ansi(false)
+            .finish();
+
+        // Other tests may have already warned about NEW_CLASS, so a class
+        // that's only used here is converted.
+        tracing::subscriber::with_default(subscriber, || {
+            for _ in 0..3 {
+                let ret = StorageClass::from("WARN_ONCE_CLASS");
+
+                assert_eq!(ret, StorageClass::Unknown("WARN_ONCE_CLASS".into()));
+            }
+
+            // Known classes aren't warned about.
+            assert_eq!(StorageClass::from("STANDARD"), StorageClass::Standard);
+        });
+
+        let output = String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+        let lines: Vec<&str> = output.lines().collect();
+
+        assert_eq!(lines.len(), 1);
+        assert!(lines[0].contains("WARN"));
+        assert!(lines[0].contains("storage_class=\"WARN_ONCE_CLASS\""));
+    }
+}
@@ -0,0 +1,49 @@
+// StorageSizer trait
+#![forbid(unsafe_code)]
+#![deny(missing_docs)]
+use anyhow::Result;
+use async_trait::async_trait;
+use chrono::{
+    DateTime,
+    Utc,
+};
+
+/// `StorageResource` is an AWS storage resource other than an S3 bucket,
+/// such as a Glacier vault, along with its size.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct StorageResource {
+    /// Name or ID of the resource.
+    pub name: String,
+
+    /// Type of the resource, for services with more than one, eg. `LUSTRE`
+    /// for an FSx file system.
+    pub resource_type: Option<String>,
+
+    /// Size of the resource in bytes.
+    ///
+    /// For resources with a provisioned capacity, this is the capacity.
+    pub size: u64,
+
+    /// Time that the size was measured at by AWS, if it's known.
+    pub timestamp: Option<DateTime<Utc>>,
+
+    /// Bytes of the provisioned `size` that are in use, if the resource has
+    /// a provisioned capacity and its usage is known.
+    pub used: Option<u64>,
+}
+
+/// `StorageSizer` represents the required methods to list the storage
+/// resources of a service other than S3 and find their sizes.
+///
+/// Unlike buckets, the services report the size of every resource as they're
+/// listed, so resources are sized in a single call.
+#[async_trait]
+pub trait StorageSizer: Send + Sync {
+    /// Returns the annotation that the resources are shown with in the
+    /// output, eg. `GLACIER_VAULT`.
+    fn kind(&self) -> &'static str;
+
+    /// Returns the resources in the region, along with their sizes, ordered
+    /// by name.
+    async fn resources(&self) -> Result<Vec<StorageResource>>;
+}
@@ -0,0 +1,220 @@
+// Differences between bucket size snapshots
+#![forbid(unsafe_code)]
+#![deny(missing_docs)]
+use crate::common::{
+    HumanSize,
+    SizeUnit,
+};
+use crate::output::{
+    GREEN,
+    RED,
+};
+use crate::snapshot::BucketSizeEntry;
+use std::collections::HashMap;
+
+/// The change in size of a single bucket between two snapshots.
+#[derive(Debug, Eq, PartialEq)]
+pub struct BucketDiff {
+    /// Name of the bucket.
+    pub bucket: String,
+
+    /// Size of the bucket in the old snapshot, `None` if the bucket is new.
+    pub old: Option<u64>,
+
+    /// Size of the bucket in the new snapshot, `None` if the bucket was
+    /// removed.
+    pub new: Option<u64>,
+}
+
+impl BucketDiff {
+    /// Returns the change in size as a `(sign, size)` pair, where `sign` is
+    /// empty if the size didn't change.
+    fn delta(&self) -> (&'static str, u64) {
+        let old = self.old.unwrap_or(0);
+        let new = self.new.unwrap_or(0);
+
+        if new > old {
+            ("+", new - old)
+        }
+        else if new < old {
+            ("-", old - new)
+        }
+        else {
+            ("", 0)
+        }
+    }
+
+    /// Returns the SGR code to color the change with when the output is
+    /// colored, red for growth and green for shrinkage, or `None` if the size
+    /// didn't change.
+    pub fn color(&self) -> Option<&'static str> {
+        match self.delta().0 {
+            "+" => Some(RED),
+            "-" => Some(GREEN),
+            _   => None,
+        }
+    }
+
+    /// Returns the change formatted for output, with sizes in the given
+    /// `unit`, eg. `+500MiB    my-bucket (was 2.3GiB, now 2.8GiB)`.
+    pub fn format(&self, unit: &SizeUnit) -> String {
+        let (sign, delta) = self.delta();
+
+        let detail = match (self.old, self.new) {
+            (Some(old), Some(new)) => {
+                format!(
+                    "was {}, now {}",
+                    old.humansize(unit),
+                    new.humansize(unit),
+                )
+            },
+            (None, Some(new)) => format!("new, now {}", new.humansize(unit)),
+            (Some(old), None) => format!("removed, was {}", old.humansize(unit)),
+            (None, None)      => unreachable!(),
+        };
+
+        format!(
+            "{sign}{delta}\t{bucket} ({detail})",
+            sign=sign,
+            delta=delta.humansize(unit),
+            bucket=self.bucket,
+            detail=detail,
+        )
+    }
+}
+
+/// Returns the differences between the `old` and `new` bucket sizes.
+///
+/// Buckets are returned in the order of `new`, followed by any buckets that
+/// were removed in the order of `old`.
+pub fn diff(old: &[BucketSizeEntry], new: &[BucketSizeEntry]) -> Vec<BucketDiff> {
+    let old_sizes: HashMap<&str, u64> = old.iter()
+        .map(|entry| (entry.bucket.as_str(), entry.size))
+        .collect();
+
+    let new_sizes: HashMap<&str, u64> = new.iter()
+        .map(|entry| (entry.bucket.as_str(), entry.size))
+        .collect();
+
+    let changed = new.iter()
+        .map(|entry| BucketDiff {
+            bucket: entry.bucket.to_owned(),
+            old:    old_sizes.get(entry.bucket.as_str()).copied(),
+            new:    Some(entry.size),
+        });
+
+    let removed = old.iter()
+        .filter(|entry| !new_sizes.contains_key(entry.bucket.as_str()))
+        .map(|entry| BucketDiff {
+            bucket: entry.bucket.to_owned(),
+            old:    Some(entry.size),
+            new:    None,
+        });
+
+    changed.chain(removed).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use std::str::FromStr;
+
+    // Convenience for building snapshot entries in the tests.
+    fn entry(bucket: &str, size: u64) -> BucketSizeEntry {
+        BucketSizeEntry {
+            bucket: bucket.into(),
+            region: None,
+            size:   size,
+        }
+    }
+
+    #[test]
+    fn test_diff() {
+        let old = vec![
+            entry("grown",     1024),
+            entry("removed",   2048),
+            entry("shrunk",    4096),
+            entry("unchanged", 8192),
+        ];
+
+        let new = vec![
+            entry("added",     512),
+            entry("grown",     3072),
+            entry("shrunk",    1024),
+            entry("unchanged", 8192),
+        ];
+
+        let unit = SizeUnit::from_str("binary").unwrap();
+
+        let ret: Vec<String> = diff(&old, &new)
+            .iter()
+            .map(|d| d.format(&unit))
+            .collect();
+
+        let expected = vec![
+            "+512B\tadded (new, now 512B)",
+            "+2KiB\tgrown (was 1KiB, now 3KiB)",
+            "-3KiB\tshrunk (was 4KiB, now 1KiB)",
+            "0B\tunchanged (was 8KiB, now 8KiB)",
+            "-2KiB\tremoved (removed, was 2KiB)",
+        ];
+
+        assert_eq!(ret, expected);
+    }
+
+    #[test]
+    fn test_diff_color() {
+        let old = vec![
+            entry("grown",     1024),
+            entry("removed",   2048),
+            entry("unchanged", 8192),
+        ];
+
+        let new = vec![
+            entry("added",     512),
+            entry("grown",     3072),
+            entry("unchanged", 8192),
+        ];
+
+        let ret: Vec<Option<&str>> = diff(&old, &new)
+            .iter()
+            .map(|d| d.color())
+            .collect();
+
+        let expected = vec![
+            Some(RED),
+            Some(RED),
+            None,
+            Some(GREEN),
+        ];
+
+        assert_eq!(ret, expected);
+    }
+
+    #[test]
+    fn test_diff_empty_snapshots() {
+        let entries = vec![
+            entry("a-bucket", 1),
+        ];
+
+        let tests = vec![
+            (vec![],          vec![],          vec![]),
+            (vec![],          entries.clone(), vec![(None, Some(1))]),
+            (entries.clone(), vec![],          vec![(Some(1), None)]),
+        ];
+
+        for test in tests {
+            let old      = test.0;
+            let new      = test.1;
+            let expected = test.2;
+
+            let ret: Vec<(Option<u64>, Option<u64>)> = diff(&old, &new)
+                .iter()
+                .map(|d| (d.old, d.new))
+                .collect();
+
+            assert_eq!(ret, expected);
+        }
+    }
+}
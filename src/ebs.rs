@@ -0,0 +1,216 @@
+// ebs: Lists the sizes of EBS snapshots alongside S3 buckets
+#![forbid(unsafe_code)]
+#![deny(missing_docs)]
+use anyhow::Result;
+use async_trait::async_trait;
+use chrono::{
+    DateTime,
+    Utc,
+};
+use crate::common::{
+    ClientConfig,
+    StorageResource,
+    StorageSizer,
+    with_api_timeout,
+};
+use crate::credentials;
+use rusoto_ec2::{
+    DescribeSnapshotsRequest,
+    Ec2,
+    Ec2Client,
+    Snapshot,
+};
+use std::time::Duration;
+use tracing::debug;
+
+/// Annotation that snapshots are shown with in the output.
+const EBS_SNAPSHOT: &str = "EBS_SNAPSHOT";
+
+/// Bytes in a GiB, the unit that EBS volume sizes are given in.
+const GIBIBYTE: u64 = 1_073_741_824;
+
+/// Owner ID that EC2 takes to mean the account making the request.
+const SELF_OWNER: &str = "self";
+
+/// Returns the `snapshot` as a `StorageResource`, sized by the size of the
+/// volume that it was taken from.
+///
+/// The timestamp is the time that the snapshot was started.
+fn snapshot_resource(snapshot: Snapshot) -> StorageResource {
+    // EC2 should never report a negative volume size, but it's treated as `0`
+    // if it does.
+    let size = match snapshot.volume_size {
+        Some(size) if size > 0 => size as u64 * GIBIBYTE,
+        _                      => 0,
+    };
+
+    let timestamp = snapshot.start_time
+        .and_then(|time| DateTime::parse_from_rfc3339(&time).ok())
+        .map(|time| time.with_timezone(&Utc));
+
+    StorageResource {
+        name:          snapshot.snapshot_id.unwrap_or_default(),
+        resource_type: None,
+        size:          size,
+        timestamp:     timestamp,
+        used:          None,
+    }
+}
+
+/// `SnapshotLister` lists the EBS snapshots owned by the account in a region.
+pub struct SnapshotLister {
+    /// The Rusoto `Ec2Client`.
+    pub client: Ec2Client,
+
+    /// Maximum time that each API call may take, if any.
+    pub api_timeout: Option<Duration>,
+}
+
+impl SnapshotLister {
+    /// Return a new `SnapshotLister` for the region in the given
+    /// `ClientConfig`.
+    pub fn new(config: &ClientConfig) -> Self {
+        let aws_client = credentials::aws_client(config);
+
+        debug!(
+            region = config.region.name(),
+            "new: Creating Ec2Client",
+        );
+
+        let client = Ec2Client::new_with_client(
+            aws_client,
+            config.region.to_owned(),
+        );
+
+        Self {
+            client:      client,
+            api_timeout: config.api_timeout,
+        }
+    }
+}
+
+#[async_trait]
+impl StorageSizer for SnapshotLister {
+    fn kind(&self) -> &'static str {
+        EBS_SNAPSHOT
+    }
+
+    /// Returns the EBS snapshots owned by the account in the region, ordered
+    /// by ID.
+    ///
+    /// Public and shared snapshots owned by other accounts aren't billed to
+    /// us, so they aren't listed.
+    async fn resources(&self) -> Result<Vec<StorageResource>> {
+        let mut next_token = None;
+        let mut snapshots  = Vec::new();
+
+        loop {
+            let input = DescribeSnapshotsRequest {
+                next_token: next_token,
+                owner_ids:  Some(vec![SELF_OWNER.into()]),
+                ..Default::default()
+            };
+
+            let output = with_api_timeout(
+                self.api_timeout,
+                self.client.describe_snapshots(input),
+            ).await?;
+
+            if let Some(descriptions) = output.snapshots {
+                snapshots.extend(
+                    descriptions.into_iter().map(snapshot_resource)
+                );
+            }
+
+            match output.next_token {
+                Some(next) => next_token = Some(next),
+                None       => break,
+            }
+        }
+
+        debug!(snapshots = snapshots.len(), "resources: Got snapshots");
+
+        snapshots.sort_by(|a, b| a.name.cmp(&b.name));
+
+        Ok(snapshots)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use pretty_assertions::assert_eq;
+    use rusoto_core::signature::SignedRequestPayload;
+    use rusoto_mock::{
+        MockCredentialsProvider,
+        MockRequestDispatcher,
+        MockResponseReader,
+        ReadMockResponse,
+    };
+
+    // Create a mock EC2 client, returning the data from the specified
+    // data_file.
+    fn mock_lister(data_file: &str) -> SnapshotLister {
+        let data = MockResponseReader::read_response("test-data", data_file);
+
+        let dispatcher = MockRequestDispatcher::default()
+            .with_body(&data)
+            .with_request_checker(|request| {
+                let payload = match &request.payload {
+                    Some(SignedRequestPayload::Buffer(b)) => {
+                        String::from_utf8(b.to_vec()).unwrap()
+                    },
+                    _ => panic!("DescribeSnapshots request had no payload"),
+                };
+
+                for param in &["Action=DescribeSnapshots", "Owner.1=self"] {
+                    assert!(payload.contains(param), "{} not in {}", param, payload);
+                }
+            });
+
+        let client = Ec2Client::new_with(
+            dispatcher,
+            MockCredentialsProvider,
+            Default::default()
+        );
+
+        SnapshotLister {
+            client:      client,
+            api_timeout: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_resources() {
+        let lister = mock_lister("ec2-describe-snapshots.xml");
+        let ret    = lister.resources().await.unwrap();
+
+        let expected = vec![
+            StorageResource {
+                name:          "snap-0123456789abcdef0".into(),
+                resource_type: None,
+                size:          8 * GIBIBYTE,
+                timestamp:     Some(Utc.ymd(2021, 3, 1).and_hms(12, 0, 0)),
+                used:          None,
+            },
+            StorageResource {
+                name:          "snap-0fedcba9876543210".into(),
+                resource_type: None,
+                size:          500 * GIBIBYTE,
+                timestamp:     Some(Utc.ymd(2021, 2, 14).and_hms(3, 30, 0)),
+                used:          None,
+            },
+            StorageResource {
+                name:          "snap-abcdef01234567890".into(),
+                resource_type: None,
+                size:          0,
+                timestamp:     None,
+                used:          None,
+            },
+        ];
+
+        assert_eq!(ret, expected);
+        assert_eq!(lister.kind(), "EBS_SNAPSHOT");
+    }
+}
@@ -0,0 +1,289 @@
+// ecr: Lists the sizes of ECR repositories alongside S3 buckets
+#![forbid(unsafe_code)]
+#![deny(missing_docs)]
+use anyhow::Result;
+use async_trait::async_trait;
+use chrono::{
+    DateTime,
+    NaiveDateTime,
+    Utc,
+};
+use crate::common::{
+    ClientConfig,
+    StorageResource,
+    StorageSizer,
+    with_api_timeout,
+};
+use crate::credentials;
+use rusoto_ecr::{
+    DescribeImagesRequest,
+    DescribeRepositoriesRequest,
+    Ecr,
+    EcrClient,
+    ImageDetail,
+};
+use std::time::Duration;
+use tracing::debug;
+
+/// Annotation that repositories are shown with in the output.
+const ECR_REPOSITORY: &str = "ECR_REPOSITORY";
+
+/// Returns the repository called `name` as a `StorageResource`, sized by
+/// adding up the sizes of its `images`.
+///
+/// The timestamp is the time that the most recent image was pushed, if the
+/// repository has any images.
+fn repository_resource(name: String, images: &[ImageDetail]) -> StorageResource {
+    // ECR should never report a negative size, but it's treated as `0` if it
+    // does.
+    let size = images.iter()
+        .filter_map(|image| image.image_size_in_bytes)
+        .filter(|size| *size > 0)
+        .map(|size| size as u64)
+        .sum();
+
+    let timestamp = images.iter()
+        .filter_map(|image| image.image_pushed_at)
+        .map(|pushed| {
+            let pushed = NaiveDateTime::from_timestamp(pushed as i64, 0);
+
+            DateTime::from_utc(pushed, Utc)
+        })
+        .max();
+
+    StorageResource {
+        name:          name,
+        resource_type: None,
+        size:          size,
+        timestamp:     timestamp,
+        used:          None,
+    }
+}
+
+/// `RepositoryLister` lists the ECR repositories in a region.
+pub struct RepositoryLister {
+    /// The Rusoto `EcrClient`.
+    pub client: EcrClient,
+
+    /// Maximum time that each API call may take, if any.
+    pub api_timeout: Option<Duration>,
+}
+
+impl RepositoryLister {
+    /// Return a new `RepositoryLister` for the region in the given
+    /// `ClientConfig`.
+    pub fn new(config: &ClientConfig) -> Self {
+        let aws_client = credentials::aws_client(config);
+
+        debug!(
+            region = config.region.name(),
+            "new: Creating EcrClient",
+        );
+
+        let client = EcrClient::new_with_client(
+            aws_client,
+            config.region.to_owned(),
+        );
+
+        Self {
+            client:      client,
+            api_timeout: config.api_timeout,
+        }
+    }
+
+    /// Returns the names of the repositories in the region.
+    async fn repository_names(&self) -> Result<Vec<String>> {
+        let mut next_token   = None;
+        let mut repositories = Vec::new();
+
+        loop {
+            let input = DescribeRepositoriesRequest {
+                next_token: next_token,
+                ..Default::default()
+            };
+
+            let output = with_api_timeout(
+                self.api_timeout,
+                self.client.describe_repositories(input),
+            ).await?;
+
+            if let Some(descriptions) = output.repositories {
+                repositories.extend(
+                    descriptions.into_iter().filter_map(|r| r.repository_name)
+                );
+            }
+
+            match output.next_token {
+                Some(next) => next_token = Some(next),
+                None       => break,
+            }
+        }
+
+        Ok(repositories)
+    }
+
+    /// Returns the images in `repository`.
+    async fn images(&self, repository: &str) -> Result<Vec<ImageDetail>> {
+        let mut next_token = None;
+        let mut images     = Vec::new();
+
+        loop {
+            let input = DescribeImagesRequest {
+                next_token:      next_token,
+                repository_name: repository.into(),
+                ..Default::default()
+            };
+
+            let output = with_api_timeout(
+                self.api_timeout,
+                self.client.describe_images(input),
+            ).await?;
+
+            if let Some(details) = output.image_details {
+                images.extend(details);
+            }
+
+            match output.next_token {
+                Some(next) => next_token = Some(next),
+                None       => break,
+            }
+        }
+
+        Ok(images)
+    }
+}
+
+#[async_trait]
+impl StorageSizer for RepositoryLister {
+    fn kind(&self) -> &'static str {
+        ECR_REPOSITORY
+    }
+
+    /// Returns the ECR repositories in the region, ordered by name.
+    ///
+    /// Images are listed for each repository in turn, and layers shared
+    /// between images are counted once for each image.
+    async fn resources(&self) -> Result<Vec<StorageResource>> {
+        let mut repositories = Vec::new();
+
+        for name in self.repository_names().await? {
+            let images = self.images(&name).await?;
+
+            debug!(
+                repository = %name,
+                images     = images.len(),
+                "resources: Got images",
+            );
+
+            repositories.push(repository_resource(name, &images));
+        }
+
+        debug!(repositories = repositories.len(), "resources: Got repositories");
+
+        repositories.sort_by(|a, b| a.name.cmp(&b.name));
+
+        Ok(repositories)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use pretty_assertions::assert_eq;
+    use rusoto_core::signature::SignedRequestPayload;
+    use rusoto_mock::{
+        MockCredentialsProvider,
+        MockRequestDispatcher,
+        MockResponseReader,
+        MultipleMockRequestDispatcher,
+        ReadMockResponse,
+    };
+
+    // Returns a mock dispatcher returning the data from the specified
+    // data_file, for a request with the given target and repository, if any.
+    fn dispatcher(
+        data_file:  &str,
+        target:     &'static str,
+        repository: Option<&'static str>,
+    ) -> MockRequestDispatcher {
+        let data = MockResponseReader::read_response("test-data", data_file);
+
+        MockRequestDispatcher::default()
+            .with_body(&data)
+            .with_request_checker(move |request| {
+                let header = request.headers.get("x-amz-target")
+                    .map(|values| String::from_utf8(values[0].clone()).unwrap());
+
+                assert_eq!(header, Some(target.to_string()));
+
+                let payload = match &request.payload {
+                    Some(SignedRequestPayload::Buffer(b)) => {
+                        String::from_utf8(b.to_vec()).unwrap()
+                    },
+                    _ => panic!("{} request had no payload", target),
+                };
+
+                if let Some(repository) = repository {
+                    let param = format!("\"repositoryName\":\"{}\"", repository);
+
+                    assert!(payload.contains(&param), "{} not in {}", param, payload);
+                }
+            })
+    }
+
+    #[tokio::test]
+    async fn test_resources() {
+        let repositories = "AmazonEC2ContainerRegistry_V20150921.DescribeRepositories";
+        let images       = "AmazonEC2ContainerRegistry_V20150921.DescribeImages";
+
+        // The api repository's images are listed over two pages.
+        let mock = MultipleMockRequestDispatcher::new(vec![
+            dispatcher("ecr-describe-repositories.json", repositories, None),
+            dispatcher("ecr-describe-images-web.json", images, Some("web")),
+            dispatcher("ecr-describe-images-api-1.json", images, Some("api")),
+            dispatcher("ecr-describe-images-api-2.json", images, Some("api")),
+            dispatcher("ecr-describe-images-empty.json", images, Some("scratch")),
+        ]);
+
+        let client = EcrClient::new_with(
+            mock,
+            MockCredentialsProvider,
+            Default::default()
+        );
+
+        let lister = RepositoryLister {
+            client:      client,
+            api_timeout: None,
+        };
+
+        let ret = lister.resources().await.unwrap();
+
+        let expected = vec![
+            StorageResource {
+                name:          "api".into(),
+                resource_type: None,
+                size:          52_428_800 + 52_430_848 + 26_214_400,
+                timestamp:     Some(Utc.ymd(2021, 3, 2).and_hms(9, 0, 0)),
+                used:          None,
+            },
+            StorageResource {
+                name:          "scratch".into(),
+                resource_type: None,
+                size:          0,
+                timestamp:     None,
+                used:          None,
+            },
+            StorageResource {
+                name:          "web".into(),
+                resource_type: None,
+                size:          104_857_600,
+                timestamp:     Some(Utc.ymd(2021, 2, 14).and_hms(3, 30, 0)),
+                used:          None,
+            },
+        ];
+
+        assert_eq!(ret, expected);
+        assert_eq!(lister.kind(), "ECR_REPOSITORY");
+    }
+}
@@ -0,0 +1,191 @@
+// efs: Lists the sizes of EFS file systems alongside S3 buckets
+#![forbid(unsafe_code)]
+#![deny(missing_docs)]
+use anyhow::Result;
+use async_trait::async_trait;
+use chrono::{
+    DateTime,
+    NaiveDateTime,
+    Utc,
+};
+use crate::common::{
+    ClientConfig,
+    StorageResource,
+    StorageSizer,
+    with_api_timeout,
+};
+use crate::credentials;
+use rusoto_efs::{
+    DescribeFileSystemsRequest,
+    Efs,
+    EfsClient,
+    FileSystemDescription,
+};
+use std::time::Duration;
+use tracing::debug;
+
+/// Annotation that file systems are shown with in the output.
+const EFS_FILE_SYSTEM: &str = "EFS_FILE_SYSTEM";
+
+/// Returns the `file_system` as a `StorageResource`, named by its ID since
+/// file system names are optional and needn't be unique.
+///
+/// EFS meters the size of file systems periodically, the timestamp is that of
+/// the last metering, if it's known.
+fn file_system_resource(file_system: FileSystemDescription) -> StorageResource {
+    let size = file_system.size_in_bytes;
+
+    let timestamp = size.timestamp
+        .map(|timestamp| {
+            let metered = NaiveDateTime::from_timestamp(timestamp as i64, 0);
+
+            DateTime::from_utc(metered, Utc)
+        });
+
+    // EFS should never report a negative size, but it's treated as `0` if it
+    // does.
+    let bytes = if size.value > 0 {
+        size.value as u64
+    }
+    else {
+        0
+    };
+
+    StorageResource {
+        name:          file_system.file_system_id,
+        resource_type: None,
+        size:          bytes,
+        timestamp:     timestamp,
+        used:          None,
+    }
+}
+
+/// `FileSystemLister` lists the EFS file systems in a region.
+pub struct FileSystemLister {
+    /// The Rusoto `EfsClient`.
+    pub client: EfsClient,
+
+    /// Maximum time that each API call may take, if any.
+    pub api_timeout: Option<Duration>,
+}
+
+impl FileSystemLister {
+    /// Return a new `FileSystemLister` for the region in the given
+    /// `ClientConfig`.
+    pub fn new(config: &ClientConfig) -> Self {
+        let aws_client = credentials::aws_client(config);
+
+        debug!(
+            region = config.region.name(),
+            "new: Creating EfsClient",
+        );
+
+        let client = EfsClient::new_with_client(
+            aws_client,
+            config.region.to_owned(),
+        );
+
+        Self {
+            client:      client,
+            api_timeout: config.api_timeout,
+        }
+    }
+}
+
+#[async_trait]
+impl StorageSizer for FileSystemLister {
+    fn kind(&self) -> &'static str {
+        EFS_FILE_SYSTEM
+    }
+
+    /// Returns the EFS file systems in the region, ordered by ID.
+    async fn resources(&self) -> Result<Vec<StorageResource>> {
+        let mut marker       = None;
+        let mut file_systems = Vec::new();
+
+        loop {
+            let input = DescribeFileSystemsRequest {
+                marker: marker,
+                ..Default::default()
+            };
+
+            let output = with_api_timeout(
+                self.api_timeout,
+                self.client.describe_file_systems(input),
+            ).await?;
+
+            if let Some(descriptions) = output.file_systems {
+                file_systems.extend(
+                    descriptions.into_iter().map(file_system_resource)
+                );
+            }
+
+            match output.next_marker {
+                Some(next) => marker = Some(next),
+                None       => break,
+            }
+        }
+
+        debug!(file_systems = file_systems.len(), "resources: Got file systems");
+
+        file_systems.sort_by(|a, b| a.name.cmp(&b.name));
+
+        Ok(file_systems)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use pretty_assertions::assert_eq;
+    use rusoto_mock::{
+        MockCredentialsProvider,
+        MockRequestDispatcher,
+        MockResponseReader,
+        ReadMockResponse,
+    };
+
+    // Create a mock EFS client, returning the data from the specified
+    // data_file.
+    fn mock_lister(data_file: &str) -> FileSystemLister {
+        let data = MockResponseReader::read_response("test-data", data_file);
+
+        let client = EfsClient::new_with(
+            MockRequestDispatcher::default().with_body(&data),
+            MockCredentialsProvider,
+            Default::default()
+        );
+
+        FileSystemLister {
+            client:      client,
+            api_timeout: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_resources() {
+        let lister = mock_lister("efs-describe-file-systems.json");
+        let ret    = lister.resources().await.unwrap();
+
+        let expected = vec![
+            StorageResource {
+                name:          "fs-01234567".into(),
+                resource_type: None,
+                size:          6144,
+                timestamp:     None,
+                used:          None,
+            },
+            StorageResource {
+                name:          "fs-89abcdef".into(),
+                resource_type: None,
+                size:          53_687_091_200,
+                timestamp:     Some(Utc.ymd(2021, 3, 1).and_hms(12, 0, 0)),
+                used:          None,
+            },
+        ];
+
+        assert_eq!(ret, expected);
+        assert_eq!(lister.kind(), "EFS_FILE_SYSTEM");
+    }
+}
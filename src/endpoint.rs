@@ -0,0 +1,103 @@
+// endpoint: Handles custom endpoints for S3 compatible storage
+#![forbid(unsafe_code)]
+#![deny(missing_docs)]
+use rusoto_core::Region;
+use url::Url;
+
+/// Region name used to sign requests to custom endpoints if no region was
+/// given.
+pub const DEFAULT_CUSTOM_REGION_NAME: &str = "custom";
+
+/// Parses the custom `endpoint` URL, returning it in the form expected by
+/// `Region::Custom`.
+///
+/// The endpoint must be an HTTP or HTTPS URL that isn't an AWS endpoint and
+/// doesn't have a query string. A trailing `/` is removed, so that it isn't
+/// doubled up when bucket names are appended to the endpoint's path.
+pub fn parse_endpoint(endpoint: &str) -> Result<String, String> {
+    // Endpoint cannot be an empty string
+    if endpoint.is_empty() {
+        return Err("Endpoint cannot be empty".into());
+    }
+
+    // Endpoint must parse as a valid URL
+    let url = Url::parse(endpoint)
+        .map_err(|e| format!("Could not parse endpoint: {}", e))?;
+
+    // We can only use HTTP or HTTPS URLs.
+    match url.scheme() {
+        "http" | "https" => Ok(()),
+        scheme           => {
+            Err(format!("URL scheme must be http or https, found {}", scheme))
+        },
+    }?;
+
+    // Endpoint cannot be an AWS endpoint
+    if let Some(hostname) = url.host_str() {
+        if hostname.contains("amazonaws.com") {
+            return Err("Endpoint cannot be used to specify AWS endpoints".into());
+        }
+    }
+
+    // Requests are made by appending to the endpoint, which would end up in
+    // the query string.
+    if url.query().is_some() || url.fragment().is_some() {
+        return Err("Endpoint cannot contain a query string or fragment".into());
+    }
+
+    // The URL is used in its parsed form, which lowercases the host and
+    // drops default ports.
+    let endpoint = url.as_str().trim_end_matches('/');
+
+    Ok(endpoint.into())
+}
+
+/// Returns a `Region::Custom` for the S3 compatible storage at `endpoint`.
+///
+/// Requests are signed for the region `name`, which some S3 compatible
+/// storage requires to match its own configured region.
+pub fn custom_region(endpoint: &str, name: &str) -> Result<Region, String> {
+    let region = Region::Custom {
+        name:     name.into(),
+        endpoint: parse_endpoint(endpoint)?,
+    };
+
+    Ok(region)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_custom_region() {
+        let tests = vec![
+            ("https://minio.example.org",              Some("https://minio.example.org")),
+            ("https://minio.example.org/",             Some("https://minio.example.org")),
+            ("https://MinIO.Example.org",              Some("https://minio.example.org")),
+            ("https://minio.example.org:443/",         Some("https://minio.example.org")),
+            ("http://127.0.0.1:9000",                  Some("http://127.0.0.1:9000")),
+            ("http://localhost:9000/",                 Some("http://localhost:9000")),
+            ("https://ceph.example.org/s3/",           Some("https://ceph.example.org/s3")),
+            ("https://s3.us-west-002.backblazeb2.com", Some("https://s3.us-west-002.backblazeb2.com")),
+            ("https://minio.example.org/?x=1",         None),
+            ("https://minio.example.org/#top",         None),
+            ("https://s3.eu-west-1.amazonaws.com",     None),
+            ("minio.example.org",                      None),
+            ("",                                       None),
+        ];
+
+        for test in tests {
+            let endpoint = test.0;
+            let expected = test.1.map(|endpoint| Region::Custom {
+                name:     "eu-central-1".into(),
+                endpoint: endpoint.into(),
+            });
+
+            let ret = custom_region(endpoint, "eu-central-1").ok();
+
+            assert_eq!(ret, expected);
+        }
+    }
+}
@@ -0,0 +1,402 @@
+// fsx: Lists the sizes of FSx file systems alongside S3 buckets
+#![forbid(unsafe_code)]
+#![deny(missing_docs)]
+use anyhow::Result;
+use async_trait::async_trait;
+use crate::common::{
+    ClientConfig,
+    StorageResource,
+    StorageSizer,
+    with_api_timeout,
+};
+use crate::credentials;
+use rusoto_fsx::{
+    DescribeFileSystemsRequest,
+    FileSystem,
+    Fsx,
+    FsxClient,
+};
+use std::time::Duration;
+use tracing::debug;
+
+#[cfg(feature = "cloudwatch")]
+use chrono::{
+    DateTime,
+    SecondsFormat,
+    Utc,
+};
+
+#[cfg(feature = "cloudwatch")]
+use rusoto_cloudwatch::{
+    CloudWatch,
+    CloudWatchClient,
+    Dimension,
+    GetMetricStatisticsInput,
+};
+
+/// Annotation that file systems are shown with in the output.
+const FSX_FILE_SYSTEM: &str = "FSX_FILE_SYSTEM";
+
+/// Bytes in a GiB, the unit that FSx storage capacities are given in.
+const GIBIBYTE: u64 = 1_073_741_824;
+
+/// CloudWatch namespace of the FSx metrics.
+#[cfg(feature = "cloudwatch")]
+const FSX_NAMESPACE: &str = "AWS/FSx";
+
+/// Period in seconds of the free capacity statistics.
+#[cfg(feature = "cloudwatch")]
+const FREE_CAPACITY_PERIOD: i64 = 300;
+
+/// How far back in seconds the latest free capacity statistic is looked for.
+#[cfg(feature = "cloudwatch")]
+const FREE_CAPACITY_WINDOW: i64 = 3600;
+
+/// Returns the name of the CloudWatch metric that gives the free storage
+/// capacity of file systems of `file_system_type`, if there is one.
+///
+/// ONTAP and OpenZFS file systems report their usage per storage tier or
+/// volume rather than as free capacity, so their usage isn't known.
+#[cfg(feature = "cloudwatch")]
+fn free_capacity_metric(file_system_type: &str) -> Option<&'static str> {
+    match file_system_type {
+        "LUSTRE"  => Some("FreeDataStorageCapacity"),
+        "WINDOWS" => Some("FreeStorageCapacity"),
+        _         => None,
+    }
+}
+
+/// Returns the `file_system` as a `StorageResource`, sized by its provisioned
+/// storage capacity.
+fn file_system_resource(file_system: FileSystem) -> StorageResource {
+    // FSx should never report a negative capacity, but it's treated as `0`
+    // if it does.
+    let capacity = match file_system.storage_capacity {
+        Some(capacity) if capacity > 0 => capacity as u64 * GIBIBYTE,
+        _                              => 0,
+    };
+
+    StorageResource {
+        name:          file_system.file_system_id.unwrap_or_default(),
+        resource_type: file_system.file_system_type,
+        size:          capacity,
+        timestamp:     None,
+        used:          None,
+    }
+}
+
+/// `FileSystemLister` lists the FSx file systems of every type in a region.
+pub struct FileSystemLister {
+    /// The Rusoto `FsxClient`.
+    pub client: FsxClient,
+
+    /// The Rusoto `CloudWatchClient` that the free capacity of file systems
+    /// is read with.
+    ///
+    /// This field will only be present when compiled with the `cloudwatch`
+    /// feature.
+    #[cfg(feature = "cloudwatch")]
+    pub cloudwatch: CloudWatchClient,
+
+    /// Maximum time that each API call may take, if any.
+    pub api_timeout: Option<Duration>,
+}
+
+impl FileSystemLister {
+    /// Return a new `FileSystemLister` for the region in the given
+    /// `ClientConfig`.
+    pub fn new(config: &ClientConfig) -> Self {
+        let aws_client = credentials::aws_client(config);
+
+        debug!(
+            region = config.region.name(),
+            "new: Creating FsxClient",
+        );
+
+        #[cfg(feature = "cloudwatch")]
+        let cloudwatch = CloudWatchClient::new_with_client(
+            aws_client.clone(),
+            config.region.to_owned(),
+        );
+
+        let client = FsxClient::new_with_client(
+            aws_client,
+            config.region.to_owned(),
+        );
+
+        Self {
+            client:      client,
+            #[cfg(feature = "cloudwatch")]
+            cloudwatch:  cloudwatch,
+            api_timeout: config.api_timeout,
+        }
+    }
+
+    /// Returns the latest free storage capacity of `file_system` in bytes
+    /// from CloudWatch, along with the time that it was measured at.
+    ///
+    /// Returns `None` if the file system type has no free capacity metric,
+    /// or if there's no recent datapoint for it.
+    #[cfg(feature = "cloudwatch")]
+    async fn free_capacity(
+        &self,
+        file_system: &StorageResource,
+    ) -> Result<Option<(u64, DateTime<Utc>)>> {
+        let metric_name = file_system.resource_type
+            .as_deref()
+            .and_then(free_capacity_metric);
+
+        let metric_name = match metric_name {
+            Some(metric_name) => metric_name,
+            None              => return Ok(None),
+        };
+
+        let end_time   = Utc::now();
+        let start_time = end_time - chrono::Duration::seconds(FREE_CAPACITY_WINDOW);
+
+        let input = GetMetricStatisticsInput {
+            dimensions: Some(vec![
+                Dimension {
+                    name:  "FileSystemId".into(),
+                    value: file_system.name.to_owned(),
+                },
+            ]),
+            end_time:    end_time.to_rfc3339_opts(SecondsFormat::Secs, true),
+            metric_name: metric_name.into(),
+            namespace:   FSX_NAMESPACE.into(),
+            period:      FREE_CAPACITY_PERIOD,
+            start_time:  start_time.to_rfc3339_opts(SecondsFormat::Secs, true),
+            statistics:  Some(vec!["Average".into()]),
+            ..Default::default()
+        };
+
+        let output = with_api_timeout(
+            self.api_timeout,
+            self.cloudwatch.get_metric_statistics(input),
+        ).await?;
+
+        let latest = output.datapoints
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|datapoint| {
+                let timestamp = DateTime::parse_from_rfc3339(&datapoint.timestamp?).ok()?;
+
+                Some((timestamp.with_timezone(&Utc), datapoint.average?))
+            })
+            .max_by_key(|datapoint| datapoint.0);
+
+        debug!(
+            file_system = %file_system.name,
+            latest      = ?latest,
+            "free_capacity: Got datapoints",
+        );
+
+        Ok(latest.map(|(timestamp, free)| (free as u64, timestamp)))
+    }
+}
+
+#[async_trait]
+impl StorageSizer for FileSystemLister {
+    fn kind(&self) -> &'static str {
+        FSX_FILE_SYSTEM
+    }
+
+    /// Returns the FSx file systems in the region, ordered by ID.
+    ///
+    /// When compiled with the `cloudwatch` feature, file systems that report
+    /// their free capacity to CloudWatch also have their used capacity.
+    async fn resources(&self) -> Result<Vec<StorageResource>> {
+        let mut next_token   = None;
+        let mut file_systems = Vec::new();
+
+        loop {
+            let input = DescribeFileSystemsRequest {
+                next_token: next_token,
+                ..Default::default()
+            };
+
+            let output = with_api_timeout(
+                self.api_timeout,
+                self.client.describe_file_systems(input),
+            ).await?;
+
+            if let Some(descriptions) = output.file_systems {
+                file_systems.extend(
+                    descriptions.into_iter().map(file_system_resource)
+                );
+            }
+
+            match output.next_token {
+                Some(next) => next_token = Some(next),
+                None       => break,
+            }
+        }
+
+        debug!(file_systems = file_systems.len(), "resources: Got file systems");
+
+        file_systems.sort_by(|a, b| a.name.cmp(&b.name));
+
+        #[cfg(feature = "cloudwatch")]
+        {
+            for file_system in &mut file_systems {
+                if let Some((free, timestamp)) = self.free_capacity(file_system).await? {
+                    file_system.used      = Some(file_system.size.saturating_sub(free));
+                    file_system.timestamp = Some(timestamp);
+                }
+            }
+        }
+
+        Ok(file_systems)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use rusoto_mock::{
+        MockCredentialsProvider,
+        MockRequestDispatcher,
+        MockResponseReader,
+        ReadMockResponse,
+    };
+
+    #[cfg(feature = "cloudwatch")]
+    use chrono::TimeZone;
+
+    #[cfg(feature = "cloudwatch")]
+    use rusoto_core::signature::SignedRequestPayload;
+
+    #[cfg(feature = "cloudwatch")]
+    use rusoto_mock::MultipleMockRequestDispatcher;
+
+    // Returns a mock dispatcher returning the data from the specified
+    // data_file.
+    fn dispatcher_with_body(data_file: &str) -> MockRequestDispatcher {
+        let data = MockResponseReader::read_response("test-data", data_file);
+
+        MockRequestDispatcher::default().with_body(&data)
+    }
+
+    // Create a mock FSx client, returning the data from the specified
+    // data_file.
+    #[cfg(not(feature = "cloudwatch"))]
+    fn mock_lister(data_file: &str) -> FileSystemLister {
+        let client = FsxClient::new_with(
+            dispatcher_with_body(data_file),
+            MockCredentialsProvider,
+            Default::default()
+        );
+
+        FileSystemLister {
+            client:      client,
+            api_timeout: None,
+        }
+    }
+
+    // Create a mock FSx client, returning the data from the specified
+    // data_file, and a mock CloudWatch client returning each of the
+    // metric_files in turn.
+    #[cfg(feature = "cloudwatch")]
+    fn mock_lister(data_file: &str, metric_files: &[(&'static str, &str)]) -> FileSystemLister {
+        let client = FsxClient::new_with(
+            dispatcher_with_body(data_file),
+            MockCredentialsProvider,
+            Default::default()
+        );
+
+        // Each request must be for the expected metric.
+        let dispatchers: Vec<MockRequestDispatcher> = metric_files.iter()
+            .map(|(metric_name, metric_file)| {
+                let metric_name = *metric_name;
+
+                dispatcher_with_body(metric_file)
+                    .with_request_checker(move |request| {
+                        let payload = match &request.payload {
+                            Some(SignedRequestPayload::Buffer(b)) => {
+                                String::from_utf8(b.to_vec()).unwrap()
+                            },
+                            _ => panic!("GetMetricStatistics request had no payload"),
+                        };
+
+                        let expected = vec![
+                            "Dimensions.member.1.Name=FileSystemId".to_string(),
+                            format!("MetricName={}", metric_name),
+                            "Namespace=AWS%2FFSx".to_string(),
+                        ];
+
+                        for param in expected {
+                            assert!(payload.contains(&param), "{} not in {}", param, payload);
+                        }
+                    })
+            })
+            .collect();
+
+        let cloudwatch = CloudWatchClient::new_with(
+            MultipleMockRequestDispatcher::new(dispatchers),
+            MockCredentialsProvider,
+            Default::default()
+        );
+
+        FileSystemLister {
+            client:      client,
+            cloudwatch:  cloudwatch,
+            api_timeout: None,
+        }
+    }
+
+    // Returns a file system resource without any usage.
+    fn file_system(id: &str, resource_type: &str, gib: u64) -> StorageResource {
+        StorageResource {
+            name:          id.into(),
+            resource_type: Some(resource_type.into()),
+            size:          gib * GIBIBYTE,
+            timestamp:     None,
+            used:          None,
+        }
+    }
+
+    #[cfg(not(feature = "cloudwatch"))]
+    #[tokio::test]
+    async fn test_resources() {
+        let lister = mock_lister("fsx-describe-file-systems.json");
+        let ret    = lister.resources().await.unwrap();
+
+        let expected = vec![
+            file_system("fs-0123456789abcdef0", "LUSTRE",  1200),
+            file_system("fs-0223456789abcdef0", "ONTAP",   1024),
+            file_system("fs-0323456789abcdef0", "OPENZFS", 64),
+            file_system("fs-0423456789abcdef0", "WINDOWS", 32),
+        ];
+
+        assert_eq!(ret, expected);
+        assert_eq!(lister.kind(), "FSX_FILE_SYSTEM");
+    }
+
+    #[cfg(feature = "cloudwatch")]
+    #[tokio::test]
+    async fn test_resources() {
+        // Only the Lustre and Windows file systems are looked up, in order.
+        let lister = mock_lister("fsx-describe-file-systems.json", &[
+            ("FreeDataStorageCapacity", "cloudwatch-get-metric-statistics-fsx-lustre.xml"),
+            ("FreeStorageCapacity",     "cloudwatch-get-metric-statistics-empty.xml"),
+        ]);
+
+        let ret = lister.resources().await.unwrap();
+
+        // The Windows file system has no recent datapoints.
+        let expected = vec![
+            StorageResource {
+                timestamp: Some(Utc.ymd(2021, 3, 1).and_hms(12, 0, 0)),
+                used:      Some(200 * GIBIBYTE),
+                ..file_system("fs-0123456789abcdef0", "LUSTRE", 1200)
+            },
+            file_system("fs-0223456789abcdef0", "ONTAP",   1024),
+            file_system("fs-0323456789abcdef0", "OPENZFS", 64),
+            file_system("fs-0423456789abcdef0", "WINDOWS", 32),
+        ];
+
+        assert_eq!(ret, expected);
+        assert_eq!(lister.kind(), "FSX_FILE_SYSTEM");
+    }
+}
@@ -0,0 +1,142 @@
+// interrupt: Cancels work when the user presses Ctrl-C
+#![forbid(unsafe_code)]
+#![deny(missing_docs)]
+use futures::channel::oneshot;
+use futures::future::{
+    self,
+    BoxFuture,
+    Either,
+    FutureExt,
+};
+use std::future::Future;
+use tracing::debug;
+
+/// `Interrupt` races work against a signal from the user, so that the work
+/// can be abandoned part way through.
+pub struct Interrupt {
+    /// Future that completes when the user interrupts us.
+    signal: BoxFuture<'static, ()>,
+
+    /// Whether the `signal` has completed.
+    interrupted: bool,
+}
+
+impl Interrupt {
+    /// Returns an `Interrupt` that is signalled when `signal` completes.
+    pub fn new<F>(signal: F) -> Self
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        Self {
+            signal:      signal.boxed(),
+            interrupted: false,
+        }
+    }
+
+    /// Returns an `Interrupt` that is signalled when Ctrl-C is pressed.
+    ///
+    /// The handler is only installed once the `Interrupt` is first run, until
+    /// then Ctrl-C kills the process as usual. If the handler can't be
+    /// installed, we're never interrupted.
+    ///
+    /// Once installed, Ctrl-C is watched for in the background, so pressing
+    /// it between runs signals the next run. Pressing it a second time exits
+    /// straight away with `EXIT_INTERRUPTED`, for work that isn't run, such
+    /// as closing clients.
+    pub fn ctrl_c() -> Self {
+        Self::new(async {
+            let (sender, receiver) = oneshot::channel();
+
+            tokio::spawn(watch_ctrl_c(sender));
+
+            // The watcher only drops the sender without signalling if the
+            // handler couldn't be installed.
+            if receiver.await.is_err() {
+                future::pending::<()>().await;
+            }
+        })
+    }
+
+    /// Returns `true` if we've been interrupted.
+    pub fn is_interrupted(&self) -> bool {
+        self.interrupted
+    }
+
+    /// Returns the output of `work`, or `None` if we're interrupted before
+    /// it completes, in which case `work` is dropped.
+    ///
+    /// Once we've been interrupted, no more work is started.
+    pub async fn run<F: Future>(&mut self, work: F) -> Option<F::Output> {
+        if self.interrupted {
+            return None;
+        }
+
+        futures::pin_mut!(work);
+
+        match future::select(work, &mut self.signal).await {
+            Either::Left((output, _)) => Some(output),
+            Either::Right(_)          => {
+                debug!("run: Interrupted");
+
+                self.interrupted = true;
+
+                None
+            },
+        }
+    }
+}
+
+/// Signals `interrupted` when Ctrl-C is first pressed, and exits with
+/// `EXIT_INTERRUPTED` when it's pressed again.
+async fn watch_ctrl_c(interrupted: oneshot::Sender<()>) {
+    if let Err(e) = tokio::signal::ctrl_c().await {
+        debug!(error = %e, "watch_ctrl_c: Couldn't install handler");
+
+        return;
+    }
+
+    debug!("watch_ctrl_c: Interrupted");
+
+    // The receiver is gone if the `Interrupt` has been dropped, in which
+    // case the second Ctrl-C still exits.
+    let _ = interrupted.send(());
+
+    if tokio::signal::ctrl_c().await.is_ok() {
+        debug!("watch_ctrl_c: Interrupted again, exiting");
+
+        ::std::process::exit(crate::EXIT_INTERRUPTED);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use std::time::Duration;
+    use tokio::time::sleep;
+
+    #[tokio::test]
+    async fn test_run() {
+        let mut interrupt = Interrupt::new(future::pending());
+
+        let ret = interrupt.run(async { 1024 }).await;
+
+        assert_eq!(ret, Some(1024));
+        assert!(!interrupt.is_interrupted());
+    }
+
+    #[tokio::test]
+    async fn test_run_interrupted() {
+        let mut interrupt = Interrupt::new(sleep(Duration::from_millis(10)));
+
+        let ret = interrupt.run(future::pending::<u64>()).await;
+
+        assert_eq!(ret, None);
+        assert!(interrupt.is_interrupted());
+
+        // Work that would complete straight away isn't started.
+        let ret = interrupt.run(async { 1024 }).await;
+
+        assert_eq!(ret, None);
+    }
+}
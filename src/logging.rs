@@ -0,0 +1,200 @@
+// Logging: Sets up the tracing subscriber that log events are written with
+#![forbid(unsafe_code)]
+#![deny(missing_docs)]
+use anyhow::Result;
+use std::str::FromStr;
+use std::sync::atomic::{
+    AtomicBool,
+    Ordering,
+};
+use tracing::Subscriber;
+use tracing_subscriber::EnvFilter;
+use tracing_subscriber::fmt::MakeWriter;
+use tracing_subscriber::util::SubscriberInitExt;
+
+/// Whether `init` set up `Json` output, in which case messages for users are
+/// written as log events too.
+static JSON_OUTPUT: AtomicBool = AtomicBool::new(false);
+
+/// `LogFormat` represents the format that log events are written in.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum LogFormat {
+    /// One JSON object per event, with the fields of the event and of the
+    /// spans that it happened in, for log aggregation systems.
+    Json,
+
+    /// Human readable lines of text.
+    Text,
+}
+
+/// This converts from the string argument we receive from the command line to
+/// our enum type.
+impl FromStr for LogFormat {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "json" => Ok(Self::Json),
+            "text" => Ok(Self::Text),
+            _      => Err("no match"),
+        }
+    }
+}
+
+/// Returns a subscriber writing the events allowed by `filter` to `writer`,
+/// in the given `format`.
+///
+/// `ansi` enables colors in `Text` output, `Json` output is never colored.
+pub fn subscriber<W>(
+    format: LogFormat,
+    filter: EnvFilter,
+    writer: W,
+    ansi: bool,
+) -> Box<dyn Subscriber + Send + Sync>
+where
+    W: MakeWriter + Send + Sync + 'static,
+{
+    let builder = tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_writer(writer);
+
+    match format {
+        LogFormat::Json => Box::new(builder.json().with_ansi(false).finish()),
+        LogFormat::Text => Box::new(builder.with_ansi(ansi).finish()),
+    }
+}
+
+/// Sets the global subscriber, writing the events allowed by `filter` to
+/// stderr in the given `format`.
+///
+/// Records from crates using `log`, such as Rusoto, are written as events
+/// too.
+pub fn init(format: LogFormat, filter: EnvFilter) -> Result<()> {
+    let ansi = atty::is(atty::Stream::Stderr);
+
+    subscriber(format, filter, std::io::stderr, ansi).try_init()?;
+
+    JSON_OUTPUT.store(format == LogFormat::Json, Ordering::Relaxed);
+
+    Ok(())
+}
+
+/// Returns `true` if log events are being written as JSON, so that messages
+/// for users should be written as events rather than plain lines.
+pub fn is_json() -> bool {
+    JSON_OUTPUT.load(Ordering::Relaxed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use serde_json::Value;
+    use std::io;
+    use std::sync::{
+        Arc,
+        Mutex,
+    };
+    use tracing::{
+        debug,
+        info_span,
+    };
+
+    // Writer appending to a buffer shared with the test.
+    #[derive(Clone, Default)]
+    struct Buffer(Arc<Mutex<Vec<u8>>>);
+
+    impl Buffer {
+        fn contents(&self) -> String {
+            String::from_utf8(self.0.lock().unwrap().clone()).unwrap()
+        }
+    }
+
+    impl io::Write for Buffer {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    // Returns the output of a debug event inside of a scan span, written by
+    // a subscriber in the given format.
+    fn scan_event(format: LogFormat) -> String {
+        let buffer = Buffer::default();
+        let writer = buffer.clone();
+
+        let subscriber = subscriber(
+            format,
+            EnvFilter::new("debug"),
+            move || writer.clone(),
+            false,
+        );
+
+        tracing::subscriber::with_default(subscriber, || {
+            let span = info_span!("scan", buckets = 1);
+            let _enter = span.enter();
+
+            debug!(bucket = "a-bucket-name", size = 1024, "Calculated size");
+        });
+
+        buffer.contents()
+    }
+
+    #[test]
+    fn test_log_format_from_str() {
+        let tests = vec![
+            ("json",   Some(LogFormat::Json)),
+            ("text",   Some(LogFormat::Text)),
+            ("pretty", None),
+        ];
+
+        for test in tests {
+            let format   = test.0;
+            let expected = test.1;
+
+            let ret = LogFormat::from_str(format).ok();
+
+            assert_eq!(ret, expected);
+        }
+    }
+
+    #[test]
+    fn test_subscriber_json() {
+        let output = scan_event(LogFormat::Json);
+
+        let event: Value = serde_json::from_str(output.trim()).unwrap();
+
+        assert_eq!(event["level"], "DEBUG");
+        assert_eq!(event["fields"]["message"], "Calculated size");
+        assert_eq!(event["fields"]["bucket"], "a-bucket-name");
+        assert_eq!(event["fields"]["size"], 1024);
+        assert_eq!(event["span"]["name"], "scan");
+        assert_eq!(event["span"]["buckets"], 1);
+    }
+
+    #[test]
+    fn test_subscriber_text() {
+        let output = scan_event(LogFormat::Text);
+
+        assert!(output.contains("DEBUG"));
+        assert!(output.contains("scan{buckets=1}"));
+        assert!(output.contains("Calculated size"));
+        assert!(output.contains("bucket=\"a-bucket-name\""));
+        assert!(output.contains("size=1024"));
+    }
+
+    #[test]
+    fn test_init() {
+        let filter = EnvFilter::new("warn");
+
+        assert!(init(LogFormat::Text, filter).is_ok());
+
+        // There can only be a single global subscriber.
+        let filter = EnvFilter::new("warn");
+
+        assert!(init(LogFormat::Json, filter).is_err());
+    }
+}
@@ -0,0 +1,38 @@
+// Macros shared by the rest of the crate
+#![forbid(unsafe_code)]
+#![deny(missing_docs)]
+
+/// Prints a warning to stderr, unless warnings are disabled by the log
+/// level, for example with `--quiet`.
+///
+/// Warnings keep their plain `Warning:` prefix rather than the logger's
+/// format, as they're aimed at users rather than debugging. With
+/// `--log-format json` they're logged as events instead, so that stderr
+/// stays machine readable.
+macro_rules! warning {
+    ($($arg:tt)+) => {
+        if $crate::logging::is_json() {
+            ::tracing::warn!($($arg)+);
+        }
+        else if ::tracing::enabled!(::tracing::Level::WARN) {
+            eprintln!("Warning: {}", format_args!($($arg)+));
+        }
+    };
+}
+
+/// Prints the result of a check to stderr, in the same way as `warning!`.
+///
+/// Notices are the results of checks that were asked for on the command
+/// line, so they're shown at the same log level as warnings, but with an
+/// `Info:` prefix.
+#[cfg(feature = "s3")]
+macro_rules! notice {
+    ($($arg:tt)+) => {
+        if $crate::logging::is_json() {
+            ::tracing::warn!(notice = true, $($arg)+);
+        }
+        else if ::tracing::enabled!(::tracing::Level::WARN) {
+            eprintln!("Info: {}", format_args!($($arg)+));
+        }
+    };
+}
@@ -3,30 +3,130 @@
 #![deny(missing_docs)]
 #![allow(clippy::redundant_field_names)]
 use anyhow::Result;
+use chrono::Utc;
 use clap::value_t;
-use log::{
+use scopeguard::ScopeGuard;
+use std::io::Write;
+use tokio::runtime::Runtime;
+use tracing::{
     debug,
     info,
+    info_span,
+    Instrument,
 };
-use rusoto_core::Region;
-use std::str::FromStr;
-use tokio::runtime::Runtime;
+use tracing_subscriber::EnvFilter;
+
+/// Macros shared by the rest of the crate.
+#[macro_use]
+mod macros;
+
+/// Loading the display names of buckets.
+mod alias;
+
+/// Analysis of the objects listed in a bucket.
+#[cfg(feature = "s3")]
+mod analysis;
 
 /// Command line parsing.
 mod cli;
 
 /// Common types and traits.
 mod common;
+
+/// AWS credentials handling.
+mod credentials;
+
+/// Differences between snapshots of bucket sizes.
+mod diff;
+
+/// EBS snapshots, listed alongside the buckets.
+mod ebs;
+
+/// ECR repositories, listed alongside the buckets.
+mod ecr;
+
+/// EFS file systems, listed alongside the buckets.
+mod efs;
+
+/// Cancelling the sizing of buckets with Ctrl-C.
+mod interrupt;
+
+/// FSx file systems, listed alongside the buckets.
+mod fsx;
+
+/// Custom endpoints for S3 compatible storage.
+#[cfg(feature = "s3")]
+mod endpoint;
+
+/// Log event formats and the tracing subscriber.
+mod logging;
+
+/// Output formats.
+mod output;
+
+/// Saving and loading snapshots of bucket sizes.
+mod snapshot;
 use common::{
+    BucketAliases,
     BucketSizer,
+    Buckets,
     ClientConfig,
     ClientMode,
-    HumanSize,
-    SizeUnit,
+    CostModel,
+    S3duError,
+    StorageSizer,
+    scanned_regions,
+};
+
+#[cfg(feature = "cloudwatch")]
+use cloudwatch::{
+    BucketMetric,
+    Publisher,
+};
+
+#[cfg(feature = "cloudwatch")]
+use common::ClassSizes;
+
+#[cfg(feature = "cloudwatch")]
+use rusoto_core::Region;
+
+#[cfg(feature = "cloudwatch")]
+use std::str::FromStr;
+
+#[cfg(feature = "cloudwatch")]
+use std::collections::{
+    BTreeMap,
+    HashMap,
+};
+
+use cli::RunOptions;
+use interrupt::Interrupt;
+use logging::LogFormat;
+
+use output::{
+    BOLD,
+    NdjsonPrinter,
+    OutputMode,
+    PlainPrinter,
+    ShownBucket,
+    Total,
+    format_failed,
+    format_request_count,
+    format_request_estimate,
+    format_resource,
+    format_skipped,
+    format_total,
+    format_would_scan,
+    output_writer,
+    paint,
+    printer,
 };
 
 #[cfg(feature = "s3")]
-use common::ObjectVersions;
+use output::format_region_check;
+
+use snapshot::BucketSizeEntry;
+use std::path::Path;
 
 /// CloudWatch Client.
 #[cfg(feature = "cloudwatch")]
@@ -36,17 +136,132 @@ mod cloudwatch;
 #[cfg(feature = "s3")]
 mod s3;
 
-/// `Client` struct wraps a `Box<dyn BucketSizer>`.
-struct Client(Box<dyn BucketSizer>);
+/// Combined CloudWatch and S3 Client.
+#[cfg(all(feature = "cloudwatch", feature = "s3"))]
+mod combined;
+
+/// Mock S3 responses and temporary files for tests.
+#[cfg(test)]
+mod test_helpers;
+
+/// Exit code used when all buckets failed to be sized.
+const EXIT_FAILURE: i32 = 1;
+
+/// Exit code used when some, but not all, buckets failed to be sized.
+const EXIT_PARTIAL_FAILURE: i32 = 2;
+
+/// Exit code used when sizing was interrupted with Ctrl-C, following the
+/// shell convention of 128 plus the signal number of `SIGINT`.
+const EXIT_INTERRUPTED: i32 = 130;
+
+/// Creates a `StorageSizer` for the region of the given `ClientConfig`.
+type NewStorageSizer = fn(&ClientConfig) -> Box<dyn StorageSizer>;
+
+/// Minimum size of the buckets displayed by `Client::du`.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+struct MinSize {
+    /// Buckets smaller than this size in bytes are hidden from the output.
+    size: u64,
+
+    /// Whether hidden buckets are also excluded from the total size.
+    affects_total: bool,
+}
+
+/// Summary of the buckets processed by `Client::du`.
+#[derive(Debug, Default, PartialEq)]
+struct DuSummary {
+    /// Sizes of the buckets that were successfully sized.
+    sized: Vec<BucketSizeEntry>,
+
+    /// Names of the buckets that failed to be sized, along with the reason
+    /// that they failed.
+    failed: Vec<(String, String)>,
+
+    /// Names of the buckets that were skipped, as access to them was denied.
+    skipped: Vec<String>,
+
+    /// Buckets that were sized and not hidden, along with any that failed
+    /// and are annotated in the output, for output once every bucket has
+    /// been sized.
+    shown: Vec<ShownBucket>,
+
+    /// Names of the buckets that were sized, but hidden from the output by
+    /// a `MinSize`.
+    hidden: Vec<String>,
+
+    /// Total of the buckets, as output.
+    total: Total,
+
+    /// Sizes of each storage class of the sized buckets, keyed on bucket
+    /// name, for the buckets that the client saw them for.
+    #[cfg(feature = "cloudwatch")]
+    classes: HashMap<String, ClassSizes>,
+
+    /// Whether sizing was interrupted, in which case the summary only holds
+    /// the buckets sized before then.
+    interrupted: bool,
+}
+
+impl DuSummary {
+    /// Adds the buckets and totals of `other` to this summary, for combining
+    /// the summaries of clients in different regions.
+    fn merge(&mut self, other: Self) {
+        self.sized.extend(other.sized);
+        self.failed.extend(other.failed);
+        self.skipped.extend(other.skipped);
+        self.shown.extend(other.shown);
+        self.hidden.extend(other.hidden);
+
+        #[cfg(feature = "cloudwatch")]
+        self.classes.extend(other.classes);
+
+        self.total.merge(other.total);
+        self.interrupted |= other.interrupted;
+    }
+
+    /// Returns the process exit code for this summary.
+    ///
+    /// Full success is `0`, total failure is `EXIT_FAILURE` and partial
+    /// success is `EXIT_PARTIAL_FAILURE`. Being interrupted takes precedence
+    /// over all of these, with `EXIT_INTERRUPTED`.
+    fn exit_code(&self) -> i32 {
+        if self.interrupted {
+            return EXIT_INTERRUPTED;
+        }
+
+        match (self.sized.len(), self.failed.len()) {
+            (_, 0) => 0,
+            (0, _) => EXIT_FAILURE,
+            _      => EXIT_PARTIAL_FAILURE,
+        }
+    }
+}
+
+/// Returns a short reason for the error `e`, for annotating the output of
+/// buckets that failed to be sized.
+fn error_reason(e: &anyhow::Error) -> String {
+    match e.downcast_ref::<S3duError>() {
+        Some(S3duError::AccessDenied(_)) => "access denied".into(),
+        #[cfg(feature = "cloudwatch")]
+        Some(S3duError::NoDatapoints(_)) => "no datapoints".into(),
+        Some(S3duError::Throttled(_))    => "throttled".into(),
+        _                                => e.to_string(),
+    }
+}
+
+/// `Client` struct wraps a `Box<dyn BucketSizer>`, along with the aliases
+/// that its buckets are printed with.
+struct Client(Box<dyn BucketSizer>, BucketAliases);
 
 /// `Client` implementation.
 impl Client {
     /// Return the appropriate AWS client with the given `ClientConfig`.
     fn new(config: ClientConfig) -> Self {
-        let mode   = &config.mode;
-        let region = &config.region;
+        let aliases = config.bucket_aliases.clone();
+        let mode    = &config.mode;
+        let region  = &config.region;
 
-        info!("Client in region {} for mode {:?}", region.name(), mode);
+        info!(region = region.name(), mode = ?mode, "Creating client");
 
         let client: Box<dyn BucketSizer> = match mode {
             #[cfg(feature = "cloudwatch")]
@@ -54,6 +269,16 @@ impl Client {
                 let client = cloudwatch::Client::new(config);
                 Box::new(client)
             },
+            #[cfg(all(feature = "cloudwatch", feature = "s3"))]
+            ClientMode::Combined => {
+                let client = combined::Client::new(config);
+                Box::new(client)
+            },
+            #[cfg(feature = "s3")]
+            ClientMode::S3 if config.auto_region => {
+                let client = s3::AutoRegionClient::new(config);
+                Box::new(client)
+            },
             #[cfg(feature = "s3")]
             ClientMode::S3 => {
                 let client = s3::Client::new(config);
@@ -61,117 +286,1763 @@ impl Client {
             },
         };
 
-        Client(client)
+        Client(client, aliases)
+    }
+
+    /// Returns this client wrapped in a guard that closes it on the given
+    /// `runtime` when it goes out of scope, so that the client is closed
+    /// even when sizing fails.
+    fn close_on_drop(self, runtime: &Runtime) -> ScopeGuard<Self, impl FnOnce(Self) + '_> {
+        scopeguard::guard(self, move |mut client| {
+            if let Err(e) = runtime.block_on(client.0.close()) {
+                warning!("Failed to close client: {}", e);
+            }
+        })
+    }
+
+    /// Returns the buckets discovered by the client, with their aliases.
+    async fn buckets(&self) -> Result<Buckets> {
+        let mut buckets = self.0.buckets().await?;
+
+        for bucket in &mut buckets {
+            bucket.alias = self.1.get(&bucket.name).cloned();
+        }
+
+        Ok(buckets)
+    }
+
+    /// Returns the buckets that `du` would size, without sizing them, for a
+    /// dry run or when only listing buckets.
+    ///
+    /// Buckets are still discovered, so that any filters are applied, but
+    /// no bucket is sized. If we're `interrupt`ed before they're listed,
+    /// `None` is returned.
+    async fn list_buckets(&self, interrupt: &mut Interrupt) -> Result<Option<Buckets>> {
+        let buckets = match interrupt.run(self.buckets()).await {
+            Some(buckets) => buckets?,
+            None          => return Ok(None),
+        };
+
+        debug!(buckets = buckets.len(), "list_buckets: Got buckets");
+
+        Ok(Some(buckets))
     }
 
-    /// Perform the actual get and output of the bucket sizes.
-    async fn du(&self, unit: SizeUnit) -> Result<()> {
+    /// Perform the actual get of the bucket sizes.
+    ///
+    /// Nothing is output while sizing, the sized buckets are returned in the
+    /// `DuSummary` for a `Printer` to render once every bucket has been
+    /// sized.
+    ///
+    /// If `fail_fast` is `true`, the first bucket that fails to be sized will
+    /// abort the run. Otherwise failures are reported on stderr and the
+    /// remaining buckets are still sized. If `include_errors` is also `true`,
+    /// failures are instead shown alongside the sized buckets, for annotating
+    /// why CloudWatch couldn't size a bucket in the text output.
+    ///
+    /// If `skip_inaccessible` is `true`, buckets that access is denied to are
+    /// skipped without being reported as failures, even with `fail_fast`.
+    ///
+    /// If `min_size` is given, buckets smaller than it are hidden from the
+    /// shown buckets. They still count towards the total unless the
+    /// `MinSize` affects the total.
+    ///
+    /// If `cost_model` is given, the estimated monthly cost of each bucket is
+    /// estimated, along with the total cost.
+    ///
+    /// Log events while sizing are in a `scan` span, with a `bucket` span for
+    /// each bucket.
+    #[tracing::instrument(name = "scan", skip_all, fields(buckets))]
+    async fn du(
+        &self,
+        include_errors: bool,
+        fail_fast: bool,
+        skip_inaccessible: bool,
+        min_size: Option<MinSize>,
+        cost_model: Option<&CostModel>,
+        interrupt: &mut Interrupt,
+    ) -> Result<DuSummary> {
         // List all of our buckets
-        let buckets = self.0.buckets().await?;
+        let buckets = self.buckets().await?;
+
+        tracing::Span::current().record("buckets", buckets.len());
 
-        debug!("du: Got buckets: {:?}", buckets);
+        info!(buckets = buckets.len(), "du: Sizing buckets");
 
-        // Track total size of all buckets.
-        let mut total_size: usize = 0;
+        let mut summary = DuSummary {
+            total: Total {
+                cost: cost_model.map(|_| 0.0),
+                ..Total::default()
+            },
+            ..DuSummary::default()
+        };
 
         // For each bucket name, get the size
         for bucket in buckets {
-            let size = self.0.bucket_size(&bucket).await?;
+            let span = info_span!("bucket", bucket = %bucket);
+
+            // The bucket being sized when we're interrupted is abandoned,
+            // leaving the buckets sized before it in the summary.
+            let result = match interrupt.run(self.0.bucket_size(&bucket).instrument(span)).await {
+                Some(result) => result,
+                None         => {
+                    warning!("Interrupted while sizing '{}', sizes are partial", bucket);
+
+                    summary.interrupted = true;
+
+                    break;
+                },
+            };
+
+            let size = match result {
+                Ok(size) => size,
+                Err(ref e) if skip_inaccessible && S3duError::is_access_denied(e) => {
+                    debug!(bucket = %bucket, "du: Skipping inaccessible bucket");
+
+                    summary.skipped.push(bucket.name);
+
+                    continue;
+                },
+                Err(e) if fail_fast => return Err(e),
+                Err(e) if include_errors => {
+                    let reason = error_reason(&e);
 
-            total_size += size;
+                    summary.failed.push((bucket.name.to_owned(), reason.to_owned()));
+                    summary.shown.push(ShownBucket::failed(bucket, reason, Utc::now()));
 
-            let size = size.humansize(&unit);
+                    continue;
+                },
+                Err(e) => {
+                    debug!(bucket = %bucket, error = %e, "du: Failed to size bucket");
 
-            println!("{size}\t{bucket}", size=size, bucket=bucket.name);
+                    summary.failed.push((bucket.name, error_reason(&e)));
+
+                    continue;
+                },
+            };
+
+            summary.sized.push(BucketSizeEntry {
+                bucket: bucket.name.to_owned(),
+                region: bucket.region.as_ref().map(|r| r.name().into()),
+                size:   size,
+            });
+
+            // Buckets without a timestamp from the client were sized live.
+            let timestamp = self.0.bucket_timestamp(&bucket)
+                .unwrap_or_else(Utc::now);
+
+            // Counts are taken before hiding buckets, so that they're still
+            // included in the total along with the bucket size.
+            #[cfg(feature = "s3")]
+            let stats = self.0.bucket_stats(&bucket);
+
+            let class_sizes = self.0.bucket_class_sizes(&bucket);
+
+            let cost = cost_model.map(|model| {
+                class_sizes.as_ref()
+                    .map(|sizes| model.monthly_cost(sizes))
+            });
+
+            // Storage class sizes are kept for publishing to CloudWatch.
+            #[cfg(feature = "cloudwatch")]
+            {
+                if let Some(ref class_sizes) = class_sizes {
+                    summary.classes.insert(bucket.name.to_owned(), class_sizes.clone());
+                }
+            }
+
+            // The rest of the bucket's reports are taken from the client
+            // whether or not the bucket is hidden, so that the client
+            // doesn't keep hidden buckets' reports for the rest of the run.
+            #[cfg(feature = "s3")]
+            let multipart_size = self.0.bucket_multipart_size(&bucket);
+
+            #[cfg(feature = "s3")]
+            let metadata = self.0.bucket_metadata(&bucket);
+
+            #[cfg(feature = "s3")]
+            let prefixes = self.0.bucket_prefixes(&bucket);
+
+            #[cfg(feature = "s3")]
+            let duplicates = self.0.bucket_duplicates(&bucket);
+
+            #[cfg(feature = "s3")]
+            let age_histogram = self.0.bucket_age_histogram(&bucket);
+
+            #[cfg(feature = "cloudwatch")]
+            let transitions = self.0.bucket_transitions(&bucket);
+
+            let total = Total {
+                size:   size,
+                latest: Some(timestamp),
+                cost:   cost.map(|cost| cost.unwrap_or(0.0)),
+                #[cfg(feature = "s3")]
+                stats:  stats,
+            };
+
+            // Hidden buckets are still recorded above, so that snapshots
+            // and diffs cover every bucket.
+            match min_size {
+                Some(min_size) if size < min_size.size => {
+                    if min_size.affects_total {
+                        summary.total.latest = summary.total.latest.max(Some(timestamp));
+                    }
+                    else {
+                        summary.total.merge(total);
+                    }
+
+                    summary.hidden.push(bucket.name);
+
+                    continue;
+                },
+                _ => summary.total.merge(total),
+            }
+
+            summary.shown.push(ShownBucket {
+                cost:           cost,
+                class_sizes:    class_sizes,
+                #[cfg(feature = "s3")]
+                stats:          stats,
+                #[cfg(feature = "s3")]
+                multipart_size: multipart_size,
+                #[cfg(feature = "s3")]
+                metadata:       metadata,
+                #[cfg(feature = "s3")]
+                prefixes:       prefixes,
+                #[cfg(feature = "s3")]
+                duplicates:     duplicates,
+                #[cfg(feature = "s3")]
+                age_histogram:  age_histogram,
+                #[cfg(feature = "cloudwatch")]
+                transitions:    transitions,
+                ..ShownBucket::new(bucket, size, timestamp)
+            });
         }
 
-        let total_size = total_size.humansize(&unit);
+        Ok(summary)
+    }
+}
+
+/// Returns the sized buckets in `summary` as metrics to publish to
+/// CloudWatch, grouped by the name of their region, if known.
+#[cfg(feature = "cloudwatch")]
+fn cloudwatch_metrics(summary: &DuSummary) -> BTreeMap<Option<String>, Vec<BucketMetric>> {
+    let mut metrics: BTreeMap<Option<String>, Vec<BucketMetric>> = BTreeMap::new();
 
-        // Display the total size the same way du(1) would, the total size
-        // followed by a `.`.
-        println!("{size}\t.", size=total_size);
+    for entry in &summary.sized {
+        let metric = BucketMetric {
+            bucket:      entry.bucket.to_owned(),
+            size:        entry.size,
+            class_sizes: summary.classes.get(&entry.bucket).cloned(),
+        };
 
-        Ok(())
+        metrics.entry(entry.region.to_owned())
+            .or_default()
+            .push(metric);
     }
+
+    metrics
 }
 
-/// Entry point
-fn main() -> Result<()> {
-    pretty_env_logger::init();
+/// Sizes the buckets for `config` and writes them to `out`, as chosen by
+/// `options`, returning the exit code.
+///
+/// Clients are created with `new_client`, once for each region that buckets
+/// are sized in, and are run on the given `runtime`. Listing, sizing and the
+/// phases after them stop once we're `interrupt`ed, returning
+/// `EXIT_INTERRUPTED`.
+fn run<F>(
+    options: &RunOptions,
+    config: ClientConfig,
+    new_client: F,
+    runtime: &Runtime,
+    out: &mut dyn Write,
+    interrupt: &mut Interrupt,
+) -> Result<i32>
+where
+    F: Fn(ClientConfig) -> Client,
+{
+    let request_counter = config.request_counter.clone();
 
-    // Parse the CLI
-    let matches = cli::parse_args();
+    // A dry run lists the buckets that would be sized and exits, so that
+    // filters can be checked before paying for the API calls to size them.
+    if options.dry_run || options.list_buckets_only {
+        // Storage class transitions are estimated to double the CloudWatch
+        // requests.
+        #[cfg(feature = "cloudwatch")]
+        let class_transitions = config.report_class_transitions;
 
-    // Get the bucket name, if any.
-    let bucket_name = match matches.value_of("BUCKET") {
-        Some(name) => Some(name.to_string()),
-        None       => None,
-    };
+        #[cfg(not(feature = "cloudwatch"))]
+        let class_transitions = false;
 
-    // Get the client mode
-    let mode = value_t!(matches, "MODE", ClientMode)?;
+        let mut buckets = Vec::new();
 
-    // Get the unit size to display
-    let unit = value_t!(matches, "UNIT", SizeUnit)?;
+        if options.all_regions {
+            for region in &scanned_regions(&config.exclude_regions) {
+                let config = ClientConfig {
+                    region: region.to_owned(),
+                    ..config.clone()
+                };
 
-    // Here we get the region, if a custom endpoint is set, that is used,
-    // otherwise we get the regular region.
-    // Unwraps on values here should be fine, as they're checked when the CLI
-    // is validated.
-    #[cfg(feature = "s3")]
-    let region = if matches.is_present("ENDPOINT") {
-        if mode == ClientMode::S3 {
-            let endpoint = matches.value_of("ENDPOINT").unwrap();
-            Region::Custom {
-                name:     "custom".into(),
-                endpoint: endpoint.into(),
+                let client = new_client(config).close_on_drop(runtime);
+
+                match runtime.block_on(client.list_buckets(interrupt)) {
+                    Ok(Some(region_buckets)) => buckets.extend(region_buckets),
+                    Ok(None)                 => {
+                        warning!("Interrupted while listing buckets in '{}', buckets are partial", region.name());
+
+                        break;
+                    },
+                    Err(e) if options.fail_fast => return Err(e),
+                    Err(e)                      => {
+                        warning!(
+                            "Failed to list buckets in '{}': {}",
+                            region.name(),
+                            e,
+                        );
+                    },
+                }
             }
         }
         else {
-            eprintln!("Error: Endpoint supplied but client mode is not S3");
-            ::std::process::exit(1);
+            let client = new_client(config).close_on_drop(runtime);
+
+            match runtime.block_on(client.list_buckets(interrupt))? {
+                Some(listed) => buckets = listed,
+                None         => warning!("Interrupted while listing buckets"),
+            }
+        }
+
+        let printer = printer(options.output, &options.unit, options.time, Utc::now())
+            .unwrap_or_else(|| Box::new(PlainPrinter {
+                unit: &options.unit,
+                time: options.time,
+                #[cfg(feature = "s3")]
+                tree: false,
+            }));
+
+        for line in printer.print_dry_run(&buckets) {
+            writeln!(out, "{}", line)?;
+        }
+
+        if options.list_buckets_only {
+            out.flush()?;
+
+            if let Some(counter) = &request_counter {
+                eprintln!("{}", format_request_count(counter));
+            }
+
+            if interrupt.is_interrupted() {
+                return Ok(EXIT_INTERRUPTED);
+            }
+
+            return Ok(0);
+        }
+
+        // Machine readable output is kept parseable by reporting the count
+        // and estimates on stderr.
+        let mut messages = vec![format_would_scan(buckets.len())];
+
+        messages.extend(format_request_estimate(&buckets, class_transitions));
+
+        for message in messages {
+            if matches!(options.output, OutputMode::Csv | OutputMode::Flux | OutputMode::Json | OutputMode::Ndjson) {
+                eprintln!("{}", message);
+            }
+            else {
+                writeln!(out, "{}", message)?;
+            }
+        }
+
+        out.flush()?;
+
+        if let Some(counter) = &request_counter {
+            eprintln!("{}", format_request_count(counter));
+        }
+
+        if interrupt.is_interrupted() {
+            return Ok(EXIT_INTERRUPTED);
+        }
+
+        return Ok(0);
+    }
+
+    // With all regions, a client is created for each region in turn and
+    // their summaries are combined. Regions that can't be listed, such as
+    // opt-in regions that aren't enabled, are skipped with a warning.
+    let summary = if options.all_regions {
+        let mut summary = DuSummary::default();
+
+        for region in &scanned_regions(&config.exclude_regions) {
+            info!(region = region.name(), "Sizing buckets");
+
+            let config = ClientConfig {
+                region: region.to_owned(),
+                ..config.clone()
+            };
+
+            let client = new_client(config).close_on_drop(runtime);
+
+            match runtime.block_on(client.du(options.include_errors, options.fail_fast, options.skip_inaccessible, options.min_size, options.cost_model.as_ref(), interrupt)) {
+                Ok(region_summary)  => summary.merge(region_summary),
+                Err(e) if options.fail_fast => return Err(e),
+                Err(e)                      => {
+                    warning!(
+                        "Failed to size buckets in '{}': {}",
+                        region.name(),
+                        e,
+                    );
+                },
+            }
+
+            if interrupt.is_interrupted() {
+                break;
+            }
         }
+
+        summary
     }
     else {
-        let region = matches.value_of("REGION").unwrap();
-        Region::from_str(region)?
-    };
+        let client = new_client(config.clone()).close_on_drop(runtime);
 
-    // Endpoint selection isn't supported for CloudWatch, so we can drop it if
-    // we're compiled without the S3 feature.
-    #[cfg(all(feature = "cloudwatch", not(feature = "s3")))]
-    let region = {
-        let region = matches.value_of("REGION").unwrap();
-        Region::from_str(region)?
+        runtime.block_on(client.du(options.include_errors, options.fail_fast, options.skip_inaccessible, options.min_size, options.cost_model.as_ref(), interrupt))?
     };
 
-    // This warning will trigger if compiled without the "s3" feature. We're
-    // aware, allow it.
-    #[allow(unused_mut)]
-    let mut config = ClientConfig {
-        bucket_name: bucket_name,
-        mode:        mode,
-        region:      region,
-        ..Default::default()
+    // Buckets are output once every bucket has been sized, so that every
+    // output format is rendered from the same summary.
+    let sized_at = summary.total.latest.unwrap_or_else(Utc::now);
+
+    if let Some(printer) = printer(options.output, &options.unit, options.time, sized_at) {
+        for line in printer.print(&summary.shown) {
+            writeln!(out, "{}", line)?;
+        }
+    }
+
+    // Storage other than buckets is sized by its own service, with a sizer
+    // created for each region.
+    let mut storage_sizers: Vec<NewStorageSizer> = Vec::new();
+
+    #[cfg(feature = "s3")]
+    {
+        if options.check_glacier_vault {
+            storage_sizers.push(|config| Box::new(s3::VaultLister::new(config)));
+        }
+    }
+
+    if options.check_ebs_snapshots {
+        storage_sizers.push(|config| Box::new(ebs::SnapshotLister::new(config)));
+    }
+
+    if options.check_ecr {
+        storage_sizers.push(|config| Box::new(ecr::RepositoryLister::new(config)));
+    }
+
+    if options.check_efs {
+        storage_sizers.push(|config| Box::new(efs::FileSystemLister::new(config)));
+    }
+
+    if options.check_fsx {
+        storage_sizers.push(|config| Box::new(fsx::FileSystemLister::new(config)));
+    }
+
+    // Other storage is listed in every region that buckets were sized in,
+    // after the buckets, and counts towards the total. Once interrupted,
+    // it isn't listed at all.
+    let summary = if storage_sizers.is_empty() || summary.interrupted {
+        summary
+    }
+    else {
+        let regions = if options.all_regions {
+            scanned_regions(&config.exclude_regions)
+        }
+        else {
+            vec![config.region.to_owned()]
+        };
+
+        let mut storage_size = 0;
+
+        for region in regions {
+            let region_config = ClientConfig {
+                region: region.to_owned(),
+                ..config.clone()
+            };
+
+            for new_sizer in &storage_sizers {
+                let sizer = new_sizer(&region_config);
+
+                info!(kind = sizer.kind(), region = region.name(), "Listing storage");
+
+                let resources = match runtime.block_on(interrupt.run(sizer.resources())) {
+                    Some(resources) => resources,
+                    None            => {
+                        warning!("Interrupted while listing {} storage", sizer.kind());
+
+                        break;
+                    },
+                };
+
+                let resources = match resources {
+                    Ok(resources)         => resources,
+                    Err(e) if options.all_regions => {
+                        warning!(
+                            "Failed to list {} storage in '{}': {}",
+                            sizer.kind(),
+                            region.name(),
+                            e,
+                        );
+
+                        continue;
+                    },
+                    Err(e) => return Err(e),
+                };
+
+                for resource in resources {
+                    writeln!(out, "{}", format_resource(&resource, sizer.kind(), &options.unit, options.time))?;
+
+                    storage_size += resource.size;
+                }
+            }
+
+            if interrupt.is_interrupted() {
+                break;
+            }
+        }
+
+        DuSummary {
+            total: Total {
+                size: summary.total.size + storage_size,
+                ..summary.total
+            },
+            interrupted: interrupt.is_interrupted(),
+            ..summary
+        }
     };
 
-    // If have s3 mode available we also need to pull in the ObjectVersions
-    // from the command line.
+    // Active Glacier jobs are reported in every region that buckets were
+    // sized in. The report is advisory, so failing to list the jobs in a
+    // region only warns.
     #[cfg(feature = "s3")]
     {
-        if config.mode == ClientMode::S3 {
-            // This should be safe, we validated this in the CLI parser.
-            let versions = matches.value_of("OBJECT_VERSIONS").unwrap();
+        if options.check_glacier_jobs && !summary.interrupted {
+            let regions = if options.all_regions {
+                scanned_regions(&config.exclude_regions)
+            }
+            else {
+                vec![config.region.to_owned()]
+            };
+
+            for region in regions {
+                let region_config = ClientConfig {
+                    region: region.to_owned(),
+                    ..config.clone()
+                };
+
+                let lister = s3::VaultLister::new(&region_config);
+
+                let jobs = match runtime.block_on(interrupt.run(lister.active_jobs())) {
+                    Some(jobs) => jobs,
+                    None       => {
+                        warning!("Interrupted while listing Glacier jobs");
+
+                        break;
+                    },
+                };
+
+                match jobs {
+                    Ok(jobs) if jobs.is_empty() => {
+                        notice!("No active Glacier jobs in {}", region.name());
+                    },
+                    Ok(jobs) => {
+                        let now = Utc::now();
+
+                        for job in &jobs {
+                            notice!("{}", s3::format_vault_job(job, now));
+                        }
+                    },
+                    Err(e) => {
+                        warning!(
+                            "Failed to list Glacier jobs in '{}': {}",
+                            region.name(),
+                            e,
+                        );
+                    },
+                }
+            }
+        }
+    }
+
+    // Only text output has a total, machine readable output can be summed
+    // by its consumer.
+    let has_total = !matches!(
+        options.output,
+        OutputMode::Csv
+            | OutputMode::Diff
+            | OutputMode::Flux
+            | OutputMode::Json
+            | OutputMode::Ndjson
+            | OutputMode::Prometheus
+    );
+
+    if has_total && options.show_total {
+        writeln!(out, "{}", paint(&format_total(&summary.total, &options.unit, options.time), BOLD, options.color))?;
+    }
+
+    if has_total {
+        if let Some(line) = format_skipped(&summary.skipped) {
+            writeln!(out, "{}", line)?;
+        }
+    }
+
+    // Buckets that failed to be sized, were skipped, or weren't selected,
+    // aren't reported as removed.
+    if let Some(path) = &options.diff_path {
+        let mut old = snapshot::load(path)?;
+
+        old.retain(|entry| {
+            let selected = match config.bucket_name.as_deref() {
+                Some(name) => entry.bucket == name,
+                None       => true,
+            };
+
+            selected
+                && !summary.failed.iter().any(|(name, _)| *name == entry.bucket)
+                && !summary.skipped.contains(&entry.bucket)
+        });
+
+        for change in diff::diff(&old, &summary.sized) {
+            let line = change.format(&options.unit);
+
+            match change.color() {
+                Some(code) => writeln!(out, "{}", paint(&line, code, options.color))?,
+                None       => writeln!(out, "{}", line)?,
+            }
+        }
+    }
+
+    // Output is flushed before anything else can fail.
+    out.flush()?;
+
+    // Failures are reported once every bucket has been sized, so that they
+    // aren't lost among the output.
+    for line in format_failed(&summary.failed) {
+        warning!("{}", line);
+    }
+
+    // A partial snapshot would look like buckets had been removed when it's
+    // diffed, so nothing is saved or published once interrupted.
+    if let Some(path) = options.snapshot_path.as_ref().filter(|_| !summary.interrupted) {
+        snapshot::save(path, &summary.sized)?;
+    }
+
+    // Sizes are published in the region of each bucket, alongside the
+    // native S3 metrics. Buckets without a known region are published in
+    // the configured region.
+    #[cfg(feature = "cloudwatch")]
+    {
+        if options.report_to_cloudwatch && !summary.interrupted {
+            for (region, metrics) in cloudwatch_metrics(&summary) {
+                let region = match region {
+                    Some(name) => Region::from_str(&name)?,
+                    None       => config.region.to_owned(),
+                };
+
+                info!(
+                    buckets = metrics.len(),
+                    region  = region.name(),
+                    "Publishing bucket sizes to CloudWatch",
+                );
 
-            // This should be safe, due to validation of the above.
-            let versions = ObjectVersions::from_str(versions).unwrap();
+                let publisher = Publisher::new(&ClientConfig {
+                    region: region,
+                    ..config.clone()
+                });
 
-            config.object_versions = versions;
+                // Metrics that were published before we're interrupted stay
+                // published, the rest aren't.
+                match runtime.block_on(interrupt.run(publisher.publish(&metrics))) {
+                    Some(published) => published?,
+                    None            => {
+                        warning!("Interrupted while publishing to CloudWatch, sizes are partially published");
+
+                        break;
+                    },
+                }
+            }
         }
     }
 
-    // The region here will come from CLI args in the future
-    let client = Client::new(config);
+    // Requests are counted until every client is done, including
+    // publishing.
+    if let Some(counter) = &request_counter {
+        eprintln!("{}", format_request_count(counter));
+    }
+
+    // Being interrupted once the buckets have been sized, such as while
+    // publishing, still exits as interrupted.
+    if interrupt.is_interrupted() {
+        return Ok(EXIT_INTERRUPTED);
+    }
+
+    Ok(summary.exit_code())
+}
 
-    Runtime::new()?.block_on(client.du(unit))
+/// Entry point
+fn main() -> Result<()> {
+    // Parse the CLI
+    let matches = cli::parse_args();
+
+    // RUST_LOG is only used when neither --quiet nor --verbose were given,
+    // otherwise the flags set the log level.
+    let verbose = matches.occurrences_of("VERBOSE");
+    let quiet   = matches.is_present("QUIET");
+
+    let filter = match ::std::env::var("RUST_LOG") {
+        Ok(filters) if verbose == 0 && !quiet => EnvFilter::new(filters),
+        _ => EnvFilter::default()
+            .add_directive(cli::log_level(verbose, quiet).into()),
+    };
+
+    let log_format = value_t!(matches, "LOG_FORMAT", LogFormat)?;
+
+    logging::init(log_format, filter)?;
+
+    // Get the format to output sizes in, diffs replace the regular output.
+    let output = cli::output_mode(&matches)?;
+
+    // Get the path of the file to write the output to instead of stdout, if
+    // any.
+    let output_file = matches.value_of("OUTPUT_FILE").map(Path::new);
+
+    // The schema of the JSON records doesn't depend on any buckets, so it's
+    // output without creating a client.
+    if output == OutputMode::NdjsonSchema {
+        let mut out = output_writer(output_file)?;
+
+        writeln!(out, "{}", serde_json::to_string_pretty(&NdjsonPrinter::schema())?)?;
+        out.flush()?;
+
+        return Ok(());
+    }
+
+    let runtime = Runtime::new()?;
+
+    // A missing profile is reported here, rather than as a failure of every
+    // API call.
+    if let Some(profile) = matches.value_of("PROFILE") {
+        runtime.block_on(credentials::check_profile(profile))?;
+    }
+
+    let config  = cli::client_config(&matches, output)?;
+    let options = cli::run_options(&matches, output, &config)?;
+
+    // Validating a bucket's region replaces sizing buckets, the exit code
+    // tells scripts whether the configured region is correct.
+    #[cfg(feature = "s3")]
+    {
+        if let Some(bucket) = matches.value_of("VALIDATE_REGION") {
+            let configured = config.region.to_owned();
+            let client     = s3::Client::new(config);
+
+            let actual = runtime.block_on(client.get_bucket_location(bucket))?;
+
+            let (matched, message) = format_region_check(bucket, &actual, &configured);
+
+            println!("{}", message);
+
+            ::std::process::exit(if matched { 0 } else { 1 });
+        }
+    }
+
+    // Progress and logs are written to stderr, so only the output goes to
+    // the output file.
+    let mut out = output_writer(output_file)?;
+
+    // Pressing Ctrl-C while buckets are listed or sized stops listing or
+    // sizing them, and the buckets found so far are output as usual before
+    // exiting. The later phases stop in the same way.
+    let mut interrupt = Interrupt::ctrl_c();
+
+    match run(&options, config, Client::new, &runtime, &mut out, &mut interrupt)? {
+        0    => Ok(()),
+        code => ::std::process::exit(code),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::anyhow;
+    use chrono::TimeZone;
+    use async_trait::async_trait;
+    use common::{
+        Bucket,
+        Buckets,
+        ClassSizes,
+        SizeUnit,
+        StorageClass,
+    };
+    use pretty_assertions::assert_eq;
+    use std::str::FromStr;
+    use std::sync::Arc;
+    use std::sync::atomic::{
+        AtomicBool,
+        Ordering,
+    };
+
+    // A BucketSizer that fails to size any bucket named in `failing`, and is
+    // denied access to any bucket named in `denied`.
+    struct MockSizer {
+        buckets: Vec<&'static str>,
+        denied:  Vec<&'static str>,
+        failing: Vec<&'static str>,
+    }
+
+    #[async_trait]
+    impl BucketSizer for MockSizer {
+        async fn buckets(&self) -> Result<Buckets> {
+            let buckets = self.buckets
+                .iter()
+                .map(|name| Bucket {
+                    alias:         None,
+                    name:          name.to_string(),
+                    region:        None,
+                    storage_types: None,
+                })
+                .collect();
+
+            Ok(buckets)
+        }
+
+        async fn bucket_size(&self, bucket: &Bucket) -> Result<u64> {
+            if self.denied.contains(&bucket.name.as_str()) {
+                Err(S3duError::AccessDenied("Access Denied".into()).into())
+            }
+            else if self.failing.contains(&bucket.name.as_str()) {
+                Err(anyhow!("Access Denied"))
+            }
+            else {
+                Ok(1024)
+            }
+        }
+
+        // Only bucket-a has storage class sizes, as if bucket-b was sized by
+        // S3 in combined mode.
+        fn bucket_class_sizes(&self, bucket: &Bucket) -> Option<ClassSizes> {
+            if bucket.name != "bucket-a" {
+                return None;
+            }
+
+            let mut sizes = ClassSizes::new();
+            sizes.insert(StorageClass::Standard, 100 * 1_073_741_824);
+
+            Some(sizes)
+        }
+    }
+
+    // An Interrupt that never signals, so that every bucket is sized.
+    fn never() -> Interrupt {
+        Interrupt::new(futures::future::pending())
+    }
+
+    // Returns the buckets shown in `summary` rendered in the `output` format,
+    // as they're written once every bucket has been sized.
+    fn render(summary: &DuSummary, output: OutputMode) -> String {
+        let unit = SizeUnit::from_str("bytes").unwrap();
+
+        let lines = printer(output, &unit, None, Utc::now())
+            .unwrap()
+            .print(&summary.shown);
+
+        lines.iter()
+            .map(|line| format!("{}\n", line))
+            .collect()
+    }
+
+    fn mock_client(failing: Vec<&'static str>) -> Client {
+        let sizer = MockSizer {
+            buckets: vec!["bucket-a", "bucket-b"],
+            denied:  Vec::new(),
+            failing: failing,
+        };
+
+        Client(Box::new(sizer), BucketAliases::new())
+    }
+
+    // A BucketSizer that fails to list buckets and records being closed.
+    struct ClosingSizer {
+        closed: Arc<AtomicBool>,
+    }
+
+    #[async_trait]
+    impl BucketSizer for ClosingSizer {
+        async fn buckets(&self) -> Result<Buckets> {
+            Err(anyhow!("Access Denied"))
+        }
+
+        async fn bucket_size(&self, _bucket: &Bucket) -> Result<u64> {
+            Ok(0)
+        }
+
+        async fn close(&mut self) -> Result<()> {
+            self.closed.store(true, Ordering::SeqCst);
+
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_close_on_drop() {
+        let runtime = Runtime::new().unwrap();
+        let closed  = Arc::new(AtomicBool::new(false));
+
+        let sizer = ClosingSizer {
+            closed: Arc::clone(&closed),
+        };
+
+        {
+            let client = Client(Box::new(sizer), BucketAliases::new()).close_on_drop(&runtime);
+
+            let ret = runtime.block_on(
+                client.du(false, false, false, None, None, &mut never()),
+            );
+
+            assert!(ret.is_err());
+            assert!(!closed.load(Ordering::SeqCst));
+        }
+
+        assert!(closed.load(Ordering::SeqCst));
+    }
+
+    #[test]
+    fn test_du_summary_exit_code() {
+        let tests = vec![
+            (0, 0, 0),
+            (2, 0, 0),
+            (1, 1, EXIT_PARTIAL_FAILURE),
+            (0, 2, EXIT_FAILURE),
+        ];
+
+        for test in tests {
+            let sized = (0..test.0)
+                .map(|i| BucketSizeEntry {
+                    bucket: format!("sized-{}", i),
+                    region: None,
+                    size:   1024,
+                })
+                .collect();
+
+            let failed = (0..test.1)
+                .map(|i| (format!("failed-{}", i), "Access Denied".into()))
+                .collect();
+
+            let summary = DuSummary {
+                sized:  sized,
+                failed: failed,
+                ..Default::default()
+            };
+
+            let expected = test.2;
+
+            assert_eq!(summary.exit_code(), expected);
+
+            // Being interrupted takes precedence over failures.
+            let summary = DuSummary {
+                interrupted: true,
+                ..summary
+            };
+
+            assert_eq!(summary.exit_code(), EXIT_INTERRUPTED);
+        }
+    }
+
+    #[test]
+    fn test_du_summary_merge() {
+        let entry = |bucket: &str, size| BucketSizeEntry {
+            bucket: bucket.into(),
+            region: None,
+            size:   size,
+        };
+
+        let early = Utc.ymd(2021, 1, 1).and_hms(0, 0, 0);
+        let late  = Utc.ymd(2021, 1, 2).and_hms(0, 0, 0);
+
+        let total = |size, latest| Total {
+            size:   size,
+            latest: Some(latest),
+            ..Default::default()
+        };
+
+        let mut summary = DuSummary {
+            sized: vec![entry("bucket-a", 1024)],
+            total: total(1024, late),
+            ..Default::default()
+        };
+
+        summary.merge(DuSummary {
+            sized:  vec![entry("bucket-b", 2048), entry("bucket-c", 4)],
+            failed: vec![("bucket-d".into(), "throttled".into())],
+            hidden: vec!["bucket-c".into()],
+            total:  total(2048, early),
+            ..Default::default()
+        });
+
+        let expected = DuSummary {
+            sized:  vec![
+                entry("bucket-a", 1024),
+                entry("bucket-b", 2048),
+                entry("bucket-c", 4),
+            ],
+            failed: vec![("bucket-d".into(), "throttled".into())],
+            hidden: vec!["bucket-c".into()],
+            total:  total(3072, late),
+            ..Default::default()
+        };
+
+        assert_eq!(summary, expected);
+    }
+
+    #[cfg(feature = "cloudwatch")]
+    #[test]
+    fn test_cloudwatch_metrics() {
+        let entry = |bucket: &str, region: Option<&str>, size| BucketSizeEntry {
+            bucket: bucket.into(),
+            region: region.map(|region| region.into()),
+            size:   size,
+        };
+
+        let class_sizes: ClassSizes = vec![
+            (StorageClass::Standard, 1024),
+        ].into_iter().collect();
+
+        let summary = DuSummary {
+            sized:   vec![
+                entry("bucket-a", Some("eu-west-1"), 1024),
+                entry("bucket-b", None, 2048),
+                entry("bucket-c", Some("eu-west-1"), 4096),
+            ],
+            classes: vec![
+                ("bucket-a".to_string(), class_sizes.clone()),
+            ].into_iter().collect(),
+            ..Default::default()
+        };
+
+        let metric = |bucket: &str, size, class_sizes| BucketMetric {
+            bucket:      bucket.into(),
+            size:        size,
+            class_sizes: class_sizes,
+        };
+
+        let expected: BTreeMap<_, _> = vec![
+            (
+                None,
+                vec![metric("bucket-b", 2048, None)],
+            ),
+            (
+                Some("eu-west-1".to_string()),
+                vec![
+                    metric("bucket-a", 1024, Some(class_sizes)),
+                    metric("bucket-c", 4096, None),
+                ],
+            ),
+        ].into_iter().collect();
+
+        assert_eq!(cloudwatch_metrics(&summary), expected);
+    }
+
+    #[test]
+    fn test_du_summary_merge_cost() {
+        let tests = vec![
+            (None,      None,      None),
+            (Some(1.5), None,      Some(1.5)),
+            (None,      Some(2.0), Some(2.0)),
+            (Some(1.5), Some(2.0), Some(3.5)),
+        ];
+
+        for test in tests {
+            let (cost, other, expected) = test;
+
+            let total = |cost| Total {
+                cost: cost,
+                ..Default::default()
+            };
+
+            let mut summary = DuSummary {
+                total: total(cost),
+                ..Default::default()
+            };
+
+            summary.merge(DuSummary {
+                total: total(other),
+                ..Default::default()
+            });
+
+            assert_eq!(summary.total.cost, expected);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_du_cost_model() {
+        let client = mock_client(Vec::new());
+
+        let tests = vec![
+            (CostModel::AwsStandard,         2.3),
+            (CostModel::AwsInfrequentAccess, 1.25),
+        ];
+
+        for test in tests {
+            let (model, expected) = test;
+
+            let ret = client.du(false, false, false, None, Some(&model), &mut never())
+                .await
+                .unwrap();
+
+            // bucket-b has no storage class sizes, so adds nothing.
+            let cost = ret.total.cost.unwrap();
+
+            assert!((cost - expected).abs() < 1e-9);
+        }
+    }
+
+    // Options for sizing buckets in the text output, in bytes.
+    fn text_options() -> RunOptions {
+        RunOptions {
+            all_regions:          false,
+            check_ebs_snapshots:  false,
+            check_ecr:            false,
+            check_efs:            false,
+            check_fsx:            false,
+            #[cfg(feature = "s3")]
+            check_glacier_jobs:   false,
+            #[cfg(feature = "s3")]
+            check_glacier_vault:  false,
+            color:                false,
+            cost_model:           None,
+            diff_path:            None,
+            dry_run:              false,
+            fail_fast:            false,
+            include_errors:       false,
+            list_buckets_only:    false,
+            min_size:             None,
+            output:               OutputMode::Text,
+            #[cfg(feature = "cloudwatch")]
+            report_to_cloudwatch: false,
+            show_total:           true,
+            skip_inaccessible:    false,
+            snapshot_path:        None,
+            time:                 None,
+            unit:                 SizeUnit::from_str("bytes").unwrap(),
+        }
+    }
+
+    #[test]
+    fn test_run() {
+        let runtime = Runtime::new().unwrap();
+        let mut out = Vec::new();
+
+        let ret = run(
+            &text_options(),
+            ClientConfig::default(),
+            |_| mock_client(vec!["bucket-a"]),
+            &runtime,
+            &mut out,
+            &mut never(),
+        )
+        .unwrap();
+
+        // The failed bucket is reported on stderr, not in the output.
+        assert_eq!(String::from_utf8(out).unwrap(), "1024\tbucket-b\n1024\t.\n");
+        assert_eq!(ret, EXIT_PARTIAL_FAILURE);
+    }
+
+    #[test]
+    fn test_run_dry_run() {
+        let runtime = Runtime::new().unwrap();
+        let mut out = Vec::new();
+
+        let options = RunOptions {
+            dry_run: true,
+            ..text_options()
+        };
+
+        let ret = run(
+            &options,
+            ClientConfig::default(),
+            |_| mock_client(vec![]),
+            &runtime,
+            &mut out,
+            &mut never(),
+        )
+        .unwrap();
+
+        // Buckets are listed without being sized.
+        let out = String::from_utf8(out).unwrap();
+
+        assert!(out.starts_with("bucket-a\nbucket-b\nWould scan 2 buckets\n"));
+        assert_eq!(ret, 0);
+    }
+
+    #[tokio::test]
+    async fn test_du_partial_failure() {
+        let client = mock_client(vec!["bucket-a"]);
+
+        let ret = client.du(false, false, false, None, None, &mut never()).await.unwrap();
+
+        // The bucket that was sized is still shown.
+        assert_eq!(render(&ret, OutputMode::Text), "1024\tbucket-b\n");
+
+        let expected = DuSummary {
+            sized:       vec![
+                BucketSizeEntry {
+                    bucket: "bucket-b".into(),
+                    region: None,
+                    size:   1024,
+                },
+            ],
+            failed:      vec![("bucket-a".into(), "Access Denied".into())],
+            skipped:     Vec::new(),
+            shown:       ret.shown.to_owned(),
+            hidden:      Vec::new(),
+            total:       Total {
+                size:   1024,
+                latest: ret.total.latest,
+                ..Default::default()
+            },
+            #[cfg(feature = "cloudwatch")]
+            classes:     HashMap::new(),
+            interrupted: false,
+        };
+
+        assert_eq!(ret, expected);
+        assert_eq!(ret.exit_code(), EXIT_PARTIAL_FAILURE);
+    }
+
+    // A BucketSizer that never finishes sizing any bucket after the first,
+    // as if the user gave up waiting for it.
+    struct StalledSizer;
+
+    #[async_trait]
+    impl BucketSizer for StalledSizer {
+        async fn buckets(&self) -> Result<Buckets> {
+            let buckets = vec!["bucket-a", "bucket-b", "bucket-c"]
+                .into_iter()
+                .map(|name| Bucket {
+                    alias:         None,
+                    name:          name.to_string(),
+                    region:        None,
+                    storage_types: None,
+                })
+                .collect();
+
+            Ok(buckets)
+        }
+
+        async fn bucket_size(&self, bucket: &Bucket) -> Result<u64> {
+            if bucket.name != "bucket-a" {
+                futures::future::pending::<()>().await;
+            }
+
+            Ok(1024)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_du_interrupted() {
+        let client = Client(Box::new(StalledSizer), BucketAliases::new());
+
+        let mut interrupt = Interrupt::new(
+            tokio::time::sleep(std::time::Duration::from_millis(10)),
+        );
+
+        let ret = client.du(false, false, false, None, None, &mut interrupt)
+            .await
+            .unwrap();
+
+        // Only the bucket sized before the interrupt is shown and included
+        // in the total.
+        assert_eq!(render(&ret, OutputMode::Text), "1024\tbucket-a\n");
+
+        let sized = vec![
+            BucketSizeEntry {
+                bucket: "bucket-a".into(),
+                region: None,
+                size:   1024,
+            },
+        ];
+
+        assert_eq!(ret.sized, sized);
+        assert_eq!(ret.total.size, 1024);
+        assert!(ret.failed.is_empty());
+        assert!(ret.interrupted);
+        assert_eq!(ret.exit_code(), EXIT_INTERRUPTED);
+
+        // Merging keeps the interruption, so that the other regions are
+        // reported as partial too.
+        let mut summary = DuSummary::default();
+        summary.merge(ret);
+
+        assert!(summary.interrupted);
+        assert_eq!(summary.total.size, 1024);
+    }
+
+    // A BucketSizer that panics if any bucket is sized, for listing buckets
+    // without sizing them.
+    struct PanickingSizer;
+
+    #[async_trait]
+    impl BucketSizer for PanickingSizer {
+        async fn buckets(&self) -> Result<Buckets> {
+            let buckets = vec!["bucket-a", "bucket-b"]
+                .into_iter()
+                .map(|name| Bucket {
+                    alias:         None,
+                    name:          name.into(),
+                    region:        None,
+                    storage_types: None,
+                })
+                .collect();
+
+            Ok(buckets)
+        }
+
+        // async_trait wraps the body in a block that the panic diverges from.
+        #[allow(clippy::diverging_sub_expression)]
+        async fn bucket_size(&self, bucket: &Bucket) -> Result<u64> {
+            panic!("bucket_size called for '{}' while listing buckets", bucket.name);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_list_buckets() {
+        let client = Client(Box::new(PanickingSizer), BucketAliases::new());
+
+        let ret: Vec<String> = client.list_buckets(&mut never())
+            .await
+            .unwrap()
+            .unwrap()
+            .into_iter()
+            .map(|bucket| bucket.name)
+            .collect();
+
+        assert_eq!(ret, vec!["bucket-a", "bucket-b"]);
+    }
+
+    // A BucketSizer that never finishes listing its buckets.
+    struct StalledLister;
+
+    #[async_trait]
+    impl BucketSizer for StalledLister {
+        async fn buckets(&self) -> Result<Buckets> {
+            futures::future::pending().await
+        }
+
+        async fn bucket_size(&self, _bucket: &Bucket) -> Result<u64> {
+            Ok(1024)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_list_buckets_interrupted() {
+        let client = Client(Box::new(StalledLister), BucketAliases::new());
+
+        let mut interrupt = Interrupt::new(
+            tokio::time::sleep(std::time::Duration::from_millis(10)),
+        );
+
+        // Listing is abandoned, rather than Ctrl-C being ignored until the
+        // buckets are listed.
+        let ret = client.list_buckets(&mut interrupt).await.unwrap();
+
+        assert_eq!(ret, None);
+        assert!(interrupt.is_interrupted());
+
+        // Later phases aren't started once we've been interrupted.
+        let client = Client(Box::new(PanickingSizer), BucketAliases::new());
+        let ret    = client.list_buckets(&mut interrupt).await.unwrap();
+
+        assert_eq!(ret, None);
+    }
+
+    #[tokio::test]
+    async fn test_du_skip_inaccessible() {
+        let sizer = MockSizer {
+            buckets: vec!["bucket-a", "bucket-b", "bucket-c"],
+            denied:  vec!["bucket-a"],
+            failing: vec!["bucket-b"],
+        };
+
+        let client = Client(Box::new(sizer), BucketAliases::new());
+
+        // Without skipping, denied buckets are failures like any other.
+        let ret = client.du(false, false, false, None, None, &mut never())
+            .await
+            .unwrap();
+
+        let failed: Vec<&str> = ret.failed
+            .iter()
+            .map(|(bucket, _)| bucket.as_str())
+            .collect();
+
+        assert_eq!(failed, vec!["bucket-a", "bucket-b"]);
+        assert_eq!(ret.skipped, Vec::<String>::new());
+
+        let ret = client.du(false, false, true, None, None, &mut never())
+            .await
+            .unwrap();
+
+        assert_eq!(ret.failed, vec![("bucket-b".to_string(), "Access Denied".into())]);
+        assert_eq!(ret.skipped, vec!["bucket-a".to_string()]);
+        assert_eq!(ret.total.size, 1024);
+
+        // Skipped buckets don't trigger fail fast, but other failures do.
+        let ret = client.du(false, true, true, None, None, &mut never()).await;
+
+        assert!(ret.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_du_fail_fast() {
+        let client = mock_client(vec!["bucket-a"]);
+
+        let ret = client.du(false, true, false, None, None, &mut never()).await;
+
+        assert!(ret.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_du_include_errors() {
+        let client = mock_client(vec!["bucket-a"]);
+
+        let ret = client.du(true, false, false, None, None, &mut never())
+            .await
+            .unwrap();
+
+        // Annotated buckets are still failures, shown in order among the
+        // sized buckets.
+        let shown: Vec<(&str, bool)> = ret.shown
+            .iter()
+            .map(|shown| (shown.bucket.name.as_str(), shown.is_sized()))
+            .collect();
+
+        assert_eq!(shown, vec![("bucket-a", false), ("bucket-b", true)]);
+        assert_eq!(ret.failed, vec![("bucket-a".to_string(), "Access Denied".into())]);
+        assert_eq!(ret.exit_code(), EXIT_PARTIAL_FAILURE);
+    }
+
+    #[tokio::test]
+    async fn test_du_bucket_aliases() {
+        let aliases = alias::load_aliases(Path::new("test-data/bucket-aliases.toml"))
+            .unwrap();
+
+        let sizer = MockSizer {
+            buckets: vec!["logs-prod-a1b2c3d4", "bucket-b"],
+            denied:  Vec::new(),
+            failing: vec!["logs-prod-a1b2c3d4"],
+        };
+
+        let client = Client(Box::new(sizer), aliases);
+
+        let ret = client.du(true, false, false, None, None, &mut never())
+            .await
+            .unwrap();
+
+        // The sizer was given the bucket name, and it's recorded in the
+        // summary, while the output uses the alias.
+        let expected = concat!(
+            "0\tprod-application-logs\tNO_CLOUDWATCH_DATA (Access Denied)\n",
+            "1024\tbucket-b\n",
+        );
+
+        assert_eq!(render(&ret, OutputMode::Text), expected);
+        assert_eq!(ret.failed, vec![("logs-prod-a1b2c3d4".to_string(), "Access Denied".into())]);
+
+        let buckets = client.list_buckets(&mut never()).await.unwrap().unwrap();
+
+        assert_eq!(buckets[0].name, "logs-prod-a1b2c3d4");
+        assert_eq!(buckets[0].alias, Some("prod-application-logs".into()));
+        assert_eq!(buckets[1].alias, None);
+    }
+
+    #[test]
+    fn test_error_reason() {
+        let tests = vec![
+            (S3duError::AccessDenied("403".into()), "access denied"),
+            (S3duError::Throttled("503".into()),    "throttled"),
+            (S3duError::Other("oops".into()),       "oops"),
+        ];
+
+        for test in tests {
+            let err      = anyhow::Error::from(test.0);
+            let expected = test.1;
+
+            let ret = error_reason(&err);
+
+            assert_eq!(ret, expected);
+        }
+    }
+
+    #[cfg(feature = "cloudwatch")]
+    #[test]
+    fn test_error_reason_no_datapoints() {
+        let err = anyhow::Error::from(S3duError::NoDatapoints("bucket".into()));
+
+        let ret = error_reason(&err);
+
+        assert_eq!(ret, "no datapoints");
+    }
+
+    #[tokio::test]
+    async fn test_du_prometheus() {
+        let client = mock_client(vec!["bucket-b"]);
+
+        let ret = client.du(true, false, false, None, None, &mut never())
+            .await
+            .unwrap();
+
+        // Only the sized bucket is output, even though the failed bucket is
+        // shown, with a sample for each of its storage classes.
+        let expected = concat!(
+            "# HELP s3du_bucket_size_bytes Size of the S3 bucket in bytes.\n",
+            "# TYPE s3du_bucket_size_bytes gauge\n",
+            "s3du_bucket_size_bytes{bucket=\"bucket-a\",storage_class=\"STANDARD\"} 107374182400\n",
+        );
+
+        assert_eq!(render(&ret, OutputMode::Prometheus), expected);
+
+        let expected = DuSummary {
+            sized:       vec![
+                BucketSizeEntry {
+                    bucket: "bucket-a".into(),
+                    region: None,
+                    size:   1024,
+                },
+            ],
+            failed:      vec![("bucket-b".into(), "Access Denied".into())],
+            skipped:     Vec::new(),
+            shown:       ret.shown.to_owned(),
+            hidden:      Vec::new(),
+            total:       Total {
+                size:   1024,
+                latest: ret.total.latest,
+                ..Default::default()
+            },
+            #[cfg(feature = "cloudwatch")]
+            classes:     vec![
+                (
+                    "bucket-a".to_string(),
+                    vec![(StorageClass::Standard, 100 * 1_073_741_824)].into_iter().collect(),
+                ),
+            ].into_iter().collect(),
+            interrupted: false,
+        };
+
+        assert_eq!(ret, expected);
+    }
+
+    #[tokio::test]
+    async fn test_du_min_size() {
+
+        // Both mock buckets are 1KiB.
+        let tests = vec![
+            ("1KiB", false, vec![],                       2048),
+            ("1MiB", false, vec!["bucket-a", "bucket-b"], 2048),
+            ("1MiB", true,  vec!["bucket-a", "bucket-b"], 0),
+        ];
+
+        for test in tests {
+            let min_size = MinSize {
+                size:          cli::parse_size(test.0).unwrap(),
+                affects_total: test.1,
+            };
+
+            let hidden = test.2;
+            let total  = test.3;
+
+            let client = mock_client(vec![]);
+
+            let ret = client.du(false, false, false, Some(min_size), None, &mut never())
+                .await
+                .unwrap();
+
+            // Hidden buckets are still sized.
+            assert_eq!(ret.sized.len(), 2);
+            assert_eq!(ret.hidden, hidden);
+            assert_eq!(ret.total.size, total);
+        }
+    }
+
+    // A BucketSizer that records the buckets whose duplicate reports were
+    // taken from it, as the S3 client removes them from its cache.
+    #[cfg(feature = "s3")]
+    struct ReportingSizer {
+        taken: Arc<std::sync::Mutex<Vec<String>>>,
+    }
+
+    #[cfg(feature = "s3")]
+    #[async_trait]
+    impl BucketSizer for ReportingSizer {
+        async fn buckets(&self) -> Result<Buckets> {
+            MockSizer {
+                buckets: vec!["bucket-a", "bucket-b"],
+                denied:  Vec::new(),
+                failing: Vec::new(),
+            }
+            .buckets()
+            .await
+        }
+
+        async fn bucket_size(&self, _bucket: &Bucket) -> Result<u64> {
+            Ok(1024)
+        }
+
+        fn bucket_duplicates(&self, bucket: &Bucket) -> Option<Vec<String>> {
+            self.taken.lock().unwrap().push(bucket.name.to_owned());
+
+            None
+        }
+    }
+
+    #[cfg(feature = "s3")]
+    #[tokio::test]
+    async fn test_du_min_size_takes_reports() {
+        let taken = Arc::new(std::sync::Mutex::new(Vec::new()));
+
+        let sizer = ReportingSizer {
+            taken: Arc::clone(&taken),
+        };
+
+        let client = Client(Box::new(sizer), BucketAliases::new());
+
+        // Both mock buckets are 1KiB, so both are hidden.
+        let min_size = MinSize {
+            size:          1_048_576,
+            affects_total: false,
+        };
+
+        let ret = client.du(false, false, false, Some(min_size), None, &mut never())
+            .await
+            .unwrap();
+
+        assert!(ret.shown.is_empty());
+        assert_eq!(*taken.lock().unwrap(), vec!["bucket-a", "bucket-b"]);
+    }
+
+    #[tokio::test]
+    async fn test_du_group_by_region() {
+        let client = mock_client(vec!["bucket-a"]);
+
+        let min_size = MinSize {
+            size:          1,
+            affects_total: false,
+        };
+
+        let ret = client.du(false, false, false, Some(min_size), None, &mut never())
+            .await
+            .unwrap();
+
+        // Only the buckets that were sized and not hidden are shown.
+        let shown: Vec<(&str, u64)> = ret.shown
+            .iter()
+            .map(|shown| (shown.bucket.name.as_str(), shown.size))
+            .collect();
+
+        assert_eq!(shown, vec![("bucket-b", 1024)]);
+        assert_eq!(render(&ret, OutputMode::GroupByRegion), "unknown:\n1024\tbucket-b\n1024\t.\n");
+        assert_eq!(ret.total.size, 1024);
+    }
+
+    #[cfg(feature = "s3")]
+    #[tokio::test]
+    async fn test_du_total_mixed_storage_classes() {
+        use crate::test_helpers::{
+            dispatcher_with_body,
+            s3_client,
+        };
+        use rusoto_mock::{
+            MockCredentialsProvider,
+            MockRequestDispatcher,
+            MultipleMockRequestDispatcher,
+        };
+        use rusoto_core::Region;
+        use rusoto_s3::S3Client;
+        use std::sync::Mutex;
+
+        // Each bucket is listed once, holding objects in a mix of storage
+        // classes.
+        let dispatchers: Vec<MockRequestDispatcher> = vec![
+            "s3-list-objects-storage-classes.xml",
+            "s3-list-objects-standard-glacier.xml",
+        ]
+        .into_iter()
+        .map(dispatcher_with_body)
+        .collect();
+
+        let buckets = vec!["bucket-a", "bucket-b"]
+            .into_iter()
+            .map(|name| Bucket {
+                alias:         None,
+                name:          name.into(),
+                region:        Some(Region::UsEast1),
+                storage_types: None,
+            })
+            .collect();
+
+        let client = s3::Client {
+            bucket_cache: Mutex::new(Some(buckets)),
+            ..s3_client(S3Client::new_with(
+                MultipleMockRequestDispatcher::new(dispatchers),
+                MockCredentialsProvider,
+                Default::default(),
+            ))
+        };
+
+        let client = Client(Box::new(client), BucketAliases::new());
+
+        let ret = client.du(false, false, false, None, None, &mut never())
+            .await
+            .unwrap();
+
+        let sizes: Vec<u64> = ret.sized.iter()
+            .map(|entry| entry.size)
+            .collect();
+
+        let sum: u64 = sizes.iter().sum();
+
+        assert_eq!(sizes, vec![15360, 1069056]);
+        assert_eq!(ret.total.size, sum);
+    }
+
+    #[cfg(feature = "s3")]
+    #[tokio::test]
+    async fn test_du_object_age_histogram() {
+        use common::ObjectVersions;
+        use test_helpers::mock_client;
+
+        // Unsigned requests size the named bucket without discovering it.
+        let sizer = s3::Client {
+            bucket_name:          Some("test-bucket".into()),
+            no_sign_request:      true,
+            object_age_histogram: true,
+            ..mock_client(Some("s3-list-objects-dates.xml"), ObjectVersions::Current)
+        };
+
+        let client = Client(Box::new(sizer), BucketAliases::new());
+
+        let ret = client.du(false, false, false, None, None, &mut never())
+            .await
+            .unwrap();
+
+        // The report is written beneath the bucket, along with the sizes.
+        let expected = concat!(
+            "31744\ttest-bucket\n",
+            "\ttest-bucket has 5 objects of 31744 bytes by age\n",
+            "\t  0-7 days: 0 objects, 0 bytes\n",
+            "\t  7-30 days: 0 objects, 0 bytes\n",
+            "\t  30-90 days: 0 objects, 0 bytes\n",
+            "\t  90-365 days: 0 objects, 0 bytes\n",
+            "\t  over 1 year: 4 objects, 15360 bytes\n",
+            "\t  unknown age: 1 object, 16384 bytes\n",
+        );
+
+        assert_eq!(render(&ret, OutputMode::Text), expected);
+    }
 }
@@ -0,0 +1,46 @@
+// Output formats
+#![forbid(unsafe_code)]
+#![deny(missing_docs)]
+
+/// `ColorChoice` enum is used to select when the output is colored.
+mod color;
+
+/// Messages reported alongside the output, such as failures and estimates.
+mod messages;
+
+/// `OutputMode` enum is used to select how the bucket sizes will be output.
+mod output_mode;
+
+/// `Printer` trait and its implementations render sized buckets.
+mod printer;
+
+/// Prometheus text exposition format.
+mod prometheus;
+
+/// Grouping of buckets by region, with a subtotal for each region.
+mod region_groups;
+
+/// `ShownBucket` struct holds a bucket shown in the output.
+mod shown_bucket;
+
+/// Plain text output, similar to `du`.
+mod text;
+
+/// Totals across all of the sized buckets.
+mod totals;
+
+/// Tree view of the prefixes in a bucket.
+#[cfg(feature = "s3")]
+mod tree;
+
+/// Destination that the output is written to.
+mod writer;
+
+pub use color::*;
+pub use messages::*;
+pub use output_mode::*;
+pub use printer::*;
+pub use shown_bucket::*;
+pub use text::*;
+pub use totals::*;
+pub use writer::*;
@@ -0,0 +1,145 @@
+// ColorChoice
+#![forbid(unsafe_code)]
+#![deny(missing_docs)]
+use std::str::FromStr;
+
+/// ANSI SGR code for bold text.
+pub const BOLD: &str = "1";
+
+/// ANSI SGR code for green text.
+pub const GREEN: &str = "32";
+
+/// ANSI SGR code for red text.
+pub const RED: &str = "31";
+
+/// `ColorChoice` represents when the output should be colored.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ColorChoice {
+    /// Always color the output, even when it isn't a terminal or `NO_COLOR`
+    /// is set.
+    Always,
+
+    /// Color the output only when stdout is a terminal and `NO_COLOR` isn't
+    /// set.
+    Auto,
+
+    /// Never color the output.
+    Never,
+}
+
+impl ColorChoice {
+    /// Returns `true` if the output should be colored, given whether the
+    /// `NO_COLOR` environment variable is set and whether stdout is a
+    /// terminal.
+    ///
+    /// As recommended by <https://no-color.org>, an explicit `Always` takes
+    /// precedence over `NO_COLOR`.
+    pub fn use_color(self, no_color: bool, is_tty: bool) -> bool {
+        match self {
+            Self::Always => true,
+            Self::Auto   => is_tty && !no_color,
+            Self::Never  => false,
+        }
+    }
+
+    /// Returns `true` if the output should be colored in the current
+    /// environment.
+    ///
+    /// Output written to a file rather than stdout is never a terminal, so
+    /// `to_file` disables `Auto` colors.
+    pub fn enabled(self, to_file: bool) -> bool {
+        // NO_COLOR only counts when it's set to a non-empty value.
+        let no_color = matches!(
+            std::env::var_os("NO_COLOR"),
+            Some(value) if !value.is_empty()
+        );
+
+        let is_tty = !to_file && atty::is(atty::Stream::Stdout);
+
+        self.use_color(no_color, is_tty)
+    }
+}
+
+/// This converts from the string argument we receive from the command line to
+/// our enum type.
+impl FromStr for ColorChoice {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "always" => Ok(Self::Always),
+            "auto"   => Ok(Self::Auto),
+            "never"  => Ok(Self::Never),
+            _        => Err("no match"),
+        }
+    }
+}
+
+/// Returns `text` wrapped in the ANSI escape sequence for the SGR `code`, if
+/// `color` is `true`, otherwise returns `text` unchanged.
+pub fn paint(text: &str, code: &str, color: bool) -> String {
+    if color {
+        format!("\x1b[{}m{}\x1b[0m", code, text)
+    }
+    else {
+        text.into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_color_choice_from_str() {
+        let tests = vec![
+            ("always", Some(ColorChoice::Always)),
+            ("auto",   Some(ColorChoice::Auto)),
+            ("never",  Some(ColorChoice::Never)),
+            ("yes",    None),
+        ];
+
+        for test in tests {
+            let choice   = test.0;
+            let expected = test.1;
+
+            let ret = ColorChoice::from_str(choice).ok();
+
+            assert_eq!(ret, expected);
+        }
+    }
+
+    #[test]
+    fn test_use_color() {
+        // (choice, no_color, is_tty, expected)
+        let tests = vec![
+            (ColorChoice::Always, false, false, true),
+            (ColorChoice::Always, false, true,  true),
+            (ColorChoice::Always, true,  false, true),
+            (ColorChoice::Always, true,  true,  true),
+            (ColorChoice::Auto,   false, false, false),
+            (ColorChoice::Auto,   false, true,  true),
+            (ColorChoice::Auto,   true,  false, false),
+            (ColorChoice::Auto,   true,  true,  false),
+            (ColorChoice::Never,  false, false, false),
+            (ColorChoice::Never,  false, true,  false),
+            (ColorChoice::Never,  true,  false, false),
+            (ColorChoice::Never,  true,  true,  false),
+        ];
+
+        for test in tests {
+            let (choice, no_color, is_tty, expected) = test;
+
+            let ret = choice.use_color(no_color, is_tty);
+
+            assert_eq!(ret, expected, "{:?} {} {}", choice, no_color, is_tty);
+        }
+    }
+
+    #[test]
+    fn test_paint() {
+        assert_eq!(paint("10GiB", BOLD, true), "\x1b[1m10GiB\x1b[0m");
+        assert_eq!(paint("10GiB", BOLD, false), "10GiB");
+    }
+}
@@ -0,0 +1,254 @@
+// Messages reported alongside the output
+#![forbid(unsafe_code)]
+#![deny(missing_docs)]
+use crate::common::{
+    Bucket,
+    RequestCounter,
+};
+
+#[cfg(feature = "s3")]
+use rusoto_core::Region;
+
+/// Returns a line reporting each bucket in `failed` and the reason that it
+/// failed to be sized.
+pub fn format_failed(failed: &[(String, String)]) -> Vec<String> {
+    failed.iter()
+        .map(|(bucket, reason)| format!("Failed to size '{}': {}", bucket, reason))
+        .collect()
+}
+
+/// Returns the message reporting the number of buckets found by a dry run.
+pub fn format_would_scan(count: usize) -> String {
+    match count {
+        1 => "Would scan 1 bucket".into(),
+        n => format!("Would scan {} buckets", n),
+    }
+}
+
+/// Returns the messages estimating the API requests that sizing `buckets`
+/// would make, for a dry run.
+///
+/// Buckets with CloudWatch storage types are sized with a
+/// `GetMetricStatistics` request for each storage type, and another for each
+/// storage type if `class_transitions` are reported. Other buckets are listed
+/// in S3 with a request for every 1,000 objects, but the number of objects
+/// isn't known until they're listed, so only the minimum is estimated.
+pub fn format_request_estimate(buckets: &[Bucket], class_transitions: bool) -> Vec<String> {
+    let per_storage_type = if class_transitions { 2 } else { 1 };
+
+    let metric_requests: usize = buckets.iter()
+        .filter_map(|bucket| bucket.storage_types.as_ref())
+        .map(|storage_types| storage_types.len() * per_storage_type)
+        .sum();
+
+    let listed = buckets.iter()
+        .filter(|bucket| bucket.storage_types.is_none())
+        .count();
+
+    let mut lines = Vec::new();
+
+    if listed < buckets.len() {
+        let line = match metric_requests {
+            1 => "Would make 1 GetMetricStatistics request".into(),
+            n => format!("Would make {} GetMetricStatistics requests", n),
+        };
+
+        lines.push(line);
+    }
+
+    if listed > 0 {
+        let line = match listed {
+            1 => "Would make at least 1 LIST request".into(),
+            n => format!("Would make at least {} LIST requests", n),
+        };
+
+        lines.push(format!(
+            "{}, one for every 1000 objects, the number of objects can't be known until listing",
+            line,
+        ));
+    }
+
+    lines
+}
+
+/// Returns the message reporting the number of API requests counted by the
+/// `counter`, in total and for each AWS service.
+pub fn format_request_count(counter: &RequestCounter) -> String {
+    let services: Vec<String> = counter.counts()
+        .iter()
+        .map(|(service, count)| format!("{}: {}", service, count))
+        .collect();
+
+    match counter.total() {
+        0     => "API requests: 0".into(),
+        total => format!("API requests: {} ({})", total, services.join(", ")),
+    }
+}
+
+/// Returns whether the `actual` region of `bucket` matches the `configured`
+/// region, along with a message giving the actual region for diagnosis.
+#[cfg(feature = "s3")]
+pub fn format_region_check(
+    bucket: &str,
+    actual: &Region,
+    configured: &Region,
+) -> (bool, String) {
+    if actual.name() == configured.name() {
+        let message = format!(
+            "'{}' is in {}, matching the configured region",
+            bucket,
+            actual.name(),
+        );
+
+        (true, message)
+    }
+    else {
+        let message = format!(
+            "'{}' is in {}, but the configured region is {}",
+            bucket,
+            actual.name(),
+            configured.name(),
+        );
+
+        (false, message)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_format_failed() {
+        let failed = vec![
+            ("bucket-a".to_string(), "access denied".to_string()),
+            ("bucket-b".to_string(), "throttled".to_string()),
+        ];
+
+        let expected = vec![
+            "Failed to size 'bucket-a': access denied",
+            "Failed to size 'bucket-b': throttled",
+        ];
+
+        assert_eq!(format_failed(&failed), expected);
+        assert!(format_failed(&[]).is_empty());
+    }
+
+    #[test]
+    fn test_format_would_scan() {
+        let tests = vec![
+            (0, "Would scan 0 buckets"),
+            (1, "Would scan 1 bucket"),
+            (5, "Would scan 5 buckets"),
+        ];
+
+        for test in tests {
+            let (count, expected) = test;
+
+            assert_eq!(format_would_scan(count), expected);
+        }
+    }
+
+    #[test]
+    fn test_format_request_estimate() {
+        let metrics_bucket = Bucket {
+            alias:         None,
+            name:          "bucket-a".into(),
+            region:        None,
+            storage_types: Some(vec![
+                "StandardStorage".into(),
+                "GlacierStorage".into(),
+            ]),
+        };
+
+        let listed_bucket = Bucket {
+            alias:         None,
+            name:          "bucket-b".into(),
+            region:        None,
+            storage_types: None,
+        };
+
+        let listed = "one for every 1000 objects, the number of objects can't be known until listing";
+
+        let tests = vec![
+            (
+                vec![],
+                false,
+                vec![],
+            ),
+            (
+                vec![metrics_bucket.clone()],
+                false,
+                vec!["Would make 2 GetMetricStatistics requests".to_string()],
+            ),
+            (
+                vec![metrics_bucket.clone()],
+                true,
+                vec!["Would make 4 GetMetricStatistics requests".to_string()],
+            ),
+            (
+                vec![listed_bucket.clone()],
+                false,
+                vec![format!("Would make at least 1 LIST request, {}", listed)],
+            ),
+            (
+                vec![metrics_bucket, listed_bucket.clone(), listed_bucket],
+                false,
+                vec![
+                    "Would make 2 GetMetricStatistics requests".to_string(),
+                    format!("Would make at least 2 LIST requests, {}", listed),
+                ],
+            ),
+        ];
+
+        for test in tests {
+            let (buckets, class_transitions, expected) = test;
+
+            assert_eq!(format_request_estimate(&buckets, class_transitions), expected);
+        }
+    }
+
+    #[test]
+    fn test_format_request_count() {
+        let counter = RequestCounter::default();
+
+        assert_eq!(format_request_count(&counter), "API requests: 0");
+
+        counter.record("s3");
+        counter.record("s3");
+        counter.record("monitoring");
+
+        assert_eq!(
+            format_request_count(&counter),
+            "API requests: 3 (monitoring: 1, s3: 2)",
+        );
+    }
+
+    #[cfg(feature = "s3")]
+    #[test]
+    fn test_format_region_check() {
+        let tests = vec![
+            (
+                Region::EuWest1,
+                Region::EuWest1,
+                true,
+                "'my-bucket' is in eu-west-1, matching the configured region",
+            ),
+            (
+                Region::EuWest1,
+                Region::UsEast1,
+                false,
+                "'my-bucket' is in eu-west-1, but the configured region is us-east-1",
+            ),
+        ];
+
+        for test in tests {
+            let (actual, configured, matched, message) = test;
+
+            let ret = format_region_check("my-bucket", &actual, &configured);
+
+            assert_eq!(ret, (matched, message.to_string()));
+        }
+    }
+}
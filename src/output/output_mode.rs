@@ -0,0 +1,122 @@
+// OutputMode
+#![forbid(unsafe_code)]
+#![deny(missing_docs)]
+use std::str::FromStr;
+
+/// `OutputMode` represents the format that bucket sizes are output in.
+///
+/// Every mode outputs the buckets once sizing is complete, nothing is output
+/// while buckets are being sized.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum OutputMode {
+    /// Output as CSV.
+    Csv,
+
+    /// Output the changes since a snapshot.
+    ///
+    /// This is selected with `--diff` rather than `--output`.
+    Diff,
+
+    /// Output as Flux annotated CSV.
+    Flux,
+
+    /// Output as `Text`, grouped by region.
+    ///
+    /// This is selected with `--group-by-region` rather than `--output`.
+    GroupByRegion,
+
+    /// Output as a single JSON array.
+    Json,
+
+    /// Output as newline delimited JSON, one bucket per line.
+    Ndjson,
+
+    /// Output the JSON Schema of each line of the `Ndjson` output, without
+    /// sizing any buckets.
+    NdjsonSchema,
+
+    /// Output in the Prometheus text exposition format.
+    Prometheus,
+
+    /// Output tab separated sizes and bucket names, similar to `du`.
+    Text,
+
+    /// Output as `Text`, with the prefixes of each bucket drawn as a tree
+    /// beneath it.
+    ///
+    /// This is selected with `--tree` rather than `--output`.
+    #[cfg(feature = "s3")]
+    Tree,
+}
+
+impl OutputMode {
+    /// Returns `true` if this is a tab separated output of sizes and names,
+    /// `Text` or `Tree`, which other lines can be added to.
+    pub fn is_text(&self) -> bool {
+        match self {
+            Self::Text => true,
+            #[cfg(feature = "s3")]
+            Self::Tree => true,
+            _          => false,
+        }
+    }
+}
+
+/// This converts from the string argument we receive from the command line to
+/// our enum type.
+impl FromStr for OutputMode {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "csv"           => Ok(Self::Csv),
+            "flux"          => Ok(Self::Flux),
+            "json"          => Ok(Self::Json),
+            "ndjson"        => Ok(Self::Ndjson),
+            "ndjson-schema" => Ok(Self::NdjsonSchema),
+            "prometheus"    => Ok(Self::Prometheus),
+            "text"          => Ok(Self::Text),
+            _               => Err("no match"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_output_mode_from_str() {
+        let tests = vec![
+            ("csv",           Some(OutputMode::Csv)),
+            ("flux",          Some(OutputMode::Flux)),
+            ("json",          Some(OutputMode::Json)),
+            ("json-schema",   None),
+            ("ndjson",        Some(OutputMode::Ndjson)),
+            ("ndjson-schema", Some(OutputMode::NdjsonSchema)),
+            ("prometheus",    Some(OutputMode::Prometheus)),
+            ("text",          Some(OutputMode::Text)),
+            ("yaml",          None),
+        ];
+
+        for test in tests {
+            let mode     = test.0;
+            let expected = test.1;
+
+            let ret = OutputMode::from_str(mode).ok();
+
+            assert_eq!(ret, expected);
+        }
+    }
+
+    #[test]
+    fn test_output_mode_is_text() {
+        assert!(OutputMode::Text.is_text());
+        assert!(!OutputMode::Csv.is_text());
+        assert!(!OutputMode::Prometheus.is_text());
+
+        #[cfg(feature = "s3")]
+        assert!(OutputMode::Tree.is_text());
+    }
+}
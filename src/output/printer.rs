@@ -0,0 +1,603 @@
+// Printers for sized buckets
+#![forbid(unsafe_code)]
+#![deny(missing_docs)]
+use chrono::{
+    DateTime,
+    SecondsFormat,
+    Utc,
+};
+use crate::common::{
+    Bucket,
+    SizeUnit,
+    TimestampFormat,
+};
+use schemars::{
+    JsonSchema,
+    schema_for,
+};
+use schemars::gen::SchemaGenerator;
+use schemars::schema::Schema;
+use serde::Serialize;
+use serde_json::Value;
+use super::output_mode::OutputMode;
+use super::prometheus::PrometheusPrinter;
+use super::region_groups::RegionGroupPrinter;
+use super::shown_bucket::ShownBucket;
+use super::text::PlainPrinter;
+
+/// Header line of the CSV output.
+const CSV_HEADER: &str = "bucket,region,size";
+
+/// Annotation and header lines of the Flux annotated CSV output.
+///
+/// The `bucket` and `region` tags are part of the group key, along with the
+/// measurement and field, so each bucket is in a table of its own.
+const FLUX_HEADER: &[&str] = &[
+    "#datatype,string,long,dateTime:RFC3339,string,string,long,string,string",
+    "#group,false,false,false,true,true,false,true,true",
+    "#default,_result,,,,,,,",
+    ",result,table,_time,_measurement,_field,_value,bucket,region",
+];
+
+/// Measurement that bucket sizes are recorded in by the Flux output.
+const FLUX_MEASUREMENT: &str = "s3_bucket";
+
+/// Field that bucket sizes are recorded in by the Flux output.
+const FLUX_FIELD: &str = "size_bytes";
+
+/// Characters that require a CSV field to be quoted.
+const CSV_SPECIAL_CHARS: &[char] = &[',', '"', '\r', '\n'];
+
+/// `Printer` renders sized buckets in an output format.
+///
+/// Printers are given every bucket at once, once sizing is complete, so that
+/// formats that wrap the buckets, such as JSON, can be rendered.
+pub trait Printer {
+    /// Returns the shown `buckets` rendered as lines of output.
+    ///
+    /// Buckets that failed to be sized are only shown by formats that can
+    /// annotate them, other formats leave them out.
+    fn print(&self, buckets: &[ShownBucket]) -> Vec<String>;
+
+    /// Returns the `buckets` found by a dry run rendered as lines of output,
+    /// without sizes as they weren't sized.
+    ///
+    /// By default, bucket names are output alone, one on each line.
+    fn print_dry_run(&self, buckets: &[Bucket]) -> Vec<String> {
+        buckets.iter()
+            .map(|bucket| bucket.display_name().to_owned())
+            .collect()
+    }
+}
+
+/// Returns the `Printer` for the `output` mode, displaying sizes in the given
+/// `unit` and times in the `time` format, where the mode displays them.
+///
+/// Flux output records every bucket as sized at `sized_at`.
+///
+/// Diff output has no printer, as it outputs the changes since a snapshot
+/// rather than the buckets, nor does the JSON Schema, which is output
+/// without sizing any buckets.
+pub fn printer<'a>(
+    output: OutputMode,
+    unit: &'a SizeUnit,
+    time: Option<TimestampFormat>,
+    sized_at: DateTime<Utc>,
+) -> Option<Box<dyn Printer + 'a>> {
+    let printer: Box<dyn Printer + 'a> = match output {
+        OutputMode::Csv           => Box::new(CsvPrinter),
+        OutputMode::Flux          => Box::new(FluxPrinter { time: sized_at }),
+        OutputMode::GroupByRegion => Box::new(RegionGroupPrinter { unit: unit }),
+        OutputMode::Json          => Box::new(JsonPrinter),
+        OutputMode::Ndjson        => Box::new(NdjsonPrinter),
+        OutputMode::Prometheus    => Box::new(PrometheusPrinter),
+        OutputMode::Text          => Box::new(PlainPrinter {
+            unit: unit,
+            time: time,
+            #[cfg(feature = "s3")]
+            tree: false,
+        }),
+        #[cfg(feature = "s3")]
+        OutputMode::Tree          => Box::new(PlainPrinter {
+            unit: unit,
+            time: time,
+            tree: true,
+        }),
+        OutputMode::Diff
+        | OutputMode::NdjsonSchema => return None,
+    };
+
+    Some(printer)
+}
+
+/// `JsonRecord` is the object output for each bucket by `JsonPrinter` and
+/// `NdjsonPrinter`.
+///
+/// The JSON Schema of the NDJSON output is generated from this struct, with
+/// the field doc comments as the descriptions of its properties.
+#[derive(JsonSchema, Serialize)]
+#[schemars(
+    title = "s3du bucket record",
+    description = "A bucket and its size, as output on each line by s3du --output=ndjson",
+)]
+struct JsonRecord {
+    /// Name of the bucket, or its alias if it has one.
+    bucket: String,
+
+    /// Present and true if the bucket was found by a dry run.
+    #[serde(default, skip_serializing_if = "is_false")]
+    dry_run: bool,
+
+    /// Region of the bucket, null if it isn't known.
+    #[schemars(schema_with = "nullable::<String>")]
+    region: Option<String>,
+
+    /// Size of the bucket in bytes, null for a dry run.
+    #[schemars(schema_with = "nullable::<u64>")]
+    size: Option<u64>,
+}
+
+impl JsonRecord {
+    /// Returns the record for `bucket` with the given `size`, or without a
+    /// size if the bucket was found by a dry run.
+    fn new(bucket: &Bucket, size: Option<u64>) -> Self {
+        Self {
+            bucket:  bucket.display_name().into(),
+            dry_run: size.is_none(),
+            region:  bucket.region.as_ref().map(|region| region.name().into()),
+            size:    size,
+        }
+    }
+}
+
+/// Returns `true` if `value` is `false`, for leaving `false` fields out of
+/// the output.
+fn is_false(value: &bool) -> bool {
+    !value
+}
+
+/// Returns the schema of a field that's always output, but may be `null`.
+///
+/// Fields with a custom schema are required, unlike `Option` fields.
+fn nullable<T: JsonSchema>(gen: &mut SchemaGenerator) -> Schema {
+    gen.subschema_for::<Option<T>>()
+}
+
+/// Returns `value` serialized as a single line of JSON.
+fn json_line<T: Serialize>(value: &T) -> String {
+    // Serializing strings, numbers and sequences of them can't fail.
+    serde_json::to_string(value)
+        .expect("JSON output serializes")
+}
+
+/// `JsonPrinter` renders the buckets as a single line JSON array of
+/// `JsonRecord` objects, with the `bucket` name, `region` and `size` in
+/// bytes.
+///
+/// The `region` is `null` for buckets without a known region. Buckets found
+/// by a dry run have a `null` size and `dry_run` set to `true`.
+pub struct JsonPrinter;
+
+impl Printer for JsonPrinter {
+    fn print(&self, buckets: &[ShownBucket]) -> Vec<String> {
+        let records: Vec<JsonRecord> = buckets.iter()
+            .filter(|shown| shown.is_sized())
+            .map(|shown| JsonRecord::new(&shown.bucket, Some(shown.size)))
+            .collect();
+
+        vec![json_line(&records)]
+    }
+
+    fn print_dry_run(&self, buckets: &[Bucket]) -> Vec<String> {
+        let records: Vec<JsonRecord> = buckets.iter()
+            .map(|bucket| JsonRecord::new(bucket, None))
+            .collect();
+
+        vec![json_line(&records)]
+    }
+}
+
+/// `NdjsonPrinter` renders each bucket as a `JsonRecord` object on a line of
+/// its own, as newline delimited JSON.
+///
+/// Records are the same as the objects in the `JsonPrinter` array.
+pub struct NdjsonPrinter;
+
+impl NdjsonPrinter {
+    /// Returns the JSON Schema describing each line output by
+    /// `NdjsonPrinter`, generated from `JsonRecord`.
+    ///
+    /// `dry_run` is only present in the output of dry runs, where `size` is
+    /// always `null`.
+    pub fn schema() -> Value {
+        serde_json::to_value(schema_for!(JsonRecord))
+            .expect("JSON Schema serializes")
+    }
+}
+
+impl Printer for NdjsonPrinter {
+    fn print(&self, buckets: &[ShownBucket]) -> Vec<String> {
+        buckets.iter()
+            .filter(|shown| shown.is_sized())
+            .map(|shown| json_line(&JsonRecord::new(&shown.bucket, Some(shown.size))))
+            .collect()
+    }
+
+    fn print_dry_run(&self, buckets: &[Bucket]) -> Vec<String> {
+        buckets.iter()
+            .map(|bucket| json_line(&JsonRecord::new(bucket, None)))
+            .collect()
+    }
+}
+
+/// `CsvPrinter` renders the buckets as CSV with a header line, with the
+/// `bucket` name, `region` and `size` in bytes.
+///
+/// The `region` is empty for buckets without a known region, as is the
+/// `size` of buckets found by a dry run.
+pub struct CsvPrinter;
+
+impl CsvPrinter {
+    /// Returns the CSV line for `bucket` with the given `size`.
+    fn line(bucket: &Bucket, size: Option<u64>) -> String {
+        let region = bucket.region
+            .as_ref()
+            .map_or("", |region| region.name());
+
+        format!(
+            "{},{},{}",
+            csv_field(bucket.display_name()),
+            csv_field(region),
+            size.map(|size| size.to_string()).unwrap_or_default(),
+        )
+    }
+}
+
+impl Printer for CsvPrinter {
+    fn print(&self, buckets: &[ShownBucket]) -> Vec<String> {
+        let mut lines = vec![CSV_HEADER.to_string()];
+
+        lines.extend(
+            buckets.iter()
+                .filter(|shown| shown.is_sized())
+                .map(|shown| Self::line(&shown.bucket, Some(shown.size)))
+        );
+
+        lines
+    }
+
+    fn print_dry_run(&self, buckets: &[Bucket]) -> Vec<String> {
+        let mut lines = vec![CSV_HEADER.to_string()];
+
+        lines.extend(
+            buckets.iter()
+                .map(|bucket| Self::line(bucket, None))
+        );
+
+        lines
+    }
+}
+
+/// `FluxPrinter` renders the buckets as Flux annotated CSV, for importing
+/// into InfluxDB 2.x or Chronograf, with the size in bytes of each bucket
+/// recorded in the `size_bytes` field of the `s3_bucket` measurement.
+///
+/// Each bucket is tagged with its `bucket` name and `region`. The `region`
+/// is empty for buckets without a known region, as is the `_value` of
+/// buckets found by a dry run.
+pub struct FluxPrinter {
+    /// Time that the buckets were sized at.
+    pub time: DateTime<Utc>,
+}
+
+impl FluxPrinter {
+    /// Returns the Flux annotated CSV lines for `buckets` with the given
+    /// sizes.
+    fn lines<'a, I>(&self, buckets: I) -> Vec<String>
+    where
+        I: Iterator<Item = (&'a Bucket, Option<u64>)>,
+    {
+        let time = self.time.to_rfc3339_opts(SecondsFormat::Secs, true);
+
+        let mut lines: Vec<String> = FLUX_HEADER.iter()
+            .map(|line| line.to_string())
+            .collect();
+
+        lines.extend(
+            buckets.enumerate()
+                .map(|(table, (bucket, size))| {
+                    let region = bucket.region
+                        .as_ref()
+                        .map_or("", |region| region.name());
+
+                    format!(
+                        ",,{},{},{},{},{},{},{}",
+                        table,
+                        time,
+                        FLUX_MEASUREMENT,
+                        FLUX_FIELD,
+                        size.map(|size| size.to_string()).unwrap_or_default(),
+                        csv_field(bucket.display_name()),
+                        csv_field(region),
+                    )
+                })
+        );
+
+        lines
+    }
+}
+
+impl Printer for FluxPrinter {
+    fn print(&self, buckets: &[ShownBucket]) -> Vec<String> {
+        self.lines(
+            buckets.iter()
+                .filter(|shown| shown.is_sized())
+                .map(|shown| (&shown.bucket, Some(shown.size)))
+        )
+    }
+
+    fn print_dry_run(&self, buckets: &[Bucket]) -> Vec<String> {
+        self.lines(buckets.iter().map(|bucket| (bucket, None)))
+    }
+}
+
+/// Returns `field` quoted as required by RFC 4180, if it contains a comma,
+/// double quote or line break.
+fn csv_field(field: &str) -> String {
+    if field.contains(CSV_SPECIAL_CHARS) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    }
+    else {
+        field.into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use chrono::TimeZone;
+    use jsonschema::JSONSchema;
+    use rusoto_core::Region;
+    use serde_json::json;
+    use std::str::FromStr;
+
+    // Returns a known set of shown buckets, one of which has no region, and
+    // one of which failed to be sized.
+    fn sized_buckets() -> Vec<ShownBucket> {
+        let timestamp = Utc.ymd(2020, 3, 1).and_hms(0, 0, 0);
+
+        vec![
+            ShownBucket::new(
+                Bucket {
+                    alias:         None,
+                    name:          "bucket-a".into(),
+                    region:        Some(Region::EuWest1),
+                    storage_types: None,
+                },
+                1024,
+                timestamp,
+            ),
+            ShownBucket::failed(
+                Bucket {
+                    alias:         None,
+                    name:          "bucket-c".into(),
+                    region:        None,
+                    storage_types: None,
+                },
+                "no datapoints".into(),
+                timestamp,
+            ),
+            ShownBucket::new(
+                Bucket {
+                    alias:         None,
+                    name:          "bucket-b".into(),
+                    region:        None,
+                    storage_types: None,
+                },
+                2048,
+                timestamp,
+            ),
+        ]
+    }
+
+    // Returns the buckets that were sized, as if found by a dry run.
+    fn dry_run_buckets() -> Vec<Bucket> {
+        sized_buckets()
+            .into_iter()
+            .filter(|shown| shown.is_sized())
+            .map(|shown| shown.bucket)
+            .collect()
+    }
+
+    #[test]
+    fn test_printer() {
+        let unit = SizeUnit::from_str("bytes").unwrap();
+        let now  = Utc::now();
+
+        let tests = vec![
+            (OutputMode::Csv,           true),
+            (OutputMode::Diff,          false),
+            (OutputMode::Flux,          true),
+            (OutputMode::GroupByRegion, true),
+            (OutputMode::Json,          true),
+            (OutputMode::Ndjson,        true),
+            (OutputMode::NdjsonSchema, false),
+            (OutputMode::Prometheus,    true),
+            (OutputMode::Text,          true),
+        ];
+
+        for test in tests {
+            let (output, expected) = test;
+
+            assert_eq!(printer(output, &unit, None, now).is_some(), expected);
+        }
+
+        // Text output shows failures, which other formats leave out.
+        let printer = printer(OutputMode::Text, &unit, None, now).unwrap();
+
+        let expected = vec![
+            "1024\tbucket-a",
+            "0\tbucket-c\tNO_CLOUDWATCH_DATA (no datapoints)",
+            "2048\tbucket-b",
+        ];
+
+        assert_eq!(printer.print(&sized_buckets()), expected);
+    }
+
+    #[test]
+    fn test_print_dry_run() {
+        let buckets = dry_run_buckets();
+
+        let tests: Vec<(Box<dyn Printer>, Vec<&str>)> = vec![
+            (
+                Box::new(PrometheusPrinter),
+                vec!["bucket-a", "bucket-b"],
+            ),
+            (
+                Box::new(JsonPrinter),
+                vec![
+                    r#"[{"bucket":"bucket-a","dry_run":true,"region":"eu-west-1","size":null},{"bucket":"bucket-b","dry_run":true,"region":null,"size":null}]"#,
+                ],
+            ),
+            (
+                Box::new(NdjsonPrinter),
+                vec![
+                    r#"{"bucket":"bucket-a","dry_run":true,"region":"eu-west-1","size":null}"#,
+                    r#"{"bucket":"bucket-b","dry_run":true,"region":null,"size":null}"#,
+                ],
+            ),
+            (
+                Box::new(CsvPrinter),
+                vec![
+                    "bucket,region,size",
+                    "bucket-a,eu-west-1,",
+                    "bucket-b,,",
+                ],
+            ),
+            (
+                Box::new(FluxPrinter { time: Utc.ymd(2020, 3, 1).and_hms(0, 0, 0) }),
+                vec![
+                    "#datatype,string,long,dateTime:RFC3339,string,string,long,string,string",
+                    "#group,false,false,false,true,true,false,true,true",
+                    "#default,_result,,,,,,,",
+                    ",result,table,_time,_measurement,_field,_value,bucket,region",
+                    ",,0,2020-03-01T00:00:00Z,s3_bucket,size_bytes,,bucket-a,eu-west-1",
+                    ",,1,2020-03-01T00:00:00Z,s3_bucket,size_bytes,,bucket-b,",
+                ],
+            ),
+        ];
+
+        for test in tests {
+            let (printer, expected) = test;
+
+            assert_eq!(printer.print_dry_run(&buckets), expected);
+        }
+    }
+
+    #[test]
+    fn test_json_printer() {
+        let expected = vec![
+            r#"[{"bucket":"bucket-a","region":"eu-west-1","size":1024},{"bucket":"bucket-b","region":null,"size":2048}]"#,
+        ];
+
+        assert_eq!(JsonPrinter.print(&sized_buckets()), expected);
+        assert_eq!(JsonPrinter.print(&[]), vec!["[]"]);
+    }
+
+    #[test]
+    fn test_ndjson_printer() {
+        let expected = vec![
+            r#"{"bucket":"bucket-a","region":"eu-west-1","size":1024}"#,
+            r#"{"bucket":"bucket-b","region":null,"size":2048}"#,
+        ];
+
+        assert_eq!(NdjsonPrinter.print(&sized_buckets()), expected);
+        assert!(NdjsonPrinter.print(&[]).is_empty());
+    }
+
+    #[test]
+    fn test_ndjson_printer_schema() {
+        let schema = NdjsonPrinter::schema();
+
+        // Fields that are always output are required, even when null.
+        assert_eq!(schema["required"], json!(["bucket", "region", "size"]));
+
+        let schema = JSONSchema::compile(&schema).unwrap();
+
+        let buckets = dry_run_buckets();
+
+        let lines = NdjsonPrinter.print(&sized_buckets())
+            .into_iter()
+            .chain(NdjsonPrinter.print_dry_run(&buckets));
+
+        // Every line output by the printer is valid against the schema.
+        for line in lines {
+            let record: Value = serde_json::from_str(&line).unwrap();
+
+            assert!(schema.is_valid(&record), "{} doesn't match the schema", line);
+        }
+
+        // Records missing a field that's always output are invalid.
+        let record = json!({"bucket": "bucket-a", "size": 1024});
+
+        assert!(!schema.is_valid(&record));
+    }
+
+    #[test]
+    fn test_csv_printer() {
+        let expected = vec![
+            "bucket,region,size",
+            "bucket-a,eu-west-1,1024",
+            "bucket-b,,2048",
+        ];
+
+        assert_eq!(CsvPrinter.print(&sized_buckets()), expected);
+        assert_eq!(CsvPrinter.print(&[]), vec!["bucket,region,size"]);
+    }
+
+    #[test]
+    fn test_flux_printer() {
+        let printer = FluxPrinter {
+            time: Utc.ymd(2020, 3, 1).and_hms(20, 59, 0),
+        };
+
+        let header = vec![
+            "#datatype,string,long,dateTime:RFC3339,string,string,long,string,string",
+            "#group,false,false,false,true,true,false,true,true",
+            "#default,_result,,,,,,,",
+            ",result,table,_time,_measurement,_field,_value,bucket,region",
+        ];
+
+        let mut expected = header.clone();
+        expected.extend(vec![
+            ",,0,2020-03-01T20:59:00Z,s3_bucket,size_bytes,1024,bucket-a,eu-west-1",
+            ",,1,2020-03-01T20:59:00Z,s3_bucket,size_bytes,2048,bucket-b,",
+        ]);
+
+        assert_eq!(printer.print(&sized_buckets()), expected);
+        assert_eq!(printer.print(&[]), header);
+
+        // Every line has a column for each annotated datatype.
+        for line in printer.print(&sized_buckets()) {
+            assert_eq!(line.split(',').count(), 9, "{}", line);
+        }
+    }
+
+    #[test]
+    fn test_csv_field() {
+        let tests = vec![
+            ("bucket-a",   "bucket-a"),
+            ("",           ""),
+            ("a,b",        r#""a,b""#),
+            (r#"a"b"#,     r#""a""b""#),
+            ("a\nb",       "\"a\nb\""),
+        ];
+
+        for test in tests {
+            let (field, expected) = test;
+
+            assert_eq!(csv_field(field), expected);
+        }
+    }
+}
@@ -0,0 +1,468 @@
+// Prometheus text exposition format
+#![forbid(unsafe_code)]
+#![deny(missing_docs)]
+use crate::common::{
+    Bucket,
+    ClassSizes,
+};
+use super::printer::Printer;
+use super::shown_bucket::ShownBucket;
+
+/// Name of the metric that bucket sizes are exposed as.
+const BUCKET_SIZE_METRIC: &str = "s3du_bucket_size_bytes";
+
+/// Help text for the `BUCKET_SIZE_METRIC`.
+const BUCKET_SIZE_HELP: &str = "Size of the S3 bucket in bytes.";
+
+/// Name of the metric that bucket object counts are exposed as.
+#[cfg(feature = "s3")]
+const BUCKET_OBJECTS_METRIC: &str = "s3du_bucket_objects";
+
+/// Help text for the `BUCKET_OBJECTS_METRIC`.
+#[cfg(feature = "s3")]
+const BUCKET_OBJECTS_HELP: &str = "Number of object versions included in the size of the S3 bucket.";
+
+/// `PrometheusPrinter` renders the buckets as metrics in the Prometheus text
+/// exposition format, for the node exporter's textfile collector.
+///
+/// Each bucket has a sample of the `BUCKET_SIZE_METRIC` for each of its
+/// storage classes, or for the whole bucket if its storage classes aren't
+/// known. Buckets with counted versions also have a sample of the
+/// `BUCKET_OBJECTS_METRIC`, after every size.
+pub struct PrometheusPrinter;
+
+impl Printer for PrometheusPrinter {
+    fn print(&self, buckets: &[ShownBucket]) -> Vec<String> {
+        let buckets: Vec<&ShownBucket> = buckets.iter()
+            .filter(|shown| shown.is_sized())
+            .collect();
+
+        let mut lines = vec![prometheus_header()];
+
+        for shown in &buckets {
+            let labels = prometheus_labels(&shown.bucket);
+
+            lines.extend(
+                prometheus_size_samples(&labels, shown.size, shown.class_sizes.as_ref())
+            );
+        }
+
+        // Object counts are a separate metric, so they're output once every
+        // size has been.
+        #[cfg(feature = "s3")]
+        {
+            let objects: Vec<String> = buckets.iter()
+                .filter_map(|shown| {
+                    let stats  = shown.stats?;
+                    let labels = prometheus_labels(&shown.bucket);
+
+                    Some(prometheus_sample(
+                        BUCKET_OBJECTS_METRIC,
+                        &labels,
+                        stats.version_count as u64,
+                    ))
+                })
+                .collect();
+
+            if !objects.is_empty() {
+                lines.push(prometheus_objects_header());
+                lines.extend(objects);
+            }
+        }
+
+        lines
+    }
+}
+
+/// Returns the `bucket` and, if it's known, `region` labels of the
+/// Prometheus samples for `bucket`.
+fn prometheus_labels(bucket: &Bucket) -> Vec<(&str, &str)> {
+    let mut labels = vec![("bucket", bucket.display_name())];
+
+    if let Some(region) = &bucket.region {
+        labels.push(("region", region.name()));
+    }
+
+    labels
+}
+
+/// Returns the `# HELP` and `# TYPE` lines for the gauge `name`.
+fn gauge_header(name: &str, help: &str) -> String {
+    format!("# HELP {name} {help}\n# TYPE {name} gauge", name=name, help=help)
+}
+
+/// Returns the `# HELP` and `# TYPE` lines for the `BUCKET_SIZE_METRIC`.
+///
+/// These must be output once, before any samples of the metric.
+fn prometheus_header() -> String {
+    gauge_header(BUCKET_SIZE_METRIC, BUCKET_SIZE_HELP)
+}
+
+/// Returns the `# HELP` and `# TYPE` lines for the `BUCKET_OBJECTS_METRIC`.
+///
+/// These must be output once, after every sample of the
+/// `BUCKET_SIZE_METRIC`, as the samples of each metric must be grouped
+/// together.
+#[cfg(feature = "s3")]
+fn prometheus_objects_header() -> String {
+    gauge_header(BUCKET_OBJECTS_METRIC, BUCKET_OBJECTS_HELP)
+}
+
+/// Returns the samples of the `BUCKET_SIZE_METRIC` for a bucket of `size`
+/// bytes with the given `labels`.
+///
+/// Buckets with `class_sizes` have a sample for each storage class, ordered
+/// by name, with a `storage_class` label. Other buckets have a single sample
+/// for the whole bucket.
+fn prometheus_size_samples(
+    labels: &[(&str, &str)],
+    size: u64,
+    class_sizes: Option<&ClassSizes>,
+) -> Vec<String> {
+    let class_sizes = match class_sizes {
+        Some(class_sizes) => class_sizes,
+        None              => {
+            return vec![prometheus_sample(BUCKET_SIZE_METRIC, labels, size)];
+        },
+    };
+
+    let mut sizes: Vec<(&str, u64)> = class_sizes.iter()
+        .map(|(class, size)| (class.name(), *size))
+        .collect();
+
+    sizes.sort();
+
+    sizes.into_iter()
+        .map(|(class, size)| {
+            let mut labels = labels.to_vec();
+            labels.push(("storage_class", class));
+
+            prometheus_sample(BUCKET_SIZE_METRIC, &labels, size)
+        })
+        .collect()
+}
+
+/// Returns a sample line for the metric `name` with the given `labels` and
+/// `value`, eg. `s3du_bucket_size_bytes{bucket="a-bucket"} 1024`.
+fn prometheus_sample(
+    name: &str,
+    labels: &[(&str, &str)],
+    value: u64,
+) -> String {
+    if labels.is_empty() {
+        return format!("{} {}", name, value);
+    }
+
+    let labels: Vec<String> = labels.iter()
+        .map(|(label, value)| {
+            format!("{}=\"{}\"", label, escape_label_value(value))
+        })
+        .collect();
+
+    format!("{}{{{}}} {}", name, labels.join(","), value)
+}
+
+/// Escapes a label value as required by the text exposition format.
+///
+/// Backslashes, double quotes and line feeds must be escaped.
+fn escape_label_value(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+
+    for c in value.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            '"'  => escaped.push_str("\\\""),
+            '\n' => escaped.push_str("\\n"),
+            _    => escaped.push(c),
+        }
+    }
+
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::StorageClass;
+    use pretty_assertions::assert_eq;
+    use rusoto_core::Region;
+
+    // Returns true if `name` is a valid metric or label name.
+    // Label names may not contain colons.
+    fn is_valid_name(name: &str, allow_colon: bool) -> bool {
+        let mut chars = name.chars();
+
+        let valid_char = |c: char| {
+            c.is_ascii_alphanumeric() || c == '_' || (allow_colon && c == ':')
+        };
+
+        match chars.next() {
+            Some(c) if !c.is_ascii_digit() && valid_char(c) => {
+                chars.all(valid_char)
+            },
+            _ => false,
+        }
+    }
+
+    // A parsed sample line, its metric name, labels and value.
+    type Sample = (String, Vec<(String, String)>, f64);
+
+    // Parses a sample line into its metric name, labels and value, returning
+    // None if the line doesn't follow the text exposition format.
+    fn parse_sample(line: &str) -> Option<Sample> {
+        let space  = line.rfind(' ')?;
+        let series = &line[..space];
+        let value  = line[space + 1..].parse().ok()?;
+
+        let (name, labels) = match series.find('{') {
+            None    => (series, ""),
+            Some(i) => {
+                let labels = series[i..].strip_prefix('{')?.strip_suffix('}')?;
+
+                (&series[..i], labels)
+            },
+        };
+
+        if !is_valid_name(name, true) {
+            return None;
+        }
+
+        let mut parsed = Vec::new();
+        let mut rest   = labels;
+
+        while !rest.is_empty() {
+            let equals    = rest.find("=\"")?;
+            let label     = &rest[..equals];
+            let remaining = &rest[equals + 2..];
+
+            if !is_valid_name(label, false) {
+                return None;
+            }
+
+            // Find the closing quote, skipping escaped characters.
+            let mut value   = String::new();
+            let mut chars   = remaining.char_indices();
+            let mut closing = None;
+
+            while let Some((i, c)) = chars.next() {
+                match c {
+                    '\\' => match chars.next()?.1 {
+                        'n' => value.push('\n'),
+                        c   => value.push(c),
+                    },
+                    '"'  => {
+                        closing = Some(i);
+                        break;
+                    },
+                    _    => value.push(c),
+                }
+            }
+
+            parsed.push((label.to_string(), value));
+
+            rest = &remaining[closing? + 1..];
+            rest = rest.strip_prefix(',').unwrap_or(rest);
+        }
+
+        Some((name.to_string(), parsed, value))
+    }
+
+    #[test]
+    fn test_prometheus_header() {
+        let ret = prometheus_header();
+        let ret: Vec<&str> = ret.lines().collect();
+
+        let expected = vec![
+            "# HELP s3du_bucket_size_bytes Size of the S3 bucket in bytes.",
+            "# TYPE s3du_bucket_size_bytes gauge",
+        ];
+
+        assert_eq!(ret, expected);
+    }
+
+    #[test]
+    fn test_prometheus_sample() {
+        let tests = vec![
+            (
+                vec![],
+                "s3du_bucket_size_bytes 1024",
+            ),
+            (
+                vec![("bucket", "a-bucket")],
+                r#"s3du_bucket_size_bytes{bucket="a-bucket"} 1024"#,
+            ),
+            (
+                vec![
+                    ("bucket",        "a-bucket"),
+                    ("region",        "us-east-1"),
+                    ("storage_class", "Standard"),
+                ],
+                r#"s3du_bucket_size_bytes{bucket="a-bucket",region="us-east-1",storage_class="Standard"} 1024"#,
+            ),
+        ];
+
+        for test in tests {
+            let labels   = test.0;
+            let expected = test.1;
+
+            let ret = prometheus_sample(BUCKET_SIZE_METRIC, &labels, 1024);
+
+            assert_eq!(ret, expected);
+        }
+    }
+
+    #[test]
+    fn test_prometheus_sample_parses() {
+        let tests = vec![
+            "a-bucket",
+            "bucket with \"quotes\"",
+            "bucket\\with\\backslashes",
+            "bucket\nwith\nnewlines",
+        ];
+
+        for bucket in tests {
+            let labels = vec![
+                ("bucket", bucket),
+                ("region", "eu-west-1"),
+            ];
+
+            let line = prometheus_sample(BUCKET_SIZE_METRIC, &labels, 123_456);
+
+            // Samples must remain on a single line.
+            assert_eq!(line.lines().count(), 1);
+
+            let (name, labels, value) = parse_sample(&line).unwrap();
+
+            let expected_labels = vec![
+                ("bucket".to_string(), bucket.to_string()),
+                ("region".to_string(), "eu-west-1".to_string()),
+            ];
+
+            assert_eq!(name, BUCKET_SIZE_METRIC);
+            assert_eq!(labels, expected_labels);
+            assert_eq!(value, 123_456.0);
+        }
+    }
+
+    #[cfg(feature = "s3")]
+    #[test]
+    fn test_prometheus_objects_header() {
+        let ret = prometheus_objects_header();
+        let ret: Vec<&str> = ret.lines().collect();
+
+        let expected = vec![
+            "# HELP s3du_bucket_objects Number of object versions included in the size of the S3 bucket.",
+            "# TYPE s3du_bucket_objects gauge",
+        ];
+
+        assert_eq!(ret, expected);
+    }
+
+    #[test]
+    fn test_prometheus_printer() {
+        let timestamp = chrono::Utc::now();
+
+        let bucket = |name: &str, region| Bucket {
+            alias:         None,
+            name:          name.into(),
+            region:        region,
+            storage_types: None,
+        };
+
+        let class_sizes: ClassSizes = vec![
+            (StorageClass::Standard, 1024),
+        ].into_iter().collect();
+
+        let buckets = vec![
+            ShownBucket {
+                class_sizes: Some(class_sizes),
+                ..ShownBucket::new(bucket("bucket-a", Some(Region::EuWest1)), 1024, timestamp)
+            },
+            ShownBucket::failed(bucket("bucket-b", None), "no datapoints".into(), timestamp),
+            ShownBucket::new(bucket("bucket \"c\"", None), 2048, timestamp),
+        ];
+
+        // Failed buckets have no samples.
+        let expected = vec![
+            "# HELP s3du_bucket_size_bytes Size of the S3 bucket in bytes.\n# TYPE s3du_bucket_size_bytes gauge",
+            r#"s3du_bucket_size_bytes{bucket="bucket-a",region="eu-west-1",storage_class="STANDARD"} 1024"#,
+            r#"s3du_bucket_size_bytes{bucket="bucket \"c\""} 2048"#,
+        ];
+
+        assert_eq!(PrometheusPrinter.print(&buckets), expected);
+        assert_eq!(PrometheusPrinter.print(&[]), vec![prometheus_header()]);
+    }
+
+    #[cfg(feature = "s3")]
+    #[test]
+    fn test_prometheus_printer_objects() {
+        use crate::common::BucketStats;
+
+        let timestamp = chrono::Utc::now();
+
+        let bucket = |name: &str, region| Bucket {
+            alias:         None,
+            name:          name.into(),
+            region:        region,
+            storage_types: None,
+        };
+
+        let stats = |version_count| Some(BucketStats {
+            version_count:       version_count,
+            delete_marker_count: 0,
+        });
+
+        let buckets = vec![
+            ShownBucket {
+                stats: stats(14),
+                ..ShownBucket::new(bucket("bucket-a", Some(Region::EuWest1)), 1024, timestamp)
+            },
+            ShownBucket {
+                stats: stats(0),
+                ..ShownBucket::new(bucket("bucket-b", None), 0, timestamp)
+            },
+        ];
+
+        let expected = vec![
+            "# HELP s3du_bucket_size_bytes Size of the S3 bucket in bytes.\n# TYPE s3du_bucket_size_bytes gauge",
+            r#"s3du_bucket_size_bytes{bucket="bucket-a",region="eu-west-1"} 1024"#,
+            r#"s3du_bucket_size_bytes{bucket="bucket-b"} 0"#,
+            "# HELP s3du_bucket_objects Number of object versions included in the size of the S3 bucket.\n# TYPE s3du_bucket_objects gauge",
+            r#"s3du_bucket_objects{bucket="bucket-a",region="eu-west-1"} 14"#,
+            r#"s3du_bucket_objects{bucket="bucket-b"} 0"#,
+        ];
+
+        assert_eq!(PrometheusPrinter.print(&buckets), expected);
+    }
+
+    #[test]
+    fn test_prometheus_size_samples() {
+        let class_sizes: ClassSizes = vec![
+            (StorageClass::StandardIA, 2048),
+            (StorageClass::Standard,   1024),
+        ].into_iter().collect();
+
+        let labels = vec![
+            ("bucket", "a \"quoted\" bucket"),
+            ("region", "eu-west-1"),
+        ];
+
+        let ret = prometheus_size_samples(&labels, 3072, Some(&class_sizes));
+
+        let expected = vec![
+            r#"s3du_bucket_size_bytes{bucket="a \"quoted\" bucket",region="eu-west-1",storage_class="STANDARD"} 1024"#,
+            r#"s3du_bucket_size_bytes{bucket="a \"quoted\" bucket",region="eu-west-1",storage_class="STANDARD_IA"} 2048"#,
+        ];
+
+        assert_eq!(ret, expected);
+
+        // Buckets without storage class sizes have a single sample.
+        let ret = prometheus_size_samples(&labels, 3072, None);
+
+        let expected = vec![
+            r#"s3du_bucket_size_bytes{bucket="a \"quoted\" bucket",region="eu-west-1"} 3072"#,
+        ];
+
+        assert_eq!(ret, expected);
+    }
+}
@@ -0,0 +1,177 @@
+// Grouping of buckets by region
+#![forbid(unsafe_code)]
+#![deny(missing_docs)]
+use crate::common::{
+    Bucket,
+    HumanSize,
+    SizeUnit,
+};
+use std::collections::BTreeMap;
+use super::printer::Printer;
+use super::shown_bucket::ShownBucket;
+
+/// Region name used for buckets without a known region.
+const UNKNOWN_REGION: &str = "unknown";
+
+/// `RegionGroupPrinter` renders the buckets in the given `unit`, grouped
+/// under a heading for each region.
+///
+/// Each group ends with the subtotal of the buckets in its region, followed
+/// by a `.` the same way as the total line.
+pub struct RegionGroupPrinter<'a> {
+    /// Unit to display the sizes in.
+    pub unit: &'a SizeUnit,
+}
+
+impl Printer for RegionGroupPrinter<'_> {
+    fn print(&self, buckets: &[ShownBucket]) -> Vec<String> {
+        let buckets: Vec<(Bucket, u64)> = buckets.iter()
+            .filter(|shown| shown.is_sized())
+            .map(|shown| (shown.bucket.to_owned(), shown.size))
+            .collect();
+
+        let mut lines = Vec::new();
+
+        for (region, buckets) in group_by_region(&buckets) {
+            lines.push(format!("{}:", region));
+
+            lines.extend(
+                buckets.iter()
+                    .map(|(bucket, size)| {
+                        format!("{}\t{}", size.humansize(self.unit), bucket.display_name())
+                    })
+            );
+
+            let subtotal = region_subtotal(&buckets);
+
+            lines.push(format!("{}\t.", subtotal.humansize(self.unit)));
+        }
+
+        lines
+    }
+}
+
+/// Returns the sized `buckets` grouped on the name of their region.
+///
+/// Regions are ordered by name, as are the buckets within each region, by
+/// the name that they're printed with.
+/// Buckets without a region are grouped under `unknown`.
+pub fn group_by_region(buckets: &[(Bucket, u64)]) -> BTreeMap<String, Vec<(Bucket, u64)>> {
+    let mut groups: BTreeMap<String, Vec<(Bucket, u64)>> = BTreeMap::new();
+
+    for (bucket, size) in buckets {
+        let region = bucket.region
+            .as_ref()
+            .map_or(UNKNOWN_REGION, |region| region.name());
+
+        groups.entry(region.into())
+            .or_default()
+            .push((bucket.to_owned(), *size));
+    }
+
+    for buckets in groups.values_mut() {
+        buckets.sort_by(|a, b| a.0.display_name().cmp(b.0.display_name()));
+    }
+
+    groups
+}
+
+/// Returns the total size of the `buckets` in a region group.
+pub fn region_subtotal(buckets: &[(Bucket, u64)]) -> u64 {
+    buckets.iter()
+        .map(|(_, size)| size)
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+    use pretty_assertions::assert_eq;
+    use rusoto_core::Region;
+    use std::str::FromStr;
+
+    fn bucket(name: &str, region: Option<Region>) -> Bucket {
+        Bucket {
+            alias:         None,
+            name:          name.into(),
+            region:        region,
+            storage_types: None,
+        }
+    }
+
+    #[test]
+    fn test_group_by_region() {
+        let buckets = vec![
+            (bucket("c-bucket", Some(Region::UsEast1)), 300),
+            (bucket("b-bucket", Some(Region::EuWest1)), 200),
+            (bucket("a-bucket", Some(Region::UsEast1)), 100),
+            (bucket("d-bucket", None),                  400),
+        ];
+
+        let ret = group_by_region(&buckets);
+
+        let regions: Vec<&str> = ret.keys()
+            .map(|region| region.as_str())
+            .collect();
+
+        assert_eq!(regions, vec!["eu-west-1", "unknown", "us-east-1"]);
+
+        let names: Vec<&str> = ret["us-east-1"].iter()
+            .map(|(bucket, _)| bucket.name.as_str())
+            .collect();
+
+        assert_eq!(names, vec!["a-bucket", "c-bucket"]);
+    }
+
+    #[test]
+    fn test_region_group_printer() {
+        let unit    = SizeUnit::from_str("bytes").unwrap();
+        let printer = RegionGroupPrinter { unit: &unit };
+        let now     = Utc::now();
+
+        let buckets = vec![
+            ShownBucket::new(bucket("b-bucket", Some(Region::UsEast1)), 2048, now),
+            ShownBucket::new(bucket("c-bucket", Some(Region::EuWest1)), 512, now),
+            ShownBucket::failed(bucket("d-bucket", Some(Region::EuWest1)), "throttled".into(), now),
+            ShownBucket::new(bucket("a-bucket", Some(Region::UsEast1)), 1024, now),
+        ];
+
+        let expected = vec![
+            "eu-west-1:",
+            "512\tc-bucket",
+            "512\t.",
+            "us-east-1:",
+            "1024\ta-bucket",
+            "2048\tb-bucket",
+            "3072\t.",
+        ];
+
+        assert_eq!(printer.print(&buckets), expected);
+        assert!(printer.print(&[]).is_empty());
+    }
+
+    #[test]
+    fn test_region_subtotal() {
+        let buckets = vec![
+            (bucket("a-bucket", Some(Region::UsEast1)), 100),
+            (bucket("b-bucket", Some(Region::EuWest1)), 200),
+            (bucket("c-bucket", Some(Region::UsEast1)), 300),
+            (bucket("d-bucket", Some(Region::EuWest1)), 5_000_000_000),
+        ];
+
+        let subtotals: Vec<(String, u64)> = group_by_region(&buckets)
+            .into_iter()
+            .map(|(region, buckets)| (region, region_subtotal(&buckets)))
+            .collect();
+
+        let expected = vec![
+            ("eu-west-1".to_string(), 5_000_000_200),
+            ("us-east-1".to_string(), 400),
+        ];
+
+        assert_eq!(subtotals, expected);
+
+        assert_eq!(region_subtotal(&[]), 0);
+    }
+}
@@ -0,0 +1,122 @@
+// Buckets shown in the output
+#![forbid(unsafe_code)]
+#![deny(missing_docs)]
+use chrono::{
+    DateTime,
+    Utc,
+};
+use crate::common::{
+    Bucket,
+    ClassSizes,
+};
+
+#[cfg(feature = "cloudwatch")]
+use crate::common::ClassTrend;
+
+#[cfg(feature = "s3")]
+use crate::common::{
+    BucketStats,
+    MetadataStats,
+    PrefixNode,
+};
+
+/// `ShownBucket` is a bucket shown in the output, along with its size and
+/// everything else that the client found out about it while sizing it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ShownBucket {
+    /// The bucket.
+    pub bucket: Bucket,
+
+    /// Size of the bucket in bytes.
+    pub size: u64,
+
+    /// Time that the bucket was sized at.
+    pub timestamp: DateTime<Utc>,
+
+    /// Estimated monthly cost of the bucket, if a `CostModel` was given.
+    ///
+    /// This is `Some(None)` for buckets without storage class sizes, which
+    /// can't be costed.
+    pub cost: Option<Option<f64>>,
+
+    /// Size of each storage class in the bucket, if the client saw them.
+    pub class_sizes: Option<ClassSizes>,
+
+    /// Version and delete marker counts, if the client counted them.
+    #[cfg(feature = "s3")]
+    pub stats: Option<BucketStats>,
+
+    /// Size of the incomplete multipart uploads included in `size`, if any
+    /// were included.
+    #[cfg(feature = "s3")]
+    pub multipart_size: Option<u64>,
+
+    /// Object metadata estimates, if the client sampled them.
+    #[cfg(feature = "s3")]
+    pub metadata: Option<MetadataStats>,
+
+    /// Prefixes within the bucket, if the client built a prefix tree.
+    #[cfg(feature = "s3")]
+    pub prefixes: Option<PrefixNode>,
+
+    /// Lines of the duplicate object report, if the client looked for
+    /// duplicates.
+    #[cfg(feature = "s3")]
+    pub duplicates: Option<Vec<String>>,
+
+    /// Lines of the object age histogram report, if the client built one.
+    #[cfg(feature = "s3")]
+    pub age_histogram: Option<Vec<String>>,
+
+    /// Storage type trends, if the client fetched them.
+    #[cfg(feature = "cloudwatch")]
+    pub transitions: Option<Vec<ClassTrend>>,
+
+    /// Reason that the bucket failed to be sized, if it's shown annotated
+    /// with the failure rather than with a size.
+    pub error: Option<String>,
+}
+
+impl ShownBucket {
+    /// Returns `bucket` sized at `size` bytes at `timestamp`, without any
+    /// other details.
+    pub fn new(bucket: Bucket, size: u64, timestamp: DateTime<Utc>) -> Self {
+        Self {
+            bucket:         bucket,
+            size:           size,
+            timestamp:      timestamp,
+            cost:           None,
+            class_sizes:    None,
+            #[cfg(feature = "s3")]
+            stats:          None,
+            #[cfg(feature = "s3")]
+            multipart_size: None,
+            #[cfg(feature = "s3")]
+            metadata:       None,
+            #[cfg(feature = "s3")]
+            prefixes:       None,
+            #[cfg(feature = "s3")]
+            duplicates:     None,
+            #[cfg(feature = "s3")]
+            age_histogram:  None,
+            #[cfg(feature = "cloudwatch")]
+            transitions:    None,
+            error:          None,
+        }
+    }
+
+    /// Returns `bucket`, which failed to be sized at `timestamp` for the
+    /// given `reason`.
+    pub fn failed(bucket: Bucket, reason: String, timestamp: DateTime<Utc>) -> Self {
+        Self {
+            error: Some(reason),
+            ..Self::new(bucket, 0, timestamp)
+        }
+    }
+
+    /// Returns `true` if the bucket was sized, rather than shown annotated
+    /// with a failure.
+    pub fn is_sized(&self) -> bool {
+        self.error.is_none()
+    }
+}
@@ -0,0 +1,737 @@
+// Text output, similar to du
+#![forbid(unsafe_code)]
+#![deny(missing_docs)]
+use chrono::{
+    DateTime,
+    Utc,
+};
+use crate::common::{
+    HumanSize,
+    SizeUnit,
+    StorageResource,
+    TimestampFormat,
+    format_timestamp,
+};
+use super::printer::Printer;
+use super::shown_bucket::ShownBucket;
+use super::totals::Total;
+
+#[cfg(feature = "cloudwatch")]
+use crate::common::ClassTrend;
+
+#[cfg(feature = "s3")]
+use crate::common::{
+    BucketStats,
+    MetadataStats,
+};
+
+#[cfg(feature = "s3")]
+use super::tree::tree_lines;
+
+/// `PlainPrinter` renders each bucket as its size in the given `unit`
+/// followed by its name, similar to `du`.
+///
+/// Anything else that the client found out about a bucket is shown around
+/// its name, or on lines beneath it.
+pub struct PlainPrinter<'a> {
+    /// Unit to display the sizes in.
+    pub unit: &'a SizeUnit,
+
+    /// Format to display the time that each bucket was sized at in, if the
+    /// time is displayed, similar to `du --time`.
+    pub time: Option<TimestampFormat>,
+
+    /// Whether the prefixes of each bucket are drawn as a tree, rather than
+    /// indented.
+    #[cfg(feature = "s3")]
+    pub tree: bool,
+}
+
+impl PlainPrinter<'_> {
+    /// Returns the lines of output for the `shown` bucket.
+    fn lines(&self, shown: &ShownBucket) -> Vec<String> {
+        let time = format_time(shown.timestamp, self.time);
+
+        // Failures are only shown when they're included in the output, to
+        // show why CloudWatch couldn't size the bucket.
+        if let Some(reason) = &shown.error {
+            return vec![format!(
+                "{size}\t{time}{bucket}\tNO_CLOUDWATCH_DATA ({reason})",
+                size=0.humansize(self.unit),
+                time=time,
+                bucket=shown.bucket.display_name(),
+                reason=reason,
+            )];
+        }
+
+        // If the client counted versions for the bucket, display the
+        // counts between the size and the bucket name.
+        #[cfg(feature = "s3")]
+        let counts = format_stats(shown.stats);
+
+        #[cfg(not(feature = "s3"))]
+        let counts = String::new();
+
+        // If incomplete multipart uploads were added to the size, show how
+        // much they added after the bucket name.
+        #[cfg(feature = "s3")]
+        let multipart = format_multipart(shown.multipart_size, self.unit);
+
+        #[cfg(not(feature = "s3"))]
+        let multipart = String::new();
+
+        let mut lines = vec![format!(
+            "{size}\t{time}{counts}{cost}{bucket}{multipart}",
+            size=shown.size.humansize(self.unit),
+            time=time,
+            counts=counts,
+            cost=format_cost(shown.cost),
+            bucket=shown.bucket.display_name(),
+            multipart=multipart,
+        )];
+
+        // If the client sampled object metadata for the bucket, display the
+        // estimates beneath the bucket.
+        #[cfg(feature = "s3")]
+        lines.extend(format_metadata(shown.metadata));
+
+        // If the client looked for duplicate objects in the bucket, display
+        // its report beneath the bucket.
+        #[cfg(feature = "s3")]
+        lines.extend(format_report(shown.duplicates.as_deref()));
+
+        // If the client built an object age histogram for the bucket, display
+        // its report beneath the bucket in the same way.
+        #[cfg(feature = "s3")]
+        lines.extend(format_report(shown.age_histogram.as_deref()));
+
+        // If the client fetched storage class trends for the bucket, display
+        // them beneath the bucket.
+        #[cfg(feature = "cloudwatch")]
+        lines.extend(format_transitions(shown.transitions.as_deref(), self.unit));
+
+        // If the client built a prefix tree for the bucket, display its
+        // prefixes beneath the bucket, either drawn as a tree or indented.
+        #[cfg(feature = "s3")]
+        {
+            match &shown.prefixes {
+                Some(tree) if self.tree => {
+                    for (size, line) in tree_lines(tree) {
+                        lines.push(format!(
+                            "{size}\t{time}{line}",
+                            size=size.humansize(self.unit),
+                            time=time,
+                            line=line,
+                        ));
+                    }
+                },
+                Some(tree) => {
+                    for (depth, node) in tree.descendants() {
+                        lines.push(format!(
+                            "{size}\t{time}{indent}{prefix}",
+                            size=node.size.humansize(self.unit),
+                            time=time,
+                            indent="  ".repeat(depth),
+                            prefix=node.prefix,
+                        ));
+                    }
+                },
+                None => {},
+            }
+        }
+
+        lines
+    }
+}
+
+impl Printer for PlainPrinter<'_> {
+    fn print(&self, buckets: &[ShownBucket]) -> Vec<String> {
+        buckets.iter()
+            .flat_map(|shown| self.lines(shown))
+            .collect()
+    }
+}
+
+/// Returns the `timestamp` formatted as an output column if a `time` format
+/// was given, otherwise returns an empty string.
+fn format_time(timestamp: DateTime<Utc>, time: Option<TimestampFormat>) -> String {
+    match time {
+        Some(fmt) => format!("{}\t", format_timestamp(timestamp, fmt)),
+        None      => String::new(),
+    }
+}
+
+/// Returns the version and delete marker counts in `stats` formatted as
+/// output columns, if any `stats` were given, otherwise returns an empty
+/// string.
+#[cfg(feature = "s3")]
+fn format_stats(stats: Option<BucketStats>) -> String {
+    match stats {
+        Some(stats) => {
+            format!(
+                "{}\t{}\t",
+                stats.version_count,
+                stats.delete_marker_count,
+            )
+        },
+        None => String::new(),
+    }
+}
+
+/// Returns an annotation for the size of the incomplete multipart uploads
+/// included in a bucket's size, in the given `unit`, if any were included,
+/// otherwise returns an empty string.
+#[cfg(feature = "s3")]
+fn format_multipart(size: Option<u64>, unit: &SizeUnit) -> String {
+    match size {
+        Some(size) => {
+            format!(
+                " (+{} in incomplete multipart uploads)",
+                size.humansize(unit),
+            )
+        },
+        None => String::new(),
+    }
+}
+
+/// Returns the estimated monthly `cost` formatted as an output column, if a
+/// cost was estimated, otherwise returns an empty string.
+///
+/// Buckets without storage class sizes, such as those sized by S3 in
+/// combined mode, can't be costed and are shown with a `-`.
+fn format_cost(cost: Option<Option<f64>>) -> String {
+    match cost {
+        Some(Some(cost)) => format!("${:.2}\t", cost),
+        Some(None)       => "-\t".into(),
+        None             => String::new(),
+    }
+}
+
+/// Returns the estimates in `metadata` formatted as a labelled line to output
+/// beneath a bucket, if any `metadata` was given.
+#[cfg(feature = "s3")]
+fn format_metadata(metadata: Option<MetadataStats>) -> Option<String> {
+    let metadata = metadata?;
+
+    if metadata.sampled == 0 {
+        return Some("\tmetadata: no objects to sample".into());
+    }
+
+    let margin = match metadata.size_margin {
+        Some(margin) => format!("+/- {:.1} bytes", margin),
+        None         => "margin unknown".into(),
+    };
+
+    let line = format!(
+        "\tmetadata: {count:.1} entries, {size:.1} bytes per object ({margin}, 95% confidence, {sampled} of {population} objects sampled)",
+        count=metadata.average_count,
+        size=metadata.average_size,
+        margin=margin,
+        sampled=metadata.sampled,
+        population=metadata.population,
+    );
+
+    Some(line)
+}
+
+/// Returns the lines of a `report` made about a bucket, indented to output
+/// beneath the bucket, if a report was made, otherwise returns no lines.
+#[cfg(feature = "s3")]
+fn format_report(report: Option<&[String]>) -> Vec<String> {
+    report.unwrap_or_default()
+        .iter()
+        .map(|line| format!("\t{}", line))
+        .collect()
+}
+
+/// Returns the storage type `trends` formatted as labelled lines to output
+/// beneath a bucket, in the given `unit`.
+///
+/// Each line shows the size and share of the bucket for a storage type at
+/// the start and end of the window, and whether that share is rising or
+/// falling, so that lifecycle transitions can be seen.
+#[cfg(feature = "cloudwatch")]
+fn format_transitions(trends: Option<&[ClassTrend]>, unit: &SizeUnit) -> Vec<String> {
+    let trends = match trends {
+        Some(trends) => trends,
+        None         => return Vec::new(),
+    };
+
+    if trends.is_empty() {
+        return vec!["\ttransitions: no datapoints in the last 30 days".into()];
+    }
+
+    let start_total: u64 = trends.iter().map(|trend| trend.start).sum();
+    let end_total: u64   = trends.iter().map(|trend| trend.end).sum();
+
+    trends.iter()
+        .map(|trend| {
+            let start_share = ClassTrend::share(trend.start, start_total);
+            let end_share   = ClassTrend::share(trend.end, end_total);
+
+            // Compare the shares as they're displayed, so that a share
+            // shown as unchanged isn't reported as moving.
+            let start_shown = format!("{:.1}", start_share);
+            let end_shown   = format!("{:.1}", end_share);
+
+            let direction = if start_shown == end_shown {
+                "steady"
+            }
+            else if end_share > start_share {
+                "rising"
+            }
+            else {
+                "falling"
+            };
+
+            format!(
+                "\ttransitions: {storage_type} {start} ({start_share}%) -> {end} ({end_share}%), {direction}",
+                storage_type=trend.storage_type,
+                start=trend.start.humansize(unit),
+                start_share=start_shown,
+                end=trend.end.humansize(unit),
+                end_share=end_shown,
+                direction=direction,
+            )
+        })
+        .collect()
+}
+
+/// Returns the text output line for the storage `resource`, annotated with
+/// its `kind` so that it can be told apart from the buckets.
+///
+/// The `time` shown is the time that AWS measured the resource at, such as
+/// the last inventory of a Glacier vault.
+///
+/// The type of the resource and how much of its capacity is used follow the
+/// `kind` in their own columns, if they're known.
+pub fn format_resource(
+    resource: &StorageResource,
+    kind: &str,
+    unit: &SizeUnit,
+    time: Option<TimestampFormat>,
+) -> String {
+    let timestamp = resource.timestamp.unwrap_or_else(Utc::now);
+
+    let resource_type = resource.resource_type
+        .as_ref()
+        .map(|resource_type| format!("\t{}", resource_type))
+        .unwrap_or_default();
+
+    let used = resource.used
+        .map(|used| format!("\t{} used", used.humansize(unit)))
+        .unwrap_or_default();
+
+    format!(
+        "{size}\t{time}{name}\t{kind}{resource_type}{used}",
+        size=resource.size.humansize(unit),
+        time=format_time(timestamp, time),
+        name=resource.name,
+        kind=kind,
+        resource_type=resource_type,
+        used=used,
+    )
+}
+
+/// Returns the line for the `total` of the text output, in the given `unit`
+/// and `time` format.
+///
+/// The total is displayed the same way du(1) would, the total size followed
+/// by a `.`. Any counts are totalled in their columns.
+pub fn format_total(
+    total: &Total,
+    unit: &SizeUnit,
+    time: Option<TimestampFormat>,
+) -> String {
+    let size = total.size.humansize(unit);
+    let time = format_time(total.latest.unwrap_or_else(Utc::now), time);
+
+    #[cfg(feature = "s3")]
+    let counts = format_stats(total.stats);
+
+    #[cfg(not(feature = "s3"))]
+    let counts = String::new();
+
+    let cost = format_cost(total.cost.map(Some));
+
+    format!(
+        "{size}\t{time}{counts}{cost}.",
+        size=size,
+        time=time,
+        counts=counts,
+        cost=cost,
+    )
+}
+
+/// Returns the footer line reporting the number of `skipped` buckets, if any
+/// were skipped.
+pub fn format_skipped(skipped: &[String]) -> Option<String> {
+    match skipped.len() {
+        0 => None,
+        1 => Some("Skipped 1 inaccessible bucket".into()),
+        n => Some(format!("Skipped {} inaccessible buckets", n)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use crate::common::Bucket;
+    use pretty_assertions::assert_eq;
+    use rusoto_core::Region;
+    use std::str::FromStr;
+
+    #[cfg(feature = "s3")]
+    use crate::common::PrefixNode;
+
+    // Returns a bucket with the given name, in eu-west-1.
+    fn bucket(name: &str) -> Bucket {
+        Bucket {
+            alias:         None,
+            name:          name.into(),
+            region:        Some(Region::EuWest1),
+            storage_types: None,
+        }
+    }
+
+    // Returns a printer for the given unit, without times or trees.
+    fn printer(unit: &SizeUnit) -> PlainPrinter<'_> {
+        PlainPrinter {
+            unit:  unit,
+            time:  None,
+            #[cfg(feature = "s3")]
+            tree:  false,
+        }
+    }
+
+    #[test]
+    fn test_plain_printer() {
+        let unit      = SizeUnit::from_str("binary").unwrap();
+        let timestamp = Utc.ymd(2020, 9, 30).and_hms(6, 12, 41);
+
+        let buckets = vec![
+            ShownBucket::new(bucket("bucket-a"), 1024, timestamp),
+            ShownBucket::failed(bucket("bucket-b"), "no datapoints".into(), timestamp),
+            ShownBucket {
+                cost: Some(Some(2.3)),
+                ..ShownBucket::new(bucket("bucket-c"), 2048, timestamp)
+            },
+        ];
+
+        let expected = vec![
+            "1KiB\tbucket-a",
+            "0B\tbucket-b\tNO_CLOUDWATCH_DATA (no datapoints)",
+            "2KiB\t$2.30\tbucket-c",
+        ];
+
+        assert_eq!(printer(&unit).print(&buckets), expected);
+        assert_eq!(printer(&unit).print(&[]), Vec::<String>::new());
+
+        let printer = PlainPrinter {
+            time: Some(TimestampFormat::Unix),
+            ..printer(&unit)
+        };
+
+        let expected = vec![
+            "1KiB\t1601446361\tbucket-a",
+            "0B\t1601446361\tbucket-b\tNO_CLOUDWATCH_DATA (no datapoints)",
+            "2KiB\t1601446361\t$2.30\tbucket-c",
+        ];
+
+        assert_eq!(printer.print(&buckets), expected);
+    }
+
+    #[cfg(feature = "s3")]
+    #[test]
+    fn test_plain_printer_s3() {
+        let unit      = SizeUnit::from_str("bytes").unwrap();
+        let timestamp = Utc.ymd(2020, 9, 30).and_hms(6, 12, 41);
+
+        let prefix = |prefix: &str, size, children| PrefixNode {
+            prefix:   prefix.into(),
+            size:     size,
+            children: children,
+        };
+
+        let shown = ShownBucket {
+            stats:          Some(BucketStats {
+                version_count:       4,
+                delete_marker_count: 1,
+            }),
+            multipart_size: Some(512),
+            metadata:       Some(MetadataStats::default()),
+            prefixes:       Some(prefix("", 4096, vec![
+                prefix("logs/", 3072, vec![
+                    prefix("logs/2021/", 3072, Vec::new()),
+                ]),
+                prefix("images/", 1024, Vec::new()),
+            ])),
+            duplicates:     Some(vec![
+                "bucket-a has 1 group of duplicate objects, wasting 1024 bytes".into(),
+                "  a.txt (STANDARD, last modified unknown)".into(),
+            ]),
+            ..ShownBucket::new(bucket("bucket-a"), 4096, timestamp)
+        };
+
+        let expected = vec![
+            "4096\t4\t1\tbucket-a (+512 in incomplete multipart uploads)",
+            "\tmetadata: no objects to sample",
+            "\tbucket-a has 1 group of duplicate objects, wasting 1024 bytes",
+            "\t  a.txt (STANDARD, last modified unknown)",
+            "3072\t  logs/",
+            "3072\t    logs/2021/",
+            "1024\t  images/",
+        ];
+
+        assert_eq!(printer(&unit).print(std::slice::from_ref(&shown)), expected);
+
+        let printer = PlainPrinter {
+            tree: true,
+            ..printer(&unit)
+        };
+
+        let expected = vec![
+            "4096\t4\t1\tbucket-a (+512 in incomplete multipart uploads)",
+            "\tmetadata: no objects to sample",
+            "\tbucket-a has 1 group of duplicate objects, wasting 1024 bytes",
+            "\t  a.txt (STANDARD, last modified unknown)",
+            "3072\t├── logs/",
+            "3072\t│   └── 2021/",
+            "1024\t└── images/",
+        ];
+
+        assert_eq!(printer.print(&[shown]), expected);
+    }
+
+    #[test]
+    fn test_plain_printer_dry_run() {
+        let unit    = SizeUnit::from_str("binary").unwrap();
+        let buckets = vec![bucket("bucket-a"), bucket("bucket-b")];
+
+        assert_eq!(printer(&unit).print_dry_run(&buckets), vec!["bucket-a", "bucket-b"]);
+    }
+
+    #[test]
+    fn test_format_cost() {
+        let tests = vec![
+            (None,            ""),
+            (Some(None),      "-\t"),
+            (Some(Some(0.0)), "$0.00\t"),
+            (Some(Some(2.3)), "$2.30\t"),
+        ];
+
+        for test in tests {
+            let (cost, expected) = test;
+
+            assert_eq!(format_cost(cost), expected);
+        }
+    }
+
+    #[test]
+    fn test_format_skipped() {
+        let tests = vec![
+            (vec![],                       None),
+            (vec!["bucket-a"],             Some("Skipped 1 inaccessible bucket")),
+            (vec!["bucket-a", "bucket-b"], Some("Skipped 2 inaccessible buckets")),
+        ];
+
+        for test in tests {
+            let skipped: Vec<String> = test.0
+                .iter()
+                .map(|name| name.to_string())
+                .collect();
+
+            let expected = test.1.map(|line| line.to_string());
+
+            assert_eq!(format_skipped(&skipped), expected);
+        }
+    }
+
+    #[cfg(feature = "s3")]
+    #[test]
+    fn test_format_multipart() {
+        let binary = SizeUnit::from_str("binary").unwrap();
+        let bytes  = SizeUnit::from_str("bytes").unwrap();
+
+        let tests = vec![
+            (Some(204_800), &binary, " (+200KiB in incomplete multipart uploads)"),
+            (Some(204_800), &bytes,  " (+204800 in incomplete multipart uploads)"),
+            (None,          &binary, ""),
+        ];
+
+        for test in tests {
+            let (size, unit, expected) = test;
+
+            assert_eq!(format_multipart(size, unit), expected);
+        }
+    }
+
+    #[cfg(feature = "s3")]
+    #[test]
+    fn test_format_stats() {
+        let stats = BucketStats {
+            version_count:       4,
+            delete_marker_count: 1,
+        };
+
+        let tests = vec![
+            (Some(stats), "4\t1\t"),
+            (None,        ""),
+        ];
+
+        for test in tests {
+            let stats    = test.0;
+            let expected = test.1;
+
+            let ret = format_stats(stats);
+
+            assert_eq!(ret, expected);
+        }
+    }
+
+    #[cfg(feature = "s3")]
+    #[test]
+    fn test_format_metadata() {
+        let metadata = MetadataStats {
+            population:    1000,
+            sampled:       100,
+            average_count: 2.5,
+            average_size:  40.25,
+            size_margin:   Some(3.125),
+        };
+
+        let unknown_margin = MetadataStats {
+            size_margin: None,
+            ..metadata
+        };
+
+        let empty = MetadataStats::default();
+
+        let tests = vec![
+            (
+                Some(metadata),
+                Some("\tmetadata: 2.5 entries, 40.2 bytes per object (+/- 3.1 bytes, 95% confidence, 100 of 1000 objects sampled)"),
+            ),
+            (
+                Some(unknown_margin),
+                Some("\tmetadata: 2.5 entries, 40.2 bytes per object (margin unknown, 95% confidence, 100 of 1000 objects sampled)"),
+            ),
+            (Some(empty), Some("\tmetadata: no objects to sample")),
+            (None,        None),
+        ];
+
+        for test in tests {
+            let metadata = test.0;
+            let expected = test.1.map(|line| line.to_string());
+
+            let ret = format_metadata(metadata);
+
+            assert_eq!(ret, expected);
+        }
+    }
+
+    #[cfg(feature = "cloudwatch")]
+    #[test]
+    fn test_format_transitions() {
+        let unit = SizeUnit::from_str("bytes").unwrap();
+
+        let trends = vec![
+            ClassTrend {
+                storage_type: "StandardStorage".into(),
+                start:        3072,
+                end:          1024,
+            },
+            ClassTrend {
+                storage_type: "GlacierStorage".into(),
+                start:        1024,
+                end:          3072,
+            },
+            ClassTrend {
+                storage_type: "StandardIAStorage".into(),
+                start:        0,
+                end:          0,
+            },
+        ];
+
+        let expected = vec![
+            "\ttransitions: StandardStorage 3072 (75.0%) -> 1024 (25.0%), falling",
+            "\ttransitions: GlacierStorage 1024 (25.0%) -> 3072 (75.0%), rising",
+            "\ttransitions: StandardIAStorage 0 (0.0%) -> 0 (0.0%), steady",
+        ];
+
+        let ret = format_transitions(Some(&trends), &unit);
+
+        assert_eq!(ret, expected);
+
+        let ret = format_transitions(Some(&[]), &unit);
+
+        assert_eq!(ret, vec!["\ttransitions: no datapoints in the last 30 days"]);
+
+        let ret = format_transitions(None, &unit);
+
+        assert!(ret.is_empty());
+    }
+
+    #[test]
+    fn test_format_resource() {
+        let unit = SizeUnit::from_str("bytes").unwrap();
+
+        let resource = StorageResource {
+            name:          "archive-2014".into(),
+            resource_type: None,
+            size:          4096,
+            timestamp:     Some(Utc.ymd(2020, 9, 30).and_hms(6, 12, 41)),
+            used:          None,
+        };
+
+        assert_eq!(
+            format_resource(&resource, "GLACIER_VAULT", &unit, None),
+            "4096\tarchive-2014\tGLACIER_VAULT",
+        );
+
+        assert_eq!(
+            format_resource(&resource, "GLACIER_VAULT", &unit, Some(TimestampFormat::Unix)),
+            "4096\t1601446361\tarchive-2014\tGLACIER_VAULT",
+        );
+
+        let resource = StorageResource {
+            name:          "fs-0123456789abcdef0".into(),
+            resource_type: Some("WINDOWS".into()),
+            size:          34_359_738_368,
+            timestamp:     Some(Utc.ymd(2021, 3, 1).and_hms(12, 0, 0)),
+            used:          Some(23_622_320_128),
+        };
+
+        assert_eq!(
+            format_resource(&resource, "FSX_FILE_SYSTEM", &unit, None),
+            "34359738368\tfs-0123456789abcdef0\tFSX_FILE_SYSTEM\tWINDOWS\t23622320128 used",
+        );
+    }
+
+    #[test]
+    fn test_format_total() {
+        let unit = SizeUnit::from_str("bytes").unwrap();
+
+        let total = Total {
+            size: 3072,
+            ..Default::default()
+        };
+
+        assert_eq!(format_total(&total, &unit, None), "3072\t.");
+
+        #[cfg(feature = "s3")]
+        {
+            let total = Total {
+                size:  3072,
+                stats: Some(BucketStats {
+                    version_count:       14,
+                    delete_marker_count: 1,
+                }),
+                ..Default::default()
+            };
+
+            assert_eq!(format_total(&total, &unit, None), "3072\t14\t1\t.");
+        }
+    }
+}
@@ -0,0 +1,95 @@
+// Totals across buckets
+#![forbid(unsafe_code)]
+#![deny(missing_docs)]
+use chrono::{
+    DateTime,
+    Utc,
+};
+
+#[cfg(feature = "s3")]
+use crate::common::BucketStats;
+
+/// `Total` is the total of everything in the output, shown on its last line.
+#[derive(Debug, Default, PartialEq)]
+pub struct Total {
+    /// Total size in bytes.
+    pub size: u64,
+
+    /// The most recent time that anything in the total was sized at, if
+    /// anything was.
+    pub latest: Option<DateTime<Utc>>,
+
+    /// Total estimated monthly cost, if a `CostModel` was given.
+    pub cost: Option<f64>,
+
+    /// Total counts of the buckets in the total, if any were counted.
+    #[cfg(feature = "s3")]
+    pub stats: Option<BucketStats>,
+}
+
+impl Total {
+    /// Adds `other` to this total, for combining the totals of clients in
+    /// different regions.
+    pub fn merge(&mut self, other: Self) {
+        self.size  += other.size;
+        self.latest = self.latest.max(other.latest);
+
+        self.cost = match (self.cost, other.cost) {
+            (None, None) => None,
+            (cost, other) => Some(cost.unwrap_or(0.0) + other.unwrap_or(0.0)),
+        };
+
+        #[cfg(feature = "s3")]
+        {
+            let stats: Vec<BucketStats> = self.stats
+                .into_iter()
+                .chain(other.stats)
+                .collect();
+
+            if !stats.is_empty() {
+                self.stats = Some(compute_totals(&stats));
+            }
+        }
+    }
+}
+
+/// Returns the `BucketStats` of every bucket in `stats` summed together.
+#[cfg(feature = "s3")]
+pub fn compute_totals(stats: &[BucketStats]) -> BucketStats {
+    stats.iter()
+        .fold(BucketStats::default(), |total, stats| {
+            BucketStats {
+                version_count:       total.version_count + stats.version_count,
+                delete_marker_count: total.delete_marker_count + stats.delete_marker_count,
+            }
+        })
+}
+
+#[cfg(all(test, feature = "s3"))]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_compute_totals() {
+        let stats = vec![
+            BucketStats {
+                version_count:       4,
+                delete_marker_count: 1,
+            },
+            BucketStats {
+                version_count:       10,
+                delete_marker_count: 0,
+            },
+            BucketStats::default(),
+        ];
+
+        let expected = BucketStats {
+            version_count:       14,
+            delete_marker_count: 1,
+        };
+
+        assert_eq!(compute_totals(&stats), expected);
+        assert_eq!(compute_totals(&[]), BucketStats::default());
+    }
+}
@@ -0,0 +1,104 @@
+// Tree view of the prefixes in a bucket
+#![forbid(unsafe_code)]
+#![deny(missing_docs)]
+use crate::common::PrefixNode;
+
+/// Drawn before the last child of a prefix.
+const LAST_BRANCH: &str = "└── ";
+
+/// Drawn before every other child of a prefix.
+const BRANCH: &str = "├── ";
+
+/// Drawn beneath a prefix that has further children below it.
+const CONTINUATION: &str = "│   ";
+
+/// Drawn beneath the last child of a prefix.
+const BLANK: &str = "    ";
+
+/// Returns the prefixes below `root` as the lines of a tree, similar to
+/// tree(1), each paired with the size of its prefix in bytes.
+///
+/// Each prefix is named relative to its parent, which it's drawn beneath.
+/// The tree only goes as deep as `root` does, which is limited by the max
+/// depth used when listing the prefixes.
+pub fn tree_lines(root: &PrefixNode) -> Vec<(u64, String)> {
+    let mut lines = Vec::new();
+
+    push_children(root, "", &mut lines);
+
+    lines
+}
+
+/// Pushes the lines for the children of `node` onto `lines`, drawn after the
+/// given `indent`.
+fn push_children(node: &PrefixNode, indent: &str, lines: &mut Vec<(u64, String)>) {
+    let last = node.children.len().saturating_sub(1);
+
+    for (i, child) in node.children.iter().enumerate() {
+        let (branch, continuation) = if i == last {
+            (LAST_BRANCH, BLANK)
+        }
+        else {
+            (BRANCH, CONTINUATION)
+        };
+
+        // Children always start with the prefix of their parent.
+        let name = child.prefix
+            .strip_prefix(node.prefix.as_str())
+            .unwrap_or(&child.prefix);
+
+        lines.push((child.size, format!("{}{}{}", indent, branch, name)));
+
+        push_children(child, &format!("{}{}", indent, continuation), lines);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    // Returns a PrefixNode with the given prefix, size and children.
+    fn node(prefix: &str, size: u64, children: Vec<PrefixNode>) -> PrefixNode {
+        PrefixNode {
+            prefix:   prefix.into(),
+            size:     size,
+            children: children,
+        }
+    }
+
+    #[test]
+    fn test_tree_lines() {
+        let tree = node("", 31_744, vec![
+            node("logs/", 14_336, vec![
+                node("logs/2019/", 2_048, Vec::new()),
+                node("logs/2020/", 12_288, vec![
+                    node("logs/2020/01/", 4_096, Vec::new()),
+                    node("logs/2020/02/", 8_192, Vec::new()),
+                ]),
+            ]),
+            node("media/", 16_384, vec![
+                node("media/cats/", 16_384, Vec::new()),
+            ]),
+        ]);
+
+        let expected = vec![
+            (14_336, "├── logs/".to_string()),
+            (2_048,  "│   ├── 2019/".to_string()),
+            (12_288, "│   └── 2020/".to_string()),
+            (4_096,  "│       ├── 01/".to_string()),
+            (8_192,  "│       └── 02/".to_string()),
+            (16_384, "└── media/".to_string()),
+            (16_384, "    └── cats/".to_string()),
+        ];
+
+        assert_eq!(tree_lines(&tree), expected);
+    }
+
+    #[test]
+    fn test_tree_lines_no_prefixes() {
+        let tree = node("", 1_024, Vec::new());
+
+        assert_eq!(tree_lines(&tree), Vec::new());
+    }
+}
@@ -0,0 +1,108 @@
+// Destination that output is written to
+#![forbid(unsafe_code)]
+#![deny(missing_docs)]
+use anyhow::Result;
+use crate::common::S3duError;
+use std::fs::File;
+use std::io::{
+    self,
+    BufWriter,
+    Write,
+};
+use std::path::Path;
+use tracing::debug;
+
+/// Returns the writer that output is written to, the file at `path` if one
+/// is given, otherwise stdout.
+///
+/// The file is created if it doesn't exist and truncated if it does. Output
+/// to the file is buffered, so the writer must be flushed once all output
+/// has been written.
+pub fn output_writer(path: Option<&Path>) -> Result<Box<dyn Write>> {
+    let path = match path {
+        Some(path) => path,
+        None       => return Ok(Box::new(io::stdout())),
+    };
+
+    debug!(path = ?path, "output_writer: Writing output to file");
+
+    let file = File::create(path)
+        .map_err(|e| {
+            S3duError::OutputFile(format!("{}: {}", path.display(), e))
+        })?;
+
+    Ok(Box::new(BufWriter::new(file)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+    use crate::common::Bucket;
+    use crate::output::{
+        JsonPrinter,
+        Printer,
+        ShownBucket,
+    };
+    use crate::test_helpers::temp_path;
+    use pretty_assertions::assert_eq;
+    use rusoto_core::Region;
+    use serde_json::Value;
+    use std::env;
+    use std::fs;
+
+    #[test]
+    fn test_output_writer_json() {
+        let path = temp_path("output-file.json");
+
+        // Any existing contents are replaced.
+        fs::write(&path, "old output that is longer than the new output\n".repeat(10)).unwrap();
+
+        let buckets = vec![
+            ShownBucket::new(
+                Bucket {
+                    alias:         None,
+                    name:          "bucket-a".into(),
+                    region:        Some(Region::EuWest1),
+                    storage_types: None,
+                },
+                1024,
+                Utc::now(),
+            ),
+        ];
+
+        {
+            let mut out = output_writer(Some(&path)).unwrap();
+
+            for line in JsonPrinter.print(&buckets) {
+                writeln!(out, "{}", line).unwrap();
+            }
+
+            out.flush().unwrap();
+        }
+
+        let contents = fs::read_to_string(&path).unwrap();
+
+        let ret: Value = serde_json::from_str(&contents).unwrap();
+
+        let expected = serde_json::json!([
+            {
+                "bucket": "bucket-a",
+                "region": "eu-west-1",
+                "size":   1024,
+            },
+        ]);
+
+        assert_eq!(ret, expected);
+    }
+
+    #[test]
+    fn test_output_writer_error() {
+        // A directory can't be created as a file.
+        let path = env::temp_dir();
+
+        let ret = output_writer(Some(&path)).err().unwrap();
+
+        assert!(matches!(ret.downcast_ref(), Some(S3duError::OutputFile(_))));
+    }
+}
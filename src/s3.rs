@@ -2,10 +2,42 @@
 #![forbid(unsafe_code)]
 #![deny(missing_docs)]
 
+/// S3 `AutoRegionClient`, sizing each bucket in its own region.
+mod auto_region;
+
 /// Implementation of the `BucketSizer` trait for our S3 `Client`.
 mod bucket_sizer;
 
+/// Bucket configuration checks for our S3 `Client`.
+mod checks;
+
 /// S3 `Client`.
 mod client;
 
+/// Detection of duplicate objects for our S3 `Client`.
+mod dedup;
+
+/// Listing of Glacier vaults alongside S3 buckets.
+mod glacier;
+
+/// Object metadata sampling for our S3 `Client`.
+mod metadata;
+
+/// Incomplete multipart upload sizing for our S3 `Client`.
+mod multipart;
+
+/// Reporting of the ages of objects for our S3 `Client`.
+mod object_age;
+
+/// Building the tree of prefixes within a bucket for our S3 `Client`.
+mod prefix_tree;
+
+/// Splitting of bucket listings into key ranges.
+mod shard;
+
+/// Bucket tag lookups for our S3 `Client`.
+mod tagging;
+
+pub use auto_region::*;
 pub use client::*;
+pub use glacier::*;
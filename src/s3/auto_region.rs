@@ -0,0 +1,562 @@
+// Implements the S3 Client that sizes each bucket in its own region
+#![forbid(unsafe_code)]
+#![deny(missing_docs)]
+use anyhow::{
+    anyhow,
+    Result,
+};
+use async_trait::async_trait;
+use crate::common::{
+    Bucket,
+    Buckets,
+    BucketSizer,
+    BucketStats,
+    ClientConfig,
+    MetadataStats,
+    PrefixNode,
+    S3duError,
+};
+use futures::stream::{
+    self,
+    StreamExt,
+};
+use rusoto_core::Region;
+use std::collections::HashMap;
+use std::sync::{
+    Arc,
+    Mutex,
+};
+use tracing::debug;
+use super::client::Client;
+
+/// The auto region `Client`.
+///
+/// This lists buckets with a `Client` in the configured region, then sizes
+/// each bucket with a `Client` in the region that the bucket is in.
+pub struct AutoRegionClient {
+    /// Cache of the `Buckets` discovered, used by `buckets` to avoid
+    /// discovering them again.
+    pub bucket_cache: Mutex<Option<Buckets>>,
+
+    /// Cache of the `Client` for each region that buckets were found in,
+    /// keyed on region.
+    pub clients: Mutex<HashMap<Region, Arc<Client>>>,
+
+    /// Configuration that the `Client` for each region is created from.
+    pub config: ClientConfig,
+
+    /// `Client` in the configured region, used for listing buckets and
+    /// looking up their locations.
+    pub discovery: Client,
+}
+
+impl AutoRegionClient {
+    /// Return a new `AutoRegionClient` with the given `ClientConfig`.
+    pub fn new(config: ClientConfig) -> Self {
+        debug!("new: Creating auto region S3 client");
+
+        Self {
+            bucket_cache: Mutex::new(None),
+            clients:      Mutex::new(HashMap::new()),
+            config:       config.clone(),
+            discovery:    Client::new(config),
+        }
+    }
+
+    /// Returns the `Client` for `region`, creating it if this is the first
+    /// bucket found in that region.
+    pub fn client_for(&self, region: &Region) -> Arc<Client> {
+        let mut clients = self.clients.lock().unwrap();
+
+        let client = clients.entry(region.to_owned())
+            .or_insert_with(|| {
+                debug!(region = region.name(), "client_for: Creating client");
+
+                let config = ClientConfig {
+                    region: region.to_owned(),
+                    ..self.config.clone()
+                };
+
+                Arc::new(Client::new(config))
+            });
+
+        Arc::clone(client)
+    }
+
+    /// Returns the `Client` that `bucket` is sized with.
+    ///
+    /// Buckets without a region were never discovered, so they're left to
+    /// the discovery `Client`'s region.
+    fn bucket_client(&self, bucket: &Bucket) -> Arc<Client> {
+        let region = bucket.region
+            .as_ref()
+            .unwrap_or(&self.discovery.region);
+
+        self.client_for(region)
+    }
+
+    /// Returns a `Bucket` for the given `bucket` name, in the region that
+    /// S3 reports for it, if we have access to it.
+    async fn discover_bucket(&self, bucket: String) -> Result<Option<Bucket>> {
+        let region = self.discovery.get_bucket_location(&bucket).await?;
+
+        debug!(bucket = %bucket, region = region.name(), "discover_bucket: Found bucket region");
+
+        if self.discovery.exclude_regions.contains(&region) {
+            debug!(bucket = %bucket, "discover_bucket: Region excluded");
+
+            return Ok(None);
+        }
+
+        // Access and tags are checked from the bucket's own region, as S3
+        // redirects these calls from other regions.
+        self.client_for(&region).check_bucket(bucket, region).await
+    }
+}
+
+#[async_trait]
+impl BucketSizer for AutoRegionClient {
+    /// Return the `Buckets` discovered in S3, from every region.
+    ///
+    /// This list of buckets will be filtered by the `bucket` argument
+    /// provided on the command line, if any.
+    ///
+    /// Discovered buckets are cached, so subsequent calls don't hit the API.
+    async fn buckets(&self) -> Result<Buckets> {
+        if let Some(buckets) = self.bucket_cache.lock().unwrap().as_ref() {
+            debug!("buckets: Returning cached buckets");
+
+            return Ok(buckets.to_owned());
+        }
+
+        // A named bucket is looked up directly, so that sizing it doesn't
+        // need permission to list every bucket. Creation dates are only
+        // returned by ListBuckets, so it's still listed to filter on them.
+        let bucket_names = match self.discovery.bucket_name.as_ref() {
+            Some(bucket_name) if self.discovery.creation_date_range.is_none() => {
+                debug!(bucket = %bucket_name, "buckets: Using named bucket");
+
+                vec![bucket_name.to_owned()]
+            },
+            bucket_name => {
+                debug!("buckets: Listing...");
+
+                let mut bucket_names = self.discovery.list_buckets().await?;
+
+                if let Some(bucket_name) = bucket_name {
+                    debug!(bucket = %bucket_name, "Filtering bucket list");
+
+                    bucket_names.retain(|b| b == bucket_name);
+                }
+
+                bucket_names
+            },
+        };
+
+        let discovered: Vec<(String, Result<Option<Bucket>>)> = stream::iter(bucket_names)
+            .map(|bucket| async move {
+                let discovered = self.discover_bucket(bucket.to_owned()).await;
+
+                (bucket, discovered)
+            })
+            .buffered(self.discovery.chunk_size)
+            .collect()
+            .await;
+
+        let mut buckets = Buckets::new();
+
+        // A bucket that fails to be discovered is handled like a bucket that
+        // fails to be sized, it doesn't stop the other buckets from being
+        // discovered unless we're failing fast.
+        for (name, bucket) in discovered {
+            match bucket {
+                Ok(Some(bucket)) => buckets.push(bucket),
+                Ok(None)         => {},
+                Err(ref e) if self.config.skip_inaccessible && S3duError::is_access_denied(e) => {
+                    debug!(bucket = %name, "buckets: Skipping inaccessible bucket");
+                },
+                Err(e) if self.config.fail_fast => return Err(e),
+                Err(e) => {
+                    debug!(bucket = %name, error = %e, "buckets: Failed to discover bucket");
+
+                    warning!("Couldn't discover '{}': {}", name, e);
+                },
+            }
+        }
+
+        *self.bucket_cache.lock().unwrap() = Some(buckets.clone());
+
+        Ok(buckets)
+    }
+
+    /// Return the size of `bucket` from the `Client` in its region.
+    async fn bucket_size(&self, bucket: &Bucket) -> Result<u64> {
+        self.bucket_client(bucket).bucket_size(bucket).await
+    }
+
+    /// Return the prefix tree from the `Client` in the region of `bucket`.
+    fn bucket_prefixes(&self, bucket: &Bucket) -> Option<PrefixNode> {
+        self.bucket_client(bucket).bucket_prefixes(bucket)
+    }
+
+    /// Return the incomplete multipart upload size from the `Client` in the
+    /// region of `bucket`.
+    fn bucket_multipart_size(&self, bucket: &Bucket) -> Option<u64> {
+        self.bucket_client(bucket).bucket_multipart_size(bucket)
+    }
+
+    /// Return the counts from the `Client` in the region of `bucket`.
+    fn bucket_stats(&self, bucket: &Bucket) -> Option<BucketStats> {
+        self.bucket_client(bucket).bucket_stats(bucket)
+    }
+
+    /// Return the metadata estimates from the `Client` in the region of
+    /// `bucket`.
+    fn bucket_metadata(&self, bucket: &Bucket) -> Option<MetadataStats> {
+        self.bucket_client(bucket).bucket_metadata(bucket)
+    }
+
+    /// Return the duplicate object report from the `Client` in the region
+    /// of `bucket`.
+    fn bucket_duplicates(&self, bucket: &Bucket) -> Option<Vec<String>> {
+        self.bucket_client(bucket).bucket_duplicates(bucket)
+    }
+
+    /// Return the object age histogram report from the `Client` in the
+    /// region of `bucket`.
+    fn bucket_age_histogram(&self, bucket: &Bucket) -> Option<Vec<String>> {
+        self.bucket_client(bucket).bucket_age_histogram(bucket)
+    }
+
+    /// Close the discovery `Client` and the `Client` for each region.
+    ///
+    /// Every client is closed even if closing one of them fails, the first
+    /// failure is returned.
+    async fn close(&mut self) -> Result<()> {
+        let mut result = self.discovery.close().await;
+
+        let clients: Vec<(Region, Arc<Client>)> = self.clients
+            .get_mut()
+            .unwrap()
+            .drain()
+            .collect();
+
+        for (region, mut client) in clients {
+            debug!(region = region.name(), "close: Closing client");
+
+            // Clients are only shared while a bucket is being sized, so
+            // nothing else holds them once sizing has finished.
+            let closed = match Arc::get_mut(&mut client) {
+                Some(client) => client.close().await,
+                None         => Err(anyhow!("Client for '{}' is still in use", region.name())),
+            };
+
+            if result.is_ok() {
+                result = closed;
+            }
+        }
+
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_helpers::{
+        MockS3Server,
+        s3_client,
+    };
+    use pretty_assertions::assert_eq;
+    use rusoto_mock::{
+        MockCredentialsProvider,
+        MockRequestDispatcher,
+        MockResponseReader,
+        MultipleMockRequestDispatcher,
+        ReadMockResponse,
+    };
+    use rusoto_s3::S3Client;
+
+    // Return a MockRequestDispatcher with a body given by the data_file,
+    // which fails the test if it's called from outside of the region.
+    fn dispatcher_in_region(
+        data_file: Option<&str>,
+        region:    Region,
+    ) -> MockRequestDispatcher {
+        let data = match data_file {
+            None    => "".to_string(),
+            Some(d) => MockResponseReader::read_response("test-data", d),
+        };
+
+        MockRequestDispatcher::default()
+            .with_body(&data)
+            .with_request_checker(move |request| {
+                assert_eq!(request.region, region);
+            })
+    }
+
+    // Create a mock S3 client in region, making requests to each of the
+    // dispatchers in turn.
+    fn mock_client(
+        dispatchers: Vec<MockRequestDispatcher>,
+        region:      Region,
+    ) -> Client {
+        let client = S3Client::new_with(
+            MultipleMockRequestDispatcher::new(dispatchers),
+            MockCredentialsProvider,
+            region.to_owned(),
+        );
+
+        Client {
+            bucket_name: Some("a-bucket-name".into()),
+            region:      region,
+            ..s3_client(client)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_bucket_sized_in_its_region() {
+        // The discovery client only finds the location of the named bucket,
+        // without listing buckets.
+        let discovery = mock_client(
+            vec![
+                dispatcher_in_region(Some("s3-get-bucket-location-eu.xml"), Region::UsEast1),
+            ],
+            Region::UsEast1,
+        );
+
+        // The client in eu-west-1 checks access to the bucket and sizes it.
+        let eu_client = mock_client(
+            vec![
+                dispatcher_in_region(None, Region::EuWest1),
+                dispatcher_in_region(Some("s3-list-objects.xml"), Region::EuWest1),
+            ],
+            Region::EuWest1,
+        );
+
+        let mut clients = HashMap::new();
+        clients.insert(Region::EuWest1, Arc::new(eu_client));
+
+        let client = AutoRegionClient {
+            bucket_cache: Mutex::new(None),
+            clients:      Mutex::new(clients),
+            config:       ClientConfig::default(),
+            discovery:    discovery,
+        };
+
+        let buckets = client.buckets().await.unwrap();
+
+        let expected = vec![
+            Bucket {
+                alias:         None,
+                name:          "a-bucket-name".into(),
+                region:        Some(Region::EuWest1),
+                storage_types: None,
+            },
+        ];
+
+        assert_eq!(buckets, expected);
+
+        let size = client.bucket_size(&buckets[0]).await.unwrap();
+
+        assert_eq!(size, 33792);
+
+        // No client was created for the discovery region.
+        let regions: Vec<Region> = client.clients
+            .lock()
+            .unwrap()
+            .keys()
+            .cloned()
+            .collect();
+
+        assert_eq!(regions, vec![Region::EuWest1]);
+    }
+
+    #[tokio::test]
+    async fn test_named_bucket_outside_region() {
+        let location = || dispatcher_in_region(
+            Some("s3-get-bucket-location-eu.xml"),
+            Region::UsWest2,
+        );
+
+        let discovery = mock_client(
+            vec![
+                dispatcher_in_region(Some("s3-list-buckets.xml"), Region::UsWest2),
+                location(),
+            ],
+            Region::UsWest2,
+        );
+
+        // A client in us-west-2 filters out the named bucket, as it's in
+        // eu-west-1.
+        let ret = discovery.buckets().await.unwrap();
+
+        assert!(ret.is_empty());
+
+        // The named bucket is found in eu-west-1.
+        let eu_client = mock_client(
+            vec![
+                dispatcher_in_region(None, Region::EuWest1),
+            ],
+            Region::EuWest1,
+        );
+
+        let mut clients = HashMap::new();
+        clients.insert(Region::EuWest1, Arc::new(eu_client));
+
+        let client = AutoRegionClient {
+            bucket_cache: Mutex::new(None),
+            clients:      Mutex::new(clients),
+            config:       ClientConfig::default(),
+            discovery:    mock_client(vec![location()], Region::UsWest2),
+        };
+
+        let ret: Vec<(String, Option<Region>)> = client.buckets()
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|bucket| (bucket.name, bucket.region))
+            .collect();
+
+        let expected = vec![
+            ("a-bucket-name".to_string(), Some(Region::EuWest1)),
+        ];
+
+        assert_eq!(ret, expected);
+    }
+
+    #[tokio::test]
+    async fn test_bucket_in_excluded_region() {
+        // The bucket isn't excluded itself, but it's in eu-west-1.
+        let mut discovery = mock_client(
+            vec![
+                dispatcher_in_region(Some("s3-get-bucket-location-eu.xml"), Region::UsEast1),
+            ],
+            Region::UsEast1,
+        );
+
+        discovery.exclude_regions = vec![Region::EuWest1];
+
+        let client = AutoRegionClient {
+            bucket_cache: Mutex::new(None),
+            clients:      Mutex::new(HashMap::new()),
+            config:       ClientConfig::default(),
+            discovery:    discovery,
+        };
+
+        let buckets = client.buckets().await.unwrap();
+
+        assert!(buckets.is_empty());
+
+        // No client was created for the excluded region.
+        assert!(client.clients.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_buckets_discovery_failures() {
+        // Returns an AutoRegionClient that fails to find the location of
+        // a-bucket-name with `status`, with the given `config`.
+        let client = |status, config| {
+            let discovery = MockS3Server::new()
+                .on("GET", "/", "s3-list-buckets.xml")
+                .on_status("GET", "/a-bucket-name?location", status)
+                .on("GET", "/another-bucket-name?location", "s3-get-bucket-location.xml")
+                .build();
+
+            let discovery = S3Client::new_with(
+                discovery,
+                MockCredentialsProvider,
+                Region::UsEast1,
+            );
+
+            let eu_client = S3Client::new_with(
+                MockS3Server::new()
+                    .on_status("HEAD", "/another-bucket-name", 200)
+                    .build(),
+                MockCredentialsProvider,
+                Region::EuWest1,
+            );
+
+            let eu_client = Client {
+                region: Region::EuWest1,
+                ..s3_client(eu_client)
+            };
+
+            let mut clients = HashMap::new();
+            clients.insert(Region::EuWest1, Arc::new(eu_client));
+
+            AutoRegionClient {
+                bucket_cache: Mutex::new(None),
+                clients:      Mutex::new(clients),
+                config:       config,
+                discovery:    s3_client(discovery),
+            }
+        };
+
+        let fail_fast = ClientConfig {
+            fail_fast: true,
+            ..ClientConfig::default()
+        };
+
+        let skip_inaccessible = ClientConfig {
+            fail_fast:         true,
+            skip_inaccessible: true,
+            ..ClientConfig::default()
+        };
+
+        // Failures are skipped, leaving the other buckets discovered, unless
+        // we're failing fast on anything but an inaccessible bucket.
+        let tests = vec![
+            (500, ClientConfig::default(),   Some(vec!["another-bucket-name"])),
+            (403, ClientConfig::default(),   Some(vec!["another-bucket-name"])),
+            (500, fail_fast.clone(),         None),
+            (403, fail_fast,                 None),
+            (403, skip_inaccessible.clone(), Some(vec!["another-bucket-name"])),
+            (500, skip_inaccessible,         None),
+        ];
+
+        for (status, config, expected) in tests {
+            let ret = client(status, config).buckets().await.ok().map(|buckets| {
+                buckets.into_iter()
+                    .map(|bucket| bucket.name)
+                    .collect::<Vec<String>>()
+            });
+
+            let expected = expected.map(|names| {
+                names.into_iter()
+                    .map(String::from)
+                    .collect::<Vec<String>>()
+            });
+
+            assert_eq!(ret, expected);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_close() {
+        let mut client = AutoRegionClient::new(ClientConfig::default());
+
+        client.client_for(&Region::EuWest1);
+        client.client_for(&Region::UsWest2);
+
+        client.close().await.unwrap();
+
+        // The client for each region was taken to be closed.
+        assert!(client.clients.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_client_for() {
+        let client = AutoRegionClient::new(ClientConfig::default());
+
+        let eu_west_1 = client.client_for(&Region::EuWest1);
+        let again     = client.client_for(&Region::EuWest1);
+        let us_west_2 = client.client_for(&Region::UsWest2);
+
+        // Clients are created once per region, in that region.
+        assert!(Arc::ptr_eq(&eu_west_1, &again));
+        assert_eq!(eu_west_1.region, Region::EuWest1);
+        assert_eq!(us_west_2.region, Region::UsWest2);
+        assert_eq!(client.clients.lock().unwrap().len(), 2);
+    }
+}
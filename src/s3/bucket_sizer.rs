@@ -3,13 +3,33 @@
 #![deny(missing_docs)]
 use anyhow::Result;
 use async_trait::async_trait;
+use chrono::Utc;
 use crate::common::{
     Bucket,
     Buckets,
     BucketSizer,
+    BucketStats,
+    MetadataStats,
+    PrefixNode,
 };
-use log::debug;
+use futures::stream::{
+    self,
+    StreamExt,
+};
+use tracing::debug;
 use super::client::Client;
+use super::dedup::duplicate_report;
+use super::object_age::age_histogram_report;
+
+/// Warns that the `check` of `bucket` failed with the error `e`.
+///
+/// Checks are made once the bucket is sized, so failing one, for example
+/// for lack of a permission that sizing doesn't need, doesn't fail sizing.
+fn check_failed(bucket: &str, check: &str, e: &anyhow::Error) {
+    debug!(bucket = bucket, check = check, error = ?e, "bucket_size: Check failed");
+
+    warning!("Couldn't check {} of '{}': {}", check, bucket, e);
+}
 
 #[async_trait]
 impl BucketSizer for Client {
@@ -18,7 +38,35 @@ impl BucketSizer for Client {
     /// This list of buckets will also be filtered by the following:
     ///   - The `bucket` argument provided on the command line
     ///   - The `Region`, ensuring it's in our currently selected `--region`
+    ///
+    /// Discovered buckets are cached, so subsequent calls don't hit the API.
     async fn buckets(&self) -> Result<Buckets> {
+        if let Some(buckets) = self.bucket_cache.lock().unwrap().as_ref() {
+            debug!("buckets: Returning cached buckets");
+
+            return Ok(buckets.to_owned());
+        }
+
+        // Unsigned requests can't list buckets or look up their location,
+        // so the named bucket is assumed to be in our region.
+        if self.no_sign_request {
+            debug!("buckets: Using named bucket for unsigned requests");
+
+            let buckets: Buckets = self.bucket_name
+                .iter()
+                .map(|name| Bucket {
+                    alias:         None,
+                    name:          name.to_owned(),
+                    region:        Some(self.region.to_owned()),
+                    storage_types: None,
+                })
+                .collect();
+
+            *self.bucket_cache.lock().unwrap() = Some(buckets.clone());
+
+            return Ok(buckets);
+        }
+
         debug!("buckets: Listing...");
 
         let mut bucket_names = self.list_buckets().await?;
@@ -26,102 +74,190 @@ impl BucketSizer for Client {
         // If we were provided with a specific bucket name on the CLI, filter
         // out buckets that don't match.
         if let Some(bucket_name) = self.bucket_name.as_ref() {
-            debug!("Filtering bucket list for '{}'", bucket_name);
+            debug!(bucket = %bucket_name, "Filtering bucket list");
 
             bucket_names.retain(|b| b == bucket_name);
         }
 
-        let mut buckets = Buckets::new();
-
-        for bucket in &bucket_names {
-            debug!("Retrieving location for '{}'", bucket);
+        // Bucket metadata calls are made `chunk_size` at a time. `buffered`
+        // is used over `buffer_unordered` so that buckets are output in the
+        // same order as `ListBuckets` returned them.
+        let discovered: Vec<Result<Option<Bucket>>> = stream::iter(bucket_names)
+            .map(|bucket| self.discover_bucket(bucket))
+            .buffered(self.chunk_size)
+            .collect()
+            .await;
 
-            let region = self.get_bucket_location(&bucket).await?;
-
-            // We can only ListBucket for the region our S3 client is in, so
-            // we filter for that region here.
-            if region == self.region || self.is_custom_client_region() {
-                // If we don't have access to the bucket, skip it.
-                if !self.head_bucket(&bucket).await {
-                    debug!("Access denied for '{}'", bucket);
-
-                    continue;
-                }
-
-                let bucket = Bucket {
-                    name:          bucket.into(),
-                    region:        Some(region),
-                    storage_types: None,
-                };
+        let mut buckets = Buckets::new();
 
+        for bucket in discovered {
+            if let Some(bucket) = bucket? {
                 buckets.push(bucket);
             }
         }
 
+        *self.bucket_cache.lock().unwrap() = Some(buckets.clone());
+
         // Finally, we have a list of buckets that we should be able to get the
         // size for.
         Ok(buckets)
     }
 
     /// Return the size of `bucket`.
-    async fn bucket_size(&self, bucket: &Bucket) -> Result<usize> {
-        debug!("bucket_size: Calculating size for '{}'", bucket.name);
+    async fn bucket_size(&self, bucket: &Bucket) -> Result<u64> {
+        debug!(bucket = %bucket, "bucket_size: Calculating size");
+
+        // With a delimiter we build the prefix tree, which also gives us the
+        // bucket size. The tree is cached for `bucket_prefixes`.
+        let size: Result<u64> = async {
+            if self.delimiter.is_some() {
+                let tree = self.prefix_tree(&bucket.name, "".into(), 0).await?;
+                let size = tree.size
+                    + self.size_incomplete_uploads(&bucket.name).await?;
+
+                self.prefix_cache.lock().unwrap().insert(bucket.name.to_owned(), tree);
+
+                Ok(size)
+            }
+            else {
+                self.size_objects(&bucket.name).await
+            }
+        }.await;
+
+        // Objects kept while sizing are only reported for sized buckets, so
+        // they're dropped here whether or not sizing succeeded.
+        let objects = self.object_cache.lock().unwrap()
+            .remove(&bucket.name)
+            .unwrap_or_default();
+
+        let size = size?;
+
+        debug!(bucket = %bucket, size = size, "bucket_size: Calculated size");
+
+        // The checks below are advisory, the bucket has already been sized,
+        // so failing one only warns rather than failing the bucket. Their
+        // results go to stderr, so that they don't end up in any structured
+        // output.
+        if self.check_logging {
+            match self.logging_check(&bucket.name).await {
+                Ok((status, warning)) => {
+                    notice!("{}", status);
+
+                    if let Some(warning) = warning {
+                        warning!("{}", warning);
+                    }
+                },
+                Err(e) => check_failed(&bucket.name, "access logging", &e),
+            }
+        }
+
+        if self.check_versioning {
+            match self.versioning_lifecycle_warning(&bucket.name).await {
+                Ok(Some(warning)) => warning!("{}", warning),
+                Ok(None)          => {},
+                Err(e)            => check_failed(&bucket.name, "versioning lifecycle", &e),
+            }
+        }
+
+        if let Some(threshold) = self.namespace_threshold {
+            match self.namespace_warning(&bucket.name, threshold).await {
+                Ok(Some(warning)) => warning!("{}", warning),
+                Ok(None)          => {},
+                Err(e)            => check_failed(&bucket.name, "namespace", &e),
+            }
+        }
+
+        // Duplicates are found among the objects kept while sizing, so they
+        // cover exactly the objects that were sized. The report is cached for
+        // `bucket_duplicates`, so that it's output with the bucket.
+        if self.find_duplicates {
+            let report = duplicate_report(&bucket.name, &objects);
 
-        let size = self.size_objects(&bucket.name).await?;
+            self.duplicate_cache.lock().unwrap().insert(bucket.name.to_owned(), report);
+        }
+
+        // The age histogram is built from the same objects as the
+        // duplicates, and cached for `bucket_age_histogram` in the same way.
+        if self.object_age_histogram {
+            let report = age_histogram_report(&bucket.name, &objects, Utc::now());
 
-        debug!("bucket_size: size for '{}' is '{}'", bucket.name, size);
+            self.age_histogram_cache.lock().unwrap().insert(bucket.name.to_owned(), report);
+        }
+
+        // Metadata is sampled once the bucket has been sized, the estimates
+        // are cached for `bucket_metadata`. Without them, the bucket is
+        // output without its metadata estimates.
+        if let Some(sample_size) = self.metadata_sample_size {
+            match self.sample_object_metadata(&bucket.name, sample_size).await {
+                Ok(metadata) => {
+                    self.metadata_cache.lock().unwrap().insert(bucket.name.to_owned(), metadata);
+                },
+                Err(e) => check_failed(&bucket.name, "object metadata", &e),
+            }
+        }
 
         Ok(size)
     }
+
+    /// Return the prefix tree built while sizing `bucket`, removing it from
+    /// the cache.
+    fn bucket_prefixes(&self, bucket: &Bucket) -> Option<PrefixNode> {
+        self.prefix_cache.lock().unwrap().remove(&bucket.name)
+    }
+
+    /// Return the size of the incomplete multipart uploads added while
+    /// sizing `bucket`, removing it from the cache.
+    fn bucket_multipart_size(&self, bucket: &Bucket) -> Option<u64> {
+        self.multipart_cache.lock().unwrap().remove(&bucket.name)
+    }
+
+    /// Return the counts made while sizing `bucket`, removing them from the
+    /// cache.
+    fn bucket_stats(&self, bucket: &Bucket) -> Option<BucketStats> {
+        self.stats_cache.lock().unwrap().remove(&bucket.name)
+    }
+
+    /// Return the metadata estimates made while sizing `bucket`, removing
+    /// them from the cache.
+    fn bucket_metadata(&self, bucket: &Bucket) -> Option<MetadataStats> {
+        self.metadata_cache.lock().unwrap().remove(&bucket.name)
+    }
+
+    /// Return the duplicate object report made while sizing `bucket`,
+    /// removing it from the cache.
+    fn bucket_duplicates(&self, bucket: &Bucket) -> Option<Vec<String>> {
+        self.duplicate_cache.lock().unwrap().remove(&bucket.name)
+    }
+
+    /// Return the object age histogram report made while sizing `bucket`,
+    /// removing it from the cache.
+    fn bucket_age_histogram(&self, bucket: &Bucket) -> Option<Vec<String>> {
+        self.age_histogram_cache.lock().unwrap().remove(&bucket.name)
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::common::ObjectVersions;
+    use crate::common::{
+        ObjectVersions,
+        StorageClass,
+    };
+    use crate::test_helpers::{
+        MockS3Server,
+        dispatcher_with_body,
+        mock_client,
+        s3_client,
+    };
     use pretty_assertions::assert_eq;
     use rusoto_core::Region;
     use rusoto_mock::{
         MockCredentialsProvider,
         MockRequestDispatcher,
-        MockResponseReader,
         MultipleMockRequestDispatcher,
-        ReadMockResponse,
     };
     use rusoto_s3::S3Client;
 
-    // Create a mock S3 client, returning the data from the specified
-    // data_file.
-    fn mock_client(
-        data_file: Option<&str>,
-        versions:  ObjectVersions,
-    ) -> Client {
-        let data = match data_file {
-            None    => "".to_string(),
-            Some(d) => MockResponseReader::read_response("test-data", d.into()),
-        };
-
-        let client = S3Client::new_with(
-            MockRequestDispatcher::default().with_body(&data),
-            MockCredentialsProvider,
-            Default::default()
-        );
-
-        Client {
-            client:          client,
-            bucket_name:     None,
-            object_versions: versions,
-            region:          Region::UsEast1,
-        }
-    }
-
-    // Return a MockRequestDispatcher with a body given by the data_file.
-    fn dispatcher_with_body(data_file: &str) -> MockRequestDispatcher {
-        let data = MockResponseReader::read_response("test-data", data_file);
-
-        MockRequestDispatcher::default().with_body(&data)
-    }
-
     #[tokio::test]
     async fn test_buckets() {
         let expected = vec![
@@ -145,14 +281,12 @@ mod tests {
             Region::EuWest1,
         );
 
-        let mut client = Client {
-            client:          s3client,
-            bucket_name:     None,
-            object_versions: ObjectVersions::Current,
-            region:          Region::EuWest1,
+        let client = Client {
+            region: Region::EuWest1,
+            ..s3_client(s3client)
         };
 
-        let buckets = Client::buckets(&mut client).await.unwrap();
+        let buckets = Client::buckets(&client).await.unwrap();
 
         let mut buckets: Vec<String> = buckets.iter()
             .map(|b| b.name.to_owned())
@@ -163,6 +297,133 @@ mod tests {
         assert_eq!(buckets, expected);
     }
 
+    #[tokio::test]
+    async fn test_buckets_discovery() {
+        // Responses are served by request, so the bucket outside of our
+        // region never needs a HeadBucket response, and the order that the
+        // buckets are discovered in doesn't matter.
+        let dispatcher = MockS3Server::new()
+            .on("GET", "/", "s3-list-buckets.xml")
+            .on("GET", "/a-bucket-name?location", "s3-get-bucket-location-eu.xml")
+            .on("GET", "/another-bucket-name?location", "s3-get-bucket-location-null.xml")
+            .on_status("HEAD", "/a-bucket-name", 200)
+            .on("GET", "/a-bucket-name?list-type=2", "s3-list-objects.xml")
+            .build();
+
+        let mut client = mock_client(None, ObjectVersions::Current);
+        client.client = S3Client::new_with(
+            dispatcher,
+            MockCredentialsProvider,
+            Region::EuWest1,
+        );
+        client.region = Region::EuWest1;
+
+        let buckets = Client::buckets(&client).await.unwrap();
+
+        let expected = vec![
+            Bucket {
+                alias:         None,
+                name:          "a-bucket-name".into(),
+                region:        Some(Region::EuWest1),
+                storage_types: None,
+            },
+        ];
+
+        assert_eq!(buckets, expected);
+
+        let size = Client::bucket_size(&client, &buckets[0]).await.unwrap();
+
+        assert_eq!(size, 33792);
+    }
+
+    #[tokio::test]
+    async fn test_buckets_discovery_access_denied() {
+        let dispatcher = MockS3Server::new()
+            .on("GET", "/", "s3-list-buckets.xml")
+            .on("GET", "/a-bucket-name?location", "s3-get-bucket-location.xml")
+            .on("GET", "/another-bucket-name?location", "s3-get-bucket-location.xml")
+            .on_status("HEAD", "/a-bucket-name", 403)
+            .on_status("HEAD", "/another-bucket-name", 200)
+            .build();
+
+        let mut client = mock_client(None, ObjectVersions::Current);
+        client.client = S3Client::new_with(
+            dispatcher,
+            MockCredentialsProvider,
+            Region::EuWest1,
+        );
+        client.region = Region::EuWest1;
+
+        let buckets: Vec<String> = Client::buckets(&client)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|bucket| bucket.name)
+            .collect();
+
+        assert_eq!(buckets, vec!["another-bucket-name"]);
+    }
+
+    #[tokio::test]
+    async fn test_buckets_no_sign_request() {
+        // No requests are mocked, the named bucket must be used without
+        // listing buckets or looking up its location.
+        let mut client = mock_client(None, ObjectVersions::Current);
+        client.bucket_name     = Some("public-bucket".into());
+        client.no_sign_request = true;
+        client.region          = Region::EuWest1;
+
+        let ret = Client::buckets(&client).await.unwrap();
+
+        let expected = vec![
+            Bucket {
+                alias:         None,
+                name:          "public-bucket".into(),
+                region:        Some(Region::EuWest1),
+                storage_types: None,
+            },
+        ];
+
+        assert_eq!(ret, expected);
+    }
+
+    #[tokio::test]
+    async fn test_bucket_count() {
+        // Only enough responses for a single listing are mocked, the count
+        // must come from the bucket cache.
+        let mock = MultipleMockRequestDispatcher::new(vec![
+            dispatcher_with_body("s3-list-buckets.xml"),
+            dispatcher_with_body("s3-get-bucket-location.xml"),
+            MockRequestDispatcher::with_status(200),
+            dispatcher_with_body("s3-get-bucket-location.xml"),
+            MockRequestDispatcher::with_status(200),
+        ]);
+
+        let s3client = S3Client::new_with(
+            mock,
+            MockCredentialsProvider,
+            Region::EuWest1,
+        );
+
+        let client = Client {
+            region: Region::EuWest1,
+            ..s3_client(s3client)
+        };
+
+        let buckets = Client::buckets(&client).await.unwrap();
+        let count   = Client::bucket_count(&client).await.unwrap();
+        let names   = Client::bucket_names(&client).await.unwrap();
+
+        assert_eq!(count, buckets.len());
+        assert_eq!(count, 2);
+
+        let expected: Vec<String> = buckets.into_iter()
+            .map(|bucket| bucket.name)
+            .collect();
+
+        assert_eq!(names, expected);
+    }
+
     #[tokio::test]
     async fn test_bucket_size() {
         let client = mock_client(
@@ -171,6 +432,7 @@ mod tests {
         );
 
         let bucket = Bucket {
+            alias:         None,
             name:          "test-bucket".into(),
             region:        None,
             storage_types: None,
@@ -182,4 +444,96 @@ mod tests {
 
         assert_eq!(ret, expected);
     }
+
+    #[tokio::test]
+    async fn test_bucket_size_duplicates() {
+        let client = Client {
+            find_duplicates: true,
+            ..mock_client(
+                Some("s3-list-objects-duplicates.xml"),
+                ObjectVersions::Current,
+            )
+        };
+
+        let bucket = Bucket {
+            alias:         None,
+            name:          "test-bucket".into(),
+            region:        None,
+            storage_types: None,
+        };
+
+        Client::bucket_size(&client, &bucket).await.unwrap();
+
+        let ret = client.bucket_duplicates(&bucket).unwrap();
+
+        assert_eq!(ret[0], "test-bucket has 1 group of duplicate objects, wasting 2048 bytes");
+        assert_eq!(ret.len(), 5);
+
+        // The report is removed from the cache once it's been returned.
+        assert_eq!(client.bucket_duplicates(&bucket), None);
+    }
+
+    #[tokio::test]
+    async fn test_bucket_size_failed_checks() {
+        // Checks that are denied only warn, the size of the bucket is still
+        // returned.
+        let dispatcher = MockS3Server::new()
+            .on("GET", "/test-bucket?list-type=2", "s3-list-objects.xml")
+            .on_status("GET", "/test-bucket?logging", 403)
+            .on_status("GET", "/test-bucket?versioning", 403)
+            .build();
+
+        let client = Client {
+            check_logging:    true,
+            check_versioning: true,
+            ..s3_client(S3Client::new_with(
+                dispatcher,
+                MockCredentialsProvider,
+                Default::default(),
+            ))
+        };
+
+        let bucket = Bucket {
+            alias:         None,
+            name:          "test-bucket".into(),
+            region:        None,
+            storage_types: None,
+        };
+
+        let ret = Client::bucket_size(&client, &bucket).await.unwrap();
+
+        assert_eq!(ret, 33792);
+    }
+
+    #[tokio::test]
+    async fn test_bucket_size_excluded_storage_classes() {
+        // The object without a storage class is in Standard storage.
+        let tests = vec![
+            (vec![],                      1_048_576 + 4_096 + 16_384),
+            (vec![StorageClass::GlacierFlexible], 4_096 + 16_384),
+        ];
+
+        for test in tests {
+            let excluded = test.0;
+            let expected = test.1;
+
+            let mut client = mock_client(
+                Some("s3-list-objects-standard-glacier.xml"),
+                ObjectVersions::Current,
+            );
+
+            client.excluded_storage_classes = excluded;
+
+            let bucket = Bucket {
+                alias:         None,
+                name:          "test-bucket".into(),
+                region:        None,
+                storage_types: None,
+            };
+
+            let ret = Client::bucket_size(&client, &bucket).await.unwrap();
+
+            assert_eq!(ret, expected);
+        }
+    }
 }
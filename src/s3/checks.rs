@@ -0,0 +1,569 @@
+// Implements bucket configuration checks for the S3 Client
+#![forbid(unsafe_code)]
+#![deny(missing_docs)]
+use anyhow::Result;
+use crate::common::{
+    S3duError,
+    timeout_api_call,
+    with_api_timeout,
+};
+use rusoto_core::RusotoError;
+use rusoto_s3::{
+    GetBucketLifecycleConfigurationRequest,
+    GetBucketLoggingRequest,
+    GetBucketVersioningRequest,
+    LifecycleRule,
+    LoggingEnabled,
+    ListObjectsV2Request,
+    S3,
+};
+use tracing::debug;
+use super::client::Client;
+
+/// Error code returned by `GetBucketLifecycleConfiguration` for buckets
+/// without a lifecycle configuration.
+const NO_SUCH_LIFECYCLE_CONFIGURATION: &str =
+    "<Code>NoSuchLifecycleConfiguration</Code>";
+
+/// Versioning status of buckets that currently have versioning enabled.
+const VERSIONING_ENABLED: &str = "Enabled";
+
+/// Status of lifecycle rules that are currently being applied.
+const RULE_ENABLED: &str = "Enabled";
+
+/// Delimiter used to find the top-level prefixes of namespace buckets.
+const NAMESPACE_DELIMITER: &str = "/";
+
+impl Client {
+    /// Returns a warning if `bucket` has versioning enabled but no enabled
+    /// lifecycle rule that expires noncurrent object versions.
+    ///
+    /// Without such a rule, old versions are kept forever and silently add
+    /// to the size of the bucket. Buckets where versioning was never enabled
+    /// or is suspended don't create new noncurrent versions, so they're not
+    /// warned about.
+    pub async fn versioning_lifecycle_warning(
+        &self,
+        bucket: &str,
+    ) -> Result<Option<String>> {
+        debug!(bucket = bucket, "versioning_lifecycle_warning");
+
+        let input = GetBucketVersioningRequest {
+            bucket: bucket.into(),
+            ..Default::default()
+        };
+
+        let output = with_api_timeout(
+            self.api_timeout,
+            self.client.get_bucket_versioning(input),
+        ).await?;
+
+        if output.status.as_deref() != Some(VERSIONING_ENABLED) {
+            return Ok(None);
+        }
+
+        let rules = self.get_bucket_lifecycle_rules(bucket).await?;
+
+        let expires_versions = rules
+            .iter()
+            .filter(|rule| rule.status == RULE_ENABLED)
+            .any(|rule| rule.noncurrent_version_expiration.is_some());
+
+        if expires_versions {
+            return Ok(None);
+        }
+
+        let warning = format!(
+            "{} has versioning enabled but no expiry rule for old versions",
+            bucket,
+        );
+
+        Ok(Some(warning))
+    }
+
+    /// Returns a warning if `bucket` has more than `threshold` top-level
+    /// prefixes, suggesting that it's a namespace bucket, with a prefix for
+    /// each tenant.
+    ///
+    /// Listing every object in such buckets is slow and costly, S3 Inventory
+    /// reports their size more cheaply. Listing stops as soon as the
+    /// `threshold` is passed.
+    pub async fn namespace_warning(
+        &self,
+        bucket: &str,
+        threshold: usize,
+    ) -> Result<Option<String>> {
+        debug!(bucket = bucket, "namespace_warning");
+
+        let mut continuation_token = None;
+        let mut prefixes           = 0;
+
+        loop {
+            let input = ListObjectsV2Request {
+                bucket:             bucket.into(),
+                continuation_token: continuation_token.to_owned(),
+                delimiter:          Some(NAMESPACE_DELIMITER.into()),
+                max_keys:           self.page_size,
+                ..Default::default()
+            };
+
+            let output = with_api_timeout(
+                self.api_timeout,
+                self.client.list_objects_v2(input),
+            ).await?;
+
+            prefixes += output.common_prefixes.map_or(0, |p| p.len());
+
+            if prefixes > threshold {
+                break;
+            }
+
+            if let Some(true) = output.is_truncated {
+                continuation_token = output.next_continuation_token;
+            }
+            else {
+                return Ok(None);
+            }
+        }
+
+        let warning = format!(
+            "{} is a namespace bucket with more than {} top-level prefixes, consider S3 Inventory instead of listing it",
+            bucket,
+            threshold,
+        );
+
+        Ok(Some(warning))
+    }
+
+    /// Returns the server access logging status of `bucket`, along with a
+    /// warning if its logging amplifies storage.
+    ///
+    /// Buckets that deliver their logs to themselves, or to a target bucket
+    /// that has logging enabled itself, log the writes of their own log
+    /// objects, so their logs grow along with every log delivery. The logging
+    /// status of the target bucket is only looked up on a best effort basis,
+    /// as it may be owned by another account.
+    pub async fn logging_check(
+        &self,
+        bucket: &str,
+    ) -> Result<(String, Option<String>)> {
+        debug!(bucket = bucket, "logging_check");
+
+        let logging = match self.get_bucket_logging(bucket).await? {
+            Some(logging) => logging,
+            None          => {
+                let status = format!("{} access logging is disabled", bucket);
+
+                return Ok((status, None));
+            },
+        };
+
+        let target = logging.target_bucket;
+
+        let status = format!(
+            "{} access logging is enabled to {} with prefix '{}'",
+            bucket,
+            target,
+            logging.target_prefix,
+        );
+
+        if target == bucket {
+            let warning = format!(
+                "{} delivers access logs to itself, each log delivery is logged again",
+                bucket,
+            );
+
+            return Ok((status, Some(warning)));
+        }
+
+        let target_logs = match self.get_bucket_logging(&target).await {
+            Ok(logging) => logging.is_some(),
+            Err(e)      => {
+                debug!(
+                    bucket = bucket,
+                    target = %target,
+                    error = %e,
+                    "logging_check: Couldn't get target bucket logging",
+                );
+
+                false
+            },
+        };
+
+        if !target_logs {
+            return Ok((status, None));
+        }
+
+        let warning = format!(
+            "{} delivers access logs to {}, which has access logging enabled, each log delivery is logged again",
+            bucket,
+            target,
+        );
+
+        Ok((status, Some(warning)))
+    }
+
+    /// Returns the server access logging configuration of `bucket`, if
+    /// logging is enabled.
+    async fn get_bucket_logging(
+        &self,
+        bucket: &str,
+    ) -> Result<Option<LoggingEnabled>> {
+        let input = GetBucketLoggingRequest {
+            bucket: bucket.into(),
+            ..Default::default()
+        };
+
+        let output = with_api_timeout(
+            self.api_timeout,
+            self.client.get_bucket_logging(input),
+        ).await?;
+
+        Ok(output.logging_enabled)
+    }
+
+    /// Returns the lifecycle rules of `bucket`.
+    ///
+    /// S3 returns a `NoSuchLifecycleConfiguration` error for buckets without
+    /// a lifecycle configuration, these are returned as having no rules.
+    async fn get_bucket_lifecycle_rules(
+        &self,
+        bucket: &str,
+    ) -> Result<Vec<LifecycleRule>> {
+        let input = GetBucketLifecycleConfigurationRequest {
+            bucket: bucket.into(),
+            ..Default::default()
+        };
+
+        let output = timeout_api_call(
+            self.api_timeout,
+            self.client.get_bucket_lifecycle_configuration(input),
+        ).await?;
+
+        let output = match output {
+            Ok(output) => output,
+            Err(RusotoError::Unknown(ref response))
+                if response.body_as_str().contains(NO_SUCH_LIFECYCLE_CONFIGURATION) =>
+            {
+                debug!(
+                    bucket = bucket,
+                    "get_bucket_lifecycle_rules: No lifecycle configuration",
+                );
+
+                return Ok(Vec::new());
+            },
+            Err(e) => return Err(S3duError::from(e).into()),
+        };
+
+        Ok(output.rules.unwrap_or_default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_helpers::s3_client;
+    use pretty_assertions::assert_eq;
+    use rusoto_mock::{
+        MockCredentialsProvider,
+        MockRequestDispatcher,
+        MockResponseReader,
+        MultipleMockRequestDispatcher,
+        ReadMockResponse,
+    };
+    use rusoto_s3::S3Client;
+
+    // Create a mock S3 client returning the versioning configuration in
+    // versioning_file, followed by the given status and the lifecycle
+    // configuration in lifecycle_file.
+    fn mock_client(
+        versioning_file: &str,
+        status:          u16,
+        lifecycle_file:  &str,
+    ) -> Client {
+        let versioning = MockResponseReader::read_response(
+            "test-data",
+            versioning_file,
+        );
+
+        let lifecycle = MockResponseReader::read_response(
+            "test-data",
+            lifecycle_file,
+        );
+
+        let dispatchers = vec![
+            MockRequestDispatcher::default().with_body(&versioning),
+            MockRequestDispatcher::with_status(status).with_body(&lifecycle),
+        ];
+
+        let client = S3Client::new_with(
+            MultipleMockRequestDispatcher::new(dispatchers),
+            MockCredentialsProvider,
+            Default::default()
+        );
+
+        Client {
+            check_versioning: true,
+            ..s3_client(client)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_versioning_lifecycle_warning() {
+        let warning = "test-bucket has versioning enabled but no expiry rule for old versions";
+
+        let tests = vec![
+            (
+                "s3-get-bucket-versioning-off.xml",
+                200,
+                "s3-get-bucket-lifecycle-configuration.xml",
+                None,
+            ),
+            (
+                "s3-get-bucket-versioning-enabled.xml",
+                200,
+                "s3-get-bucket-lifecycle-configuration.xml",
+                None,
+            ),
+            (
+                "s3-get-bucket-versioning-enabled.xml",
+                200,
+                "s3-get-bucket-lifecycle-configuration-no-expiry.xml",
+                Some(warning),
+            ),
+            (
+                "s3-get-bucket-versioning-enabled.xml",
+                404,
+                "s3-get-bucket-lifecycle-configuration-no-such-lifecycle-configuration.xml",
+                Some(warning),
+            ),
+            (
+                "s3-get-bucket-versioning-suspended.xml",
+                404,
+                "s3-get-bucket-lifecycle-configuration-no-such-lifecycle-configuration.xml",
+                None,
+            ),
+        ];
+
+        for test in tests {
+            let (versioning_file, status, lifecycle_file, expected) = test;
+
+            let client = mock_client(versioning_file, status, lifecycle_file);
+
+            let ret = client.versioning_lifecycle_warning("test-bucket")
+                .await
+                .unwrap();
+
+            assert_eq!(ret, expected.map(|warning| warning.to_string()));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_versioning_lifecycle_warning_access_denied() {
+        let client = mock_client(
+            "s3-get-bucket-versioning-enabled.xml",
+            403,
+            "s3-get-bucket-lifecycle-configuration.xml",
+        );
+
+        let ret = client.versioning_lifecycle_warning("test-bucket")
+            .await
+            .unwrap_err();
+
+        let ret = ret.downcast_ref::<S3duError>().unwrap();
+
+        assert!(matches!(ret, S3duError::AccessDenied(_)));
+    }
+
+    // Create a mock S3 client listing the objects in each of the data_files
+    // in turn.
+    fn mock_list_client(data_files: &[&str]) -> Client {
+        let dispatchers: Vec<MockRequestDispatcher> = data_files.iter()
+            .map(|data_file| {
+                let data = MockResponseReader::read_response(
+                    "test-data",
+                    data_file,
+                );
+
+                MockRequestDispatcher::default().with_body(&data)
+            })
+            .collect();
+
+        let mut client = mock_client(
+            "s3-get-bucket-versioning-off.xml",
+            200,
+            "s3-get-bucket-lifecycle-configuration.xml",
+        );
+
+        client.client = S3Client::new_with(
+            MultipleMockRequestDispatcher::new(dispatchers),
+            MockCredentialsProvider,
+            Default::default()
+        );
+
+        client
+    }
+
+    // Create a mock S3 client returning the given status and the logging
+    // configuration in each of the data_files in turn.
+    fn mock_logging_client(responses: &[(u16, &str)]) -> Client {
+        let dispatchers: Vec<MockRequestDispatcher> = responses.iter()
+            .map(|(status, data_file)| {
+                let data = MockResponseReader::read_response(
+                    "test-data",
+                    data_file,
+                );
+
+                MockRequestDispatcher::with_status(*status).with_body(&data)
+            })
+            .collect();
+
+        let mut client = mock_list_client(&[]);
+
+        client.client = S3Client::new_with(
+            MultipleMockRequestDispatcher::new(dispatchers),
+            MockCredentialsProvider,
+            Default::default()
+        );
+
+        client
+    }
+
+    #[tokio::test]
+    async fn test_logging_check() {
+        let enabled = "test-bucket access logging is enabled to log-bucket with prefix 'logs/test-bucket/'";
+        let warning = "test-bucket delivers access logs to log-bucket, which has access logging enabled, each log delivery is logged again";
+
+        let tests = vec![
+            (
+                vec![
+                    (200, "s3-get-bucket-logging-disabled.xml"),
+                ],
+                "test-bucket access logging is disabled",
+                None,
+            ),
+            (
+                vec![
+                    (200, "s3-get-bucket-logging.xml"),
+                    (200, "s3-get-bucket-logging-disabled.xml"),
+                ],
+                enabled,
+                None,
+            ),
+            (
+                vec![
+                    (200, "s3-get-bucket-logging.xml"),
+                    (200, "s3-get-bucket-logging.xml"),
+                ],
+                enabled,
+                Some(warning),
+            ),
+            // The target bucket belongs to another account.
+            (
+                vec![
+                    (200, "s3-get-bucket-logging.xml"),
+                    (403, "s3-get-bucket-logging-disabled.xml"),
+                ],
+                enabled,
+                None,
+            ),
+            // The target bucket isn't looked up for buckets logging to
+            // themselves, the mock only has a single response.
+            (
+                vec![
+                    (200, "s3-get-bucket-logging-self.xml"),
+                ],
+                "test-bucket access logging is enabled to test-bucket with prefix 'logs/'",
+                Some("test-bucket delivers access logs to itself, each log delivery is logged again"),
+            ),
+        ];
+
+        for test in tests {
+            let (responses, status, warning) = test;
+
+            let client = mock_logging_client(&responses);
+
+            let ret = client.logging_check("test-bucket").await.unwrap();
+
+            let expected = (
+                status.to_string(),
+                warning.map(|warning| warning.to_string()),
+            );
+
+            assert_eq!(ret, expected);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_logging_check_access_denied() {
+        let client = mock_logging_client(&[
+            (403, "s3-get-bucket-logging-disabled.xml"),
+        ]);
+
+        let ret = client.logging_check("test-bucket").await.unwrap_err();
+
+        let ret = ret.downcast_ref::<S3duError>().unwrap();
+
+        assert!(matches!(ret, S3duError::AccessDenied(_)));
+    }
+
+    #[tokio::test]
+    async fn test_namespace_warning() {
+        let warning = "test-bucket is a namespace bucket with more than 1 top-level prefixes, consider S3 Inventory instead of listing it";
+
+        let tests = vec![
+            (1, Some(warning)),
+            (2, None),
+        ];
+
+        for test in tests {
+            let (threshold, expected) = test;
+
+            let client = mock_list_client(&[
+                "s3-list-objects-delimiter-root.xml",
+            ]);
+
+            let ret = client.namespace_warning("test-bucket", threshold)
+                .await
+                .unwrap();
+
+            assert_eq!(ret, expected.map(|warning| warning.to_string()));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_namespace_warning_truncated() {
+        // Prefixes are counted across pages, two on each page.
+        let tests = vec![
+            (3, true),
+            (4, false),
+        ];
+
+        for test in tests {
+            let (threshold, expected) = test;
+
+            let client = mock_list_client(&[
+                "s3-list-objects-delimiter-truncated.xml",
+                "s3-list-objects-delimiter-root.xml",
+            ]);
+
+            let ret = client.namespace_warning("test-bucket", threshold)
+                .await
+                .unwrap();
+
+            assert_eq!(ret.is_some(), expected);
+        }
+
+        // Listing stops once the threshold is passed, the mock only has a
+        // single page to return.
+        let client = mock_list_client(&[
+            "s3-list-objects-delimiter-truncated.xml",
+        ]);
+
+        let ret = client.namespace_warning("test-bucket", 1)
+            .await
+            .unwrap();
+
+        assert!(ret.is_some());
+    }
+}
@@ -2,69 +2,318 @@
 #![forbid(unsafe_code)]
 #![deny(missing_docs)]
 use anyhow::Result;
+use chrono::{
+    DateTime,
+    Utc,
+};
 use crate::common::{
+    Bucket,
     BucketNames,
+    Buckets,
+    BucketStats,
     ClientConfig,
+    MetadataStats,
     ObjectVersions,
+    PrefixNode,
+    S3duError,
+    StorageClass,
+    billable_size,
+    timeout_api_call,
+    with_api_timeout,
 };
-use log::debug;
+use crate::credentials;
+use futures::future;
 use rayon::prelude::*;
 use rusoto_core::Region;
 use rusoto_s3::{
     HeadBucketRequest,
     GetBucketLocationRequest,
-    ListMultipartUploadsRequest,
     ListObjectsV2Request,
     ListObjectVersionsRequest,
-    ListPartsRequest,
+    Object,
     S3,
     S3Client,
 };
+use std::collections::HashMap;
+use std::ops::Range;
 use std::str::FromStr;
+use std::sync::Mutex;
+use std::time::Duration;
+use tracing::debug;
+use super::shard::{
+    KeyRange,
+    key_ranges,
+};
+
+/// Returns an object or part `size` from S3 as a `u64`.
+///
+/// Sizes are typed as `i64` by Rusoto, S3 should never return a negative size
+/// but if it does it's treated as `0`.
+pub fn object_size(size: i64) -> u64 {
+    if size > 0 {
+        size as u64
+    }
+    else {
+        0
+    }
+}
 
 /// The S3 `Client`.
 pub struct Client {
     /// The Rusoto `S3Client`.
     pub client: S3Client,
 
+    /// Maximum time that each API call may take, if any.
+    pub api_timeout: Option<Duration>,
+
+    /// Cache of the `Buckets` discovered by this client.
+    pub bucket_cache: Mutex<Option<Buckets>>,
+
+    /// Cache of the object age histogram reports made while sizing buckets,
+    /// keyed on bucket name.
+    pub age_histogram_cache: Mutex<HashMap<String, Vec<String>>>,
+
     /// Selected bucket name, if any.
     pub bucket_name: Option<String>,
 
+    /// Whether the server access logging status of buckets is shown while
+    /// they're sized.
+    pub check_logging: bool,
+
+    /// Whether buckets are checked for versioning without a lifecycle rule
+    /// expiring old versions while they're sized.
+    pub check_versioning: bool,
+
+    /// Number of bucket metadata API calls to make at once.
+    pub chunk_size: usize,
+
+    /// Whether object versions and delete markers are counted into the
+    /// `stats_cache`.
+    pub count_versions: bool,
+
+    /// Only buckets created within this half-open range are listed, if
+    /// given.
+    pub creation_date_range: Option<Range<DateTime<Utc>>>,
+
+    /// Delimiter used to group objects into prefixes, if any.
+    pub delimiter: Option<String>,
+
+    /// Cache of the duplicate object reports made while sizing buckets,
+    /// keyed on bucket name.
+    pub duplicate_cache: Mutex<HashMap<String, Vec<String>>>,
+
+    /// Regions that buckets are skipped in once their location is found.
+    pub exclude_regions: Vec<Region>,
+
+    /// Storage classes that are excluded from the bucket size.
+    pub excluded_storage_classes: Vec<StorageClass>,
+
+    /// Whether duplicate objects are reported after sizing each bucket.
+    pub find_duplicates: bool,
+
+    /// Whether incomplete multipart uploads are added to the bucket size.
+    pub include_multipart_uploads: bool,
+
+    /// Whether object sizes are raised to the minimum billable object size
+    /// of their storage class.
+    pub include_size_on_disk: bool,
+
+    /// Maximum depth of prefixes to list when a `delimiter` is set.
+    pub max_depth: usize,
+
+    /// Maximum number of objects that may be listed in a bucket before
+    /// sizing it is abandoned, if any.
+    pub max_objects: Option<usize>,
+
+    /// Cache of the `MetadataStats` sampled while sizing buckets, keyed on
+    /// bucket name.
+    pub metadata_cache: Mutex<HashMap<String, MetadataStats>>,
+
+    /// Number of objects to sample the metadata of in each bucket, if any.
+    pub metadata_sample_size: Option<usize>,
+
+    /// Only objects last modified after this time are sized, if given.
+    pub modified_after: Option<DateTime<Utc>>,
+
+    /// Only objects last modified before this time are sized, if given.
+    pub modified_before: Option<DateTime<Utc>>,
+
+    /// Cache of the sizes of the incomplete multipart uploads added to bucket
+    /// sizes, keyed on bucket name.
+    pub multipart_cache: Mutex<HashMap<String, u64>>,
+
+    /// Buckets with more than this many top-level prefixes are warned about
+    /// as namespace buckets, if given.
+    pub namespace_threshold: Option<usize>,
+
+    /// Whether requests are unsigned, in which case buckets can't be listed
+    /// and the named bucket is assumed to be in our `region`.
+    pub no_sign_request: bool,
+
+    /// Whether a histogram of object ages is reported after sizing each
+    /// bucket.
+    pub object_age_histogram: bool,
+
+    /// Cache of the current objects sized in each bucket, keyed on bucket
+    /// name, kept for the reports that need every object.
+    pub object_cache: Mutex<HashMap<String, Vec<Object>>>,
+
+    /// `(key, value)` tags that objects must have to be sized.
+    pub object_tag_filters: Vec<(String, String)>,
+
     /// Configuration for which objects to list in the bucket.
     pub object_versions: ObjectVersions,
 
+    /// Maximum number of keys requested in each page of object listings, if
+    /// not the S3 default.
+    pub page_size: Option<i64>,
+
+    /// Cache of the `PrefixNode` trees built while sizing buckets, keyed on
+    /// bucket name.
+    pub prefix_cache: Mutex<HashMap<String, PrefixNode>>,
+
     /// `Region` that we're listing buckets in.
     pub region: Region,
+
+    /// Prefixes that bucket listings are split at for concurrent listing.
+    pub shard_prefixes: Vec<String>,
+
+    /// Cache of the `BucketStats` counted while sizing buckets, keyed on
+    /// bucket name.
+    pub stats_cache: Mutex<HashMap<String, BucketStats>>,
+
+    /// `(key, value)` tags that buckets must have to be sized.
+    pub tag_filters: Vec<(String, String)>,
 }
 
 impl Client {
     /// Return a new S3 `Client` with the given `ClientConfig`.
     pub fn new(config: ClientConfig) -> Self {
+        let aws_client  = credentials::aws_client(&config);
         let bucket_name = config.bucket_name;
         let region      = config.region;
 
         debug!(
-            "new: Creating S3Client in region '{}'",
-            region.name(),
+            region = region.name(),
+            "new: Creating S3Client",
         );
 
-        let client = S3Client::new(region.to_owned());
+        let client = S3Client::new_with_client(
+            aws_client,
+            region.to_owned(),
+        );
 
         Self {
-            client:          client,
-            bucket_name:     bucket_name,
-            object_versions: config.object_versions,
-            region:          region,
+            client:                    client,
+            age_histogram_cache:       Mutex::new(HashMap::new()),
+            api_timeout:               config.api_timeout,
+            bucket_cache:              Mutex::new(None),
+            bucket_name:               bucket_name,
+            check_logging:             config.check_logging,
+            check_versioning:          config.check_versioning,
+            chunk_size:                config.chunk_size,
+            count_versions:            config.count_versions,
+            creation_date_range:       config.creation_date_range,
+            delimiter:                 config.delimiter,
+            duplicate_cache:           Mutex::new(HashMap::new()),
+            exclude_regions:           config.exclude_regions,
+            excluded_storage_classes:  config.excluded_storage_classes,
+            find_duplicates:           config.find_duplicates,
+            include_multipart_uploads: config.include_multipart_uploads,
+            include_size_on_disk:      config.include_size_on_disk,
+            max_depth:                 config.max_depth,
+            max_objects:               config.max_objects,
+            metadata_cache:            Mutex::new(HashMap::new()),
+            metadata_sample_size:      config.metadata_sample_size,
+            modified_after:            config.modified_after,
+            modified_before:           config.modified_before,
+            multipart_cache:           Mutex::new(HashMap::new()),
+            namespace_threshold:       config.namespace_threshold,
+            no_sign_request:           config.no_sign_request,
+            object_age_histogram:      config.object_age_histogram,
+            object_cache:              Mutex::new(HashMap::new()),
+            object_tag_filters:        config.object_tag_filters,
+            object_versions:           config.object_versions,
+            page_size:                 config.page_size,
+            prefix_cache:              Mutex::new(HashMap::new()),
+            region:                    region,
+            shard_prefixes:            config.shard_prefixes,
+            stats_cache:               Mutex::new(HashMap::new()),
+            tag_filters:               config.tag_filters,
+        }
+    }
+
+    /// Returns a `Bucket` for the given `bucket` name if it is in the region
+    /// of our client and we have access to it, otherwise returns `None`.
+    ///
+    /// This makes the per-bucket metadata API calls required for discovery.
+    pub async fn discover_bucket(&self, bucket: String) -> Result<Option<Bucket>> {
+        debug!(bucket = %bucket, "Retrieving location");
+
+        let region = self.get_bucket_location(&bucket).await?;
+
+        if self.exclude_regions.contains(&region) {
+            debug!(bucket = %bucket, region = region.name(), "Region excluded");
+
+            return Ok(None);
+        }
+
+        // We can only ListBucket for the region our S3 client is in, so
+        // we filter for that region here.
+        if region != self.region && !self.is_custom_client_region() {
+            return Ok(None);
         }
+
+        self.check_bucket(bucket, region).await
+    }
+
+    /// Returns a `Bucket` for the given `bucket` name in `region` if we have
+    /// access to it and it matches our tag filters, otherwise returns `None`.
+    ///
+    /// The `bucket` must be in the region of our client.
+    pub async fn check_bucket(
+        &self,
+        bucket: String,
+        region: Region,
+    ) -> Result<Option<Bucket>> {
+        // If we don't have access to the bucket, skip it.
+        if !self.head_bucket(&bucket).await {
+            debug!(bucket = %bucket, "Access denied");
+
+            return Ok(None);
+        }
+
+        // If the bucket doesn't have the tags we're filtering on, skip it.
+        if !self.matches_tag_filters(&bucket).await? {
+            debug!(bucket = %bucket, "Tags don't match");
+
+            return Ok(None);
+        }
+
+        let bucket = Bucket {
+            alias:         None,
+            name:          bucket,
+            region:        Some(region),
+            storage_types: None,
+        };
+
+        Ok(Some(bucket))
     }
 
     /// Returns a list of bucket names.
+    ///
+    /// If we were given a `creation_date_range`, only the buckets created
+    /// within it are returned.
     pub async fn list_buckets(&self) -> Result<BucketNames> {
-        let output = self.client.list_buckets().await?;
+        let output = with_api_timeout(
+            self.api_timeout,
+            self.client.list_buckets(),
+        ).await?;
 
         let bucket_names = if let Some(buckets) = output.buckets {
             buckets
                 .par_iter()
+                .filter(|b| self.created_in_range(b.creation_date.as_deref()))
                 .filter_map(|b| b.name.to_owned())
                 .collect()
         }
@@ -75,23 +324,56 @@ impl Client {
         Ok(bucket_names)
     }
 
+    /// Returns `true` if a bucket with the given `creation_date` is within
+    /// our `creation_date_range`, or if we weren't given a range.
+    ///
+    /// Buckets without a creation date can't be placed in a range, so they're
+    /// never within one.
+    fn created_in_range(&self, creation_date: Option<&str>) -> bool {
+        let range = match &self.creation_date_range {
+            Some(range) => range,
+            None        => return true,
+        };
+
+        let created = creation_date
+            .and_then(|date| DateTime::parse_from_rfc3339(date).ok());
+
+        match created {
+            Some(created) => range.contains(&created.with_timezone(&Utc)),
+            None          => {
+                debug!(
+                    creation_date = ?creation_date,
+                    "created_in_range: Couldn't parse creation date",
+                );
+
+                false
+            },
+        }
+    }
+
     /// Return the bucket location (`Region`) for the given `bucket`.
     ///
     /// This method will properly handle the case of the `null` (empty) and
     /// `EU` location constraints, by replacing them with `us-east-1` and
     /// `eu-west-1` respectively.
     pub async fn get_bucket_location(&self, bucket: &str) -> Result<Region> {
-        debug!("get_bucket_location for '{}'", bucket);
+        debug!(bucket = bucket, "get_bucket_location");
 
         let input = GetBucketLocationRequest {
             bucket: bucket.to_owned(),
             ..Default::default()
         };
 
-        let output   = self.client.get_bucket_location(input).await?;
-        let location = output.location_constraint.expect("location");
+        let output = with_api_timeout(
+            self.api_timeout,
+            self.client.get_bucket_location(input),
+        ).await?;
 
-        debug!("GetBucketLocation API returned '{}'", location);
+        // Buckets in us-east-1 have no location constraint, which may be
+        // returned as either a missing or an empty location constraint.
+        let location = output.location_constraint.unwrap_or_default();
+
+        debug!(location = %location, "GetBucketLocation API returned");
 
         // Location constraints for sufficiently old buckets in S3 may not
         // quite meet expectations. These returns are badly documented and the
@@ -102,9 +384,10 @@ impl Client {
             _    => location,
         };
 
-        let location = Region::from_str(&location)?;
+        let location = Region::from_str(&location)
+            .map_err(|e| S3duError::InvalidRegion(e.to_string()))?;
 
-        debug!("Final location: {:?}", location);
+        debug!(location = ?location, "Final location");
 
         Ok(location)
     }
@@ -112,89 +395,162 @@ impl Client {
     /// Returns a `bool` indicating if we have access to the given `bucket` or
     /// not.
     pub async fn head_bucket(&self, bucket: &str) -> bool {
-        debug!("head_bucket for '{}'", bucket);
+        debug!(bucket = bucket, "head_bucket");
 
         let input = HeadBucketRequest {
             bucket: bucket.into(),
             ..Default::default()
         };
 
-        let output = self.client.head_bucket(input).await;
+        let output = timeout_api_call(
+            self.api_timeout,
+            self.client.head_bucket(input),
+        ).await;
 
-        debug!("head_bucket output for '{}' -> '{:?}'", bucket, output);
+        debug!(bucket = bucket, output = ?output, "head_bucket output");
 
-        output.is_ok()
+        matches!(output, Ok(Ok(_)))
     }
 
-    /// Returns a bool indicating if the region is a custom region
-    pub fn is_custom_client_region(&self) -> bool {
-        matches!(self.region, Region::Custom { .. })
+    /// Returns a `bool` indicating if objects in the given `storage_class`
+    /// are excluded from the bucket size.
+    pub fn is_excluded_storage_class(&self, storage_class: &Option<String>) -> bool {
+        if self.excluded_storage_classes.is_empty() {
+            return false;
+        }
+
+        let storage_class = StorageClass::from(storage_class.to_owned());
+
+        self.excluded_storage_classes.contains(&storage_class)
     }
 
-    /// List in-progress multipart uploads
-    async fn size_multipart_uploads(&self, bucket: &str) -> Result<usize> {
-        let mut key_marker       = None;
-        let mut size             = 0;
-        let mut upload_id_marker = None;
+    /// Returns the size of an object of `size` bytes in `storage_class`.
+    ///
+    /// If we're including the size on disk, this is raised to the minimum
+    /// billable object size of the storage class.
+    pub fn stored_size(&self, size: i64, storage_class: &Option<String>) -> u64 {
+        let size = object_size(size);
 
-        loop {
-            let input = ListMultipartUploadsRequest {
-                bucket:           bucket.into(),
-                key_marker:       key_marker.to_owned(),
-                upload_id_marker: upload_id_marker.to_owned(),
-                ..Default::default()
-            };
+        if !self.include_size_on_disk {
+            return size;
+        }
 
-            let output = self.client.list_multipart_uploads(input).await?;
+        let storage_class = StorageClass::from(storage_class.to_owned());
 
-            if let Some(uploads) = output.uploads {
-                // No iterator here since we need to call an async method.
-                for upload in uploads {
-                    let key       = upload.key.expect("upload key");
-                    let upload_id = upload.upload_id.expect("upload_id");
+        billable_size(size, &storage_class)
+    }
 
-                    size += self.size_parts(bucket, &key, &upload_id).await?;
-                }
-            }
+    /// Returns a `bool` indicating if an object with the given `last_modified`
+    /// time is within our `modified_after` and `modified_before` times.
+    ///
+    /// The range includes `modified_after` and excludes `modified_before`, so
+    /// that whole days can be given as consecutive midnights.
+    ///
+    /// Objects with a missing or unparseable `last_modified` time can't be
+    /// placed in the range, so they are skipped with a warning.
+    pub fn is_in_modified_range(
+        &self,
+        key: &Option<String>,
+        last_modified: &Option<String>,
+    ) -> bool {
+        if self.modified_after.is_none() && self.modified_before.is_none() {
+            return true;
+        }
 
-            if let Some(true) = output.is_truncated {
-                key_marker       = output.next_key_marker;
-                upload_id_marker = output.next_upload_id_marker;
-            }
-            else {
-                break;
-            }
+        let last_modified = last_modified.as_deref().unwrap_or_default();
+
+        let last_modified = match DateTime::parse_from_rfc3339(last_modified) {
+            Ok(dt) => dt.with_timezone(&Utc),
+            Err(_) => {
+                warning!(
+                    "Skipping '{}', could not parse last modified time '{}'",
+                    key.as_deref().unwrap_or_default(),
+                    last_modified,
+                );
+
+                return false;
+            },
+        };
+
+        let after = match self.modified_after {
+            Some(after) => last_modified >= after,
+            None        => true,
+        };
+
+        let before = match self.modified_before {
+            Some(before) => last_modified < before,
+            None         => true,
+        };
+
+        after && before
+    }
+
+    /// Returns the total stored size of the `objects`.
+    pub fn objects_size(&self, objects: &[&Object]) -> u64 {
+        objects
+            .par_iter()
+            .filter_map(|o| {
+                o.size.map(|size| self.stored_size(size, &o.storage_class))
+            })
+            .sum()
+    }
+
+    /// Keeps the sized `objects` of `bucket` in our `object_cache`, if a
+    /// report that needs every object was requested.
+    ///
+    /// Objects are kept as each page is sized, so the reports cover exactly
+    /// the objects that were sized, without listing the bucket again.
+    pub fn keep_objects(&self, bucket: &str, objects: &[&Object]) {
+        if !self.find_duplicates && !self.object_age_histogram {
+            return;
         }
 
-        Ok(size)
+        self.object_cache.lock().unwrap()
+            .entry(bucket.into())
+            .or_default()
+            .extend(objects.iter().map(|o| (*o).to_owned()));
+    }
+
+    /// Returns a bool indicating if the region is a custom region
+    pub fn is_custom_client_region(&self) -> bool {
+        matches!(self.region, Region::Custom { .. })
     }
 
     /// List object versions and filter according to `ObjectVersions`.
     ///
     /// This will be used when the size of `All` or `NonCurrent` objects is
-    /// requested.
-    async fn size_object_versions(&self, bucket: &str) -> Result<usize> {
-        debug!("size_object_versions for '{}'", bucket);
+    /// requested. Delete markers are returned separately from versions and
+    /// have no size, so they never add to the size.
+    ///
+    /// If we're counting versions, the number of versions included in the
+    /// size and the number of delete markers are stored in the `stats_cache`.
+    async fn size_object_versions(&self, bucket: &str) -> Result<u64> {
+        debug!(bucket = bucket, "size_object_versions");
 
         let mut next_key_marker        = None;
         let mut next_version_id_marker = None;
         let mut size                   = 0;
+        let mut stats                  = BucketStats::default();
 
         // Loop until all object versions are processed
         loop {
             let input = ListObjectVersionsRequest {
                 bucket:            bucket.into(),
                 key_marker:        next_key_marker.to_owned(),
+                max_keys:          self.page_size,
                 version_id_marker: next_version_id_marker.to_owned(),
                 ..Default::default()
             };
 
-            let output = self.client.list_object_versions(input).await?;
+            let output = with_api_timeout(
+                self.api_timeout,
+                self.client.list_object_versions(input),
+            ).await?;
 
             // Depending on which object versions we're paying attention to,
             // we may or may not filter here.
             if let Some(versions) = output.versions {
-                size += versions
+                let sizes: Vec<u64> = versions
                     .par_iter()
                     .filter_map(|v| {
                         // Here we take out object version selection into
@@ -205,7 +561,15 @@ impl Client {
                         // Multipart isn't handled here.
                         let is_latest = v.is_latest.unwrap();
 
-                        match self.object_versions {
+                        if self.is_excluded_storage_class(&v.storage_class) {
+                            return None;
+                        }
+
+                        if !self.is_in_modified_range(&v.key, &v.last_modified) {
+                            return None;
+                        }
+
+                        let size = match self.object_versions {
                             ObjectVersions::All     => v.size,
                             ObjectVersions::Current => {
                                 if is_latest {
@@ -224,13 +588,25 @@ impl Client {
                                     v.size
                                 }
                             },
-                        }
+                        };
+
+                        size.map(|size| self.stored_size(size, &v.storage_class))
                     })
-                    .sum::<i64>() as usize;
+                    .collect();
+
+                stats.version_count += sizes.len();
+
+                size += sizes.iter().sum::<u64>();
+            }
+
+            if let Some(delete_markers) = output.delete_markers {
+                stats.delete_marker_count += delete_markers.len();
             }
 
             // Check if we need to continue processing bucket output and store
             // the continuation tokens for the next loop if so.
+            // A missing is_truncated (None) is treated as not truncated, even
+            // if markers were returned, so we can't loop forever.
             if let Some(true) = output.is_truncated {
                 next_key_marker        = output.next_key_marker;
                 next_version_id_marker = output.next_version_id_marker;
@@ -240,16 +616,41 @@ impl Client {
             }
         }
 
+        if self.count_versions {
+            debug!(bucket = bucket, stats = %stats, "size_object_versions: Counted");
+
+            self.stats_cache.lock().unwrap().insert(bucket.into(), stats);
+        }
+
         Ok(size)
     }
 
-    /// Return the size of current object versions in the bucket.
+    /// Return the size of current object versions in the bucket, optionally
+    /// limited to objects under `prefix`, within the given key `range`.
+    ///
+    /// Sizes are summed as each page of objects is listed, so only a single
+    /// page of objects is held in memory at once, no matter how large the
+    /// bucket is.
+    ///
+    /// If we were given `max_objects`, listing is abandoned with an error
+    /// giving the partial size once more objects than that have been listed.
     ///
     /// This will be used when the size of `Current` objects is requested.
-    async fn size_current_objects(&self, bucket: &str) -> Result<usize> {
-        debug!("size_current_objects for '{}'", bucket);
+    pub async fn size_current_objects(
+        &self,
+        bucket: &str,
+        prefix: Option<&str>,
+        range: &KeyRange,
+    ) -> Result<u64> {
+        debug!(
+            bucket = bucket,
+            prefix = ?prefix,
+            range  = ?range,
+            "size_current_objects",
+        );
 
         let mut continuation_token = None;
+        let mut listed             = 0;
         let mut size               = 0;
 
         // Loop until all objects are processed.
@@ -257,17 +658,66 @@ impl Client {
             let input = ListObjectsV2Request {
                 bucket:             bucket.into(),
                 continuation_token: continuation_token.to_owned(),
+                max_keys:           self.page_size,
+                prefix:             prefix.map(|p| p.into()),
+                start_after:        range.start_after.to_owned(),
                 ..Default::default()
             };
 
-            let output = self.client.list_objects_v2(input).await?;
+            let output = with_api_timeout(
+                self.api_timeout,
+                self.client.list_objects_v2(input),
+            ).await?;
+
+            // Keys are listed in order, so once we've seen a key past the end
+            // of our range, there's nothing more for us to list.
+            let mut past_end = false;
 
             // Process the contents and add up the sizes
             if let Some(contents) = output.contents {
-                size += contents
+                let last_key = contents.last()
+                    .and_then(|o| o.key.as_ref());
+
+                past_end = matches!(last_key, Some(key) if range.is_past_end(key));
+
+                let objects: Vec<&Object> = contents
                     .par_iter()
-                    .filter_map(|o| o.size)
-                    .sum::<i64>() as usize;
+                    .filter(|o| matches!(&o.key, Some(key) if range.contains(key)))
+                    .filter(|o| !self.is_excluded_storage_class(&o.storage_class))
+                    .filter(|o| self.is_in_modified_range(&o.key, &o.last_modified))
+                    .collect();
+
+                // Object tags can only be checked with an API call for each
+                // object, so these are made outside of the parallel iterator.
+                let objects = if self.object_tag_filters.is_empty() {
+                    objects
+                }
+                else {
+                    self.tagged_objects(bucket, &objects).await?
+                };
+
+                size += self.objects_size(&objects);
+
+                self.keep_objects(bucket, &objects);
+
+                listed += contents.len();
+            }
+
+            if let Some(max_objects) = self.max_objects {
+                if listed > max_objects {
+                    let message = format!(
+                        "more than {} objects listed in {}, {} bytes sized so far",
+                        max_objects,
+                        bucket,
+                        size,
+                    );
+
+                    return Err(S3duError::MaxObjects(message).into());
+                }
+            }
+
+            if past_end {
+                break;
             }
 
             // If the output was truncated (Some(true)), we should have a
@@ -286,73 +736,73 @@ impl Client {
 
     /// A wrapper to call the appropriate bucket sizing function depending on
     /// the `ObjectVersions` configuration the `Client` was created with.
-    pub async fn size_objects(&self, bucket: &str) -> Result<usize> {
-        debug!("size_objects: '{}' with {:?}", bucket, self.object_versions);
+    pub async fn size_objects(&self, bucket: &str) -> Result<u64> {
+        debug!(bucket = bucket, versions = ?self.object_versions, "size_objects");
 
-        match self.object_versions {
+        let size = match self.object_versions {
             ObjectVersions::All => {
                 let mut size = 0;
 
                 size += self.size_multipart_uploads(bucket).await?;
                 size += self.size_object_versions(bucket).await?;
 
-                Ok(size)
+                size
             },
             ObjectVersions::Current => {
-                self.size_current_objects(bucket).await
+                if self.shard_prefixes.is_empty() {
+                    self.size_current_objects(
+                        bucket,
+                        None,
+                        &KeyRange::default(),
+                    ).await?
+                }
+                else {
+                    self.size_sharded_objects(bucket).await?
+                }
             },
             ObjectVersions::Multipart => {
-                self.size_multipart_uploads(bucket).await
+                self.size_multipart_uploads(bucket).await?
             },
             ObjectVersions::NonCurrent => {
-                self.size_object_versions(bucket).await
+                self.size_object_versions(bucket).await?
             },
-        }
-    }
-
-    /// List parts of an in-progress multipart upload
-    async fn size_parts(
-        &self,
-        bucket: &str,
-        key: &str,
-        upload_id: &str,
-    ) -> Result<usize> {
-        let mut part_number_marker = None;
-        let mut size               = 0;
+        };
 
-        loop {
-            let input = ListPartsRequest {
-                bucket:             bucket.into(),
-                key:                key.into(),
-                part_number_marker: part_number_marker.to_owned(),
-                upload_id:          upload_id.into(),
-                ..Default::default()
-            };
+        Ok(size + self.size_incomplete_uploads(bucket).await?)
+    }
 
-            let output = self.client.list_parts(input).await?;
+    /// Return the size of current object versions in the bucket, listing
+    /// the key ranges between our `shard_prefixes` concurrently.
+    ///
+    /// This will be used when the size of `Current` objects is requested and
+    /// `shard_prefixes` were given.
+    async fn size_sharded_objects(&self, bucket: &str) -> Result<u64> {
+        let ranges = key_ranges(&self.shard_prefixes);
 
-            if let Some(parts) = output.parts {
-                size += parts
-                    .par_iter()
-                    .filter_map(|p| p.size)
-                    .sum::<i64>() as usize;
-            }
+        debug!(
+            bucket = bucket,
+            shards = ranges.len(),
+            "size_sharded_objects",
+        );
 
-            if let Some(true) = output.is_truncated {
-                part_number_marker = output.next_part_number_marker;
-            }
-            else {
-                break;
-            }
-        }
+        let sizes = future::try_join_all(
+            ranges.iter()
+                .map(|range| self.size_current_objects(bucket, None, range))
+        ).await?;
 
-        Ok(size)
+        Ok(sizes.iter().sum())
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::test_helpers::{
+        dispatcher_with_body,
+        mock_client,
+        s3_client,
+    };
+    use chrono::TimeZone;
     use pretty_assertions::assert_eq;
     use rusoto_mock::{
         MockCredentialsProvider,
@@ -362,38 +812,6 @@ mod tests {
         ReadMockResponse,
     };
 
-    // Create a mock S3 client, returning the data from the specified
-    // data_file.
-    fn mock_client(
-        data_file: Option<&str>,
-        versions:  ObjectVersions,
-    ) -> Client {
-        let data = match data_file {
-            None    => "".to_string(),
-            Some(d) => MockResponseReader::read_response("test-data", d.into()),
-        };
-
-        let client = S3Client::new_with(
-            MockRequestDispatcher::default().with_body(&data),
-            MockCredentialsProvider,
-            Default::default()
-        );
-
-        Client {
-            client:          client,
-            bucket_name:     None,
-            object_versions: versions,
-            region:          Region::UsEast1,
-        }
-    }
-
-    // Return a MockRequestDispatcher with a body given by the data_file.
-    fn dispatcher_with_body(data_file: &str) -> MockRequestDispatcher {
-        let data = MockResponseReader::read_response("test-data", data_file);
-
-        MockRequestDispatcher::default().with_body(&data)
-    }
-
     // Create a mock client that returns a specific status code and empty
     // response body.
     fn mock_client_with_status(status: u16) -> Client {
@@ -405,12 +823,7 @@ mod tests {
             Default::default()
         );
 
-        Client {
-            client:          client,
-            bucket_name:     None,
-            object_versions: ObjectVersions::Current,
-            region:          Region::UsEast1,
-        }
+        s3_client(client)
     }
 
     #[tokio::test]
@@ -432,6 +845,139 @@ mod tests {
         }
     }
 
+    #[tokio::test]
+    async fn test_discover_bucket() {
+        let tests = vec![
+            (Region::EuWest1, true),
+            (Region::UsEast1, false),
+        ];
+
+        for test in tests {
+            let region   = test.0;
+            let expected = test.1;
+
+            let mock = MultipleMockRequestDispatcher::new(vec![
+                dispatcher_with_body("s3-get-bucket-location.xml"),
+                MockRequestDispatcher::with_status(200),
+            ]);
+
+            let s3client = S3Client::new_with(
+                mock,
+                MockCredentialsProvider,
+                region.to_owned(),
+            );
+
+            let client = Client {
+                region: region,
+                ..s3_client(s3client)
+            };
+
+            let ret = Client::discover_bucket(&client, "test-bucket".into())
+                .await
+                .unwrap();
+
+            assert_eq!(ret.is_some(), expected);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_discover_bucket_excluded_region() {
+        // The bucket is in our region, but the region is excluded, so access
+        // to the bucket is never checked.
+        let mock = MultipleMockRequestDispatcher::new(vec![
+            dispatcher_with_body("s3-get-bucket-location.xml"),
+        ]);
+
+        let s3client = S3Client::new_with(
+            mock,
+            MockCredentialsProvider,
+            Region::EuWest1,
+        );
+
+        let client = Client {
+            client:          s3client,
+            exclude_regions: vec![Region::ApSoutheast1, Region::EuWest1],
+            region:          Region::EuWest1,
+            ..mock_client(None, ObjectVersions::Current)
+        };
+
+        let ret = Client::discover_bucket(&client, "test-bucket".into())
+            .await
+            .unwrap();
+
+        assert_eq!(ret, None);
+    }
+
+    #[tokio::test]
+    async fn test_discover_bucket_tag_filters() {
+        let tests = vec![
+            ("s3-get-bucket-tagging.xml",                 200, true),
+            ("s3-get-bucket-tagging-no-such-tag-set.xml", 404, false),
+        ];
+
+        for test in tests {
+            let tagging  = test.0;
+            let status   = test.1;
+            let expected = test.2;
+
+            let data = MockResponseReader::read_response("test-data", tagging);
+
+            let mock = MultipleMockRequestDispatcher::new(vec![
+                dispatcher_with_body("s3-get-bucket-location.xml"),
+                MockRequestDispatcher::with_status(200),
+                MockRequestDispatcher::with_status(status).with_body(&data),
+            ]);
+
+            let mut client = mock_client(None, ObjectVersions::Current);
+            client.region      = Region::EuWest1;
+            client.tag_filters = vec![("team".into(), "platform".into())];
+            client.client      = S3Client::new_with(
+                mock,
+                MockCredentialsProvider,
+                Region::EuWest1,
+            );
+
+            let ret = Client::discover_bucket(&client, "test-bucket".into())
+                .await
+                .unwrap();
+
+            assert_eq!(ret.is_some(), expected);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_discover_bucket_us_east_1() {
+        // Buckets in us-east-1 have an empty or missing location constraint,
+        // they must not be filtered out by a client in us-east-1. An empty
+        // response body results in a missing location constraint.
+        let tests = vec![
+            dispatcher_with_body("s3-get-bucket-location-null.xml"),
+            MockRequestDispatcher::with_status(200),
+        ];
+
+        for location in tests {
+            let mock = MultipleMockRequestDispatcher::new(vec![
+                location,
+                MockRequestDispatcher::with_status(200),
+            ]);
+
+            let s3client = S3Client::new_with(
+                mock,
+                MockCredentialsProvider,
+                Region::UsEast1,
+            );
+
+            let client = s3_client(s3client);
+
+            let ret = Client::discover_bucket(&client, "test-bucket".into())
+                .await
+                .unwrap()
+                .unwrap();
+
+            assert_eq!(ret.region, Some(Region::UsEast1));
+        }
+    }
+
     #[tokio::test]
     async fn test_get_bucket_location_err() {
         let client = mock_client(
@@ -511,43 +1057,80 @@ mod tests {
     }
 
     #[tokio::test]
-    async fn test_size_multipart_uploads() {
-        let expected = 204800;
+    async fn test_list_buckets_creation_date_range() {
+        let date = |s| DateTime::parse_from_rfc3339(s)
+            .unwrap()
+            .with_timezone(&Utc);
 
-        let mock = MultipleMockRequestDispatcher::new(vec![
-            dispatcher_with_body("s3-list-multipart-uploads.xml"),
-            dispatcher_with_body("s3-list-parts.xml"),
-        ]);
+        // a-bucket-name was created at 2020-03-12T10:57:32Z and
+        // another-bucket-name at 2020-03-10T10:58:12Z.
+        let tests = vec![
+            (
+                "2020-03-01T00:00:00Z",
+                "2020-04-01T00:00:00Z",
+                vec!["a-bucket-name", "another-bucket-name"],
+            ),
+            (
+                "2020-03-11T00:00:00Z",
+                "2020-04-01T00:00:00Z",
+                vec!["a-bucket-name"],
+            ),
+            // The end of the range is excluded.
+            (
+                "2020-03-10T10:58:12Z",
+                "2020-03-12T10:57:32Z",
+                vec!["another-bucket-name"],
+            ),
+            (
+                "2021-01-01T00:00:00+01:00",
+                "2022-01-01T00:00:00+01:00",
+                vec![],
+            ),
+        ];
 
-        let s3client = S3Client::new_with(
-            mock,
-            MockCredentialsProvider,
-            Default::default(),
-        );
+        for test in tests {
+            let (start, end, expected) = test;
 
-        let mut client = Client {
-            client:          s3client,
-            bucket_name:     Some("test-bucket".into()),
-            object_versions: ObjectVersions::Current,
-            region:          Default::default(),
-        };
+            let mut client = mock_client(
+                Some("s3-list-buckets.xml"),
+                ObjectVersions::Current,
+            );
 
-        let size = Client::size_multipart_uploads(
-            &mut client,
-            "test-bucket",
-        ).await.unwrap();
+            client.creation_date_range = Some(date(start)..date(end));
+
+            let mut ret = Client::list_buckets(&client).await.unwrap();
+            ret.sort();
 
-        assert_eq!(size, expected);
+            assert_eq!(ret, expected);
+        }
+    }
+
+    #[test]
+    fn test_created_in_range() {
+        let mut client = mock_client(None, ObjectVersions::Current);
+
+        // Without a range, every bucket is included.
+        assert!(client.created_in_range(None));
+
+        let start = Utc.ymd(2020, 3, 1).and_hms(0, 0, 0);
+        let end   = Utc.ymd(2020, 4, 1).and_hms(0, 0, 0);
+
+        client.creation_date_range = Some(start..end);
+
+        assert!(client.created_in_range(Some("2020-03-12T10:57:32.000Z")));
+        assert!(!client.created_in_range(Some("2020-04-01T00:00:00.000Z")));
+        assert!(!client.created_in_range(Some("not a date")));
+        assert!(!client.created_in_range(None));
     }
 
     #[tokio::test]
     async fn test_size_objects_current() {
-        let mut client = mock_client(
+        let client = mock_client(
             Some("s3-list-objects.xml"),
             ObjectVersions::Current,
         );
 
-        let ret = Client::size_objects(&mut client, "test-bucket")
+        let ret = Client::size_objects(&client, "test-bucket")
             .await
             .unwrap();
 
@@ -556,6 +1139,433 @@ mod tests {
         assert_eq!(ret, expected);
     }
 
+    // Sizes beyond 4GiB must not be truncated, even on 32-bit targets, and
+    // negative sizes are ignored.
+    #[tokio::test]
+    async fn test_size_objects_larger_than_4gib() {
+        let client = mock_client(
+            Some("s3-list-objects-large.xml"),
+            ObjectVersions::Current,
+        );
+
+        let ret = Client::size_objects(&client, "test-bucket")
+            .await
+            .unwrap();
+
+        let expected: u64 = 8 * 1024 * 1024 * 1024;
+
+        assert_eq!(ret, expected);
+    }
+
+    #[test]
+    fn test_object_size() {
+        let tests = vec![
+            (0,           0),
+            (1024,        1024),
+            (5368709120,  5368709120),
+            (-1,          0),
+            (i64::MIN,    0),
+        ];
+
+        for test in tests {
+            let size     = test.0;
+            let expected = test.1;
+
+            assert_eq!(object_size(size), expected);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_size_current_objects_key_range() {
+        let client = mock_client(
+            Some("s3-list-objects.xml"),
+            ObjectVersions::Current,
+        );
+
+        let tests = vec![
+            (None,          None,          33_792),
+            (None,          Some("file1"), 1_024),
+            (Some("file1"), None,          32_768),
+            (Some("file1"), Some("file1"), 0),
+        ];
+
+        for test in tests {
+            let range = KeyRange {
+                start_after: test.0.map(|s: &str| s.into()),
+                end:         test.1.map(|s: &str| s.into()),
+            };
+
+            let expected = test.2;
+
+            let ret = client.size_current_objects("test-bucket", None, &range)
+                .await
+                .unwrap();
+
+            assert_eq!(ret, expected);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_size_current_objects_paginated() {
+        // The second page must be requested with the continuation token from
+        // the first.
+        let second_page = dispatcher_with_body("s3-list-objects.xml")
+            .with_request_checker(|request| {
+                let token = request.params
+                    .get("continuation-token")
+                    .cloned()
+                    .flatten();
+
+                let expected = "1ueGcxLPRx1Tr/XYExHnhbYLgveDs2J/wm36Hy4vbOwM=";
+
+                assert_eq!(token.as_deref(), Some(expected));
+            });
+
+        let mock = MultipleMockRequestDispatcher::new(vec![
+            dispatcher_with_body("s3-list-objects-truncated.xml"),
+            second_page,
+        ]);
+
+        let mut client = mock_client(None, ObjectVersions::Current);
+        client.client = S3Client::new_with(
+            mock,
+            MockCredentialsProvider,
+            Default::default(),
+        );
+
+        let ret = client.size_current_objects(
+            "test-bucket",
+            None,
+            &KeyRange::default(),
+        ).await.unwrap();
+
+        // The sum of every object across both pages.
+        let expected = 2_048 + 1_024 + 32_768;
+
+        assert_eq!(ret, expected);
+    }
+
+    #[tokio::test]
+    async fn test_size_current_objects_page_size() {
+        // Every page must be requested with our page size, and each page
+        // after the first with the continuation token from the one before.
+        let page = |data_file, expected_token: Option<&'static str>| {
+            dispatcher_with_body(data_file)
+                .with_request_checker(move |request| {
+                    let max_keys = request.params
+                        .get("max-keys")
+                        .cloned()
+                        .flatten();
+
+                    let token = request.params
+                        .get("continuation-token")
+                        .cloned()
+                        .flatten();
+
+                    assert_eq!(max_keys.as_deref(), Some("1"));
+                    assert_eq!(token.as_deref(), expected_token);
+                })
+        };
+
+        let mock = MultipleMockRequestDispatcher::new(vec![
+            page("s3-list-objects-truncated.xml", None),
+            page("s3-list-objects-page-2.xml", Some("1ueGcxLPRx1Tr/XYExHnhbYLgveDs2J/wm36Hy4vbOwM=")),
+            page("s3-list-objects-page-3.xml", Some("1Z2ntoPPoWqcD1Lk8D8u6nRLfmhYXPLbwpQb7PVLOkA=")),
+        ]);
+
+        let mut client = mock_client(None, ObjectVersions::Current);
+        client.client = S3Client::new_with(
+            mock,
+            MockCredentialsProvider,
+            Default::default(),
+        );
+        client.page_size = Some(1);
+
+        let ret = client.size_current_objects(
+            "test-bucket",
+            None,
+            &KeyRange::default(),
+        ).await.unwrap();
+
+        // The sum of the single object on each of the three pages.
+        let expected = 2_048 + 4_096 + 8_192;
+
+        assert_eq!(ret, expected);
+    }
+
+    #[tokio::test]
+    async fn test_size_current_objects_max_objects() {
+        // The first page has one object, the second has two.
+        let tests = vec![
+            (Some(1), Err("more than 1 objects listed in test-bucket, 35840 bytes sized so far")),
+            (Some(2), Err("more than 2 objects listed in test-bucket, 35840 bytes sized so far")),
+            (Some(3), Ok(35_840)),
+            (None,    Ok(35_840)),
+        ];
+
+        for test in tests {
+            let (max_objects, expected) = test;
+
+            let mock = MultipleMockRequestDispatcher::new(vec![
+                dispatcher_with_body("s3-list-objects-truncated.xml"),
+                dispatcher_with_body("s3-list-objects.xml"),
+            ]);
+
+            let mut client = mock_client(None, ObjectVersions::Current);
+            client.client = S3Client::new_with(
+                mock,
+                MockCredentialsProvider,
+                Default::default(),
+            );
+            client.max_objects = max_objects;
+
+            let ret = client.size_current_objects(
+                "test-bucket",
+                None,
+                &KeyRange::default(),
+            ).await;
+
+            let ret = ret.map_err(|e| {
+                match e.downcast_ref::<S3duError>() {
+                    Some(S3duError::MaxObjects(message)) => message.to_owned(),
+                    _                                   => panic!("{}", e),
+                }
+            });
+
+            assert_eq!(ret, expected.map_err(|e| e.to_string()));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_size_current_objects_stops_past_range_end() {
+        // Only the first page is mocked. The listing is truncated, but the
+        // last key is already past the end of the range, so no further pages
+        // may be requested.
+        let mock = MultipleMockRequestDispatcher::new(vec![
+            dispatcher_with_body("s3-list-objects-truncated.xml"),
+        ]);
+
+        let mut client = mock_client(None, ObjectVersions::Current);
+        client.client = S3Client::new_with(
+            mock,
+            MockCredentialsProvider,
+            Default::default(),
+        );
+
+        let range = KeyRange {
+            start_after: None,
+            end:         Some("file".into()),
+        };
+
+        let ret = client.size_current_objects("test-bucket", None, &range)
+            .await
+            .unwrap();
+
+        assert_eq!(ret, 0);
+    }
+
+    #[tokio::test]
+    async fn test_size_objects_sharded() {
+        // Sharding must not change the size, no matter where the shards are
+        // split.
+        let tests = vec![
+            vec![],
+            vec!["file1"],
+            vec!["file0", "file1", "file2", "file3"],
+            vec!["a", "m", "z"],
+        ];
+
+        for test in tests {
+            let mut client = mock_client(
+                Some("s3-list-objects.xml"),
+                ObjectVersions::Current,
+            );
+
+            client.shard_prefixes = test.iter()
+                .map(|p| p.to_string())
+                .collect();
+
+            let ret = Client::size_objects(&client, "test-bucket")
+                .await
+                .unwrap();
+
+            assert_eq!(ret, 33_792);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_size_objects_include_multipart_uploads() {
+        // Current objects are listed first, followed by the incomplete
+        // multipart uploads and their parts.
+        let tests = vec![
+            (false, 33_792),
+            (true,  33_792 + 204_800),
+        ];
+
+        for test in tests {
+            let include  = test.0;
+            let expected = test.1;
+
+            let mock = MultipleMockRequestDispatcher::new(vec![
+                dispatcher_with_body("s3-list-objects.xml"),
+                dispatcher_with_body("s3-list-multipart-uploads.xml"),
+                dispatcher_with_body("s3-list-parts.xml"),
+            ]);
+
+            let mut client = mock_client(None, ObjectVersions::Current);
+            client.include_multipart_uploads = include;
+            client.client = S3Client::new_with(
+                mock,
+                MockCredentialsProvider,
+                Default::default(),
+            );
+
+            let ret = Client::size_objects(&client, "test-bucket")
+                .await
+                .unwrap();
+
+            assert_eq!(ret, expected);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_size_objects_excluded_storage_classes() {
+        let tests = vec![
+            (vec![],                                                         15_360),
+            (vec![StorageClass::GlacierFlexible],                            11_264),
+            (vec![StorageClass::DeepArchive, StorageClass::GlacierFlexible], 3_072),
+        ];
+
+        for test in tests {
+            let excluded = test.0;
+            let expected = test.1;
+
+            let mut client = mock_client(
+                Some("s3-list-objects-storage-classes.xml"),
+                ObjectVersions::Current,
+            );
+
+            client.excluded_storage_classes = excluded;
+
+            let ret = Client::size_objects(&client, "test-bucket")
+                .await
+                .unwrap();
+
+            assert_eq!(ret, expected);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_size_objects_include_size_on_disk() {
+        let tests = vec![
+            (false, 15_360),
+            (true,  1_024 + 131_072 + 40_960 + 40_960),
+        ];
+
+        for test in tests {
+            let include  = test.0;
+            let expected = test.1;
+
+            let mut client = mock_client(
+                Some("s3-list-objects-storage-classes.xml"),
+                ObjectVersions::Current,
+            );
+
+            client.include_size_on_disk = include;
+
+            let ret = Client::size_objects(&client, "test-bucket")
+                .await
+                .unwrap();
+
+            assert_eq!(ret, expected);
+        }
+    }
+
+    #[test]
+    fn test_is_in_modified_range() {
+        let after  = Some(Utc.ymd(2019, 1, 1).and_hms(0, 0, 0));
+        let before = Some(Utc.ymd(2021, 1, 1).and_hms(0, 0, 0));
+
+        let tests = vec![
+            (None,  None,   "2018-06-01T00:00:00.000Z",  true),
+            (None,  None,   "not-a-timestamp",           true),
+            (after, None,   "2018-06-01T00:00:00.000Z",  false),
+            (after, None,   "2018-12-31T23:59:59.999Z",  false),
+            (after, None,   "2019-01-01T00:00:00.000Z",  true),
+            (after, None,   "2020-06-01T00:00:00.000Z",  true),
+            (None,  before, "2020-06-01T00:00:00.000Z",  true),
+            (None,  before, "2021-06-01T00:00:00.000Z",  false),
+            (None,  before, "2020-12-31T23:59:59.999Z",  true),
+            (after, before, "2020-06-01T00:00:00+01:00", true),
+            (after, before, "2021-01-01T00:30:00+01:00", true),
+            (after, before, "2021-01-01T00:00:00.000Z",  false),
+            (after, before, "not-a-timestamp",           false),
+            (after, before, "",                          false),
+        ];
+
+        for test in tests {
+            let mut client = mock_client(None, ObjectVersions::Current);
+            client.modified_after  = test.0;
+            client.modified_before = test.1;
+
+            let last_modified = Some(test.2.to_string());
+            let expected      = test.3;
+
+            let ret = client.is_in_modified_range(
+                &Some("test-object".into()),
+                &last_modified,
+            );
+
+            assert_eq!(ret, expected);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_size_objects_modified_range() {
+        let after  = Some(Utc.ymd(2019, 1, 1).and_hms(0, 0, 0));
+        let before = Some(Utc.ymd(2021, 1, 1).and_hms(0, 0, 0));
+
+        // The object with an unparseable timestamp is only counted when no
+        // range is given.
+        let tests = vec![
+            (None,  None,   1_024 + 2_048 + 4_096 + 8_192 + 16_384),
+            (after, None,   2_048 + 4_096 + 8_192),
+            (None,  before, 1_024 + 2_048 + 4_096),
+            (after, before, 2_048 + 4_096),
+        ];
+
+        for test in tests {
+            let expected = test.2;
+
+            let mut client = mock_client(
+                Some("s3-list-objects-dates.xml"),
+                ObjectVersions::Current,
+            );
+
+            client.modified_after  = test.0;
+            client.modified_before = test.1;
+
+            let ret = Client::size_objects(&client, "test-bucket")
+                .await
+                .unwrap();
+
+            assert_eq!(ret, expected);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_size_objects_access_denied() {
+        let client = mock_client_with_status(403);
+
+        let ret = Client::size_objects(&client, "test-bucket")
+            .await
+            .unwrap_err();
+
+        let ret = ret.downcast_ref::<S3duError>().unwrap();
+
+        assert!(matches!(ret, S3duError::AccessDenied(_)));
+    }
+
     #[tokio::test]
     async fn test_size_objects_all_noncurrent() {
         // Current expects 0 here because our mock client won't return any
@@ -570,12 +1580,12 @@ mod tests {
             let versions      = test.0;
             let expected_size = test.1;
 
-            let mut client = mock_client(
+            let client = mock_client(
                 Some("s3-list-object-versions.xml"),
                 versions,
             );
 
-            let ret = Client::size_objects(&mut client, "test-bucket")
+            let ret = Client::size_objects(&client, "test-bucket")
                 .await
                 .unwrap();
 
@@ -584,21 +1594,102 @@ mod tests {
     }
 
     #[tokio::test]
-    async fn test_size_parts() {
-        let client = mock_client(
-            Some("s3-list-parts.xml"),
-            ObjectVersions::Current,
+    async fn test_size_object_versions_multiple() {
+        // report.csv has one current and two non-current versions.
+        // deleted.csv has a delete marker as its current version and one
+        // non-current version.
+        let tests = vec![
+            (ObjectVersions::All,        4_096 + 2_048 + 1_024 + 512),
+            (ObjectVersions::Current,    4_096),
+            (ObjectVersions::NonCurrent, 2_048 + 1_024 + 512),
+        ];
+
+        for test in tests {
+            let versions = test.0;
+            let expected = test.1;
+
+            let client = mock_client(
+                Some("s3-list-object-versions-multiple.xml"),
+                versions,
+            );
+
+            let ret = Client::size_object_versions(&client, "test-bucket")
+                .await
+                .unwrap();
+
+            assert_eq!(ret, expected);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_size_object_versions_no_truncated() {
+        // The response has markers but no IsTruncated. Only one response is
+        // available, so requesting another page would panic.
+        let mock = MultipleMockRequestDispatcher::new(vec![
+            dispatcher_with_body("s3-list-object-versions-no-truncated.xml"),
+        ]);
+
+        let s3client = S3Client::new_with(
+            mock,
+            MockCredentialsProvider,
+            Default::default(),
         );
 
-        let ret = Client::size_parts(
-            &client,
-            "test-bucket",
-            "test.zip",
-            "abc123",
-        ).await.unwrap();
+        let client = Client {
+            client: s3client,
+            ..mock_client(None, ObjectVersions::All)
+        };
 
-        let expected = 1024 * 100 * 2;
+        let ret = Client::size_object_versions(&client, "test-bucket")
+            .await
+            .unwrap();
 
-        assert_eq!(ret, expected);
+        assert_eq!(ret, 4_096 + 2_048 + 1_024 + 512);
+    }
+
+    #[tokio::test]
+    async fn test_size_object_versions_count() {
+        // Only versions included in the size are counted, but all delete
+        // markers are.
+        let tests = vec![
+            (ObjectVersions::All,        4_096 + 2_048 + 1_024 + 512, 4, 1),
+            (ObjectVersions::NonCurrent, 2_048 + 1_024 + 512,         3, 1),
+        ];
+
+        for test in tests {
+            let versions = test.0;
+            let expected = test.1;
+
+            let expected_stats = BucketStats {
+                version_count:       test.2,
+                delete_marker_count: test.3,
+            };
+
+            let mut client = mock_client(
+                Some("s3-list-object-versions-multiple.xml"),
+                versions,
+            );
+
+            client.count_versions = true;
+
+            let ret = Client::size_object_versions(&client, "test-bucket")
+                .await
+                .unwrap();
+
+            let stats = client.stats_cache.lock().unwrap().remove("test-bucket");
+
+            assert_eq!(ret, expected);
+            assert_eq!(stats, Some(expected_stats));
+        }
+
+        // Nothing is counted unless we asked for counts.
+        let client = mock_client(
+            Some("s3-list-object-versions-multiple.xml"),
+            ObjectVersions::All,
+        );
+
+        Client::size_object_versions(&client, "test-bucket").await.unwrap();
+
+        assert!(client.stats_cache.lock().unwrap().is_empty());
     }
 }
@@ -0,0 +1,285 @@
+// Implements detection of duplicate objects for the S3 Client
+#![forbid(unsafe_code)]
+#![deny(missing_docs)]
+use rusoto_s3::Object;
+use std::collections::HashMap;
+use tracing::debug;
+use super::client::object_size;
+
+/// `DuplicateGroup` is a group of objects with the same ETag and size, which
+/// are very likely to be copies of each other.
+#[derive(Debug, Eq, PartialEq)]
+pub struct DuplicateGroup {
+    /// ETag shared by the objects, including its quotes.
+    pub etag: String,
+
+    /// Size of each of the objects in bytes.
+    pub size: u64,
+
+    /// Keys of the objects, ordered by key.
+    pub keys: Vec<String>,
+}
+
+impl DuplicateGroup {
+    /// Returns the space in bytes used by every copy but one.
+    pub fn wasted(&self) -> u64 {
+        self.size * (self.keys.len() as u64 - 1)
+    }
+}
+
+/// Returns the groups of `objects` that share an ETag, ordered by the space
+/// that they waste, most first.
+///
+/// Objects are grouped by size as well as ETag, since multipart ETags only
+/// describe the parts that an object was uploaded in. Empty objects, such as
+/// folder markers, share an ETag but don't waste any space, so they're never
+/// reported.
+pub fn find_duplicates(objects: &[Object]) -> Vec<DuplicateGroup> {
+    let mut groups: HashMap<(&str, u64), Vec<String>> = HashMap::new();
+
+    for object in objects {
+        let (etag, key, size) = match (&object.e_tag, &object.key, object.size) {
+            (Some(etag), Some(key), Some(size)) => (etag, key, object_size(size)),
+            _                                   => continue,
+        };
+
+        if size == 0 {
+            continue;
+        }
+
+        groups.entry((etag, size))
+            .or_default()
+            .push(key.to_owned());
+    }
+
+    let mut duplicates: Vec<DuplicateGroup> = groups
+        .into_iter()
+        .filter(|(_, keys)| keys.len() > 1)
+        .map(|((etag, size), mut keys)| {
+            keys.sort();
+
+            DuplicateGroup {
+                etag: etag.into(),
+                size: size,
+                keys: keys,
+            }
+        })
+        .collect();
+
+    duplicates.sort_by(|a, b| {
+        b.wasted().cmp(&a.wasted())
+            .then_with(|| a.etag.cmp(&b.etag))
+    });
+
+    duplicates
+}
+
+/// Returns the report of the duplicate `groups` found in `bucket`, listing
+/// the storage class and last modified time of each of the `objects` in a
+/// group beneath it.
+fn format_duplicates(
+    bucket: &str,
+    objects: &[Object],
+    groups: &[DuplicateGroup],
+) -> Vec<String> {
+    if groups.is_empty() {
+        return vec![format!("{} has no duplicate objects", bucket)];
+    }
+
+    let objects: HashMap<&str, &Object> = objects.iter()
+        .filter_map(|o| o.key.as_deref().map(|key| (key, o)))
+        .collect();
+
+    let wasted: u64 = groups.iter()
+        .map(DuplicateGroup::wasted)
+        .sum();
+
+    let count = match groups.len() {
+        1 => "1 group".into(),
+        n => format!("{} groups", n),
+    };
+
+    let mut lines = vec![format!(
+        "{} has {} of duplicate objects, wasting {} bytes",
+        bucket,
+        count,
+        wasted,
+    )];
+
+    for group in groups {
+        lines.push(format!(
+            "{} has {} copies of {} bytes with ETag {}, wasting {} bytes",
+            bucket,
+            group.keys.len(),
+            group.size,
+            group.etag,
+            group.wasted(),
+        ));
+
+        for key in &group.keys {
+            let object = objects.get(key.as_str());
+
+            let storage_class = object
+                .and_then(|o| o.storage_class.as_deref())
+                .unwrap_or("STANDARD");
+
+            let last_modified = object
+                .and_then(|o| o.last_modified.as_deref())
+                .unwrap_or("unknown");
+
+            lines.push(format!(
+                "  {} ({}, last modified {})",
+                key,
+                storage_class,
+                last_modified,
+            ));
+        }
+    }
+
+    lines
+}
+
+/// Returns the report of duplicate objects among the `objects` sized in
+/// `bucket`.
+pub fn duplicate_report(bucket: &str, objects: &[Object]) -> Vec<String> {
+    let groups = find_duplicates(objects);
+
+    debug!(
+        bucket = bucket,
+        groups = groups.len(),
+        "duplicate_report: Found duplicates",
+    );
+
+    format_duplicates(bucket, objects, &groups)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::ObjectVersions;
+    use crate::s3::Client;
+    use crate::test_helpers::mock_client;
+    use pretty_assertions::assert_eq;
+
+    // Returns an object with the given key, ETag and size.
+    fn object(key: &str, etag: &str, size: i64) -> Object {
+        Object {
+            e_tag:         Some(etag.into()),
+            key:           Some(key.into()),
+            last_modified: Some("2020-03-12T11:04:09.000Z".into()),
+            size:          Some(size),
+            storage_class: Some("STANDARD".into()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_find_duplicates() {
+        let objects = vec![
+            object("a/report.csv",       "\"aaa\"", 1024),
+            object("b/report.csv",       "\"aaa\"", 1024),
+            object("c/report-copy.csv",  "\"aaa\"", 1024),
+            object("a/image.png",        "\"bbb\"", 4096),
+            object("backup/image.png",   "\"bbb\"", 4096),
+            object("unique.txt",         "\"ccc\"", 512),
+            // Same multipart ETag, but a different size.
+            object("upload-1.bin",       "\"ddd-2\"", 8192),
+            object("upload-2.bin",       "\"ddd-2\"", 16384),
+            // Empty folder markers.
+            object("a/",                 "\"d41d8cd98f00b204e9800998ecf8427e\"", 0),
+            object("b/",                 "\"d41d8cd98f00b204e9800998ecf8427e\"", 0),
+            Object {
+                key: Some("no-etag".into()),
+                size: Some(1024),
+                ..Default::default()
+            },
+        ];
+
+        let ret = find_duplicates(&objects);
+
+        let expected = vec![
+            DuplicateGroup {
+                etag: "\"bbb\"".into(),
+                size: 4096,
+                keys: vec![
+                    "a/image.png".into(),
+                    "backup/image.png".into(),
+                ],
+            },
+            DuplicateGroup {
+                etag: "\"aaa\"".into(),
+                size: 1024,
+                keys: vec![
+                    "a/report.csv".into(),
+                    "b/report.csv".into(),
+                    "c/report-copy.csv".into(),
+                ],
+            },
+        ];
+
+        assert_eq!(ret, expected);
+        assert_eq!(ret[0].wasted(), 4096);
+        assert_eq!(ret[1].wasted(), 2048);
+    }
+
+    #[test]
+    fn test_find_duplicates_none() {
+        let objects = vec![
+            object("file1", "\"aaa\"", 1024),
+            object("file2", "\"bbb\"", 1024),
+        ];
+
+        assert_eq!(find_duplicates(&objects), Vec::new());
+        assert_eq!(find_duplicates(&[]), Vec::new());
+    }
+
+    #[test]
+    fn test_format_duplicates() {
+        let objects = vec![
+            object("a/report.csv", "\"aaa\"", 1024),
+            object("b/report.csv", "\"aaa\"", 1024),
+        ];
+
+        let groups = find_duplicates(&objects);
+        let ret    = format_duplicates("test-bucket", &objects, &groups);
+
+        let expected = vec![
+            "test-bucket has 1 group of duplicate objects, wasting 1024 bytes",
+            "test-bucket has 2 copies of 1024 bytes with ETag \"aaa\", wasting 1024 bytes",
+            "  a/report.csv (STANDARD, last modified 2020-03-12T11:04:09.000Z)",
+            "  b/report.csv (STANDARD, last modified 2020-03-12T11:04:09.000Z)",
+        ];
+
+        assert_eq!(ret, expected);
+
+        let ret = format_duplicates("test-bucket", &objects, &[]);
+
+        assert_eq!(ret, vec!["test-bucket has no duplicate objects"]);
+    }
+
+    #[tokio::test]
+    async fn test_duplicate_report() {
+        let client = Client {
+            find_duplicates: true,
+            ..mock_client(Some("s3-list-objects-duplicates.xml"), ObjectVersions::Current)
+        };
+
+        client.size_objects("test-bucket").await.unwrap();
+
+        let objects = client.object_cache.lock().unwrap()
+            .remove("test-bucket")
+            .unwrap();
+
+        let ret = duplicate_report("test-bucket", &objects);
+
+        let expected = vec![
+            "test-bucket has 1 group of duplicate objects, wasting 2048 bytes",
+            "test-bucket has 3 copies of 1024 bytes with ETag \"1d921b22129502cbbe5cbaf2c8bac682\", wasting 2048 bytes",
+            "  2020/file1 (STANDARD, last modified 2020-03-12T11:04:09.000Z)",
+            "  2021/file1 (GLACIER, last modified 2021-01-04T09:00:00.000Z)",
+            "  file1 (STANDARD, last modified 2020-03-10T11:05:09.000Z)",
+        ];
+
+        assert_eq!(ret, expected);
+    }
+}
@@ -0,0 +1,411 @@
+// Lists the sizes and active jobs of Glacier vaults alongside S3 buckets
+#![forbid(unsafe_code)]
+#![deny(missing_docs)]
+use anyhow::Result;
+use async_trait::async_trait;
+use chrono::{
+    DateTime,
+    Duration as ChronoDuration,
+    Utc,
+};
+use crate::common::{
+    ClientConfig,
+    StorageResource,
+    StorageSizer,
+    with_api_timeout,
+};
+use crate::credentials;
+use rusoto_glacier::{
+    DescribeVaultOutput,
+    Glacier,
+    GlacierClient,
+    GlacierJobDescription,
+    ListJobsInput,
+    ListVaultsInput,
+};
+use std::time::Duration;
+use tracing::debug;
+use super::client::object_size;
+
+/// Account ID that refers to the account owning the credentials in Glacier
+/// requests.
+const CURRENT_ACCOUNT: &str = "-";
+
+/// Annotation that vaults are shown with in the output.
+const GLACIER_VAULT: &str = "GLACIER_VAULT";
+
+/// `VaultJob` is a job that's still active in a Glacier vault.
+#[derive(Debug, PartialEq)]
+pub struct VaultJob {
+    /// Name of the vault that the job is running in.
+    pub vault: String,
+
+    /// ID of the job.
+    pub job_id: String,
+
+    /// The job type, `Select`, `ArchiveRetrieval` or `InventoryRetrieval`.
+    pub action: String,
+
+    /// Status of the job, which is `InProgress` for active jobs.
+    pub status: String,
+
+    /// Time that the job was created at, if Glacier reported it.
+    pub created: Option<DateTime<Utc>>,
+}
+
+impl VaultJob {
+    /// Returns the `job` described by Glacier as running in `vault`.
+    fn new(vault: &str, job: GlacierJobDescription) -> Self {
+        let created = job.creation_date
+            .and_then(|date| DateTime::parse_from_rfc3339(&date).ok())
+            .map(|date| date.with_timezone(&Utc));
+
+        Self {
+            vault:   vault.into(),
+            job_id:  job.job_id.unwrap_or_default(),
+            action:  job.action.unwrap_or_default(),
+            status:  job.status_code.unwrap_or_default(),
+            created: created,
+        }
+    }
+}
+
+/// Returns the `elapsed` time in days, hours and minutes, leaving out the
+/// leading units that are zero.
+fn format_elapsed(elapsed: ChronoDuration) -> String {
+    let minutes = elapsed.num_minutes().max(0);
+    let days    = minutes / (24 * 60);
+    let hours   = minutes / 60 % 24;
+    let minutes = minutes % 60;
+
+    match (days, hours) {
+        (0, 0) => format!("{}m", minutes),
+        (0, _) => format!("{}h {}m", hours, minutes),
+        _      => format!("{}d {}h {}m", days, hours, minutes),
+    }
+}
+
+/// Returns the report of the active `job`, with the time that it has been
+/// running for at `now`.
+pub fn format_vault_job(job: &VaultJob, now: DateTime<Utc>) -> String {
+    let elapsed = match job.created {
+        Some(created) => format!("running for {}", format_elapsed(now - created)),
+        None          => "running for an unknown time".into(),
+    };
+
+    format!(
+        "'{}' has a {} job {}, {}: {}",
+        job.vault,
+        job.action,
+        job.status,
+        elapsed,
+        job.job_id,
+    )
+}
+
+/// Returns the `vault` as a `StorageResource`, sized as of its last
+/// inventory.
+///
+/// Vaults that haven't been inventoried yet have no timestamp.
+fn vault_resource(vault: DescribeVaultOutput) -> StorageResource {
+    let inventory_date = vault.last_inventory_date
+        .and_then(|date| DateTime::parse_from_rfc3339(&date).ok())
+        .map(|date| date.with_timezone(&Utc));
+
+    StorageResource {
+        name:          vault.vault_name.unwrap_or_default(),
+        resource_type: None,
+        size:          object_size(vault.size_in_bytes.unwrap_or(0)),
+        timestamp:     inventory_date,
+        used:          None,
+    }
+}
+
+/// `VaultLister` lists the Glacier vaults in a region.
+pub struct VaultLister {
+    /// The Rusoto `GlacierClient`.
+    pub client: GlacierClient,
+
+    /// Maximum time that each API call may take, if any.
+    pub api_timeout: Option<Duration>,
+}
+
+impl VaultLister {
+    /// Return a new `VaultLister` for the region in the given `ClientConfig`.
+    pub fn new(config: &ClientConfig) -> Self {
+        let aws_client = credentials::aws_client(config);
+
+        debug!(
+            region = config.region.name(),
+            "new: Creating GlacierClient",
+        );
+
+        let client = GlacierClient::new_with_client(
+            aws_client,
+            config.region.to_owned(),
+        );
+
+        Self {
+            client:      client,
+            api_timeout: config.api_timeout,
+        }
+    }
+
+    /// Returns the descriptions of every Glacier vault in the region, in the
+    /// order that Glacier lists them.
+    async fn vaults(&self) -> Result<Vec<DescribeVaultOutput>> {
+        let mut marker = None;
+        let mut vaults = Vec::new();
+
+        loop {
+            let input = ListVaultsInput {
+                account_id: CURRENT_ACCOUNT.into(),
+                marker:     marker,
+                ..Default::default()
+            };
+
+            let output = with_api_timeout(
+                self.api_timeout,
+                self.client.list_vaults(input),
+            ).await?;
+
+            if let Some(vault_list) = output.vault_list {
+                vaults.extend(vault_list);
+            }
+
+            match output.marker {
+                Some(next) => marker = Some(next),
+                None       => break,
+            }
+        }
+
+        debug!(vaults = vaults.len(), "vaults: Got vaults");
+
+        Ok(vaults)
+    }
+
+    /// Returns the jobs in `vault` that haven't completed yet.
+    async fn vault_jobs(&self, vault: &str) -> Result<Vec<VaultJob>> {
+        let mut marker = None;
+        let mut jobs   = Vec::new();
+
+        loop {
+            let input = ListJobsInput {
+                account_id: CURRENT_ACCOUNT.into(),
+                completed:  Some("false".into()),
+                marker:     marker,
+                vault_name: vault.into(),
+                ..Default::default()
+            };
+
+            let output = with_api_timeout(
+                self.api_timeout,
+                self.client.list_jobs(input),
+            ).await?;
+
+            if let Some(job_list) = output.job_list {
+                jobs.extend(job_list.into_iter().map(|job| VaultJob::new(vault, job)));
+            }
+
+            match output.marker {
+                Some(next) => marker = Some(next),
+                None       => break,
+            }
+        }
+
+        debug!(vault = vault, jobs = jobs.len(), "vault_jobs: Got jobs");
+
+        Ok(jobs)
+    }
+
+    /// Returns the active jobs in every Glacier vault in the region, such as
+    /// Glacier Select and archive retrievals, ordered by vault name.
+    ///
+    /// Retrievals are charged for as they run, so these are reported for
+    /// auditing their costs.
+    pub async fn active_jobs(&self) -> Result<Vec<VaultJob>> {
+        let mut names: Vec<String> = self.vaults()
+            .await?
+            .into_iter()
+            .filter_map(|vault| vault.vault_name)
+            .collect();
+
+        names.sort();
+
+        let mut jobs = Vec::new();
+
+        for name in names {
+            jobs.extend(self.vault_jobs(&name).await?);
+        }
+
+        Ok(jobs)
+    }
+}
+
+#[async_trait]
+impl StorageSizer for VaultLister {
+    fn kind(&self) -> &'static str {
+        GLACIER_VAULT
+    }
+
+    /// Returns the Glacier vaults in the region, ordered by name.
+    ///
+    /// `ListVaults` returns the same description of each vault as
+    /// `DescribeVault`, so vaults aren't described individually.
+    async fn resources(&self) -> Result<Vec<StorageResource>> {
+        let mut vaults: Vec<StorageResource> = self.vaults()
+            .await?
+            .into_iter()
+            .map(vault_resource)
+            .collect();
+
+        vaults.sort_by(|a, b| a.name.cmp(&b.name));
+
+        Ok(vaults)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_helpers::dispatcher_with_body;
+    use chrono::TimeZone;
+    use pretty_assertions::assert_eq;
+    use rusoto_mock::{
+        MockCredentialsProvider,
+        MultipleMockRequestDispatcher,
+    };
+
+    // Create a mock Glacier client, returning the data from the specified
+    // data_file.
+    fn mock_lister(data_file: &str) -> VaultLister {
+        let client = GlacierClient::new_with(
+            dispatcher_with_body(data_file),
+            MockCredentialsProvider,
+            Default::default()
+        );
+
+        VaultLister {
+            client:      client,
+            api_timeout: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_resources() {
+        let lister = mock_lister("glacier-list-vaults.json");
+        let ret    = lister.resources().await.unwrap();
+
+        let expected = vec![
+            StorageResource {
+                name:          "archive-2014".into(),
+                resource_type: None,
+                size:          2_199_023_255_552,
+                timestamp:     Some(Utc.ymd(2020, 9, 30).and_hms(6, 12, 41)),
+                used:          None,
+            },
+            StorageResource {
+                name:          "new-vault".into(),
+                resource_type: None,
+                size:          0,
+                timestamp:     None,
+                used:          None,
+            },
+        ];
+
+        assert_eq!(ret, expected);
+        assert_eq!(lister.kind(), "GLACIER_VAULT");
+    }
+
+    #[test]
+    fn test_format_elapsed() {
+        let tests = vec![
+            (ChronoDuration::seconds(59),   "0m"),
+            (ChronoDuration::minutes(45),   "45m"),
+            (ChronoDuration::minutes(125),  "2h 5m"),
+            (ChronoDuration::minutes(1501), "1d 1h 1m"),
+            (ChronoDuration::minutes(-5),   "0m"),
+        ];
+
+        for test in tests {
+            let (elapsed, expected) = test;
+
+            assert_eq!(format_elapsed(elapsed), expected);
+        }
+    }
+
+    #[test]
+    fn test_format_vault_job() {
+        let now = Utc.ymd(2020, 9, 30).and_hms(8, 5, 0);
+
+        let job = VaultJob {
+            vault:   "archive-2014".into(),
+            job_id:  "job-1".into(),
+            action:  "Select".into(),
+            status:  "InProgress".into(),
+            created: Some(Utc.ymd(2020, 9, 30).and_hms(6, 0, 0)),
+        };
+
+        assert_eq!(
+            format_vault_job(&job, now),
+            "'archive-2014' has a Select job InProgress, running for 2h 5m: job-1",
+        );
+
+        let job = VaultJob {
+            created: None,
+            ..job
+        };
+
+        assert_eq!(
+            format_vault_job(&job, now),
+            "'archive-2014' has a Select job InProgress, running for an unknown time: job-1",
+        );
+    }
+
+    #[tokio::test]
+    async fn test_active_jobs() {
+        // Vaults are listed, then the jobs in each vault in name order.
+        let client = GlacierClient::new_with(
+            MultipleMockRequestDispatcher::new(vec![
+                dispatcher_with_body("glacier-list-vaults.json"),
+                dispatcher_with_body("glacier-list-jobs.json")
+                    .with_request_checker(|request| {
+                        assert!(request.path.contains("/vaults/archive-2014/jobs"));
+                        assert_eq!(request.params.get("completed"), Some(&Some("false".into())));
+                    }),
+                dispatcher_with_body("glacier-list-jobs-empty.json")
+                    .with_request_checker(|request| {
+                        assert!(request.path.contains("/vaults/new-vault/jobs"));
+                    }),
+            ]),
+            MockCredentialsProvider,
+            Default::default(),
+        );
+
+        let lister = VaultLister {
+            client:      client,
+            api_timeout: None,
+        };
+
+        let ret = lister.active_jobs().await.unwrap();
+
+        let expected = vec![
+            VaultJob {
+                vault:   "archive-2014".into(),
+                job_id:  "HkF9p6o7yjhFx-K3CGl6fuSm6VzW9T7esGQfco8nUXVYwS0jlb5gq1JZ55yHgt5vP54ZShjoQzQVVh7vEXAMPLEjobID".into(),
+                action:  "Select".into(),
+                status:  "InProgress".into(),
+                created: Some(Utc.ymd(2020, 9, 30).and_hms(6, 0, 0)),
+            },
+            VaultJob {
+                vault:   "archive-2014".into(),
+                job_id:  "l7IL5-EkXyEY9Ws95fClzIbk2O5uLYaFdAYOi-azsX_Z8V6NH4yERHzars8wTKYQMX6nBDI9cMNHzyZJO59-8N9aHWav".into(),
+                action:  "ArchiveRetrieval".into(),
+                status:  "InProgress".into(),
+                created: None,
+            },
+        ];
+
+        assert_eq!(ret, expected);
+    }
+}
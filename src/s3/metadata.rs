@@ -0,0 +1,242 @@
+// Implements object metadata sampling for the S3 Client
+#![forbid(unsafe_code)]
+#![deny(missing_docs)]
+use anyhow::Result;
+use crate::common::{
+    MetadataStats,
+    with_api_timeout,
+};
+use futures::stream::{
+    self,
+    StreamExt,
+};
+use rusoto_s3::{
+    HeadObjectRequest,
+    ListObjectsV2Request,
+    S3,
+};
+use std::collections::BinaryHeap;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{
+    Hash,
+    Hasher,
+};
+use tracing::debug;
+use super::client::Client;
+
+/// Returns the hash used to decide which keys are sampled.
+fn sample_hash(key: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    key.hash(&mut hasher);
+    hasher.finish()
+}
+
+impl Client {
+    /// Returns the estimates of the user-defined metadata on the current
+    /// objects in `bucket`, made from a sample of up to `sample_size` objects.
+    ///
+    /// This lists the bucket again to take the sample and makes one
+    /// `HeadObject` call for each sampled object, `chunk_size` at a time.
+    pub async fn sample_object_metadata(
+        &self,
+        bucket: &str,
+        sample_size: usize,
+    ) -> Result<MetadataStats> {
+        debug!(bucket = bucket, "sample_object_metadata");
+
+        let (population, keys) = self.sample_keys(bucket, sample_size).await?;
+
+        let samples: Vec<Result<(usize, usize)>> = stream::iter(keys)
+            .map(|key| self.object_metadata(bucket, key))
+            .buffer_unordered(self.chunk_size)
+            .collect()
+            .await;
+
+        let samples = samples
+            .into_iter()
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(MetadataStats::from_samples(population, &samples))
+    }
+
+    /// Returns the number of metadata entries on the object `key` in `bucket`
+    /// and the size of their keys and values in bytes.
+    async fn object_metadata(
+        &self,
+        bucket: &str,
+        key: String,
+    ) -> Result<(usize, usize)> {
+        let input = HeadObjectRequest {
+            bucket: bucket.into(),
+            key:    key,
+            ..Default::default()
+        };
+
+        let output = with_api_timeout(
+            self.api_timeout,
+            self.client.head_object(input),
+        ).await?;
+
+        let metadata = output.metadata.unwrap_or_default();
+
+        let size = metadata
+            .iter()
+            .map(|(key, value)| key.len() + value.len())
+            .sum();
+
+        Ok((metadata.len(), size))
+    }
+
+    /// Returns the number of current objects in `bucket` that would be
+    /// sized, along with the keys of up to `sample_size` of them.
+    ///
+    /// The keys with the smallest hashes are kept, which spreads the sample
+    /// over the whole bucket without holding every key in memory.
+    async fn sample_keys(
+        &self,
+        bucket: &str,
+        sample_size: usize,
+    ) -> Result<(usize, Vec<String>)> {
+        let mut continuation_token = None;
+        let mut population         = 0;
+        let mut sample             = BinaryHeap::new();
+
+        loop {
+            let input = ListObjectsV2Request {
+                bucket:             bucket.into(),
+                continuation_token: continuation_token.to_owned(),
+                max_keys:           self.page_size,
+                ..Default::default()
+            };
+
+            let output = with_api_timeout(
+                self.api_timeout,
+                self.client.list_objects_v2(input),
+            ).await?;
+
+            let keys = output.contents
+                .unwrap_or_default()
+                .into_iter()
+                .filter(|o| !self.is_excluded_storage_class(&o.storage_class))
+                .filter(|o| self.is_in_modified_range(&o.key, &o.last_modified))
+                .filter_map(|o| o.key);
+
+            for key in keys {
+                population += 1;
+
+                sample.push((sample_hash(&key), key));
+
+                // The largest hash is dropped, keeping the smallest.
+                if sample.len() > sample_size {
+                    sample.pop();
+                }
+            }
+
+            if let Some(true) = output.is_truncated {
+                continuation_token = output.next_continuation_token;
+            }
+            else {
+                break;
+            }
+        }
+
+        let keys = sample
+            .into_iter()
+            .map(|(_, key)| key)
+            .collect();
+
+        Ok((population, keys))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_helpers::{
+        dispatcher_with_body,
+        s3_client,
+    };
+    use pretty_assertions::assert_eq;
+    use rusoto_mock::{
+        MockCredentialsProvider,
+        MockRequestDispatcher,
+        MultipleMockRequestDispatcher,
+    };
+    use rusoto_s3::S3Client;
+
+    // Create a mock S3 client that lists the objects in the specified
+    // data_file, then returns the given metadata headers from each
+    // HeadObject call.
+    fn mock_client(data_file: &str, heads: Vec<Vec<(&str, &str)>>) -> Client {
+        let mut dispatchers = vec![
+            dispatcher_with_body(data_file),
+        ];
+
+        for headers in heads {
+            let mut dispatcher = MockRequestDispatcher::default();
+
+            for (key, value) in headers {
+                dispatcher = dispatcher.with_header(key, value);
+            }
+
+            dispatchers.push(dispatcher);
+        }
+
+        let client = S3Client::new_with(
+            MultipleMockRequestDispatcher::new(dispatchers),
+            MockCredentialsProvider,
+            Default::default()
+        );
+
+        s3_client(client)
+    }
+
+    #[tokio::test]
+    async fn test_sample_keys() {
+        let client = mock_client("s3-list-objects.xml", Vec::new());
+
+        let (population, keys) = client.sample_keys("test-bucket", 1)
+            .await
+            .unwrap();
+
+        // Only the key with the smallest hash is kept.
+        let expected = if sample_hash("file1") < sample_hash("file2") {
+            "file1"
+        }
+        else {
+            "file2"
+        };
+
+        assert_eq!(population, 2);
+        assert_eq!(keys, vec![expected]);
+    }
+
+    #[tokio::test]
+    async fn test_sample_object_metadata() {
+        // Both objects are sampled, one with two metadata entries and one
+        // without any.
+        let heads = vec![
+            vec![
+                ("x-amz-meta-team", "platform"),
+                ("x-amz-meta-owner", "ops"),
+            ],
+            Vec::new(),
+        ];
+
+        let client = mock_client("s3-list-objects.xml", heads);
+
+        let ret = client.sample_object_metadata("test-bucket", 100)
+            .await
+            .unwrap();
+
+        let expected = MetadataStats {
+            population:    2,
+            sampled:       2,
+            average_count: 1.0,
+            average_size:  10.0,
+            size_margin:   Some(0.0),
+        };
+
+        assert_eq!(ret, expected);
+    }
+}
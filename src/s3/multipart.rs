@@ -0,0 +1,243 @@
+// Implements sizing of incomplete multipart uploads for the S3 Client
+#![forbid(unsafe_code)]
+#![deny(missing_docs)]
+use anyhow::Result;
+use crate::common::{
+    ObjectVersions,
+    with_api_timeout,
+};
+use rayon::prelude::*;
+use rusoto_s3::{
+    ListMultipartUploadsRequest,
+    ListPartsRequest,
+    S3,
+};
+use tracing::debug;
+use super::client::{
+    Client,
+    object_size,
+};
+
+impl Client {
+    /// Returns the size of incomplete multipart uploads in the bucket if the
+    /// `Client` was configured to include them, otherwise returns `0`.
+    ///
+    /// `All` and `Multipart` object versions already include incomplete
+    /// multipart uploads, so they aren't counted again here.
+    ///
+    /// Buckets with incomplete multipart uploads have their size stored in
+    /// the `multipart_cache`, so that it can be shown alongside the bucket.
+    pub async fn size_incomplete_uploads(&self, bucket: &str) -> Result<u64> {
+        let already_included = matches!(
+            self.object_versions,
+            ObjectVersions::All | ObjectVersions::Multipart
+        );
+
+        if !self.include_multipart_uploads || already_included {
+            return Ok(0);
+        }
+
+        debug!(bucket = bucket, "size_incomplete_uploads");
+
+        let size = self.size_multipart_uploads(bucket).await?;
+
+        if size > 0 {
+            self.multipart_cache.lock().unwrap().insert(bucket.into(), size);
+        }
+
+        Ok(size)
+    }
+
+    /// List in-progress multipart uploads
+    pub async fn size_multipart_uploads(&self, bucket: &str) -> Result<u64> {
+        let mut key_marker       = None;
+        let mut size             = 0;
+        let mut upload_id_marker = None;
+
+        loop {
+            let input = ListMultipartUploadsRequest {
+                bucket:           bucket.into(),
+                key_marker:       key_marker.to_owned(),
+                upload_id_marker: upload_id_marker.to_owned(),
+                ..Default::default()
+            };
+
+            let output = with_api_timeout(
+                self.api_timeout,
+                self.client.list_multipart_uploads(input),
+            ).await?;
+
+            if let Some(uploads) = output.uploads {
+                // No iterator here since we need to call an async method.
+                for upload in uploads {
+                    let key       = upload.key.expect("upload key");
+                    let upload_id = upload.upload_id.expect("upload_id");
+
+                    size += self.size_parts(bucket, &key, &upload_id).await?;
+                }
+            }
+
+            if let Some(true) = output.is_truncated {
+                key_marker       = output.next_key_marker;
+                upload_id_marker = output.next_upload_id_marker;
+            }
+            else {
+                break;
+            }
+        }
+
+        Ok(size)
+    }
+
+    /// List parts of an in-progress multipart upload
+    async fn size_parts(
+        &self,
+        bucket: &str,
+        key: &str,
+        upload_id: &str,
+    ) -> Result<u64> {
+        let mut part_number_marker = None;
+        let mut size               = 0;
+
+        loop {
+            let input = ListPartsRequest {
+                bucket:             bucket.into(),
+                key:                key.into(),
+                part_number_marker: part_number_marker.to_owned(),
+                upload_id:          upload_id.into(),
+                ..Default::default()
+            };
+
+            let output = with_api_timeout(
+                self.api_timeout,
+                self.client.list_parts(input),
+            ).await?;
+
+            if let Some(parts) = output.parts {
+                size += parts
+                    .par_iter()
+                    .filter_map(|p| p.size)
+                    .map(object_size)
+                    .sum::<u64>();
+            }
+
+            if let Some(true) = output.is_truncated {
+                part_number_marker = output.next_part_number_marker;
+            }
+            else {
+                break;
+            }
+        }
+
+        Ok(size)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_helpers::{
+        dispatcher_with_body,
+        s3_client,
+    };
+    use pretty_assertions::assert_eq;
+    use rusoto_mock::{
+        MockCredentialsProvider,
+        MultipleMockRequestDispatcher,
+    };
+    use rusoto_s3::S3Client;
+
+    // Create a mock S3 client listing a single incomplete multipart upload
+    // with two parts.
+    fn mock_client(
+        include_multipart_uploads: bool,
+        versions: ObjectVersions,
+    ) -> Client {
+        let mock = MultipleMockRequestDispatcher::new(vec![
+            dispatcher_with_body("s3-list-multipart-uploads.xml"),
+            dispatcher_with_body("s3-list-parts.xml"),
+        ]);
+
+        let client = S3Client::new_with(
+            mock,
+            MockCredentialsProvider,
+            Default::default(),
+        );
+
+        Client {
+            bucket_name:               Some("test-bucket".into()),
+            include_multipart_uploads: include_multipart_uploads,
+            object_versions:           versions,
+            region:                    Default::default(),
+            ..s3_client(client)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_size_multipart_uploads() {
+        let expected = 204800;
+
+        let client = mock_client(false, ObjectVersions::Current);
+
+        let size = Client::size_multipart_uploads(
+            &client,
+            "test-bucket",
+        ).await.unwrap();
+
+        assert_eq!(size, expected);
+    }
+
+    #[tokio::test]
+    async fn test_size_incomplete_uploads() {
+        let tests = vec![
+            (false, ObjectVersions::Current,    0),
+            (true,  ObjectVersions::Current,    204_800),
+            (true,  ObjectVersions::All,        0),
+            (true,  ObjectVersions::Multipart,  0),
+            (true,  ObjectVersions::NonCurrent, 204_800),
+        ];
+
+        for test in tests {
+            let (include, versions, expected) = test;
+
+            let client = mock_client(include, versions);
+
+            let ret = Client::size_incomplete_uploads(&client, "test-bucket")
+                .await
+                .unwrap();
+
+            assert_eq!(ret, expected);
+
+            // Only sizes that were added to the bucket are cached.
+            let cached = client.multipart_cache
+                .lock()
+                .unwrap()
+                .remove("test-bucket");
+
+            let expected = Some(expected).filter(|size| *size > 0);
+
+            assert_eq!(cached, expected);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_size_parts() {
+        let mut client = mock_client(false, ObjectVersions::Current);
+        client.client = S3Client::new_with(
+            dispatcher_with_body("s3-list-parts.xml"),
+            MockCredentialsProvider,
+            Default::default(),
+        );
+
+        let ret = Client::size_parts(
+            &client,
+            "test-bucket",
+            "test.zip",
+            "abc123",
+        ).await.unwrap();
+
+        let expected = 1024 * 100 * 2;
+
+        assert_eq!(ret, expected);
+    }
+}
@@ -0,0 +1,161 @@
+// Implements reporting of the ages of objects for the S3 Client
+#![forbid(unsafe_code)]
+#![deny(missing_docs)]
+use chrono::{
+    DateTime,
+    Utc,
+};
+use crate::analysis::{
+    AgeHistogram,
+    age_histogram,
+};
+use rusoto_s3::Object;
+use tracing::debug;
+
+/// Returns `count` objects, with the correct plural.
+fn objects(count: u64) -> String {
+    match count {
+        1 => "1 object".into(),
+        n => format!("{} objects", n),
+    }
+}
+
+/// Returns the report of the age `histogram` of `bucket`, with a line for
+/// each bin beneath the totals.
+fn format_age_histogram(bucket: &str, histogram: &AgeHistogram) -> Vec<String> {
+    let mut lines = vec![format!(
+        "{} has {} of {} bytes by age",
+        bucket,
+        objects(histogram.count()),
+        histogram.size(),
+    )];
+
+    let unknown = Some(&histogram.unknown)
+        .filter(|bin| bin.count > 0);
+
+    for bin in histogram.bins.iter().chain(unknown) {
+        lines.push(format!(
+            "  {}: {}, {} bytes",
+            bin.label,
+            objects(bin.count),
+            bin.size,
+        ));
+    }
+
+    lines
+}
+
+/// Returns the report of the ages at `now` of the `objects` sized in
+/// `bucket`.
+pub fn age_histogram_report(
+    bucket:  &str,
+    objects: &[Object],
+    now:     DateTime<Utc>,
+) -> Vec<String> {
+    let histogram = age_histogram(objects, now);
+
+    debug!(
+        bucket  = bucket,
+        objects = histogram.count(),
+        "age_histogram_report: Built histogram",
+    );
+
+    format_age_histogram(bucket, &histogram)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analysis::AgeBin;
+    use crate::common::ObjectVersions;
+    use crate::s3::Client;
+    use crate::test_helpers::mock_client;
+    use pretty_assertions::assert_eq;
+
+    // Returns the bin with the given counts and size.
+    fn bin(label: &'static str, count: u64, size: u64) -> AgeBin {
+        AgeBin {
+            label: label,
+            count: count,
+            size:  size,
+        }
+    }
+
+    #[test]
+    fn test_format_age_histogram() {
+        let histogram = AgeHistogram {
+            bins: vec![
+                bin("0-7 days",    1, 1024),
+                bin("7-30 days",   0, 0),
+                bin("30-90 days",  2, 4096),
+                bin("90-365 days", 0, 0),
+                bin("over 1 year", 0, 0),
+            ],
+            unknown: bin("unknown age", 0, 0),
+        };
+
+        let expected = vec![
+            "test-bucket has 3 objects of 5120 bytes by age",
+            "  0-7 days: 1 object, 1024 bytes",
+            "  7-30 days: 0 objects, 0 bytes",
+            "  30-90 days: 2 objects, 4096 bytes",
+            "  90-365 days: 0 objects, 0 bytes",
+            "  over 1 year: 0 objects, 0 bytes",
+        ];
+
+        assert_eq!(format_age_histogram("test-bucket", &histogram), expected);
+    }
+
+    #[tokio::test]
+    async fn test_age_histogram_report() {
+        let client = Client {
+            bucket_name:          Some("test-bucket".into()),
+            object_age_histogram: true,
+            ..mock_client(Some("s3-list-objects-dates.xml"), ObjectVersions::Current)
+        };
+        let size   = client.size_objects("test-bucket").await.unwrap();
+
+        let objects = client.object_cache.lock().unwrap()
+            .remove("test-bucket")
+            .unwrap();
+
+        let ret = age_histogram_report("test-bucket", &objects, Utc::now());
+
+        let expected = vec![
+            "test-bucket has 5 objects of 31744 bytes by age",
+            "  0-7 days: 0 objects, 0 bytes",
+            "  7-30 days: 0 objects, 0 bytes",
+            "  30-90 days: 0 objects, 0 bytes",
+            "  90-365 days: 0 objects, 0 bytes",
+            "  over 1 year: 4 objects, 15360 bytes",
+            "  unknown age: 1 object, 16384 bytes",
+        ];
+
+        assert_eq!(ret, expected);
+        assert_eq!(size, 31744);
+    }
+
+    #[tokio::test]
+    async fn test_age_histogram_report_filtered() {
+        let client = Client {
+            bucket_name:              Some("test-bucket".into()),
+            excluded_storage_classes: vec!["GLACIER".into()],
+            object_age_histogram:     true,
+            ..mock_client(Some("s3-list-objects-duplicates.xml"), ObjectVersions::Current)
+        };
+
+        let size = client.size_objects("test-bucket").await.unwrap();
+
+        let objects = client.object_cache.lock().unwrap()
+            .remove("test-bucket")
+            .unwrap();
+
+        let histogram = age_histogram(&objects, Utc::now());
+
+        // Only the objects that were sized are in the histogram, the object
+        // in Glacier is excluded from both.
+        assert_eq!(histogram.count(), 5);
+        assert_eq!(histogram.size(), size);
+        assert_eq!(size, 34816);
+    }
+}
@@ -0,0 +1,222 @@
+// Implements building the tree of prefixes in a bucket for the S3 Client
+#![forbid(unsafe_code)]
+#![deny(missing_docs)]
+use anyhow::Result;
+use crate::common::{
+    PrefixNode,
+    with_api_timeout,
+};
+use futures::future::{
+    BoxFuture,
+    FutureExt,
+};
+use rayon::prelude::*;
+use rusoto_s3::{
+    ListObjectsV2Request,
+    Object,
+    S3,
+};
+use tracing::debug;
+use super::client::Client;
+use super::shard::KeyRange;
+
+impl Client {
+    /// Returns a tree of the prefixes in `bucket` under `prefix`, down to
+    /// `max_depth`, using our `delimiter`.
+    ///
+    /// Prefixes at `max_depth` are sized as a whole, without listing the
+    /// prefixes below them. Only current object versions are sized.
+    pub fn prefix_tree<'a>(
+        &'a self,
+        bucket: &'a str,
+        prefix: String,
+        depth: usize,
+    ) -> BoxFuture<'a, Result<PrefixNode>> {
+        async move {
+            debug!(bucket = bucket, prefix = %prefix, depth = depth, "prefix_tree");
+
+            let mut node = PrefixNode::new(prefix);
+
+            if depth >= self.max_depth {
+                node.size = self.size_current_objects(
+                    bucket,
+                    Some(&node.prefix),
+                    &KeyRange::default(),
+                ).await?;
+
+                return Ok(node);
+            }
+
+            let mut continuation_token = None;
+            let mut prefixes           = Vec::new();
+
+            loop {
+                let input = ListObjectsV2Request {
+                    bucket:             bucket.into(),
+                    continuation_token: continuation_token.to_owned(),
+                    delimiter:          self.delimiter.to_owned(),
+                    max_keys:           self.page_size,
+                    prefix:             Some(node.prefix.to_owned()),
+                    ..Default::default()
+                };
+
+                let output = with_api_timeout(
+                    self.api_timeout,
+                    self.client.list_objects_v2(input),
+                ).await?;
+
+                // Contents are the objects directly under this prefix.
+                if let Some(contents) = output.contents {
+                    let objects: Vec<&Object> = contents
+                        .par_iter()
+                        .filter(|o| !self.is_excluded_storage_class(&o.storage_class))
+                        .filter(|o| self.is_in_modified_range(&o.key, &o.last_modified))
+                        .collect();
+
+                    node.size += self.objects_size(&objects);
+
+                    self.keep_objects(bucket, &objects);
+                }
+
+                // CommonPrefixes are the virtual directories under this
+                // prefix.
+                if let Some(common_prefixes) = output.common_prefixes {
+                    prefixes.extend(
+                        common_prefixes
+                            .into_iter()
+                            .filter_map(|p| p.prefix)
+                    );
+                }
+
+                if let Some(true) = output.is_truncated {
+                    continuation_token = output.next_continuation_token;
+                }
+                else {
+                    break;
+                }
+            }
+
+            for prefix in prefixes {
+                let child = self.prefix_tree(bucket, prefix, depth + 1).await?;
+
+                node.size += child.size;
+                node.children.push(child);
+            }
+
+            Ok(node)
+        }.boxed()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_helpers::{
+        dispatcher_with_body,
+        s3_client,
+    };
+    use pretty_assertions::assert_eq;
+    use rusoto_mock::{
+        MockCredentialsProvider,
+        MultipleMockRequestDispatcher,
+    };
+    use rusoto_s3::S3Client;
+
+    #[tokio::test]
+    async fn test_prefix_tree() {
+        // Prefixes are listed depth first, with logs/2020/ at max_depth being
+        // sized without a delimiter.
+        let mock = MultipleMockRequestDispatcher::new(vec![
+            dispatcher_with_body("s3-list-objects-delimiter-root.xml"),
+            dispatcher_with_body("s3-list-objects-delimiter-logs.xml"),
+            dispatcher_with_body("s3-list-objects-delimiter-logs-2020.xml"),
+            dispatcher_with_body("s3-list-objects-delimiter-media.xml"),
+        ]);
+
+        let s3client = S3Client::new_with(
+            mock,
+            MockCredentialsProvider,
+            Default::default(),
+        );
+
+        let client = Client {
+            delimiter: Some("/".into()),
+            max_depth: 2,
+            ..s3_client(s3client)
+        };
+
+        let ret = Client::prefix_tree(&client, "test-bucket", "".into(), 0)
+            .await
+            .unwrap();
+
+        let expected = PrefixNode {
+            prefix:   "".into(),
+            size:     31_744,
+            children: vec![
+                PrefixNode {
+                    prefix:   "logs/".into(),
+                    size:     14_336,
+                    children: vec![
+                        PrefixNode {
+                            prefix:   "logs/2020/".into(),
+                            size:     12_288,
+                            children: Vec::new(),
+                        },
+                    ],
+                },
+                PrefixNode {
+                    prefix:   "media/".into(),
+                    size:     16_384,
+                    children: Vec::new(),
+                },
+            ],
+        };
+
+        assert_eq!(ret, expected);
+    }
+
+    #[tokio::test]
+    async fn test_prefix_tree_top_level() {
+        // At the default max_depth, each top level folder is sized by a
+        // prefixed listing without a delimiter.
+        let mock = MultipleMockRequestDispatcher::new(vec![
+            dispatcher_with_body("s3-list-objects-delimiter-root.xml"),
+            dispatcher_with_body("s3-list-objects-prefix-logs.xml"),
+            dispatcher_with_body("s3-list-objects-delimiter-media.xml"),
+        ]);
+
+        let s3client = S3Client::new_with(
+            mock,
+            MockCredentialsProvider,
+            Default::default(),
+        );
+
+        let client = Client {
+            delimiter: Some("/".into()),
+            ..s3_client(s3client)
+        };
+
+        let ret = Client::prefix_tree(&client, "test-bucket", "".into(), 0)
+            .await
+            .unwrap();
+
+        let expected = PrefixNode {
+            prefix:   "".into(),
+            size:     31_744,
+            children: vec![
+                PrefixNode {
+                    prefix:   "logs/".into(),
+                    size:     14_336,
+                    children: Vec::new(),
+                },
+                PrefixNode {
+                    prefix:   "media/".into(),
+                    size:     16_384,
+                    children: Vec::new(),
+                },
+            ],
+        };
+
+        assert_eq!(ret, expected);
+    }
+}
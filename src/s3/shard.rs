@@ -0,0 +1,186 @@
+// KeyRange
+#![forbid(unsafe_code)]
+#![deny(missing_docs)]
+
+/// A range of object keys in a bucket, listed by a single shard when sizing
+/// a bucket with shard prefixes.
+///
+/// Keys after `start_after`, up to and including `end`, are within the range.
+/// A `None` on either side leaves that side of the range unbounded.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct KeyRange {
+    /// Keys must sort after this key to be within the range.
+    pub start_after: Option<String>,
+
+    /// Keys must sort before or equal to this key to be within the range.
+    pub end: Option<String>,
+}
+
+impl KeyRange {
+    /// Returns `true` if the `key` is within this range.
+    pub fn contains(&self, key: &str) -> bool {
+        !self.is_before(key) && !self.is_past_end(key)
+    }
+
+    /// Returns `true` if the `key` sorts at or before the start of this
+    /// range.
+    fn is_before(&self, key: &str) -> bool {
+        match self.start_after.as_deref() {
+            Some(start_after) => key <= start_after,
+            None              => false,
+        }
+    }
+
+    /// Returns `true` if the `key` sorts after the end of this range.
+    pub fn is_past_end(&self, key: &str) -> bool {
+        match self.end.as_deref() {
+            Some(end) => key > end,
+            None      => false,
+        }
+    }
+}
+
+/// Returns the `KeyRange`s produced by splitting a bucket listing at each of
+/// the given `prefixes`.
+///
+/// The ranges are contiguous, so every possible key is within exactly one of
+/// them regardless of which prefixes are given. Empty and duplicate prefixes
+/// are ignored.
+pub fn key_ranges(prefixes: &[String]) -> Vec<KeyRange> {
+    let mut boundaries: Vec<&str> = prefixes.iter()
+        .map(|p| p.as_str())
+        .filter(|p| !p.is_empty())
+        .collect();
+
+    boundaries.sort_unstable();
+    boundaries.dedup();
+
+    let mut ranges      = Vec::with_capacity(boundaries.len() + 1);
+    let mut start_after = None;
+
+    for boundary in boundaries {
+        let end = Some(boundary.to_string());
+
+        ranges.push(KeyRange {
+            start_after: start_after,
+            end:         end.clone(),
+        });
+
+        start_after = end;
+    }
+
+    ranges.push(KeyRange {
+        start_after: start_after,
+        end:         None,
+    });
+
+    ranges
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    // Convenience for building a KeyRange in the tests.
+    fn range(start_after: Option<&str>, end: Option<&str>) -> KeyRange {
+        KeyRange {
+            start_after: start_after.map(|s| s.into()),
+            end:         end.map(|s| s.into()),
+        }
+    }
+
+    #[test]
+    fn test_key_ranges() {
+        let tests = vec![
+            (
+                vec![],
+                vec![
+                    range(None, None),
+                ],
+            ),
+            (
+                vec!["m"],
+                vec![
+                    range(None,      Some("m")),
+                    range(Some("m"), None),
+                ],
+            ),
+            (
+                vec!["c", "a", "", "b", "a"],
+                vec![
+                    range(None,      Some("a")),
+                    range(Some("a"), Some("b")),
+                    range(Some("b"), Some("c")),
+                    range(Some("c"), None),
+                ],
+            ),
+        ];
+
+        for test in tests {
+            let prefixes: Vec<String> = test.0
+                .iter()
+                .map(|p| p.to_string())
+                .collect();
+
+            let expected = test.1;
+
+            let ret = key_ranges(&prefixes);
+
+            assert_eq!(ret, expected);
+        }
+    }
+
+    #[test]
+    fn test_key_ranges_cover_every_key() {
+        let prefixes: Vec<String> = "02468ace"
+            .chars()
+            .map(|c| c.to_string())
+            .collect();
+
+        let ranges = key_ranges(&prefixes);
+
+        let keys = vec![
+            "",
+            "0",
+            "0/file",
+            "1",
+            "9zzz",
+            "a",
+            "e",
+            "e/file",
+            "f",
+            "logs/2020",
+            "~",
+        ];
+
+        for key in keys {
+            let count = ranges.iter()
+                .filter(|range| range.contains(key))
+                .count();
+
+            assert_eq!(count, 1, "'{}' must be in exactly one range", key);
+        }
+    }
+
+    #[test]
+    fn test_key_range_is_past_end() {
+        let tests = vec![
+            (range(None, None),      "z",  false),
+            (range(None, Some("m")), "l",  false),
+            (range(None, Some("m")), "m",  false),
+            (range(None, Some("m")), "m/", true),
+            (range(None, Some("m")), "n",  true),
+        ];
+
+        for test in tests {
+            let range    = test.0;
+            let key      = test.1;
+            let expected = test.2;
+
+            let ret = range.is_past_end(key);
+
+            assert_eq!(ret, expected);
+        }
+    }
+}
@@ -0,0 +1,339 @@
+// Implements bucket tag lookups for the S3 Client
+#![forbid(unsafe_code)]
+#![deny(missing_docs)]
+use anyhow::Result;
+use crate::common::{
+    S3duError,
+    timeout_api_call,
+    with_api_timeout,
+};
+use rusoto_core::RusotoError;
+use futures::future;
+use rusoto_s3::{
+    GetBucketTaggingRequest,
+    GetObjectTaggingRequest,
+    Object,
+    S3,
+};
+use std::collections::HashMap;
+use tracing::debug;
+use super::client::Client;
+
+/// Error code returned by `GetBucketTagging` for buckets without any tags.
+const NO_SUCH_TAG_SET: &str = "<Code>NoSuchTagSet</Code>";
+
+impl Client {
+    /// Returns the tags of `bucket`, keyed on tag key.
+    ///
+    /// S3 returns a `NoSuchTagSet` error for buckets without any tags, these
+    /// are returned as having no tags.
+    pub async fn get_bucket_tags(
+        &self,
+        bucket: &str,
+    ) -> Result<HashMap<String, String>> {
+        debug!(bucket = bucket, "get_bucket_tags");
+
+        let input = GetBucketTaggingRequest {
+            bucket: bucket.into(),
+            ..Default::default()
+        };
+
+        let output = timeout_api_call(
+            self.api_timeout,
+            self.client.get_bucket_tagging(input),
+        ).await?;
+
+        let output = match output {
+            Ok(output) => output,
+            Err(RusotoError::Unknown(ref response))
+                if response.body_as_str().contains(NO_SUCH_TAG_SET) =>
+            {
+                debug!(bucket = bucket, "get_bucket_tags: No tags");
+
+                return Ok(HashMap::new());
+            },
+            Err(e) => return Err(S3duError::from(e).into()),
+        };
+
+        let tags = output.tag_set
+            .into_iter()
+            .map(|tag| (tag.key, tag.value))
+            .collect();
+
+        Ok(tags)
+    }
+
+    /// Returns a `bool` indicating if `bucket` has every tag in our
+    /// `tag_filters`.
+    ///
+    /// Buckets always match if there are no `tag_filters`, without looking up
+    /// their tags.
+    pub async fn matches_tag_filters(&self, bucket: &str) -> Result<bool> {
+        if self.tag_filters.is_empty() {
+            return Ok(true);
+        }
+
+        let tags = self.get_bucket_tags(bucket).await?;
+
+        let matches = self.tag_filters
+            .iter()
+            .all(|(key, value)| tags.get(key) == Some(value));
+
+        Ok(matches)
+    }
+
+    /// Returns the tags of the object at `key` in `bucket`, keyed on tag key.
+    pub async fn get_object_tags(
+        &self,
+        bucket: &str,
+        key: &str,
+    ) -> Result<HashMap<String, String>> {
+        debug!(bucket = bucket, key = key, "get_object_tags");
+
+        let input = GetObjectTaggingRequest {
+            bucket: bucket.into(),
+            key:    key.into(),
+            ..Default::default()
+        };
+
+        let output = with_api_timeout(
+            self.api_timeout,
+            self.client.get_object_tagging(input),
+        ).await?;
+
+        let tags = output.tag_set
+            .into_iter()
+            .map(|tag| (tag.key, tag.value))
+            .collect();
+
+        Ok(tags)
+    }
+
+    /// Returns a `bool` indicating if the object at `key` in `bucket` has
+    /// every tag in our `object_tag_filters`.
+    pub async fn matches_object_tag_filters(
+        &self,
+        bucket: &str,
+        key: &str,
+    ) -> Result<bool> {
+        let tags = self.get_object_tags(bucket, key).await?;
+
+        let matches = self.object_tag_filters
+            .iter()
+            .all(|(key, value)| tags.get(key) == Some(value));
+
+        Ok(matches)
+    }
+
+    /// Returns the `objects` in `bucket` that match our
+    /// `object_tag_filters`.
+    ///
+    /// Object tags are looked up `chunk_size` objects at a time.
+    pub async fn tagged_objects<'a>(
+        &self,
+        bucket: &str,
+        objects: &[&'a Object],
+    ) -> Result<Vec<&'a Object>> {
+        let mut tagged = Vec::new();
+
+        for chunk in objects.chunks(self.chunk_size) {
+            let matches = future::try_join_all(
+                chunk.iter()
+                    .map(|object| self.is_tagged_object(bucket, object))
+            ).await?;
+
+            tagged.extend(
+                chunk.iter()
+                    .zip(matches)
+                    .filter(|(_, matches)| *matches)
+                    .map(|(object, _)| *object)
+            );
+        }
+
+        Ok(tagged)
+    }
+
+    /// Returns `true` if `object` in `bucket` matches our
+    /// `object_tag_filters`.
+    async fn is_tagged_object(
+        &self,
+        bucket: &str,
+        object: &Object,
+    ) -> Result<bool> {
+        let key = object.key.as_deref().unwrap_or_default();
+
+        let matches = self.matches_object_tag_filters(bucket, key).await?;
+
+        if !matches {
+            debug!(bucket = bucket, key = key, "Tags don't match");
+        }
+
+        Ok(matches)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_helpers::{
+        dispatcher_with_body,
+        s3_client,
+    };
+    use pretty_assertions::assert_eq;
+    use rusoto_mock::{
+        MockCredentialsProvider,
+        MockRequestDispatcher,
+        MockResponseReader,
+        MultipleMockRequestDispatcher,
+        ReadMockResponse,
+    };
+    use rusoto_s3::S3Client;
+
+    // Create a mock S3 client with the given tag_filters, returning the given
+    // status and the data from the specified data_file.
+    fn mock_client(
+        status:      u16,
+        data_file:   &str,
+        tag_filters: Vec<(&str, &str)>,
+    ) -> Client {
+        let data = MockResponseReader::read_response("test-data", data_file);
+
+        let client = S3Client::new_with(
+            MockRequestDispatcher::with_status(status).with_body(&data),
+            MockCredentialsProvider,
+            Default::default()
+        );
+
+        let tag_filters = tag_filters
+            .into_iter()
+            .map(|(key, value)| (key.into(), value.into()))
+            .collect();
+
+        Client {
+            tag_filters: tag_filters,
+            ..s3_client(client)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_get_bucket_tags() {
+        let client = mock_client(200, "s3-get-bucket-tagging.xml", vec![]);
+
+        let ret = client.get_bucket_tags("test-bucket").await.unwrap();
+
+        let mut expected = HashMap::new();
+        expected.insert("environment".to_string(), "production".to_string());
+        expected.insert("team".to_string(), "platform".to_string());
+
+        assert_eq!(ret, expected);
+    }
+
+    #[tokio::test]
+    async fn test_get_bucket_tags_no_such_tag_set() {
+        let client = mock_client(
+            404,
+            "s3-get-bucket-tagging-no-such-tag-set.xml",
+            vec![],
+        );
+
+        let ret = client.get_bucket_tags("test-bucket").await.unwrap();
+
+        assert!(ret.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_get_bucket_tags_access_denied() {
+        let client = mock_client(403, "s3-get-bucket-tagging.xml", vec![]);
+
+        let ret = client.get_bucket_tags("test-bucket").await.unwrap_err();
+        let ret = ret.downcast_ref::<S3duError>().unwrap();
+
+        assert!(matches!(ret, S3duError::AccessDenied(_)));
+    }
+
+    #[tokio::test]
+    async fn test_matches_tag_filters() {
+        // The tagged bucket has team=platform and environment=production.
+        let tests = vec![
+            (vec![],                                                    true),
+            (vec![("team", "platform")],                                true),
+            (vec![("team", "platform"), ("environment", "production")], true),
+            (vec![("team", "platform"), ("environment", "staging")],    false),
+            (vec![("team", "data")],                                    false),
+            (vec![("owner", "platform")],                               false),
+        ];
+
+        for test in tests {
+            let tag_filters = test.0;
+            let expected    = test.1;
+
+            let client = mock_client(200, "s3-get-bucket-tagging.xml", tag_filters);
+
+            let ret = client.matches_tag_filters("test-bucket").await.unwrap();
+
+            assert_eq!(ret, expected);
+        }
+
+        // A bucket without tags never matches.
+        let client = mock_client(
+            404,
+            "s3-get-bucket-tagging-no-such-tag-set.xml",
+            vec![("team", "platform")],
+        );
+
+        let ret = client.matches_tag_filters("test-bucket").await.unwrap();
+
+        assert_eq!(ret, false);
+    }
+
+    #[tokio::test]
+    async fn test_get_object_tags() {
+        let mut client = mock_client(200, "s3-get-object-tagging.xml", vec![]);
+        client.object_tag_filters = vec![("team".into(), "platform".into())];
+
+        let ret = client.get_object_tags("test-bucket", "file1").await.unwrap();
+
+        let mut expected = HashMap::new();
+        expected.insert("team".to_string(), "platform".to_string());
+
+        assert_eq!(ret, expected);
+
+        let ret = client.matches_object_tag_filters("test-bucket", "file1")
+            .await
+            .unwrap();
+
+        assert_eq!(ret, true);
+    }
+
+    #[tokio::test]
+    async fn test_size_objects_object_tag_filters() {
+        // file1 (1024 bytes) is tagged team=platform, file2 (32768 bytes)
+        // has no tags.
+        let tests = vec![
+            (1, 1024),
+            (2, 1024),
+        ];
+
+        for test in tests {
+            let (chunk_size, expected) = test;
+
+            let mut client = mock_client(200, "s3-get-object-tagging.xml", vec![]);
+
+            client.chunk_size         = chunk_size;
+            client.object_tag_filters = vec![("team".into(), "platform".into())];
+            client.client             = S3Client::new_with(
+                MultipleMockRequestDispatcher::new(vec![
+                    dispatcher_with_body("s3-list-objects.xml"),
+                    dispatcher_with_body("s3-get-object-tagging.xml"),
+                    dispatcher_with_body("s3-get-object-tagging-empty.xml"),
+                ]),
+                MockCredentialsProvider,
+                Default::default()
+            );
+
+            let ret = client.size_objects("test-bucket").await.unwrap();
+
+            assert_eq!(ret, expected);
+        }
+    }
+}
@@ -0,0 +1,121 @@
+// Snapshots of bucket sizes
+#![forbid(unsafe_code)]
+#![deny(missing_docs)]
+use anyhow::Result;
+use crate::common::S3duError;
+use serde::{
+    Deserialize,
+    Serialize,
+};
+use std::fs::File;
+use std::io::{
+    BufReader,
+    BufWriter,
+    Write,
+};
+use std::path::Path;
+use tracing::debug;
+
+/// The size of a single bucket, as recorded in a snapshot.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct BucketSizeEntry {
+    /// Name of the bucket.
+    pub bucket: String,
+
+    /// Name of the region that the bucket is in, if known.
+    pub region: Option<String>,
+
+    /// Size of the bucket in bytes.
+    pub size: u64,
+}
+
+/// Saves the bucket sizes in `entries` as a JSON snapshot at `path`,
+/// replacing any existing file.
+pub fn save(path: &Path, entries: &[BucketSizeEntry]) -> Result<()> {
+    debug!(entries = entries.len(), path = ?path, "save: Saving entries");
+
+    let snapshot_error = |e: &dyn std::fmt::Display| {
+        S3duError::Snapshot(format!("{}: {}", path.display(), e))
+    };
+
+    let file = File::create(path)
+        .map_err(|e| snapshot_error(&e))?;
+
+    let mut writer = BufWriter::new(file);
+
+    serde_json::to_writer_pretty(&mut writer, entries)
+        .map_err(|e| snapshot_error(&e))?;
+
+    writeln!(writer)
+        .and_then(|_| writer.flush())
+        .map_err(|e| snapshot_error(&e))?;
+
+    Ok(())
+}
+
+/// Loads the bucket sizes from the JSON snapshot at `path`.
+pub fn load(path: &Path) -> Result<Vec<BucketSizeEntry>> {
+    debug!(path = ?path, "load: Loading entries");
+
+    let snapshot_error = |e: &dyn std::fmt::Display| {
+        S3duError::Snapshot(format!("{}: {}", path.display(), e))
+    };
+
+    let file = File::open(path)
+        .map_err(|e| snapshot_error(&e))?;
+
+    let entries = serde_json::from_reader(BufReader::new(file))
+        .map_err(|e| snapshot_error(&e))?;
+
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_helpers::temp_path;
+    use pretty_assertions::assert_eq;
+    use std::fs;
+
+    #[test]
+    fn test_save_load() {
+        let path = temp_path("save-load.json");
+
+        let entries = vec![
+            BucketSizeEntry {
+                bucket: "a-bucket".into(),
+                region: Some("eu-west-1".into()),
+                size:   1024,
+            },
+            BucketSizeEntry {
+                bucket: "another-bucket".into(),
+                region: None,
+                size:   0,
+            },
+        ];
+
+        save(&path, &entries).unwrap();
+        let ret = load(&path).unwrap();
+
+        assert_eq!(ret, entries);
+    }
+
+    #[test]
+    fn test_load_errors() {
+        let path = temp_path("load-errors.json");
+
+        // Missing file.
+        let ret = load(&path).unwrap_err();
+        let ret = ret.downcast_ref::<S3duError>().unwrap();
+
+        assert!(matches!(ret, S3duError::Snapshot(_)));
+
+        // Malformed file.
+        fs::write(&path, "{\"bucket\": ").unwrap();
+        let ret = load(&path).unwrap_err();
+
+        let ret = ret.downcast_ref::<S3duError>().unwrap();
+
+        assert!(matches!(ret, S3duError::Snapshot(_)));
+    }
+}
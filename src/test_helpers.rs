@@ -0,0 +1,22 @@
+// Helpers shared between tests
+#![forbid(unsafe_code)]
+#![deny(missing_docs)]
+
+/// `MockS3Server` serves recorded S3 responses by request method and path.
+#[cfg(feature = "s3")]
+mod mock_server;
+
+/// `s3_client` returns an S3 `Client` with default options for tests, along
+/// with mock clients and dispatchers built on it.
+#[cfg(feature = "s3")]
+mod s3_client;
+
+/// `temp_path` returns a path to a file that's removed once a test is done
+/// with it.
+mod temp_path;
+
+#[cfg(feature = "s3")]
+pub use mock_server::*;
+#[cfg(feature = "s3")]
+pub use s3_client::*;
+pub use temp_path::*;
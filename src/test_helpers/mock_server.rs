@@ -0,0 +1,218 @@
+// MockS3Server
+#![forbid(unsafe_code)]
+#![deny(missing_docs)]
+use rusoto_core::request::{
+    DispatchSignedRequest,
+    DispatchSignedRequestFuture,
+};
+use rusoto_core::signature::SignedRequest;
+use rusoto_mock::{
+    MockRequestDispatcher,
+    MockResponseReader,
+    ReadMockResponse,
+};
+use std::time::Duration;
+
+/// A request that a `MockS3Server` responds to.
+struct Route {
+    /// HTTP method of the request, eg. `GET`.
+    method: String,
+
+    /// Path of the request, eg. `/a-bucket-name`.
+    path: String,
+
+    /// Query parameters that the request must have, with their value if it
+    /// must also match.
+    params: Vec<(String, Option<String>)>,
+
+    /// Dispatcher returning the response to the request.
+    dispatcher: MockRequestDispatcher,
+}
+
+impl Route {
+    /// Returns a new `Route` for `method` and `path`, where `path` may be
+    /// followed by the query parameters that the request must have, eg.
+    /// `/a-bucket-name?list-type=2`.
+    fn new(method: &str, path: &str, dispatcher: MockRequestDispatcher) -> Self {
+        let mut parts = path.splitn(2, '?');
+
+        let path  = parts.next().unwrap_or_default();
+        let query = parts.next().unwrap_or_default();
+
+        let params = query.split('&')
+            .filter(|param| !param.is_empty())
+            .map(|param| {
+                let mut parts = param.splitn(2, '=');
+
+                let key   = parts.next().unwrap_or_default();
+                let value = parts.next();
+
+                (key.into(), value.map(|value| value.into()))
+            })
+            .collect();
+
+        Self {
+            method:     method.into(),
+            path:       path.into(),
+            params:     params,
+            dispatcher: dispatcher,
+        }
+    }
+
+    /// Returns `true` if `request` has our method and path, and all of our
+    /// query parameters.
+    fn matches(&self, request: &SignedRequest) -> bool {
+        request.method == self.method
+            && request.path == self.path
+            && self.params.iter().all(|(key, value)| {
+                match (request.params.get(key), value) {
+                    (Some(_), None)          => true,
+                    (Some(actual), Some(_))  => actual == value,
+                    (None, _)                => false,
+                }
+            })
+    }
+}
+
+/// Builds a `MockS3Dispatcher`, serving recorded responses by the method and
+/// path of each request.
+///
+/// Unlike `MultipleMockRequestDispatcher`, responses don't depend on the
+/// order that requests are made in, so code making several different S3 API
+/// calls can be tested end-to-end.
+///
+/// ```rust
+/// let dispatcher = MockS3Server::new()
+///     .on("GET", "/", "s3-list-buckets.xml")
+///     .on("GET", "/a-bucket-name?location", "s3-get-bucket-location.xml")
+///     .on_status("HEAD", "/a-bucket-name", 200)
+///     .build();
+/// ```
+#[derive(Default)]
+pub struct MockS3Server {
+    /// Routes in the order that they were added.
+    routes: Vec<Route>,
+}
+
+impl MockS3Server {
+    /// Returns a new `MockS3Server` without any routes.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Responds to `method` requests for `path` with the body in the
+    /// `test-data` file `data_file`.
+    ///
+    /// `path` may be followed by query parameters that the request must
+    /// have, eg. `/a-bucket-name?location`.
+    pub fn on(self, method: &str, path: &str, data_file: &str) -> Self {
+        let data = MockResponseReader::read_response("test-data", data_file);
+
+        self.route(method, path, MockRequestDispatcher::default().with_body(&data))
+    }
+
+    /// Responds to `method` requests for `path` with `status` and an empty
+    /// body.
+    pub fn on_status(self, method: &str, path: &str, status: u16) -> Self {
+        self.route(method, path, MockRequestDispatcher::with_status(status))
+    }
+
+    /// Returns the `MockS3Dispatcher` serving our routes.
+    pub fn build(self) -> MockS3Dispatcher {
+        MockS3Dispatcher {
+            routes: self.routes,
+        }
+    }
+
+    /// Adds a route responding with `dispatcher`.
+    fn route(
+        mut self,
+        method: &str,
+        path: &str,
+        dispatcher: MockRequestDispatcher,
+    ) -> Self {
+        self.routes.push(Route::new(method, path, dispatcher));
+
+        self
+    }
+}
+
+/// Dispatcher for the routes of a `MockS3Server`.
+///
+/// Requests are answered by the first route that matches them. Requests
+/// without a matching route fail the test.
+pub struct MockS3Dispatcher {
+    /// Routes in the order that they were added.
+    routes: Vec<Route>,
+}
+
+impl DispatchSignedRequest for MockS3Dispatcher {
+    fn dispatch(
+        &self,
+        request: SignedRequest,
+        timeout: Option<Duration>,
+    ) -> DispatchSignedRequestFuture {
+        let route = self.routes.iter()
+            .find(|route| route.matches(&request))
+            .unwrap_or_else(|| {
+                panic!(
+                    "No mock response for {} {} {:?}",
+                    request.method,
+                    request.path,
+                    request.params,
+                )
+            });
+
+        route.dispatcher.dispatch(request, timeout)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use rusoto_core::Region;
+
+    // Returns a request for method and path, with the given query params.
+    fn request(
+        method: &str,
+        path:   &str,
+        params: &[(&str, Option<&str>)],
+    ) -> SignedRequest {
+        let mut request = SignedRequest::new(method, "s3", &Region::UsEast1, path);
+
+        for (key, value) in params {
+            match value {
+                Some(value) => request.add_param(*key, *value),
+                None        => {
+                    request.params.insert((*key).into(), None);
+                },
+            }
+        }
+
+        request
+    }
+
+    #[test]
+    fn test_route_matches() {
+        let route = |path| Route::new("GET", path, MockRequestDispatcher::default());
+
+        let tests = vec![
+            ("/",                        request("GET", "/", &[]),                                  true),
+            ("/",                        request("HEAD", "/", &[]),                                 false),
+            ("/bucket",                  request("GET", "/", &[]),                                  false),
+            ("/bucket?location",         request("GET", "/bucket", &[("location", None)]),          true),
+            ("/bucket?location",         request("GET", "/bucket", &[]),                            false),
+            ("/bucket?list-type=2",      request("GET", "/bucket", &[("list-type", Some("2"))]),    true),
+            ("/bucket?list-type=2",      request("GET", "/bucket", &[("list-type", Some("1"))]),    false),
+            ("/bucket?list-type",        request("GET", "/bucket", &[("list-type", Some("2"))]),    true),
+            ("/bucket",                  request("GET", "/bucket", &[("versions", None)]),          true),
+        ];
+
+        for test in tests {
+            let (path, request, expected) = test;
+
+            assert_eq!(route(path).matches(&request), expected, "{}", path);
+        }
+    }
+}
@@ -0,0 +1,97 @@
+// s3_client
+#![forbid(unsafe_code)]
+#![deny(missing_docs)]
+use crate::common::ObjectVersions;
+use crate::s3::Client;
+use rusoto_core::Region;
+use rusoto_mock::{
+    MockCredentialsProvider,
+    MockRequestDispatcher,
+    MockResponseReader,
+    ReadMockResponse,
+};
+use rusoto_s3::S3Client;
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+/// Returns an S3 `Client` making requests with `client`, with every option
+/// set to its default.
+///
+/// Tests needing other options override them with struct update syntax, so
+/// that new options only need adding here.
+///
+/// ```rust
+/// let client = Client {
+///     delimiter: Some("/".into()),
+///     ..s3_client(s3client)
+/// };
+/// ```
+pub fn s3_client(client: S3Client) -> Client {
+    Client {
+        client:                    client,
+        age_histogram_cache:       Mutex::new(HashMap::new()),
+        api_timeout:               None,
+        bucket_cache:              Mutex::new(None),
+        bucket_name:               None,
+        check_logging:             false,
+        check_versioning:          false,
+        chunk_size:                1,
+        count_versions:            false,
+        creation_date_range:       None,
+        delimiter:                 None,
+        duplicate_cache:           Mutex::new(HashMap::new()),
+        exclude_regions:           Vec::new(),
+        excluded_storage_classes:  Vec::new(),
+        find_duplicates:           false,
+        include_multipart_uploads: false,
+        include_size_on_disk:      false,
+        max_depth:                 1,
+        max_objects:               None,
+        metadata_cache:            Mutex::new(HashMap::new()),
+        metadata_sample_size:      None,
+        modified_after:            None,
+        modified_before:           None,
+        multipart_cache:           Mutex::new(HashMap::new()),
+        namespace_threshold:       None,
+        no_sign_request:           false,
+        object_age_histogram:      false,
+        object_cache:              Mutex::new(HashMap::new()),
+        object_tag_filters:        Vec::new(),
+        object_versions:           ObjectVersions::Current,
+        page_size:                 None,
+        prefix_cache:              Mutex::new(HashMap::new()),
+        region:                    Region::UsEast1,
+        shard_prefixes:            Vec::new(),
+        stats_cache:               Mutex::new(HashMap::new()),
+        tag_filters:               Vec::new(),
+    }
+}
+
+/// Returns a `MockRequestDispatcher` responding with the contents of the
+/// `data_file` in `test-data`.
+pub fn dispatcher_with_body(data_file: &str) -> MockRequestDispatcher {
+    let data = MockResponseReader::read_response("test-data", data_file);
+
+    MockRequestDispatcher::default().with_body(&data)
+}
+
+/// Returns an S3 `Client` selecting the object `versions`, which responds to
+/// every request with the contents of the `data_file` in `test-data`, or
+/// with an empty body if no `data_file` is given.
+pub fn mock_client(data_file: Option<&str>, versions: ObjectVersions) -> Client {
+    let dispatcher = match data_file {
+        Some(data_file) => dispatcher_with_body(data_file),
+        None            => MockRequestDispatcher::default().with_body(""),
+    };
+
+    let client = S3Client::new_with(
+        dispatcher,
+        MockCredentialsProvider,
+        Default::default(),
+    );
+
+    Client {
+        object_versions: versions,
+        ..s3_client(client)
+    }
+}
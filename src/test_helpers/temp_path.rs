@@ -0,0 +1,47 @@
+// temp_path
+#![forbid(unsafe_code)]
+#![deny(missing_docs)]
+use std::ops::Deref;
+use std::path::{
+    Path,
+    PathBuf,
+};
+use tempfile::TempDir;
+
+/// `TempPath` is a path to a file in a temporary directory of its own.
+///
+/// The directory is removed, along with anything written to the path, when
+/// the `TempPath` is dropped, even if the test using it fails.
+pub struct TempPath {
+    /// Temporary directory that the path is in.
+    _dir: TempDir,
+
+    /// Path to the file.
+    path: PathBuf,
+}
+
+impl AsRef<Path> for TempPath {
+    fn as_ref(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Deref for TempPath {
+    type Target = Path;
+
+    fn deref(&self) -> &Self::Target {
+        &self.path
+    }
+}
+
+/// Returns a path to a file called `name`, which doesn't exist yet, in a new
+/// temporary directory.
+pub fn temp_path(name: &str) -> TempPath {
+    let dir  = TempDir::new().unwrap();
+    let path = dir.path().join(name);
+
+    TempPath {
+        _dir: dir,
+        path: path,
+    }
+}